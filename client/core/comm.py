@@ -1,5 +1,9 @@
 import asyncio
+import hashlib
+import hmac
+import json
 import logging
+import zlib
 from pathlib import Path
 
 import websockets
@@ -9,14 +13,35 @@ from core.config import get_config
 
 logger = logging.getLogger(__name__)
 
+# Close code of the server when a newer connection with the same API key took over
+CLOSE_CODE_REPLACED = 4001
+# Handshake header carrying the token to resume a dropped connection
+RESUME_TOKEN_HEADER = "X-WS-Resume-Token"
+# Handshake header carrying the protocol version of the server
+PROTOCOL_VERSION_HEADER = "X-WS-Protocol-Version"
+# Protocol version of the messages this client sends
+PROTOCOL_VERSION = 1
+# Handshake header opting into compressed messages (value: "deflate"), echoed by the server if accepted.
+# Not the standard permessage-deflate extension: Larger text messages of the server arrive as binary
+# messages holding the zlib stream of the UTF-8 text, smaller ones stay text messages
+COMPRESSION_HEADER = "X-WS-Compression"
+
 
 class WsClient:
-    def __init__(self, url: str):
+    def __init__(self, url: str, signing_secret: str = ""):
         self.url: str = url
+        # Secret shared with the server. If set, messages must be signed with it
+        self.signing_secret: bytes | None = signing_secret.encode("utf-8") or None
         self.api_key: str | None = self.load_api_key()
         self.websocket: ClientConnection | None = None
         self.running: bool = False
         self.heartbeat_timeout: int = 90
+        # Set if another client took over the connection. Reconnecting would only displace it in turn
+        self.replaced: bool = False
+        # Issued by the server on connect. Sent on reconnect to receive the messages missed meanwhile
+        self.resume_token: str | None = None
+        # Metadata (e.g. shard id) the server filters broadcasts by. Sent after every connect
+        self.tags: dict[str, str] = {}
 
     def load_api_key(self) -> str | None:
         """Load API key from .secret file"""
@@ -39,9 +64,21 @@ class WsClient:
     async def connect(self) -> bool:
         """Establish WebSocket connection with API key in header"""
         if self.api_key is not None:
-            headers = {"X-API-Key": self.api_key}
+            # Opt into compressed messages. The server only compresses if enabled on its side
+            headers = {"X-API-Key": self.api_key, COMPRESSION_HEADER: "deflate"}
+            if self.resume_token is not None:
+                headers[RESUME_TOKEN_HEADER] = self.resume_token
             try:
                 self.websocket = await connect(self.url, additional_headers=headers)
+                self.resume_token = self.websocket.response.headers.get(RESUME_TOKEN_HEADER)
+                server_version = self.websocket.response.headers.get(PROTOCOL_VERSION_HEADER)
+                if server_version is not None and int(server_version) != PROTOCOL_VERSION:
+                    logger.warning(
+                        f"Server speaks protocol version {server_version}, "
+                        f"client speaks {PROTOCOL_VERSION}"
+                    )
+                if self.tags:
+                    await self.send_message({"type": "set_tags", "tags": self.tags})
                 self.running = True
                 logger.info(f"Connected to {self.url}")
                 return True
@@ -55,15 +92,74 @@ class WsClient:
         try:
             while self.running and self.websocket:
                 message = await self.websocket.recv()
+                if isinstance(message, bytes):
+                    # Binary messages are zlib-compressed text messages, see COMPRESSION_HEADER
+                    message = zlib.decompress(message).decode("utf-8")
+                if isinstance(message, str) and self.signing_secret is not None:
+                    message = self.verify_message(message)
+                    if message is None:
+                        logger.warning("Dropped message with an invalid signature")
+                        continue
                 if isinstance(message, str):
                     logger.info("Received event message from server")
                     await self.handle_server_message(message)
-        except websockets.exceptions.ConnectionClosed:
-            logger.info("Connection closed by server")
+        except websockets.exceptions.ConnectionClosed as e:
+            if e.rcvd is not None and e.rcvd.code == CLOSE_CODE_REPLACED:
+                logger.warning("Connection replaced by a newer connection with the same API key")
+                self.replaced = True
+            else:
+                logger.info("Connection closed by server")
             self.running = False
         except Exception as e:
             logger.error(f"Error in receive task: {e}")
 
+    async def send_message(self, message: dict):
+        """Send a protocol message. The server answers subscription changes with `ack`/`error`"""
+        if self.websocket is not None:
+            await self.websocket.send(json.dumps({"version": PROTOCOL_VERSION, **message}))
+
+    async def subscribe(
+        self, code: str, channel_id: int, guild_id: int, thread_id: int | None = None
+    ):
+        """Subscribe a channel (or a thread within it) to a notification code"""
+        await self.send_message(
+            {
+                "type": "subscribe",
+                "code": code,
+                "channel_id": channel_id,
+                "guild_id": guild_id,
+                "thread_id": thread_id,
+            }
+        )
+
+    async def unsubscribe(
+        self, code: str, channel_id: int, guild_id: int, thread_id: int | None = None
+    ):
+        """Unsubscribe a channel (or a thread within it) from a notification code"""
+        await self.send_message(
+            {
+                "type": "unsubscribe",
+                "code": code,
+                "channel_id": channel_id,
+                "guild_id": guild_id,
+                "thread_id": thread_id,
+            }
+        )
+
+    def verify_message(self, message: str) -> str | None:
+        """Unwrap a signed message. Returns its payload or None if the signature doesn't match"""
+        try:
+            envelope = json.loads(message)
+            payload, signature = envelope["payload"], envelope["signature"]
+        except (ValueError, KeyError, TypeError):
+            return None
+        if not isinstance(payload, str) or not isinstance(signature, str):
+            return None
+        expected = hmac.new(self.signing_secret, payload.encode("utf-8"), hashlib.sha256)
+        if not hmac.compare_digest(expected.hexdigest(), signature):
+            return None
+        return payload
+
     async def heartbeat_task(self):
         """Monitor server activity and close if no response"""
         last_activity = asyncio.get_event_loop().time()
@@ -110,5 +206,5 @@ def get_wsclient():
     global wsclient
     if wsclient is None:
         config = get_config()
-        wsclient = WsClient(config.server_ws_url)
+        wsclient = WsClient(config.server_ws_url, config.ws_signing_secret)
     return wsclient
@@ -22,6 +22,8 @@ class Config:
     owner_id: int
 
     logging_level: str = "INFO"
+    # Secret shared with the server to verify signed websocket messages. Empty = unsigned
+    ws_signing_secret: str = ""
     color_default: int = 0x1B6C8E
     color_error: int = 0x8E1B1B
 
@@ -55,6 +57,7 @@ class Config:
                 server_ws_url=os.getenv("SERVER_WS_URL", ""),
                 server_api_url=os.getenv("SERVER_API_URL", ""),
                 logging_level=os.getenv("CLIENT_LOGGING_LEVEL", "INFO"),
+                ws_signing_secret=os.getenv("SERVER_WS_SIGNING_SECRET", ""),
                 repo=os.getenv("CLIENT_REPO_URL"),
                 owner_id=int(os.getenv("OWNER_ID")),
             )
@@ -1,6 +1,6 @@
 #!/bin/bash
 diesel migration run
 diesel print-schema > src/db/schema.rs
-# Included editing as otherwise the array structure would include nullable text which is not compatible with Vec<String>
-sed -i 's/Array<Nullable<Text>>/Array<Text>/g' src/db/schema.rs
+# Included editing as otherwise the array structure would include nullable elements which are not compatible with Vec<T>
+sed -i -E 's/Array<Nullable<(\w+)>>/Array<\1>/g' src/db/schema.rs
 cargo fmt
\ No newline at end of file
@@ -0,0 +1,185 @@
+//! Health endpoints for orchestration, monitoring and troubleshooting.
+use std::{future::Future, time::Instant};
+
+use actix_web::{http::StatusCode, web, HttpResponse};
+use diesel::{sql_query, RunQueryDsl};
+use serde::Serialize;
+
+use crate::{
+    db::with_connection,
+    utils::{
+        breaker::BREAKER,
+        comm::{
+            auth::extractor::{AdminManage, AuthedClaims},
+            websocket::manager::get_manager,
+        },
+        error::KohakuError,
+        scheduler::{try_get_scheduler, Scheduler},
+    },
+};
+
+/// Mounts `/api/ready` and `/metrics`. Has to be configured before [`super::configure`],
+/// otherwise the deprecated `/api` scope shadows `/api/ready`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/api/ready", web::get().to(ready))
+        .route("/metrics", web::get().to(metrics));
+}
+
+/// Health of the [`Scheduler`]
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SchedulerHealth {
+    pub running: bool,
+    pub jobs: usize,
+}
+
+impl SchedulerHealth {
+    /// # Parameters
+    /// - `scheduler` : The [`Scheduler`], or [`None`] if it is not initialized
+    pub fn from_scheduler(scheduler: Option<&Scheduler>) -> Self {
+        Self {
+            running: scheduler.is_some_and(Scheduler::is_running),
+            jobs: scheduler.map_or(0, Scheduler::job_count),
+        }
+    }
+
+    /// Renders the health as gauges in the Prometheus text format
+    pub fn to_metrics(&self) -> String {
+        format!(
+            "# HELP kohaku_scheduler_running Whether the scheduler is running (1) or not (0)\n\
+             # TYPE kohaku_scheduler_running gauge\n\
+             kohaku_scheduler_running {}\n\
+             # HELP kohaku_scheduler_jobs Amount of scheduled jobs\n\
+             # TYPE kohaku_scheduler_jobs gauge\n\
+             kohaku_scheduler_jobs {}\n",
+            u8::from(self.running),
+            self.jobs
+        )
+    }
+}
+
+/// Helper: Health of the global [`Scheduler`]
+fn scheduler_health() -> SchedulerHealth {
+    SchedulerHealth::from_scheduler(try_get_scheduler().as_deref())
+}
+
+/// GET /api/ready
+///
+/// # Returns
+/// The scheduler health. `200 OK` if the scheduler is running, `503 Service Unavailable` otherwise
+async fn ready() -> HttpResponse {
+    let health = scheduler_health();
+    let status = if health.running {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    HttpResponse::build(status).json(serde_json::json!({ "scheduler": health }))
+}
+
+/// GET /metrics
+///
+/// # Returns
+/// The gauges of [`SchedulerHealth::to_metrics`] and [`CircuitBreaker::to_metrics`](crate::utils::breaker::CircuitBreaker::to_metrics)
+async fn metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(scheduler_health().to_metrics() + &BREAKER.to_metrics())
+}
+
+// ========================================== Ping ============================================= //
+
+/// Outcome of a single timed probe of [`ping`]
+#[derive(Debug, Serialize)]
+pub struct Probe<T: Serialize> {
+    /// Round-trip time of the probe in milliseconds
+    pub latency_ms: f64,
+    /// Result of the probe, [`None`] if it failed
+    pub value: Option<T>,
+    /// Cause of a failed probe
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Timings of [`ping`] per subsystem
+#[derive(Debug, Serialize)]
+pub struct PingResponse {
+    /// `SELECT 1` against the database
+    pub database: Probe<bool>,
+    /// Whether the scheduler is running, see [`Scheduler::ping`]
+    pub scheduler: Probe<bool>,
+    /// Current amount of websocket connections
+    pub websocket: Probe<usize>,
+}
+
+/// Runs a probe and measures its round-trip time
+///
+/// # Parameters
+/// - `f` : The probe. Its error is reported instead of failing the whole ping
+///
+/// # Returns
+/// The timed [`Probe`]
+pub async fn probe<T, F, Fut>(f: F) -> Probe<T>
+where
+    T: Serialize,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, KohakuError>>,
+{
+    let start = Instant::now();
+    let result = f().await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    match result {
+        Ok(value) => Probe {
+            latency_ms,
+            value: Some(value),
+            error: None,
+        },
+        Err(e) => Probe {
+            latency_ms,
+            value: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Admin ping endpoint.
+///
+/// Probes the database, the scheduler and the websocket manager one after another, so a slow subsystem is obvious.
+/// Failing probes are reported in the response instead of failing the request.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`PingResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+pub async fn ping(_claims: AuthedClaims<AdminManage>) -> Result<HttpResponse, KohakuError> {
+    let database = probe(|| {
+        with_connection(|conn| {
+            sql_query("SELECT 1")
+                .execute(conn)
+                .map(|_| true)
+                .map_err(KohakuError::DatabaseError)
+        })
+    })
+    .await;
+    let scheduler = probe(|| async {
+        match try_get_scheduler() {
+            Some(scheduler) => Ok(scheduler.ping().await),
+            None => Err(KohakuError::InternalServerError(
+                "Scheduler is not initialized".to_string(),
+            )),
+        }
+    })
+    .await;
+    let websocket = probe(|| async { Ok(get_manager()?.connection_count()) }).await;
+
+    Ok(HttpResponse::Ok().json(PingResponse {
+        database,
+        scheduler,
+        websocket,
+    }))
+}
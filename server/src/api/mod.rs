@@ -0,0 +1,18 @@
+//! Versioned HTTP API.
+//!
+//! Every version gets its own module with a `configure` function that mounts its routes.
+//! A new version (e.g. `/api/v2`) can be added next to the existing ones without breaking clients.
+use actix_web::{middleware::DefaultHeaders, web};
+
+pub mod health;
+pub mod v1;
+
+/// Mounts all API versions. `/api` is kept as deprecated alias of `/api/v1`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/api/v1").configure(v1::configure))
+        .service(
+            web::scope("/api")
+                .wrap(DefaultHeaders::new().add(("Deprecation", "true")))
+                .configure(v1::configure),
+        );
+}
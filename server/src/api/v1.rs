@@ -0,0 +1,29 @@
+use actix_web::web;
+
+use crate::{
+    api::health,
+    utils::{comm, scheduler},
+};
+
+/// Configures the routes of API version 1
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/auth").configure(comm::auth::routes::configure))
+        .service(web::scope("/events").configure(comm::events::routes::configure))
+        .service(
+            web::scope("/admin")
+                .route("/ping", web::get().to(health::ping))
+                .route(
+                    "/signing-key/rotate",
+                    web::post().to(comm::auth::routes::rotate_signing_key),
+                )
+                .route(
+                    "/codes/purge-stale",
+                    web::post().to(comm::events::routes::purge_stale_codes),
+                )
+                .route(
+                    "/ws/connections",
+                    web::get().to(comm::websocket::routes::list_connections),
+                )
+                .service(web::scope("/tasks").configure(scheduler::routes::configure)),
+        );
+}
@@ -0,0 +1,3 @@
+DROP INDEX idx_auth_audit_created_at;
+
+DROP TABLE auth_audit;
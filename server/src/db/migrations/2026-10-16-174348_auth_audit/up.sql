@@ -0,0 +1,11 @@
+CREATE TABLE auth_audit (
+  id SERIAL PRIMARY KEY,
+  event_type VARCHAR(32) NOT NULL,
+  key_id INTEGER,
+  owner VARCHAR(255),
+  success BOOLEAN NOT NULL,
+  ip VARCHAR(45),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_auth_audit_created_at ON auth_audit(created_at);
@@ -0,0 +1 @@
+ALTER TABLE api_keys ADD COLUMN allowed_ips TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,6 @@
+DROP INDEX idx_notification_targets_guild;
+DROP INDEX idx_notification_targets_code;
+
+DROP TABLE notification_targets;
+
+DROP TABLE notification_codes;
@@ -0,0 +1,21 @@
+CREATE TABLE notification_codes (
+  code VARCHAR(64) PRIMARY KEY,
+  description TEXT,
+  last_used TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE notification_targets (
+  id SERIAL PRIMARY KEY,
+  code VARCHAR(64) NOT NULL REFERENCES notification_codes(code) ON DELETE CASCADE,
+  channel_id BIGINT NOT NULL,
+  guild_id BIGINT NOT NULL,
+  thread_id BIGINT,
+  format TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+  UNIQUE NULLS NOT DISTINCT (code, channel_id, guild_id, thread_id)
+);
+
+CREATE INDEX idx_notification_targets_code ON notification_targets(code);
+CREATE INDEX idx_notification_targets_guild ON notification_targets(guild_id);
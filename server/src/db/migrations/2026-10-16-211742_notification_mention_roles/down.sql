@@ -0,0 +1 @@
+ALTER TABLE notification_targets DROP COLUMN mention_roles;
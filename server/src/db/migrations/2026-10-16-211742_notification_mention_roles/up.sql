@@ -0,0 +1 @@
+ALTER TABLE notification_targets ADD COLUMN mention_roles BIGINT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1 @@
+ALTER TABLE notification_targets ADD COLUMN active BOOLEAN NOT NULL DEFAULT TRUE;
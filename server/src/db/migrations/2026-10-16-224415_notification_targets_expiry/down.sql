@@ -0,0 +1,3 @@
+DROP INDEX idx_notification_targets_expires_at;
+
+ALTER TABLE notification_targets DROP COLUMN expires_at;
@@ -0,0 +1,3 @@
+ALTER TABLE notification_targets ADD COLUMN expires_at TIMESTAMP;
+
+CREATE INDEX idx_notification_targets_expires_at ON notification_targets(expires_at);
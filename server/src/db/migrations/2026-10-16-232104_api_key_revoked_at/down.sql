@@ -0,0 +1,3 @@
+DROP INDEX idx_api_keys_revoked_at;
+
+ALTER TABLE api_keys DROP COLUMN revoked_at;
@@ -0,0 +1,3 @@
+ALTER TABLE api_keys ADD COLUMN revoked_at TIMESTAMP;
+
+CREATE INDEX idx_api_keys_revoked_at ON api_keys(revoked_at);
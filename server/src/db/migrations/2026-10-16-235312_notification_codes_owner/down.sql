@@ -0,0 +1,3 @@
+DROP INDEX idx_notification_codes_owner;
+
+ALTER TABLE notification_codes DROP COLUMN owner;
@@ -0,0 +1,3 @@
+ALTER TABLE notification_codes ADD COLUMN owner VARCHAR(255);
+
+CREATE INDEX idx_notification_codes_owner ON notification_codes(owner);
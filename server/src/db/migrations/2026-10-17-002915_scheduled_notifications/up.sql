@@ -0,0 +1,12 @@
+CREATE TABLE scheduled_notifications (
+  id SERIAL PRIMARY KEY,
+  code VARCHAR(64) NOT NULL REFERENCES notification_codes(code) ON DELETE CASCADE,
+  triggering_event TEXT NOT NULL,
+  embed JSONB,
+  message TEXT,
+  send_at TIMESTAMP NOT NULL,
+  sent_at TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_scheduled_notifications_pending ON scheduled_notifications(send_at) WHERE sent_at IS NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE notification_codes DROP COLUMN last_error_at;
+ALTER TABLE notification_codes DROP COLUMN last_error;
+ALTER TABLE notification_codes DROP COLUMN last_success;
@@ -0,0 +1,3 @@
+ALTER TABLE notification_codes ADD COLUMN last_success TIMESTAMP;
+ALTER TABLE notification_codes ADD COLUMN last_error TEXT;
+ALTER TABLE notification_codes ADD COLUMN last_error_at TIMESTAMP;
@@ -1,4 +1,7 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
 
 use diesel::pg::PgConnection;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
@@ -6,7 +9,7 @@ use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
 use once_cell::sync::Lazy;
-use tracing::info;
+use tracing::{info, warn};
 
 #[cfg(not(test))]
 use crate::utils::config::get_config;
@@ -16,10 +19,15 @@ pub mod schema;
 
 pub type Pool = diesel::r2d2::Pool<ConnectionManager<PgConnection>>;
 pub type Connection = PooledConnection<diesel::r2d2::ConnectionManager<PgConnection>>;
+#[cfg(feature = "async-pool")]
+pub type AsyncConnection = deadpool_diesel::postgres::Object;
 
 static DB_POLL: Lazy<Arc<Mutex<Pool>>> =
     Lazy::new(|| Arc::new(Mutex::new(establish_connection_pool())));
 
+#[cfg(feature = "async-pool")]
+static ASYNC_DB_POOL: Lazy<deadpool_diesel::postgres::Pool> = Lazy::new(establish_async_pool);
+
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/db/migrations");
 
 /// Will select DATABASE_URL in a non-test environment (cargo run)
@@ -35,22 +43,205 @@ fn get_database_url() -> String {
         .expect("TEST_DATABASE_URL must be set for a testing environment")
 }
 
+/// Will select the configured retry policy (attempts, base delay) in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_retry_policy() -> (u32, Duration) {
+    let config = get_config();
+    (
+        config.database_retry_attempts,
+        Duration::from_millis(config.database_retry_delay_ms),
+    )
+}
+
+/// Will select a fixed retry policy (attempts, base delay) in a test environment (cargo test)
+#[cfg(test)]
+fn get_retry_policy() -> (u32, Duration) {
+    (3, Duration::from_millis(50))
+}
+
+/// Will select the configured wait for a free connection per attempt in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_checkout_timeout() -> Duration {
+    Duration::from_millis(get_config().database_checkout_timeout_ms)
+}
+
+/// Will select a fixed wait for a free connection per attempt in a test environment (cargo test)
+#[cfg(test)]
+fn get_checkout_timeout() -> Duration {
+    Duration::from_secs(2)
+}
+
 fn establish_connection_pool() -> Pool {
     let database_url = get_database_url();
     let manager = ConnectionManager::<PgConnection>::new(database_url);
 
+    // Short checkout timeout, so the retry policy bounds the wait instead of r2d2's default of 30 seconds
     r2d2::Pool::builder()
+        .connection_timeout(get_checkout_timeout())
         .build(manager)
         .expect("Failed to create pool!")
 }
 
-pub fn get_connection() -> Result<Connection, KohakuError> {
-    let pool = DB_POLL.lock().unwrap();
-    pool.get().map_err(KohakuError::DatabaseConnectionError)
+#[cfg(feature = "async-pool")]
+fn establish_async_pool() -> deadpool_diesel::postgres::Pool {
+    let manager = deadpool_diesel::postgres::Manager::new(
+        get_database_url(),
+        deadpool_diesel::Runtime::Tokio1,
+    );
+
+    deadpool_diesel::postgres::Pool::builder(manager)
+        .build()
+        .expect("Failed to create async pool!")
+}
+
+pub async fn get_connection() -> Result<Connection, KohakuError> {
+    get_connection_from(&DB_POLL).await
+}
+
+/// Acquires a connection from a shared pool, see [`get_connection_with_retry`].
+///
+/// A poisoned lock (a thread panicked while holding it) is recovered instead of failing every following checkout,
+/// as the pool itself stays consistent.
+///
+/// # Parameters
+/// - `pool` : Shared [`Pool`] to acquire the connection from
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : A pooled [`Connection`]
+/// - [`Err`] : A [`KohakuError::DatabaseConnectionError`] of the last attempt
+pub async fn get_connection_from(pool: &Mutex<Pool>) -> Result<Connection, KohakuError> {
+    // Clone the (internally reference counted) pool so the lock isn't held while retrying
+    let pool = lock_pool(pool).clone();
+    let (attempts, base_delay) = get_retry_policy();
+    get_connection_with_retry(&pool, attempts, base_delay).await
+}
+
+/// Helper: Locks the pool, recovering it if the lock is poisoned
+fn lock_pool(pool: &Mutex<Pool>) -> MutexGuard<'_, Pool> {
+    pool.lock().unwrap_or_else(|poisoned| {
+        warn!("[Database] Recovering pool lock poisoned by a panicked thread");
+        poisoned.into_inner()
+    })
+}
+
+/// Acquires a connection from the given pool, retrying with exponential backoff on failure.
+///
+/// The first attempt happens immediately. Every further attempt waits `base_delay * 2^(n-1)` beforehand.
+/// Checkouts run on a blocking thread and the backoff is awaited, so the async worker is never stalled.
+///
+/// # Parameters
+/// - `pool` : [`Pool`] to acquire the connection from
+/// - `attempts` : Maximum amount of attempts (at least one attempt is always made)
+/// - `base_delay` : Delay before the second attempt
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : A pooled [`Connection`]
+/// - [`Err`] : A [`KohakuError::DatabaseConnectionError`] of the last attempt
+pub async fn get_connection_with_retry(
+    pool: &Pool,
+    attempts: u32,
+    base_delay: Duration,
+) -> Result<Connection, KohakuError> {
+    let mut attempt = 1;
+    let mut delay = base_delay;
+
+    loop {
+        let checkout = pool.clone();
+        let result = tokio::task::spawn_blocking(move || checkout.get())
+            .await
+            .map_err(|e| {
+                KohakuError::InternalServerError(format!("Database checkout failed: {}", e))
+            })?;
+        match result {
+            Ok(conn) => return Ok(conn),
+            Err(e) if attempt >= attempts => return Err(KohakuError::DatabaseConnectionError(e)),
+            Err(e) => {
+                warn!(
+                    "[Database] Couldn't acquire connection (attempt {}/{}): {} - retrying in {:?}",
+                    attempt, attempts, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Acquires a connection from the async pool without blocking the async runtime.
+///
+/// Queries have to be run via [`deadpool_diesel::postgres::Object::interact`], which moves them onto a blocking thread.
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : A pooled [`AsyncConnection`]
+/// - [`Err`] : A [`KohakuError::OperationError`] if no connection could be acquired
+#[cfg(feature = "async-pool")]
+pub async fn get_connection_async() -> Result<AsyncConnection, KohakuError> {
+    ASYNC_DB_POOL
+        .get()
+        .await
+        .map_err(|e| KohakuError::OperationError {
+            operation: "acquiring an async database connection".to_string(),
+            source: Box::new(e),
+        })
+}
+
+/// Runs database operations on a pooled connection.
+///
+/// With the `async-pool` feature, `f` runs on a blocking thread of the async pool ([`get_connection_async`]).
+/// Otherwise `f` runs on a blocking thread with a connection of [`get_connection`]. Either way the async worker is not stalled.
+///
+/// # Parameters
+/// - `f` : Operations to run with the connection
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The result of `f`
+/// - [`Err`] : A [`KohakuError`] of `f` or of acquiring the connection
+pub async fn with_connection<F, T>(f: F) -> Result<T, KohakuError>
+where
+    F: FnOnce(&mut PgConnection) -> Result<T, KohakuError> + Send + 'static,
+    T: Send + 'static,
+{
+    #[cfg(feature = "async-pool")]
+    {
+        get_connection_async()
+            .await?
+            .interact(f)
+            .await
+            .map_err(|e| {
+                KohakuError::InternalServerError(format!("Database operation failed: {}", e))
+            })?
+    }
+    #[cfg(not(feature = "async-pool"))]
+    {
+        let mut conn = get_connection().await?;
+        tokio::task::spawn_blocking(move || f(&mut conn))
+            .await
+            .map_err(|e| {
+                KohakuError::InternalServerError(format!("Database operation failed: {}", e))
+            })?
+    }
+}
+
+pub async fn migrate() -> Result<(), KohakuError> {
+    let mut conn = get_connection().await?;
+    run_migrations(&mut conn)
 }
 
-pub fn migrate() -> Result<(), KohakuError> {
-    let mut conn = get_connection()?;
+/// Applies all pending migrations on the given connection
+///
+/// # Parameters
+/// - `conn` : Connection to the database to migrate
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : If all pending migrations were applied
+/// - [`Err`] : A [`KohakuError::InternalServerError`] if a migration failed
+pub fn run_migrations(conn: &mut PgConnection) -> Result<(), KohakuError> {
     let mig = conn
         .run_pending_migrations(MIGRATIONS)
         .map_err(|e| KohakuError::InternalServerError(format!("Migration failed: {}", e)))?;
@@ -11,5 +11,78 @@ diesel::table! {
         owner -> Varchar,
         scopes -> Array<Text>,
         created_at -> Timestamp,
+        allowed_ips -> Array<Text>,
+        revoked_at -> Nullable<Timestamp>,
     }
 }
+
+diesel::table! {
+    auth_audit (id) {
+        id -> Int4,
+        #[max_length = 32]
+        event_type -> Varchar,
+        key_id -> Nullable<Int4>,
+        #[max_length = 255]
+        owner -> Nullable<Varchar>,
+        success -> Bool,
+        #[max_length = 45]
+        ip -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notification_codes (code) {
+        #[max_length = 64]
+        code -> Varchar,
+        description -> Nullable<Text>,
+        last_used -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        #[max_length = 255]
+        owner -> Nullable<Varchar>,
+        last_success -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        last_error_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    notification_targets (id) {
+        id -> Int4,
+        #[max_length = 64]
+        code -> Varchar,
+        channel_id -> Int8,
+        guild_id -> Int8,
+        thread_id -> Nullable<Int8>,
+        format -> Nullable<Text>,
+        created_at -> Timestamp,
+        mention_roles -> Array<Int8>,
+        active -> Bool,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    scheduled_notifications (id) {
+        id -> Int4,
+        #[max_length = 64]
+        code -> Varchar,
+        triggering_event -> Text,
+        embed -> Nullable<Jsonb>,
+        message -> Nullable<Text>,
+        send_at -> Timestamp,
+        sent_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(notification_targets -> notification_codes (code));
+diesel::joinable!(scheduled_notifications -> notification_codes (code));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    auth_audit,
+    notification_codes,
+    notification_targets,
+    scheduled_notifications,
+);
@@ -1,40 +1,90 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware::from_fn, web, App, HttpServer};
+use std::path::Path;
 use tracing::{error, info};
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::{
+    fmt::writer::{BoxMakeWriter, MakeWriterExt},
+    FmtSubscriber,
+};
 
 use crate::{
     db::migrate,
     utils::{
-        comm::{self, auth::jwt::init_jwtservice, websocket::manager::init_manager},
-        config::{get_config, init_config},
+        comm::{
+            self,
+            auth::{
+                jwt::init_jwtservice,
+                tasks::{AuthAuditPurge, RevokedKeysPurge},
+            },
+            events::{
+                notifications::reschedule_pending,
+                tasks::{ExpiredSubscriptionsCleanup, SentNotificationsPurge, StaleCodesPurge},
+            },
+            websocket::{
+                manager::init_manager,
+                tasks::{DeliveryRetry, StaleConnectionReaper},
+            },
+        },
+        config::{get_config, init_config, LogFormat},
+        middleware::{
+            compression::build_compression,
+            cors::build_cors,
+            logging::request_logger,
+            payload::{build_json_config, build_payload_config, build_query_config},
+        },
         scheduler::{get_scheduler, init_scheduler},
     },
 };
 
+mod api;
 mod db;
 mod utils;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenvy::dotenv().ok();
-    if init_config().is_err() {
-        error!("Couldn't initialize config!");
-    }
+    // No tracing subscriber is installed before the config is loaded, so the error is returned to reach stderr
+    init_config()
+        .map_err(|e| std::io::Error::other(format!("Couldn't initialize config: {}", e)))?;
     let config = get_config();
 
-    FmtSubscriber::builder()
+    // Optional rolling log file next to stdout. The guard flushes the file writer on shutdown
+    let (writer, log_guard) = match &config.log_file {
+        Some(log_file) => {
+            let path = Path::new(log_file);
+            let directory = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let file_name = path.file_name().unwrap_or(path.as_os_str());
+            let (file_writer, guard) = tracing_appender::non_blocking(
+                tracing_appender::rolling::daily(directory, file_name),
+            );
+            (
+                BoxMakeWriter::new(std::io::stdout.and(file_writer)),
+                Some(guard),
+            )
+        }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    };
+
+    let subscriber = FmtSubscriber::builder()
         .with_max_level(config.logging_level)
         .with_line_number(true)
         //.with_file(true)
         .with_target(false)
         .with_thread_ids(true)
-        .pretty()
-        .init();
+        // No color codes in the log file
+        .with_ansi(log_guard.is_none())
+        .with_writer(writer);
+    match config.log_format {
+        LogFormat::Pretty => subscriber.pretty().init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
     info!("Logging initialized!");
 
     // Setup database
     info!("Running database migration ...");
-    if let Err(e) = migrate() {
+    if let Err(e) = migrate().await {
         error!("{}", e);
     }
 
@@ -45,6 +95,36 @@ async fn main() -> std::io::Result<()> {
     } else {
         info!("Scheduler initilialized! Starting scheduler ...");
         let scheduler = get_scheduler().await;
+        if let Err(e) = scheduler.add_task(ExpiredSubscriptionsCleanup::new()).await {
+            error!("Couldn't schedule expired subscriptions cleanup: {}", e);
+        }
+        if let Err(e) = scheduler.add_task(StaleConnectionReaper::new()).await {
+            error!("Couldn't schedule stale connection reaper: {}", e);
+        }
+        if let Err(e) = scheduler.add_task(DeliveryRetry::new()).await {
+            error!("Couldn't schedule delivery retry: {}", e);
+        }
+        if let Err(e) = scheduler.add_task(RevokedKeysPurge::new()).await {
+            error!("Couldn't schedule revoked keys purge: {}", e);
+        }
+        if config.history_retention_days > 0 {
+            if let Err(e) = scheduler.add_task(AuthAuditPurge::new()).await {
+                error!("Couldn't schedule audit log purge: {}", e);
+            }
+            if let Err(e) = scheduler.add_task(SentNotificationsPurge::new()).await {
+                error!("Couldn't schedule sent notifications purge: {}", e);
+            }
+        }
+        if config.events_stale_code_days > 0 {
+            if let Err(e) = scheduler.add_task(StaleCodesPurge::new()).await {
+                error!("Couldn't schedule stale codes purge: {}", e);
+            }
+        }
+        match reschedule_pending().await {
+            Ok(0) => {}
+            Ok(pending) => info!("Rescheduled {} pending notification(s)", pending),
+            Err(e) => error!("Couldn't reschedule pending notifications: {}", e),
+        }
         if scheduler.start().await.is_err() {
             error!("Couldn't start scheduler!");
         }
@@ -60,17 +140,34 @@ async fn main() -> std::io::Result<()> {
     }
 
     // Start websocket
-    let _ = init_manager();
+    info!("Setting up websocket manager ...");
+    if init_manager().is_ok() {
+        info!("Websocket manager started!");
+    } else {
+        error!("Couldn't initialize websocket manager! Notifications will be dropped!");
+    }
 
-    HttpServer::new(|| {
+    let mut server = HttpServer::new(|| {
+        let config = get_config();
         App::new()
-            .service(
-                web::scope("/api")
-                    .service(web::scope("/auth").configure(comm::auth::routes::configure)),
+            .app_data(build_json_config(&config))
+            .app_data(build_payload_config(&config))
+            .app_data(build_query_config())
+            .wrap(build_compression(&config))
+            .wrap(build_cors(&config))
+            .wrap(from_fn(request_logger))
+            .configure(api::health::configure)
+            // Before the `/api` scope, which would shadow it otherwise
+            .route(
+                "/api/ws",
+                web::get().to(comm::websocket::routes::ws_handler),
             )
+            .configure(api::configure)
             .route("/ws", web::get().to(comm::websocket::routes::ws_handler))
-    })
-    .bind((config.server_addr.clone(), config.server_port))?
-    .run()
-    .await
+    });
+    for (addr, port) in &config.server_binds {
+        server = server.bind((addr.as_str(), *port))?;
+        info!("Listening on {}:{}", addr, port);
+    }
+    server.run().await
 }
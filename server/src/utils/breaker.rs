@@ -0,0 +1,225 @@
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use tracing::{info, warn};
+
+use crate::utils::error::KohakuError;
+
+/// Circuit breaker shared by all calls to external services
+pub static BREAKER: Lazy<CircuitBreaker> = Lazy::new(get_breaker);
+
+/// Will select the configured thresholds in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_breaker() -> CircuitBreaker {
+    let config = crate::utils::config::get_config();
+    CircuitBreaker::new(
+        config.breaker_failure_threshold,
+        config.breaker_cooldown_sec,
+    )
+}
+
+/// Will select fixed thresholds in a test environment (cargo test)
+#[cfg(test)]
+fn get_breaker() -> CircuitBreaker {
+    CircuitBreaker::new(3, 60)
+}
+
+/// State of the circuit of a single service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls pass through
+    Closed,
+    /// The cooldown passed, calls pass through to test whether the service recovered
+    HalfOpen,
+    /// Calls fail fast until the cooldown passed
+    Open,
+}
+
+impl CircuitState {
+    /// Value of the state in [`CircuitBreaker::to_metrics`]
+    pub fn as_gauge(&self) -> u8 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        }
+    }
+}
+
+/// Failures of a single service
+#[derive(Debug, Default)]
+struct Circuit {
+    /// Consecutive failed calls
+    failures: u32,
+    /// Unix timestamp (seconds) the circuit was opened at
+    opened_at: Option<i64>,
+}
+
+/// Stops calling external services which keep failing.
+///
+/// After `failure_threshold` consecutive failures of a service its circuit opens and calls fail fast with a
+/// [`KohakuError::ExternalServiceError`] for `cooldown_secs`. Afterwards the circuit is half-open: calls pass through again,
+/// a success closes the circuit while a failure opens it for another cooldown.
+/// Meant to wrap [`retry_with_backoff`](crate::utils::retry::retry_with_backoff), so an outage isn't retried over and over:
+/// ```ignore
+/// let body = BREAKER
+///     .call("anilist", || retry_with_backoff(3, Duration::from_millis(500), fetch))
+///     .await?;
+/// ```
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown_secs: i64,
+    /// Circuits by service name
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl CircuitBreaker {
+    /// # Parameters
+    /// - `failure_threshold` : Consecutive failures opening the circuit of a service
+    /// - `cooldown_secs` : Seconds an opened circuit fails fast before it gets half-open
+    pub fn new(failure_threshold: u32, cooldown_secs: i64) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown_secs,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Current state of the circuit of a service
+    pub fn state(&self, service: &str) -> CircuitState {
+        self.state_at(service, Utc::now().timestamp())
+    }
+
+    /// Same as [`CircuitBreaker::state`] at a given time.
+    ///
+    /// # Parameters
+    /// - `service` : Name of the service
+    /// - `now` : Current time as unix timestamp (seconds)
+    pub fn state_at(&self, service: &str, now: i64) -> CircuitState {
+        let circuits = self.circuits.lock().unwrap();
+        circuits
+            .get(service)
+            .map_or(CircuitState::Closed, |circuit| {
+                self.circuit_state(circuit, now)
+            })
+    }
+
+    /// Helper: State of a circuit at a given time
+    fn circuit_state(&self, circuit: &Circuit, now: i64) -> CircuitState {
+        match circuit.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if now < opened_at + self.cooldown_secs => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Checks whether a service may be called.
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : If the circuit of the service is closed or half-open
+    /// - [`Err`] : A [`KohakuError::ExternalServiceError`] if the circuit is open
+    pub fn check(&self, service: &str) -> Result<(), KohakuError> {
+        self.check_at(service, Utc::now().timestamp())
+    }
+
+    /// Same as [`CircuitBreaker::check`] at a given time.
+    ///
+    /// # Parameters
+    /// - `service` : Name of the service
+    /// - `now` : Current time as unix timestamp (seconds)
+    pub fn check_at(&self, service: &str, now: i64) -> Result<(), KohakuError> {
+        let circuits = self.circuits.lock().unwrap();
+        match circuits.get(service) {
+            Some(circuit) if self.circuit_state(circuit, now) == CircuitState::Open => {
+                let retry_in = circuit.opened_at.unwrap_or(now) + self.cooldown_secs - now;
+                Err(KohakuError::ExternalServiceError(format!(
+                    "Circuit of `{}` is open after {} consecutive failures - retry in {}s",
+                    service, circuit.failures, retry_in
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records a successful call, closing the circuit of the service
+    pub fn record_success(&self, service: &str) {
+        if let Some(circuit) = self.circuits.lock().unwrap().remove(service) {
+            if circuit.opened_at.is_some() {
+                info!("[Breaker] Circuit of `{}` closed again", service);
+            }
+        }
+    }
+
+    /// Records a failed call, opening the circuit of the service once the threshold is reached
+    pub fn record_failure(&self, service: &str) {
+        self.record_failure_at(service, Utc::now().timestamp());
+    }
+
+    /// Same as [`CircuitBreaker::record_failure`] at a given time.
+    ///
+    /// # Parameters
+    /// - `service` : Name of the service
+    /// - `now` : Current time as unix timestamp (seconds)
+    pub fn record_failure_at(&self, service: &str, now: i64) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(service.to_string()).or_default();
+        circuit.failures += 1;
+        if circuit.failures >= self.failure_threshold {
+            warn!(
+                "[Breaker] Circuit of `{}` opened after {} consecutive failures - failing fast for {}s",
+                service, circuit.failures, self.cooldown_secs
+            );
+            circuit.opened_at = Some(now);
+        }
+    }
+
+    /// Runs a call to an external service, unless its circuit is open.
+    ///
+    /// # Parameters
+    /// - `service` : Name of the service
+    /// - `f` : The call. Its outcome is recorded for the circuit of the service
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The result of the call
+    /// - [`Err`] : A [`KohakuError::ExternalServiceError`] if the circuit is open, the error of the call otherwise
+    pub async fn call<F, Fut, T>(&self, service: &str, f: F) -> Result<T, KohakuError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, KohakuError>>,
+    {
+        self.check(service)?;
+        let result = f().await;
+        match result {
+            Ok(_) => self.record_success(service),
+            Err(_) => self.record_failure(service),
+        }
+        result
+    }
+
+    /// Renders the state of every tracked circuit as gauges in the Prometheus text format.
+    /// See [`CircuitState::as_gauge`] for the values
+    pub fn to_metrics(&self) -> String {
+        let now = Utc::now().timestamp();
+        let circuits = self.circuits.lock().unwrap();
+        let mut states = circuits
+            .iter()
+            .map(|(service, circuit)| (service, self.circuit_state(circuit, now)))
+            .collect::<Vec<_>>();
+        states.sort_by_key(|(service, _)| *service);
+
+        let mut metrics = "# HELP kohaku_circuit_state State of the circuit of an external service (0 = closed, 1 = half-open, 2 = open)\n\
+             # TYPE kohaku_circuit_state gauge\n"
+            .to_string();
+        for (service, state) in states {
+            metrics.push_str(&format!(
+                "kohaku_circuit_state{{service=\"{}\"}} {}\n",
+                service,
+                state.as_gauge()
+            ));
+        }
+        metrics
+    }
+}
@@ -4,17 +4,22 @@ use argon2::{
 };
 use rand::Rng;
 
-use crate::utils::error::KohakuError;
+use crate::utils::{comm::auth::models::ApiKey, error::KohakuError};
 
 /// Available chars for random string generation
 pub const CHARSET: &[u8] =
     b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!#$%&*+-/=";
 
+/// Available chars for the random part of key prefixes.
+/// Alphanumeric only, as prefixes are stored, looked up and logged in plain text
+pub const PREFIX_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
 /// Generates an API key and returns the full key as well as the 10-char long prefix of said key.
 ///
 /// # Returns
 /// A pair ([`String`], [`String`]) where the first string is the `full_key`, with a length of 42 chars,
 /// and the second string is the `prefix`, with a length of 10 chars.
+/// The prefix is built from [`PREFIX_CHARSET`], the secret from the full [`CHARSET`].
 ///
 /// # Examples
 /// ```rust
@@ -23,7 +28,7 @@ pub const CHARSET: &[u8] =
 /// assert!(key.starts_with(&prefix));
 /// ```
 pub fn generate_key() -> (String, String) {
-    let prefix = format!("khk_{}", random_string(6));
+    let prefix = format!("khk_{}", random_string_from(PREFIX_CHARSET, 6));
     let secret = random_string(31);
 
     let full_key = format!("{}_{}", prefix, secret);
@@ -45,12 +50,24 @@ pub fn generate_key() -> (String, String) {
 /// assert_eq!(random_string.len(), 5);
 /// ```
 pub fn random_string(length: usize) -> String {
+    random_string_from(CHARSET, length)
+}
+
+/// Generate a random string of given size and based on the given alphabet.
+///
+/// # Parameters
+/// - `charset` : Alphabet of the result. Must not be empty
+/// - `length` : A `usize`d measurement how many chars the result should have
+///
+/// # Returns
+/// (Pseudo) randomized [`String`] of size `length` with the alphabet `charset`
+pub fn random_string_from(charset: &[u8], length: usize) -> String {
     let mut rng = rand::rng();
 
     (0..length)
         .map(|_| {
-            let idx = rng.random_range(0..CHARSET.len());
-            CHARSET[idx] as char
+            let idx = rng.random_range(0..charset.len());
+            charset[idx] as char
         })
         .collect()
 }
@@ -111,6 +128,26 @@ pub fn verify_key(key: &str, hash: &str) -> Result<bool, KohakuError> {
     }
 }
 
+/// Finds the candidate whose hash matches the given API key.
+///
+/// All candidates are verified, even after a match was found, so the response time doesn't reveal
+/// the position of the matching key. Candidates with a corrupted hash never match.
+///
+/// # Parameters
+/// - `full_key` : Prior generated API key
+/// - `candidates` : [`ApiKey`]s to check, usually all keys sharing the prefix of `full_key`
+///
+/// # Returns
+/// An [`Option`] which is either
+/// - [`Some`] : The first [`ApiKey`] matching `full_key`
+/// - [`None`] : If no candidate matches
+pub fn find_matching_key<'a>(full_key: &str, candidates: &'a [ApiKey]) -> Option<&'a ApiKey> {
+    candidates.iter().fold(None, |found, candidate| {
+        let matches = verify_key(full_key, &candidate.hashed_key).unwrap_or(false);
+        found.or(matches.then_some(candidate))
+    })
+}
+
 /// Extracts the prefix from a given API Key.
 ///
 /// Format is `khk_XXXXXX_XXXX...` and the prefix ends at (excludingly) the second '_'
@@ -0,0 +1,103 @@
+use std::{marker::PhantomData, ops::Deref};
+
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+
+use crate::utils::{
+    comm::auth::{
+        check_authorization_token,
+        models::Claims,
+        scopes::{Scope, ADMIN_MANAGE, EVENTS_MANAGE, EVENTS_SUBSCRIBE, KEYS_MANAGE},
+    },
+    error::KohakuError,
+};
+
+/// Set of scopes a handler requires from the calling token. Used as type parameter of [`AuthedClaims`].
+pub trait RequiredScopes {
+    /// Required [`Scope`]s. An empty slice only requires a valid token.
+    const SCOPES: &'static [Scope];
+}
+
+/// Only requires a valid, not blacklisted token
+pub struct NoScopes;
+
+impl RequiredScopes for NoScopes {
+    const SCOPES: &'static [Scope] = &[];
+}
+
+/// Requires `keys:manage` (bootstrap token)
+pub struct KeysManage;
+
+impl RequiredScopes for KeysManage {
+    const SCOPES: &'static [Scope] = &[KEYS_MANAGE];
+}
+
+/// Requires `admin:manage`
+pub struct AdminManage;
+
+impl RequiredScopes for AdminManage {
+    const SCOPES: &'static [Scope] = &[ADMIN_MANAGE];
+}
+
+/// Requires `events:subscribe`
+pub struct EventsSubscribe;
+
+impl RequiredScopes for EventsSubscribe {
+    const SCOPES: &'static [Scope] = &[EVENTS_SUBSCRIBE];
+}
+
+/// Requires `events:manage`
+pub struct EventsManage;
+
+impl RequiredScopes for EventsManage {
+    const SCOPES: &'static [Scope] = &[EVENTS_MANAGE];
+}
+
+/// Extractor for the [`Claims`] of an authorized request.
+///
+/// Runs [`check_authorization_token`] with the scopes of `S` before the handler is called,
+/// so handlers only need to take it as an argument. Failing checks return the corresponding
+/// [`KohakuError`] response without calling the handler.
+///
+/// # Examples
+/// ```rust
+/// async fn handler(claims: AuthedClaims<KeysManage>) -> Result<HttpResponse, KohakuError> {
+///     info!("Called by {}", claims.owner);
+///     Ok(HttpResponse::Ok().finish())
+/// }
+/// ```
+pub struct AuthedClaims<S: RequiredScopes = NoScopes> {
+    pub claims: Claims,
+    _scopes: PhantomData<S>,
+}
+
+impl<S: RequiredScopes> AuthedClaims<S> {
+    /// Consumes the extractor and returns the inner [`Claims`]
+    pub fn into_inner(self) -> Claims {
+        self.claims
+    }
+}
+
+impl<S: RequiredScopes> Deref for AuthedClaims<S> {
+    type Target = Claims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.claims
+    }
+}
+
+impl<S: RequiredScopes> FromRequest for AuthedClaims<S> {
+    type Error = KohakuError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let claims = check_authorization_token(&req, Some(S::SCOPES)).await?;
+            Ok(AuthedClaims {
+                claims,
+                _scopes: PhantomData,
+            })
+        })
+    }
+}
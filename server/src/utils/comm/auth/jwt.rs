@@ -1,32 +1,212 @@
 use chrono::{Duration, NaiveDateTime, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use std::{collections::HashMap, sync::Arc};
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock as SyncRwLock, RwLockReadGuard, RwLockWriteGuard},
+};
 use tokio::sync::{OnceCell, RwLock};
+use tracing::warn;
+use uuid::Uuid;
 
-use crate::utils::comm::auth::token_duration;
+use crate::utils::comm::auth::{
+    scopes::{parse_scopes, RESERVED_SCOPE},
+    token_duration,
+};
 #[allow(unused_imports)] // ApiKey is linked in the documentation
 use crate::utils::{
-    comm::auth::models::{ApiKey, Claims, TokenResponse, TokenType},
-    config::get_config,
+    comm::auth::models::{ApiKey, Claims, ResumeClaims, TokenResponse, TokenType},
+    config::{get_config, MIN_ENCRYPTION_KEY_LEN},
     error::KohakuError,
 };
 
 static JWT_SERVICE: OnceCell<Arc<JWTService>> = OnceCell::const_new();
 
+/// Suffix of the `aud` claim of resume tokens, so they are never accepted as regular tokens (and vice versa)
+const RESUME_AUDIENCE_SUFFIX: &str = ":ws-resume";
+
+/// Default maximum amount of blacklisted API keys, see [`JWTService::with_blacklist_max`]
+pub const BLACKLIST_MAX_DEFAULT: usize = 10_000;
+
+/// Keys tokens are signed and verified with, swapped by [`JWTService::rotate_key`]
+struct SigningKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// Decoding key before the last rotation and the unix timestamp (seconds) until which it is still accepted
+    previous: Option<(DecodingKey, i64)>,
+}
+
 /// JsonWebToken Service for generating, verifying and managing JWTs
 pub struct JWTService {
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
+    keys: SyncRwLock<SigningKeys>,
+    /// `iss` claim of issued tokens, required on validation
+    issuer: String,
+    /// `aud` claim of issued tokens, required on validation
+    audience: String,
     // Blacklist for API Key revokation to ensure early denying of still active JWTs
     blacklist: RwLock<HashMap<i32, NaiveDateTime>>,
+    /// Maximum amount of blacklisted API keys, see [`JWTService::blacklist_key`]
+    blacklist_max: usize,
+    /// Refreshes per API key since its last login, see [`JWTService::record_refresh`]
+    refresh_counts: RwLock<HashMap<i32, u32>>,
+    /// Maximum amount of refreshes per login. `0` allows unlimited refreshes
+    refresh_max: u32,
+}
+
+/// Will select the configured issuer and audience in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_token_identity() -> (String, String) {
+    let config = get_config();
+    (config.jwt_issuer.clone(), config.jwt_audience.clone())
+}
+
+/// Will select a fixed issuer and audience in a test environment (cargo test)
+#[cfg(test)]
+fn get_token_identity() -> (String, String) {
+    ("kohaku-test".to_string(), "kohaku-test-api".to_string())
+}
+
+/// Will select the configured refresh limit in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_refresh_max() -> u32 {
+    get_config().jwt_refresh_max
+}
+
+/// Will allow unlimited refreshes in a test environment (cargo test)
+#[cfg(test)]
+fn get_refresh_max() -> u32 {
+    0
+}
+
+/// Will select the configured blacklist size in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_blacklist_max() -> usize {
+    get_config().jwt_blacklist_max
+}
+
+/// Will select [`BLACKLIST_MAX_DEFAULT`] in a test environment (cargo test)
+#[cfg(test)]
+fn get_blacklist_max() -> usize {
+    BLACKLIST_MAX_DEFAULT
 }
 
 impl JWTService {
-    pub fn new(encryption_key: &[u8]) -> Self {
+    /// # Parameters
+    /// - `encryption_key` : Secret to sign and verify tokens with
+    /// - `issuer` : `iss` claim of issued tokens. Tokens of other issuers are rejected
+    /// - `audience` : `aud` claim of issued tokens. Tokens for other audiences are rejected
+    pub fn new(encryption_key: &[u8], issuer: String, audience: String) -> Self {
         Self {
-            encoding_key: EncodingKey::from_secret(encryption_key),
-            decoding_key: DecodingKey::from_secret(encryption_key),
+            keys: SyncRwLock::new(SigningKeys {
+                encoding: EncodingKey::from_secret(encryption_key),
+                decoding: DecodingKey::from_secret(encryption_key),
+                previous: None,
+            }),
+            issuer,
+            audience,
             blacklist: RwLock::new(HashMap::new()),
+            blacklist_max: BLACKLIST_MAX_DEFAULT,
+            refresh_counts: RwLock::new(HashMap::new()),
+            refresh_max: 0,
+        }
+    }
+
+    /// Bounds the blacklist to the given amount of API keys. Defaults to [`BLACKLIST_MAX_DEFAULT`].
+    ///
+    /// # Parameters
+    /// - `blacklist_max` : Maximum amount of blacklisted API keys, see [`JWTService::blacklist_key`]
+    pub fn with_blacklist_max(mut self, blacklist_max: usize) -> Self {
+        self.blacklist_max = blacklist_max;
+        self
+    }
+
+    /// Bounds the amount of access tokens minted from refresh tokens per login. Defaults to `0` (unlimited).
+    ///
+    /// # Parameters
+    /// - `refresh_max` : Maximum amount of refreshes per API key, see [`JWTService::record_refresh`]
+    pub fn with_refresh_max(mut self, refresh_max: u32) -> Self {
+        self.refresh_max = refresh_max;
+        self
+    }
+
+    /// Replaces the secret tokens are signed and verified with, without a restart.
+    ///
+    /// Tokens signed with the previous secret are still accepted for `grace_secs`, so in-flight tokens keep working
+    /// until clients fetched new ones. Only the secret of the last rotation is kept.
+    /// The new secret is not persisted: `SERVER_ENCRYPTION_KEY` must be updated as well to survive a restart.
+    ///
+    /// # Parameters
+    /// - `new_key` : New secret, at least [`MIN_ENCRYPTION_KEY_LEN`] bytes long
+    /// - `grace_secs` : Seconds tokens of the previous secret are still accepted. `0` rejects them immediately.
+    ///   At most the lifetime of a refresh token, as no token of the previous secret outlives it
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The unix timestamp (seconds) until which tokens of the previous secret are accepted
+    /// - [`Err`] : A [`KohakuError::ValidationError`] if the secret is too short or the grace window is negative or too long
+    pub fn rotate_key(&self, new_key: &[u8], grace_secs: i64) -> Result<i64, KohakuError> {
+        if new_key.len() < MIN_ENCRYPTION_KEY_LEN {
+            return Err(KohakuError::ValidationError(format!(
+                "Signing key must be at least {} bytes long",
+                MIN_ENCRYPTION_KEY_LEN
+            )));
+        }
+        if grace_secs < 0 {
+            return Err(KohakuError::ValidationError(
+                "Grace window must not be negative".to_string(),
+            ));
+        }
+        let max_grace_secs = token_duration(&TokenType::Refresh) as i64;
+        let grace_until = Utc::now()
+            .timestamp()
+            .checked_add(grace_secs)
+            .filter(|_| grace_secs <= max_grace_secs)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(format!(
+                    "Grace window must not exceed {} seconds",
+                    max_grace_secs
+                ))
+            })?;
+        let mut keys = self.write_keys();
+        let previous = std::mem::replace(&mut keys.decoding, DecodingKey::from_secret(new_key));
+        keys.encoding = EncodingKey::from_secret(new_key);
+        keys.previous = Some((previous, grace_until));
+        Ok(grace_until)
+    }
+
+    /// Helper: Read access to the signing keys, recovering the lock if a thread panicked while holding it
+    fn read_keys(&self) -> RwLockReadGuard<'_, SigningKeys> {
+        self.keys.read().unwrap_or_else(|poisoned| {
+            warn!("[Authentication] Recovering signing key lock poisoned by a panicked thread");
+            poisoned.into_inner()
+        })
+    }
+
+    /// Helper: Write access to the signing keys, recovering the lock if a thread panicked while holding it
+    fn write_keys(&self) -> RwLockWriteGuard<'_, SigningKeys> {
+        self.keys.write().unwrap_or_else(|poisoned| {
+            warn!("[Authentication] Recovering signing key lock poisoned by a panicked thread");
+            poisoned.into_inner()
+        })
+    }
+
+    /// Helper: Decodes a token with the current key, falling back to the previous key within its grace window
+    fn decode_token<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> jsonwebtoken::errors::Result<TokenData<T>> {
+        let keys = self.read_keys();
+        match decode::<T>(token, &keys.decoding, validation) {
+            Err(e) if *e.kind() == ErrorKind::InvalidSignature => match &keys.previous {
+                Some((previous, until)) if Utc::now().timestamp() < *until => {
+                    decode::<T>(token, previous, validation)
+                }
+                _ => Err(e),
+            },
+            result => result,
         }
     }
 
@@ -55,7 +235,74 @@ impl JWTService {
         scopes: Vec<String>,
         token_type: TokenType,
     ) -> Result<String, KohakuError> {
-        let management_scope = scopes.contains(&"keys:manage".to_string());
+        self.create_token_not_before(owner, key_id, scopes, token_type, None)
+    }
+
+    /// Create one token for the given API key and scopes that only becomes valid at a given time.
+    ///
+    /// Same as [`JWTService::create_token`], but the lifetime of the token starts at `nbf` instead of now.
+    ///
+    /// # Parameters
+    /// - `owner` : [`String`] based identifier which service / user uses this key
+    /// - `key_id`: Identifier of API key in the database
+    /// - `scopes`: [`String`] based vector that grants permissions in a `category:verb` manner
+    /// - `token_type`: [`TokenType::Bootstrap`], [`TokenType::Access`] or [`TokenType::Refresh`]
+    /// - `nbf`: Optional unix timestamp before which the token is rejected. If [`None`] the token is valid immediately
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : A [`String`] representation of the token
+    /// - [`Err`] : A [`KohakuError`] if some operation fails or the input is invalid
+    pub fn create_token_not_before(
+        &self,
+        owner: String,
+        key_id: i32,
+        scopes: Vec<String>,
+        token_type: TokenType,
+        nbf: Option<usize>,
+    ) -> Result<String, KohakuError> {
+        self.build_token(owner, key_id, scopes, token_type, nbf, None)
+    }
+
+    /// Create one token for the given API key and scopes that is bound to a client.
+    ///
+    /// Same as [`JWTService::create_token`], but the token carries a `cid` claim. Routes can then
+    /// compare it against the identifier the client presents (e.g. on refresh).
+    ///
+    /// # Parameters
+    /// - `owner` : [`String`] based identifier which service / user uses this key
+    /// - `key_id`: Identifier of API key in the database
+    /// - `scopes`: [`String`] based vector that grants permissions in a `category:verb` manner
+    /// - `token_type`: [`TokenType::Bootstrap`], [`TokenType::Access`] or [`TokenType::Refresh`]
+    /// - `client_id`: Optional client identifier / device hash. If [`None`] the token is not bound
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : A [`String`] representation of the token
+    /// - [`Err`] : A [`KohakuError`] if some operation fails or the input is invalid
+    pub fn create_bound_token(
+        &self,
+        owner: String,
+        key_id: i32,
+        scopes: Vec<String>,
+        token_type: TokenType,
+        client_id: Option<String>,
+    ) -> Result<String, KohakuError> {
+        self.build_token(owner, key_id, scopes, token_type, None, client_id)
+    }
+
+    /// Helper: Validates the arguments and encodes the token
+    fn build_token(
+        &self,
+        owner: String,
+        key_id: i32,
+        scopes: Vec<String>,
+        token_type: TokenType,
+        nbf: Option<usize>,
+        cid: Option<String>,
+    ) -> Result<String, KohakuError> {
+        let scopes = parse_scopes(&scopes)?;
+        let management_scope = scopes.contains(&RESERVED_SCOPE);
         let is_bootstrap = token_type == TokenType::Bootstrap;
 
         // Check if given Arguments are valid (`keys:manage` exlcusively and uniquely for bootstrap key & key_id = -1 for bootstrap)
@@ -80,17 +327,23 @@ impl JWTService {
         let now = Utc::now().timestamp() as usize;
         let duration = token_duration(&token_type);
 
+        let valid_from = nbf.map_or(now, |nbf| nbf.max(now));
+
         let claims = Claims {
             owner,
             key_id,
-            scopes: scopes.clone(),
+            scopes,
             token_type,
-            exp: now + duration,
+            exp: valid_from + duration,
             iat: now,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            nbf,
+            cid,
         };
 
         // Create token
-        encode(&Header::default(), &claims, &self.encoding_key)
+        encode(&Header::default(), &claims, &self.read_keys().encoding)
             .map_err(|e| KohakuError::InternalServerError(e.to_string()))
     }
 
@@ -105,7 +358,7 @@ impl JWTService {
     pub fn create_bootstrap_token(&self) -> Result<TokenResponse, KohakuError> {
         let owner = "system".to_string();
         let key_id = -1;
-        let scopes = vec!["keys:manage".to_string()];
+        let scopes = vec![RESERVED_SCOPE.to_string()];
         let token_type = TokenType::Bootstrap;
 
         let token = self.create_token(owner, key_id, scopes, token_type)?;
@@ -137,13 +390,41 @@ impl JWTService {
         owner: &str,
         scopes: Vec<String>,
     ) -> Result<TokenResponse, KohakuError> {
-        let access_token =
-            self.create_token(owner.to_string(), key_id, scopes.clone(), TokenType::Access)?;
-        let refresh_token = self.create_token(
+        self.create_bound_tokens(key_id, owner, scopes, None)
+    }
+
+    /// Same as [`JWTService::create_tokens`], but both tokens are bound to a client. Calls [`JWTService::create_bound_token`].
+    ///
+    /// # Parameters
+    /// - `key_id` : Identifier of the underlying [`ApiKey`] inside the database
+    /// - `owner` : Identifier which service / user uses this key
+    /// - `scopes` : Permission scopes given in a `category:verb` manner
+    /// - `client_id` : Optional client identifier / device hash. If [`None`] the tokens are not bound
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : A [`TokenResponse`] holding the access and refresh token
+    /// - [`Err`] : See [`JWTService::create_tokens`]
+    pub fn create_bound_tokens(
+        &self,
+        key_id: i32,
+        owner: &str,
+        scopes: Vec<String>,
+        client_id: Option<String>,
+    ) -> Result<TokenResponse, KohakuError> {
+        let access_token = self.create_bound_token(
+            owner.to_string(),
+            key_id,
+            scopes.clone(),
+            TokenType::Access,
+            client_id.clone(),
+        )?;
+        let refresh_token = self.create_bound_token(
             owner.to_string(),
             key_id,
             scopes.clone(),
             TokenType::Refresh,
+            client_id,
         )?;
 
         Ok(TokenResponse {
@@ -162,20 +443,92 @@ impl JWTService {
     /// # Returns
     /// A [`Result`] which is either
     /// - [`Ok`] : The [`Claims`] of the given token
-    /// - [`Err`]: A [`KohakuError::ValidationError`] when the validation fails
+    /// - [`Err`]: A [`KohakuError::ValidationError`] when the validation fails, e.g. the token is used before its `nbf`
+    ///   or was issued by another instance (`iss`) / for another audience (`aud`)
     pub fn validate_token(&self, token: &str) -> Result<Claims, KohakuError> {
-        let validation = Validation::default();
-        let token_data = decode::<Claims>(token, &self.decoding_key, &validation)
-            .map_err(|e| KohakuError::ValidationError(e.to_string()))?;
+        let mut validation = Validation::default();
+        validation.validate_nbf = true;
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        let token_data = self
+            .decode_token::<Claims>(token, &validation)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ImmatureSignature => {
+                    KohakuError::ValidationError("Token is not valid yet (nbf)".to_string())
+                }
+                ErrorKind::InvalidIssuer => KohakuError::ValidationError(
+                    "Token was issued by another issuer (iss)".to_string(),
+                ),
+                ErrorKind::InvalidAudience => KohakuError::ValidationError(
+                    "Token is intended for another audience (aud)".to_string(),
+                ),
+                _ => KohakuError::ValidationError(e.to_string()),
+            })?;
         Ok(token_data.claims)
     }
 
+    /// Creates a resume token for a websocket connection.
+    ///
+    /// The token is signed like every other JWT (HS256) and binds the session to the API key,
+    /// so only a client of the same key can pick up the buffered messages of a dropped connection.
+    ///
+    /// # Parameters
+    /// - `key_id` : Identifier of the API key the connection was established with
+    /// - `session_id` : Client id of the connection, see [`crate::utils::comm::websocket::connection::WsClientInfo::client_id`]
+    /// - `lifetime_secs` : Seconds the token stays valid
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : A [`String`] representation of the token
+    /// - [`Err`] : A [KohakuError::InternalServerError] when the encoding fails
+    pub fn create_resume_token(
+        &self,
+        key_id: i32,
+        session_id: Uuid,
+        lifetime_secs: usize,
+    ) -> Result<String, KohakuError> {
+        let now = Utc::now().timestamp() as usize;
+        let claims = ResumeClaims {
+            key_id,
+            sid: session_id,
+            exp: now + lifetime_secs,
+            iat: now,
+            iss: self.issuer.clone(),
+            aud: format!("{}{}", self.audience, RESUME_AUDIENCE_SUFFIX),
+        };
+        encode(&Header::default(), &claims, &self.read_keys().encoding)
+            .map_err(|e| KohakuError::InternalServerError(e.to_string()))
+    }
+
+    /// Validates a resume token created by [`JWTService::create_resume_token`].
+    ///
+    /// # Parameters
+    /// - `token` - A [`String`] representation reference of the resume token
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The [`ResumeClaims`] of the given token
+    /// - [`Err`]: A [`KohakuError::ValidationError`] when the validation fails, e.g. the token expired or is a regular token
+    pub fn validate_resume_token(&self, token: &str) -> Result<ResumeClaims, KohakuError> {
+        let mut validation = Validation::default();
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[format!("{}{}", self.audience, RESUME_AUDIENCE_SUFFIX)]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        self.decode_token::<ResumeClaims>(token, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| KohakuError::ValidationError(format!("Invalid resume token: {}", e)))
+    }
+
     /// Blacklist an API key on revokation.
     ///
     /// This feature is used when an API key gets revoked to ensure that still active JWTs get denied.
     ///
     /// Expiration time is currently: Time of blacklisting + 30 minutes
     /// At the current implementation every JWT access token will expire regardless.
+    ///
+    /// If the blacklist exceeds its maximum size, expired entries are removed first and then the entries
+    /// expiring soonest are evicted. Tokens of evicted keys are accepted again, so every eviction is logged.
     /// # Parameters
     /// - `key_id` : Identifier of the underlying [`ApiKey`] inside the database
     /// - `duration` : [`Option<i64>`] Amount of seconds the key is blacklisted for. If [None] the default of 30 minutes is the used duration.
@@ -185,8 +538,29 @@ impl JWTService {
         duration: Option<i64>,
     ) -> Result<(), KohakuError> {
         let dur = duration.unwrap_or(30 * 60);
-        let expiry = Utc::now().naive_utc() + Duration::seconds(dur);
-        self.blacklist.write().await.insert(key_id, expiry);
+        let now = Utc::now().naive_utc();
+        let expiry = now + Duration::seconds(dur);
+        let mut blklist = self.blacklist.write().await;
+        blklist.insert(key_id, expiry);
+
+        if blklist.len() > self.blacklist_max {
+            blklist.retain(|_, &mut expiry| expiry >= now);
+        }
+        if blklist.len() > self.blacklist_max {
+            let mut by_expiry = blklist
+                .iter()
+                .map(|(id, expiry)| (*expiry, *id))
+                .collect::<Vec<(NaiveDateTime, i32)>>();
+            by_expiry.sort_unstable();
+            let excess = blklist.len() - self.blacklist_max;
+            for (expiry, id) in by_expiry.into_iter().take(excess) {
+                blklist.remove(&id);
+                warn!(
+                    "[Authentication] - Blacklist full ({} keys): Evicted API key {} blacklisted until {}, its tokens are accepted again",
+                    self.blacklist_max, id, expiry
+                );
+            }
+        }
 
         Ok(())
     }
@@ -214,6 +588,35 @@ impl JWTService {
         blklist.retain(|_, &mut expiry| expiry >= now);
     }
 
+    /// Counts a refresh of an API key, rejecting it once the key reached the maximum of refreshes.
+    ///
+    /// This limits the use of a stolen refresh token: After the limit, a new login is required, see [`JWTService::reset_refreshes`].
+    /// # Parameters
+    /// - `key_id` : Identifier of the underlying [`ApiKey`] inside the database
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The amount of refreshes of the key including this one
+    /// - [`Err`] : A [`KohakuError::Unauthorized`] if the key reached the maximum of refreshes
+    pub async fn record_refresh(&self, key_id: i32) -> Result<u32, KohakuError> {
+        let mut counts = self.refresh_counts.write().await;
+        let count = counts.entry(key_id).or_insert(0);
+        if self.refresh_max > 0 && *count >= self.refresh_max {
+            return Err(KohakuError::Unauthorized(
+                "Refresh limit reached, please login again".to_string(),
+            ));
+        }
+        *count += 1;
+        Ok(*count)
+    }
+
+    /// Resets the refreshes of an API key, e.g. after a login.
+    /// # Parameters
+    /// - `key_id` : Identifier of the underlying [`ApiKey`] inside the database
+    pub async fn reset_refreshes(&self, key_id: i32) {
+        self.refresh_counts.write().await.remove(&key_id);
+    }
+
     /// Test Helper: Returns current instance of blacklist
     #[cfg(test)]
     pub async fn read_blacklist(&self) -> HashMap<i32, NaiveDateTime> {
@@ -231,7 +634,12 @@ impl JWTService {
 /// - [`Ok`] : [`JWTService`] is now accessible via [get_jwtservice]
 /// - [`Err`] : A [KohakuError::InternalServerError] if the [`JWTService`] is already initialized
 pub fn init_jwtservice(encryption_key: &[u8]) -> Result<(), KohakuError> {
-    let service = Arc::new(JWTService::new(encryption_key));
+    let (issuer, audience) = get_token_identity();
+    let service = Arc::new(
+        JWTService::new(encryption_key, issuer, audience)
+            .with_blacklist_max(get_blacklist_max())
+            .with_refresh_max(get_refresh_max()),
+    );
     JWT_SERVICE.set(service).map_err(|_| {
         KohakuError::InternalServerError("JWTService already initialized".to_string())
     })?;
@@ -0,0 +1,170 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use std::{collections::HashMap, sync::Mutex};
+use tokio::sync::RwLock;
+
+use crate::utils::{comm::websocket::limiter::RateLimiter, error::KohakuError};
+
+/// Failed attempts before a key (prefix / IP) gets locked
+pub const MAX_FAILED_LOGINS: usize = 5;
+/// Time window (seconds) in which failed attempts are counted
+pub const FAILED_LOGIN_WINDOW_SEC: i64 = 15 * 60;
+/// Lockout duration (seconds) after reaching [`MAX_FAILED_LOGINS`]
+pub const LOGIN_LOCKOUT_SEC: i64 = 15 * 60;
+
+struct FailureEntry {
+    count: usize,
+    window_start: NaiveDateTime,
+    locked_until: Option<NaiveDateTime>,
+}
+
+/// Tracks failed login attempts per identifier (e.g. `prefix:khk_abcdef` or `ip:127.0.0.1`)
+/// and locks an identifier for a while after too many failures within a window.
+pub struct LoginLimiter {
+    max_failures: usize,
+    window: Duration,
+    lockout: Duration,
+    failures: RwLock<HashMap<String, FailureEntry>>,
+}
+
+impl LoginLimiter {
+    /// # Parameters
+    /// - `max_failures` : Failed attempts within `window_secs` before an identifier gets locked
+    /// - `window_secs` : Time window (seconds) in which failed attempts are counted
+    /// - `lockout_secs` : Lockout duration (seconds)
+    pub fn new(max_failures: usize, window_secs: i64, lockout_secs: i64) -> Self {
+        Self {
+            max_failures,
+            window: Duration::seconds(window_secs),
+            lockout: Duration::seconds(lockout_secs),
+            failures: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Checks if an identifier is currently locked.
+    ///
+    /// # Parameters
+    /// - `id` : Identifier of the attempt source
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The identifier may attempt a login
+    /// - [`Err`] : A [`KohakuError::RateLimitExceeded`] if the identifier is locked
+    pub async fn check(&self, id: &str) -> Result<(), KohakuError> {
+        let now = Utc::now().naive_utc();
+        let failures = self.failures.read().await;
+
+        if let Some(until) = failures.get(id).and_then(|entry| entry.locked_until) {
+            if until > now {
+                return Err(KohakuError::RateLimitExceeded(format!(
+                    "Too many failed login attempts, try again in {} seconds",
+                    (until - now).num_seconds().max(1)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a failed attempt. Locks the identifier if it reached the maximum of failures within the window.
+    ///
+    /// # Parameters
+    /// - `id` : Identifier of the attempt source
+    pub async fn record_failure(&self, id: &str) {
+        self.cleanup_expired().await;
+        let now = Utc::now().naive_utc();
+        let mut failures = self.failures.write().await;
+        let entry = failures.entry(id.to_string()).or_insert(FailureEntry {
+            count: 0,
+            window_start: now,
+            locked_until: None,
+        });
+
+        // Start a new window if the old one (or the lockout) is over
+        let lock_expired = entry.locked_until.is_some_and(|until| until <= now);
+        if lock_expired || entry.window_start + self.window < now {
+            entry.count = 0;
+            entry.window_start = now;
+            entry.locked_until = None;
+        }
+
+        entry.count += 1;
+        if entry.count >= self.max_failures {
+            entry.locked_until = Some(now + self.lockout);
+        }
+    }
+
+    /// Resets the failed attempts of an identifier (e.g. after a successful login).
+    ///
+    /// # Parameters
+    /// - `id` : Identifier of the attempt source
+    pub async fn reset(&self, id: &str) {
+        self.failures.write().await.remove(id);
+    }
+
+    /// Cleans up entries whose window and lockout are both over.
+    pub async fn cleanup_expired(&self) {
+        let now = Utc::now().naive_utc();
+        let window = self.window;
+        let mut failures = self.failures.write().await;
+
+        failures.retain(|_, entry| {
+            entry.window_start + window >= now || entry.locked_until.is_some_and(|u| u > now)
+        });
+    }
+}
+
+impl Default for LoginLimiter {
+    fn default() -> Self {
+        Self::new(
+            MAX_FAILED_LOGINS,
+            FAILED_LOGIN_WINDOW_SEC,
+            LOGIN_LOCKOUT_SEC,
+        )
+    }
+}
+
+/// Limits how often a single API key may call an endpoint within a sliding window,
+/// e.g. to keep a compromised bootstrap token from mass-creating keys.
+pub struct KeyRateLimiter {
+    max_requests: usize,
+    window_secs: i64,
+    limiters: Mutex<HashMap<i32, RateLimiter>>,
+}
+
+impl KeyRateLimiter {
+    /// # Parameters
+    /// - `max_requests` : Requests per API key allowed within the window
+    /// - `window_secs` : Length of the sliding window (seconds)
+    pub fn new(max_requests: usize, window_secs: i64) -> Self {
+        Self {
+            max_requests,
+            window_secs,
+            limiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks if an API key may make another request right now and counts it if so.
+    ///
+    /// # Parameters
+    /// - `key_id` : Id of the API key of the caller (`-1` for the bootstrap key)
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The request is within the limit
+    /// - [`Err`] : A [`KohakuError::RateLimitExceeded`] if the key exceeded the limit
+    pub fn check(&self, key_id: i32) -> Result<(), KohakuError> {
+        let allowed = self
+            .limiters
+            .lock()
+            .unwrap()
+            .entry(key_id)
+            .or_insert_with(|| RateLimiter::new(self.max_requests, self.window_secs))
+            .check_and_add();
+        if !allowed {
+            return Err(KohakuError::RateLimitExceeded(format!(
+                "At most {} requests per {} seconds are allowed",
+                self.max_requests, self.window_secs
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,25 @@
+use std::net::IpAddr;
+
 use actix_web::HttpRequest;
 
 use crate::utils::{
     comm::auth::{
-        api_key::{extract_prefix, verify_key},
+        api_key::{extract_prefix, find_matching_key},
         jwt::get_jwtservice,
         models::{get_apikey, ApiKey, Claims, TokenType},
+        scopes::Scope,
     },
     error::KohakuError,
 };
 
 pub mod api_key;
+pub mod extractor;
 pub mod jwt;
+pub mod limiter;
 pub mod models;
 pub mod routes;
+pub mod scopes;
+pub mod tasks;
 
 /// Helper: Quick lookup for token type duration (seconds)
 pub fn token_duration(token_type: &TokenType) -> usize {
@@ -37,31 +44,26 @@ pub async fn check_authorization_key(key: &str) -> Result<ApiKey, KohakuError> {
     let prefix = extract_prefix(key)?;
     let candidates = get_apikey(None, Some(prefix)).await?;
 
-    let mut verified_key = None;
-    for candidate in candidates {
-        if verify_key(key, &candidate.hashed_key)? {
-            verified_key = Some(candidate);
-            break;
-        }
-    }
-    if verified_key.is_none() {
-        return Err(KohakuError::Unauthorized("Invalid API key".to_string()));
-    }
-
-    // Note: If the implementation changes and blacklisting doesn't mean deletion in the
-    // database, a blacklist check must be implemented here
-
-    Ok(verified_key.unwrap())
+    // Revoked keys are excluded by `get_apikey`, so no blacklist check is needed here
+    find_matching_key(key, &candidates)
+        .cloned()
+        .ok_or_else(|| KohakuError::Unauthorized("Invalid API key".to_string()))
 }
 
 /// Checks if the given token is valid and its corresponding key is not blacklisted
 ///
 /// # Parameters
 /// - `token` : [`String`] representation of the token
-/// - `required_scopes` : Optional required token scopes for permission handling. If [`None`] not further permissions needed.
+/// - `required_scopes` : Optional required token [`Scope`]s for permission handling. If [`None`] not further permissions needed.
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [`Claims`] of the given token
+/// - [`Err`] : A [`KohakuError::Unauthorized`] if the token is missing or revoked, a [`KohakuError::ValidationError`]
+///   if the token is invalid, or a [`KohakuError::Forbidden`] if the token lacks the required scopes
 pub async fn check_authorization_token(
     req: &HttpRequest,
-    required_scopes: Option<Vec<&str>>,
+    required_scopes: Option<&[Scope]>,
 ) -> Result<Claims, KohakuError> {
     let token = extract_token(req);
     if token.is_none() {
@@ -81,13 +83,10 @@ pub async fn check_authorization_token(
     }
 
     // Check scopes
-    let permission = required_scopes.is_none()
-        || required_scopes
-            .unwrap()
-            .iter()
-            .all(|scope| claims.scopes.contains(&scope.to_string()));
+    let permission = required_scopes
+        .is_none_or(|required| required.iter().all(|scope| claims.scopes.contains(scope)));
     if !permission {
-        return Err(KohakuError::Unauthorized(
+        return Err(KohakuError::Forbidden(
             "API Key has not the required permissions!".to_string(),
         ));
     }
@@ -107,6 +106,109 @@ pub fn extract_key(req: &HttpRequest) -> Option<&str> {
     req.headers().get("X-API-Key").and_then(|h| h.to_str().ok())
 }
 
+/// Header under which clients can present an identifier / device hash to bind their tokens to
+pub const CLIENT_ID_HEADER: &str = "X-Client-Id";
+/// Maximum length of a client identifier
+pub const CLIENT_ID_MAX_LEN: usize = 128;
+
+/// Extracts the client identifier under `X-Client-Id` from the header
+///
+/// # Parameters
+/// - `req` : [`HttpRequest`] given by the endpoint
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : [`Some`] with the trimmed identifier, or [`None`] if the header is missing or blank
+/// - [`Err`] : A [`KohakuError::ValidationError`] if the identifier is not readable or longer than [`CLIENT_ID_MAX_LEN`]
+pub fn extract_client_id(req: &HttpRequest) -> Result<Option<String>, KohakuError> {
+    let Some(value) = req.headers().get(CLIENT_ID_HEADER) else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| KohakuError::ValidationError("Invalid client id".to_string()))?
+        .trim();
+    if value.len() > CLIENT_ID_MAX_LEN {
+        return Err(KohakuError::ValidationError(format!(
+            "Client id must not be longer than {} characters",
+            CLIENT_ID_MAX_LEN
+        )));
+    }
+    Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+/// Extracts the source IP of the request from the connection itself (forwarding headers are not trusted)
+///
+/// # Parameters
+/// - `req` : [`HttpRequest`] given by the endpoint
+///
+/// # Returns
+/// An [`Option`] which is either
+/// - [`Some`] : A String representation of the peer IP
+/// - [`None`] : If the peer address is unknown (e.g. in tests)
+pub fn peer_ip(req: &HttpRequest) -> Option<String> {
+    req.peer_addr().map(|addr| addr.ip().to_string())
+}
+
+/// Parses an allowlist entry, either an exact IP (`10.0.0.1`) or a CIDR range (`10.0.0.0/24`)
+///
+/// # Parameters
+/// - `rule` : Entry of [`ApiKey::allowed_ips`]
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The network address and its prefix length in bits
+/// - [`Err`] : A [`KohakuError::ValidationError`] if the entry is neither an IP nor a CIDR range
+pub fn parse_ip_rule(rule: &str) -> Result<(IpAddr, u32), KohakuError> {
+    let invalid = || KohakuError::ValidationError(format!("Invalid IP or CIDR range: {}", rule));
+    let (addr, prefix) = match rule.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (rule, None),
+    };
+    let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(p) => p.trim().parse().map_err(|_| invalid())?,
+        None => max_prefix,
+    };
+    if prefix > max_prefix {
+        return Err(invalid());
+    }
+    Ok((addr, prefix))
+}
+
+/// Checks if an IP is covered by an allowlist of IPs / CIDR ranges. An empty allowlist allows any IP.
+///
+/// # Parameters
+/// - `allowed_ips` : Allowlist as stored in [`ApiKey::allowed_ips`]
+/// - `ip` : Source IP of the request, see [`peer_ip`]
+///
+/// # Returns
+/// `true` if the allowlist is empty or one entry covers `ip`. Unknown or unparsable IPs are never allowed by a non-empty list.
+pub fn is_ip_allowed(allowed_ips: &[String], ip: Option<&str>) -> bool {
+    if allowed_ips.is_empty() {
+        return true;
+    }
+    let Some(ip) = ip.and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+        return false;
+    };
+
+    allowed_ips
+        .iter()
+        .filter_map(|rule| parse_ip_rule(rule).ok())
+        .any(|(network, prefix)| match (network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        })
+}
+
 /// Extracts the token under `Authorization:` from the header
 ///
 /// # Parameters
@@ -1,13 +1,20 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{prelude::*, query_dsl::methods::FilterDsl};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::{
     db::{
         self, get_connection,
         schema::{self},
     },
-    utils::error::KohakuError,
+    utils::{
+        comm::auth::{
+            parse_ip_rule,
+            scopes::{validate_scopes, Scope},
+        },
+        error::KohakuError,
+    },
 };
 
 // =========================================== API ============================================= //
@@ -16,12 +23,15 @@ use crate::{
 pub struct CreateKeyRequest {
     pub owner: String,
     pub scopes: Vec<String>,
+    /// IPs or CIDR ranges the key may log in from. Empty = any IP
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct CreateKeyResponse {
     pub api_key: String,
-    pub scopes: Vec<String>,
+    pub scopes: Vec<Scope>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,15 +39,99 @@ pub struct RevokeKeyRequest {
     pub api_key: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RotateKeyRequest {
+    /// Prefix of the key to rotate (e.g. `khk_a1b2c3`)
+    pub key_prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeOwnerRequest {
+    pub owner: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeOwnerResponse {
+    /// Amount of revoked keys
+    pub revoked: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateSigningKeyRequest {
+    /// New secret to sign JWTs with
+    pub key: String,
+    /// Seconds tokens of the previous secret are still accepted. Defaults to the lifetime of an access token, at most that of a refresh token
+    #[serde(default)]
+    pub grace_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateSigningKeyResponse {
+    /// Unix timestamp (seconds) until which tokens of the previous secret are accepted
+    pub previous_valid_until: i64,
+}
+
+/// Identity of the caller, see [`Claims`]. Ids, client bindings and issuer details are left out
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WhoAmIResponse {
+    pub owner: String,
+    pub scopes: Vec<Scope>,
+    pub token_type: TokenType,
+    /// Expiration Timestamp of the token
+    pub exp: usize,
+}
+
+impl From<Claims> for WhoAmIResponse {
+    fn from(claims: Claims) -> Self {
+        Self {
+            owner: claims.owner,
+            scopes: claims.scopes,
+            token_type: claims.token_type,
+            exp: claims.exp,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RefreshRequest {
     pub refresh_token: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<i64>,
+}
+
+/// Public view of an [struct@ApiKey] without its hash. The only projection of a key used in HTTP responses
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyPublic {
+    pub id: i32,
+    pub key_prefix: String,
+    pub owner: String,
+    pub scopes: Vec<String>,
+    pub allowed_ips: Vec<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<ApiKey> for ApiKeyPublic {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            id: key.id,
+            key_prefix: key.key_prefix,
+            owner: key.owner,
+            scopes: key.scopes,
+            allowed_ips: key.allowed_ips,
+            created_at: key.created_at,
+        }
+    }
+}
+
 // ========================================= API Keys ========================================== //
 
-/// Representation of database entry of a given ApiKey
-#[derive(Debug, Queryable, Selectable, Serialize, Deserialize, Clone)]
+/// Representation of database entry of a given ApiKey.
+///
+/// Deliberately not [`Serialize`], so the hash can't end up in a response. HTTP responses use [`ApiKeyPublic`]
+#[derive(Debug, Queryable, Selectable, Clone)]
 #[diesel(table_name = crate::db::schema::api_keys)]
 pub struct ApiKey {
     /// Serial Primary Key given by the database
@@ -52,6 +146,10 @@ pub struct ApiKey {
     pub scopes: Vec<String>,
     /// Timestamp of creation (Default: Current Time UTC)
     pub created_at: NaiveDateTime,
+    /// IPs or CIDR ranges the key may log in from. Empty = any IP
+    pub allowed_ips: Vec<String>,
+    /// Timestamp of revocation. [`None`] while the key is active
+    pub revoked_at: Option<NaiveDateTime>,
 }
 
 /// Form to create a new [struct@ApiKey].
@@ -62,6 +160,7 @@ pub struct NewApiKey {
     pub key_prefix: String,
     pub owner: String,
     pub scopes: Vec<String>,
+    pub allowed_ips: Vec<String>,
 }
 
 /// Creates an entry for the API key in the database
@@ -70,32 +169,34 @@ pub struct NewApiKey {
 /// - `hashed_key` : Hashed [`String`] presentation of the actual full key
 /// - `key_prefix` : 10-char long [`String`] prefix of the actual full key
 /// - `owner` : [`String`] identifier of the service or user that uses this API key
-/// - `scopes`: Vector of [`String`]s that map the actual permissions in a `category:verb` manner
+/// - `scopes`: Vector of [`Scope`]s that map the actual permissions. Must be listed in [`crate::utils::comm::auth::scopes::KNOWN_SCOPES`]
+/// - `allowed_ips`: Vector of IPs or CIDR ranges the key may log in from. Empty = any IP
 ///
 /// # Returns
 /// A [`Result`] which is either
 /// - [`Ok`] : A [struct@ApiKey] that represents the now stored API key in the database.
-/// - [`Err`] : A [enum@KohakuError] based on the failing operation.
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if a scope is unknown or reserved, or another [enum@KohakuError] based on the failing operation.
 ///
 pub async fn create_apikey(
     hashed_key: String,
     key_prefix: String,
     owner: String,
-    scopes: Vec<String>,
+    scopes: Vec<Scope>,
+    allowed_ips: Vec<String>,
 ) -> Result<ApiKey, KohakuError> {
-    for scp in &scopes {
-        if scp.starts_with("keys") {
-            return Err(KohakuError::ValidationError("Illegal Argument: Any scope of the category `key` is not allowed for general API keys!".to_string()));
-        }
+    validate_scopes(&scopes)?;
+    for rule in &allowed_ips {
+        parse_ip_rule(rule)?;
     }
 
-    let mut conn = get_connection()?;
+    let mut conn = get_connection().await?;
 
     let new_key = NewApiKey {
         hashed_key,
         key_prefix,
         owner,
-        scopes: scopes.clone(),
+        scopes: scopes.iter().map(Scope::to_string).collect(),
+        allowed_ips,
     };
 
     diesel::insert_into(schema::api_keys::table)
@@ -107,6 +208,7 @@ pub async fn create_apikey(
 /// Gets an entry for an identifieable API key in the database
 ///
 /// `id` will be one either 0 or 1 entry, while `key_prefix` is not unique and therefore can result in n entries.
+/// Revoked keys are never returned.
 /// # Parameters
 /// - `id_` : Serial primary key of the database. Either this or `key_prefix` must be set
 /// - `key_prefix_` : 10-char long [`String`] prefix of the actual full key. Either this or `id` must be set
@@ -123,8 +225,8 @@ pub async fn get_apikey(
     if id_.is_none() && key_prefix_.is_none() {
         return Err(KohakuError::ValidationError("Illegal Argument: At least one of the parameters - `id` and/or `key_prefix` must be set!".to_string()));
     }
-    let mut conn = get_connection()?;
-    let mut query = api_keys.into_boxed();
+    let mut conn = get_connection().await?;
+    let mut query = FilterDsl::filter(api_keys, revoked_at.is_null()).into_boxed();
 
     if let Some(i) = id_ {
         query = FilterDsl::filter(query, id.eq(i));
@@ -137,39 +239,262 @@ pub async fn get_apikey(
     query.load(&mut conn).map_err(KohakuError::DatabaseError)
 }
 
-/// Removes an entry representing an API key from the database
+/// Lists all active (not revoked) API keys issued to an owner
 ///
 /// # Parameters
-/// - `id_` : Serial primary key of the database. Either this or `key_prefix` must be set
-/// - `key_prefix_` : 10-char long [`String`] prefix of the actual full key. Either this or `id` must be set
+/// - `owner_` : [`String`] identifier of the service or user the keys were issued to
 ///
 /// # Returns
 /// A [`Result`] which is either
-/// - [`Ok`] : The API key was deleted from the database
+/// - [`Ok`] : All active [struct@ApiKey]s of `owner_`, oldest first
 /// - [`Err`] : A [enum@KohakuError] based on the failing operation
-pub async fn delete_apikey(
-    id_: Option<i32>,
-    key_prefix_: Option<String>,
-) -> Result<(), KohakuError> {
+pub async fn list_apikeys(owner_: &str) -> Result<Vec<ApiKey>, KohakuError> {
     use db::schema::api_keys::dsl::*;
-    if id_.is_none() && key_prefix_.is_none() {
-        return Err(KohakuError::ValidationError("Illegal Argument: At least one of the parameters - `id` and/or `key_prefix` must be set!".to_string()));
-    }
-    let mut conn = get_connection()?;
-    let mut query = diesel::delete(api_keys).into_boxed();
+    let mut conn = get_connection().await?;
 
-    if let Some(i) = id_ {
-        query = FilterDsl::filter(query, id.eq(i));
-    }
+    FilterDsl::filter(api_keys, owner.eq(owner_).and(revoked_at.is_null()))
+        .order(id.asc())
+        .load(&mut conn)
+        .map_err(KohakuError::DatabaseError)
+}
 
-    if let Some(kp) = key_prefix_ {
-        query = FilterDsl::filter(query, key_prefix.eq(kp));
+/// Revokes an API key (soft delete).
+///
+/// The entry stays in the database for audits, but is excluded from [`get_apikey`] and [`list_apikeys`].
+///
+/// # Parameters
+/// - `id_` : Serial primary key of the database
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : `true` if an active key got revoked, `false` if no such key exists or it was already revoked
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn revoke_apikey(id_: i32) -> Result<bool, KohakuError> {
+    use db::schema::api_keys::dsl::*;
+    let mut conn = get_connection().await?;
+
+    let updated = diesel::update(FilterDsl::filter(
+        api_keys,
+        id.eq(id_).and(revoked_at.is_null()),
+    ))
+    .set(revoked_at.eq(Utc::now().naive_utc()))
+    .execute(&mut conn)
+    .map_err(KohakuError::DatabaseError)?;
+    Ok(updated > 0)
+}
+
+/// Replaces an API key by a new one with the same owner, scopes and allowed IPs.
+///
+/// Creating the new key and revoking the old one happens in a single transaction:
+/// If either fails, nothing is changed and the old key stays valid.
+///
+/// # Parameters
+/// - `old_id` : Serial primary key of the key to replace. Must be active
+/// - `hashed_key_` : Hashed [`String`] presentation of the new full key
+/// - `key_prefix_` : 10-char long [`String`] prefix of the new full key
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The new [struct@ApiKey]
+/// - [`Err`] : A [enum@KohakuError::NotFound] if the old key doesn't exist or was already revoked, or another [enum@KohakuError] based on the failing operation
+pub async fn rotate_apikey(
+    old_id: i32,
+    hashed_key_: String,
+    key_prefix_: String,
+) -> Result<ApiKey, KohakuError> {
+    use db::schema::api_keys::dsl::*;
+    let mut conn = get_connection().await?;
+
+    conn.transaction::<_, KohakuError, _>(|conn| {
+        let old: ApiKey = diesel::update(FilterDsl::filter(
+            api_keys,
+            id.eq(old_id).and(revoked_at.is_null()),
+        ))
+        .set(revoked_at.eq(Utc::now().naive_utc()))
+        .get_result(conn)
+        .map_err(KohakuError::not_found_or_database(
+            "API key could not be found!",
+        ))?;
+
+        let new_key = NewApiKey {
+            hashed_key: hashed_key_,
+            key_prefix: key_prefix_,
+            owner: old.owner,
+            scopes: old.scopes,
+            allowed_ips: old.allowed_ips,
+        };
+        Ok(diesel::insert_into(api_keys)
+            .values(&new_key)
+            .get_result(conn)?)
+    })
+}
+
+/// Revokes all active API keys of an owner (soft delete, see [`revoke_apikey`])
+///
+/// # Parameters
+/// - `owner_` : [`String`] identifier of the service or user the keys were issued to
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The ids of the revoked [struct@ApiKey]s. Empty if the owner has no active keys
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn revoke_apikeys_by_owner(owner_: &str) -> Result<Vec<i32>, KohakuError> {
+    use db::schema::api_keys::dsl::*;
+    let mut conn = get_connection().await?;
+
+    diesel::update(FilterDsl::filter(
+        api_keys,
+        owner.eq(owner_).and(revoked_at.is_null()),
+    ))
+    .set(revoked_at.eq(Utc::now().naive_utc()))
+    .returning(id)
+    .get_results(&mut conn)
+    .map_err(KohakuError::DatabaseError)
+}
+
+/// Permanently removes API keys that were revoked before a given point in time
+///
+/// # Parameters
+/// - `before` : Keys revoked before this timestamp (UTC) get removed
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : Amount of removed [struct@ApiKey]s
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn purge_revoked(before: NaiveDateTime) -> Result<usize, KohakuError> {
+    use db::schema::api_keys::dsl::*;
+    let mut conn = get_connection().await?;
+
+    diesel::delete(FilterDsl::filter(api_keys, revoked_at.lt(before)))
+        .execute(&mut conn)
+        .map_err(KohakuError::DatabaseError)
+}
+
+// ========================================== Audit ============================================ //
+
+/// Kind of authentication event stored in the audit log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthEventType {
+    Login,
+    Refresh,
+    Create,
+    Revoke,
+}
+
+impl AuthEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventType::Login => "login",
+            AuthEventType::Refresh => "refresh",
+            AuthEventType::Create => "create",
+            AuthEventType::Revoke => "revoke",
+        }
     }
+}
+
+/// Representation of database entry of an authentication event
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::db::schema::auth_audit)]
+pub struct AuthEvent {
+    /// Serial Primary Key given by the database
+    pub id: i32,
+    /// Kind of event (see [`AuthEventType`])
+    pub event_type: String,
+    /// Id of the affected [struct@ApiKey]. [`None`] if the key couldn't be identified
+    pub key_id: Option<i32>,
+    /// Identifier which service / user the affected key belongs to
+    pub owner: Option<String>,
+    /// If the operation succeeded
+    pub success: bool,
+    /// Source IP of the request
+    pub ip: Option<String>,
+    /// Timestamp of the event (Default: Current Time UTC)
+    pub created_at: NaiveDateTime,
+}
+
+/// Form to create a new [struct@AuthEvent].
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::db::schema::auth_audit)]
+pub struct NewAuthEvent {
+    pub event_type: String,
+    pub key_id: Option<i32>,
+    pub owner: Option<String>,
+    pub success: bool,
+    pub ip: Option<String>,
+}
+
+/// Stores an authentication event in the audit log
+///
+/// # Parameters
+/// - `event_type` : Kind of event
+/// - `key_id` : Id of the affected [struct@ApiKey], if known
+/// - `owner` : Owner of the affected [struct@ApiKey], if known
+/// - `success` : If the operation succeeded
+/// - `ip` : Source IP of the request, if known
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The stored [struct@AuthEvent]
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn record_auth_event(
+    event_type: AuthEventType,
+    key_id: Option<i32>,
+    owner: Option<String>,
+    success: bool,
+    ip: Option<String>,
+) -> Result<AuthEvent, KohakuError> {
+    let mut conn = get_connection().await?;
 
-    query
+    let event = NewAuthEvent {
+        event_type: event_type.as_str().to_string(),
+        key_id,
+        owner,
+        success,
+        ip,
+    };
+
+    diesel::insert_into(schema::auth_audit::table)
+        .values(&event)
+        .get_result(&mut conn)
+        .map_err(KohakuError::DatabaseError)
+}
+
+/// Gets the most recent authentication events, newest first
+///
+/// # Parameters
+/// - `limit` : Maximum amount of events to return
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : Up to `limit` [struct@AuthEvent]s
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn get_auth_events(limit: i64) -> Result<Vec<AuthEvent>, KohakuError> {
+    use db::schema::auth_audit::dsl::*;
+    let mut conn = get_connection().await?;
+
+    auth_audit
+        .order((created_at.desc(), id.desc()))
+        .limit(limit)
+        .load(&mut conn)
+        .map_err(KohakuError::DatabaseError)
+}
+
+/// Permanently removes authentication events that occurred before a given point in time
+///
+/// # Parameters
+/// - `before` : Events created before this timestamp (UTC) get removed
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : Amount of removed [struct@AuthEvent]s
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn purge_auth_events(before: NaiveDateTime) -> Result<usize, KohakuError> {
+    use db::schema::auth_audit::dsl::*;
+    let mut conn = get_connection().await?;
+
+    diesel::delete(FilterDsl::filter(auth_audit, created_at.lt(before)))
         .execute(&mut conn)
-        .map_err(KohakuError::DatabaseError)?;
-    Ok(())
+        .map_err(KohakuError::DatabaseError)
 }
 
 // =========================================== JWT ============================================= //
@@ -193,18 +518,62 @@ pub struct Claims {
     pub owner: String,
     /// Id of corresponding [struct@ApiKey]
     pub key_id: i32,
-    /// Scopes (same as [struct@ApiKey])
-    pub scopes: Vec<String>,
+    /// Scopes (same as [struct@ApiKey]), serialized in a `category:verb` manner
+    pub scopes: Vec<Scope>,
     /// Bootstrap, Access or Refresh
     pub token_type: TokenType,
     /// Expiration Timestamp
     pub exp: usize,
     /// Issued-at Timestamp
     pub iat: usize,
+    /// Issuer: The Kohaku instance that created the token
+    pub iss: String,
+    /// Audience: Who the token is intended for
+    pub aud: String,
+    /// Not-before Timestamp. The token is rejected before this point in time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    /// Client binding: Identifier / device hash of the client the token was issued to (see `X-Client-Id`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+}
+
+impl Claims {
+    /// Seconds until the token expires. Negative if it already expired
+    pub fn remaining_secs(&self) -> i64 {
+        self.exp as i64 - Utc::now().timestamp()
+    }
+
+    /// Whether the token is expired (i.e. no lifetime is left)
+    pub fn is_expired(&self) -> bool {
+        self.remaining_secs() <= 0
+    }
+
+    /// Seconds since the token was issued
+    pub fn issued_ago(&self) -> i64 {
+        Utc::now().timestamp() - self.iat as i64
+    }
+}
+
+/// Claims of a websocket resume token, see [`crate::utils::comm::auth::jwt::JWTService::create_resume_token`]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResumeClaims {
+    /// Id of the [struct@ApiKey] the connection was established with
+    pub key_id: i32,
+    /// Session id: The client id of the connection that can be resumed
+    pub sid: Uuid,
+    /// Expiration Timestamp
+    pub exp: usize,
+    /// Issued-at Timestamp
+    pub iat: usize,
+    /// Issuer: The Kohaku instance that created the token
+    pub iss: String,
+    /// Audience: Always the configured audience with a `:ws-resume` suffix
+    pub aud: String,
 }
 
-/// Response of creating a (pair of) token(s)
-#[derive(Debug, Serialize, Clone)]
+/// Response of creating a (pair of) token(s). Bootstrap, login and refresh share this shape.
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TokenResponse {
     pub access_token: String,
     pub refresh_token: Option<String>,
@@ -1,15 +1,28 @@
 use actix_web::{web, HttpRequest, HttpResponse};
-use tracing::info;
+use once_cell::sync::Lazy;
+use tracing::{error, info, warn};
 
 use crate::utils::{
-    comm::auth::{
-        api_key::{extract_prefix, generate_key, hash_key, verify_key},
-        check_authorization_key, check_authorization_token, extract_key,
-        jwt::get_jwtservice,
-        models::{
-            create_apikey, delete_apikey, get_apikey, CreateKeyRequest, CreateKeyResponse,
-            RevokeKeyRequest, TokenResponse, TokenType,
+    comm::{
+        auth::{
+            api_key::{extract_prefix, find_matching_key, generate_key, hash_key},
+            check_authorization_key, check_authorization_token, extract_client_id, extract_key,
+            extractor::{AuthedClaims, KeysManage, RequiredScopes},
+            is_ip_allowed,
+            jwt::get_jwtservice,
+            limiter::{KeyRateLimiter, LoginLimiter},
+            models::{
+                create_apikey, get_apikey, get_auth_events, list_apikeys, record_auth_event,
+                revoke_apikey, revoke_apikeys_by_owner, rotate_apikey, ApiKeyPublic, AuditQuery,
+                AuthEventType, CreateKeyRequest, CreateKeyResponse, RevokeKeyRequest,
+                RevokeOwnerRequest, RevokeOwnerResponse, RotateKeyRequest, RotateSigningKeyRequest,
+                RotateSigningKeyResponse, TokenResponse, TokenType, WhoAmIResponse,
+            },
+            peer_ip,
+            scopes::{parse_scopes, Scope, RESERVED_SCOPE},
+            token_duration,
         },
+        websocket::manager::get_manager,
     },
     config::get_config,
     error::KohakuError,
@@ -20,13 +33,98 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.route("/login", web::post().to(login))
         .route("/manage/refresh", web::post().to(refresh))
         .route("/manage/create", web::post().to(create))
-        .route("/manage/revoke", web::post().to(revoke));
+        .route("/manage/revoke", web::post().to(revoke))
+        .route("/manage/revoke-owner", web::post().to(revoke_owner))
+        .route("/manage/rotate", web::post().to(rotate))
+        .route("/keys/mine", web::get().to(my_keys))
+        .route("/whoami", web::get().to(whoami))
+        .route("/audit", web::get().to(audit_log));
+}
+
+/// Tracks failed logins per key prefix and per IP
+static LOGIN_LIMITER: Lazy<LoginLimiter> = Lazy::new(LoginLimiter::default);
+/// Limits [`create`] per API key of the caller
+static CREATE_LIMITER: Lazy<KeyRateLimiter> = Lazy::new(key_manage_limiter);
+/// Limits [`revoke`] per API key of the caller
+static REVOKE_LIMITER: Lazy<KeyRateLimiter> = Lazy::new(key_manage_limiter);
+
+/// Will select the configured key management limit in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn key_manage_limiter() -> KeyRateLimiter {
+    let config = get_config();
+    KeyRateLimiter::new(
+        config.keys_manage_max_requests,
+        config.keys_manage_window_sec,
+    )
+}
+
+/// Will select a fixed key management limit in a test environment (cargo test)
+#[cfg(test)]
+fn key_manage_limiter() -> KeyRateLimiter {
+    KeyRateLimiter::new(3, 60)
+}
+
+/// Default amount of audit events returned by [`audit_log`]
+const AUDIT_DEFAULT_LIMIT: i64 = 100;
+/// Maximum amount of audit events returned by [`audit_log`]
+const AUDIT_MAX_LIMIT: i64 = 1000;
+
+/// Helper: Records an authentication event. Failing to store the event is logged but does not fail the request.
+async fn audit(
+    event_type: AuthEventType,
+    key_id: Option<i32>,
+    owner: Option<String>,
+    success: bool,
+    ip: Option<String>,
+) {
+    if let Err(e) = record_auth_event(event_type, key_id, owner, success, ip).await {
+        error!(
+            "[Authentication] - Couldn't record {} event: {}",
+            event_type.as_str(),
+            e
+        );
+    }
+}
+
+/// Helper: Passes on the claims of an [`AuthedClaims`] extractor, recording its rejection (e.g. an expired or forged token,
+/// or missing scopes) in the audit log. Handlers take the extractor as [`Result`] to get the rejection at all
+///
+/// # Parameters
+/// - `claims` : Outcome of the [`AuthedClaims`] extractor
+/// - `event_type` : Kind of event the rejected request attempted
+/// - `ip` : Source IP of the request, if known
+async fn audit_rejection<S: RequiredScopes>(
+    claims: Result<AuthedClaims<S>, KohakuError>,
+    event_type: AuthEventType,
+    ip: Option<String>,
+) -> Result<AuthedClaims<S>, KohakuError> {
+    match claims {
+        Ok(claims) => Ok(claims),
+        Err(e) => {
+            warn!(
+                "[Authentication] - Rejected {} request from {:?}: {}",
+                event_type.as_str(),
+                ip,
+                e
+            );
+            audit(event_type, None, None, false, ip).await;
+            Err(e)
+        }
+    }
 }
 
 /// API Key login endpoint.
 ///
+/// Keys with a non-empty `allowed_ips` list only log in from a covered peer IP.
+/// Issued tokens are not bound to the IP, so the allowlist is only enforced here.
+///
+/// Clients may opt in to token binding by sending an `X-Client-Id` header. The issued tokens then
+/// carry the identifier and [`refresh`] only accepts it from the same client.
+///
+/// A successful login resets the refresh limit of the key, see [`refresh`].
+///
 /// # Parameters
-/// - `req` : [`HttpRequest`] header to hold the `X-API-Key` value.
+/// - `req` : [`HttpRequest`] header to hold the `X-API-Key` and optional `X-Client-Id` value.
 ///
 /// # Returns
 /// A [`Result`] which either is
@@ -36,11 +134,19 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
 /// # Errors
 /// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
 async fn login(req: HttpRequest) -> Result<HttpResponse, KohakuError> {
+    let ip = peer_ip(&req);
+    let ip_id = ip.as_ref().map(|ip| format!("ip:{}", ip));
+    if let Some(id) = &ip_id {
+        LOGIN_LIMITER.check(id).await?;
+    }
+
     let api_key = extract_key(&req);
     if api_key.is_none() {
+        audit(AuthEventType::Login, None, None, false, ip).await;
         return Err(KohakuError::Unauthorized("Missing API key".to_string()));
     }
     let api_key = api_key.unwrap();
+    let client_id = extract_client_id(&req)?;
     let config = get_config();
     let service = get_jwtservice()?;
 
@@ -48,20 +154,93 @@ async fn login(req: HttpRequest) -> Result<HttpResponse, KohakuError> {
     if api_key == config.bootstrap_key {
         // Return bootstrap JWTs
         let response = service.create_bootstrap_token()?;
+        audit(
+            AuthEventType::Login,
+            Some(-1),
+            Some("system".to_string()),
+            true,
+            ip,
+        )
+        .await;
         return Ok(HttpResponse::Ok().json(response));
     }
+    // Throttle brute force attempts on the same prefix, even if they come from different IPs
+    let prefix_id = extract_prefix(api_key)
+        .ok()
+        .map(|prefix| format!("prefix:{}", prefix));
+    if let Some(id) = &prefix_id {
+        LOGIN_LIMITER.check(id).await?;
+    }
+    let limiter_ids = [ip_id, prefix_id];
+
     // Check if API Key can be found in database
-    let verified_key = check_authorization_key(api_key).await?;
+    let verified_key = match check_authorization_key(api_key).await {
+        Ok(key) => key,
+        Err(e) => {
+            if matches!(
+                e,
+                KohakuError::Unauthorized(_) | KohakuError::ValidationError(_)
+            ) {
+                for id in limiter_ids.iter().flatten() {
+                    LOGIN_LIMITER.record_failure(id).await;
+                }
+                warn!("[Authentication] - Failed login attempt from {:?}", ip);
+            }
+            audit(AuthEventType::Login, None, None, false, ip).await;
+            return Err(e);
+        }
+    };
+
+    // Valid key, but used from an IP outside of its allowlist => Counts as failed attempt
+    if !is_ip_allowed(&verified_key.allowed_ips, ip.as_deref()) {
+        for id in limiter_ids.iter().flatten() {
+            LOGIN_LIMITER.record_failure(id).await;
+        }
+        warn!(
+            "[Authentication] - Key with prefix {} used from disallowed IP {:?}",
+            verified_key.key_prefix, ip
+        );
+        audit(
+            AuthEventType::Login,
+            Some(verified_key.id),
+            Some(verified_key.owner),
+            false,
+            ip,
+        )
+        .await;
+        return Err(KohakuError::Unauthorized(
+            "API key is not allowed from this IP".to_string(),
+        ));
+    }
+    for id in limiter_ids.iter().flatten() {
+        LOGIN_LIMITER.reset(id).await;
+    }
     let scopes = verified_key.scopes.clone();
-    let response = service.create_tokens(verified_key.id, &verified_key.owner, scopes)?;
+    let response =
+        service.create_bound_tokens(verified_key.id, &verified_key.owner, scopes, client_id)?;
+    service.reset_refreshes(verified_key.id).await;
+    audit(
+        AuthEventType::Login,
+        Some(verified_key.id),
+        Some(verified_key.owner),
+        true,
+        ip,
+    )
+    .await;
 
     Ok(HttpResponse::Ok().json(response))
 }
 
 /// API Key refresh endpoint.
 ///
+/// Refresh tokens bound to a client (see [`login`]) are only accepted with the same `X-Client-Id`.
+/// A mismatch hints at a stolen token, so the key gets blacklisted in addition to rejecting the request.
+/// Refreshes are limited per login (`SERVER_JWT_REFRESH_MAX`), afterwards a new login is required.
+/// Refresh tokens of revoked keys are rejected, even after their blacklist entry expired.
+///
 /// # Parameters
-/// - `req` : [`HttpRequest`] header to hold the `Authorization` with the refresh JWT
+/// - `req` : [`HttpRequest`] of the caller, used for auditing and holding the optional `X-Client-Id`
+/// - `claims` : [`AuthedClaims`] of the refresh JWT given via `Authorization` header. Rejections are recorded in the audit log
 ///
 /// # Returns
 /// A [`Result`] which either is
@@ -70,23 +249,105 @@ async fn login(req: HttpRequest) -> Result<HttpResponse, KohakuError> {
 ///
 /// # Errors
 /// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
-async fn refresh(req: HttpRequest) -> Result<HttpResponse, KohakuError> {
-    let claims = check_authorization_token(&req, None).await?;
+async fn refresh(
+    req: HttpRequest,
+    claims: Result<AuthedClaims, KohakuError>,
+) -> Result<HttpResponse, KohakuError> {
+    let ip = peer_ip(&req);
+    let claims = audit_rejection(claims, AuthEventType::Refresh, ip.clone())
+        .await?
+        .into_inner();
     // Check if token is a refresh token
     if claims.token_type != TokenType::Refresh {
+        audit(
+            AuthEventType::Refresh,
+            Some(claims.key_id),
+            Some(claims.owner),
+            false,
+            ip,
+        )
+        .await;
         return Err(KohakuError::ValidationError(
             "Invalid token type".to_string(),
         ));
     }
 
-    // Valid, not blacklisted refresh token => Create new access token
+    // The blacklist entry of a revoked key expires, so the database decides whether the key is still usable
+    if get_apikey(Some(claims.key_id), None).await?.is_empty() {
+        warn!(
+            "[Authentication] - Refresh token of revoked key {} used from {:?}",
+            claims.key_id, ip
+        );
+        audit(
+            AuthEventType::Refresh,
+            Some(claims.key_id),
+            Some(claims.owner),
+            false,
+            ip,
+        )
+        .await;
+        return Err(KohakuError::Unauthorized(
+            "API Key is blacklisted / was revoked!".to_string(),
+        ));
+    }
+
     let service = get_jwtservice()?;
-    let token = service.create_token(
-        claims.owner,
+    // Bound refresh token => Must be presented by the client it was issued to
+    if let Some(bound_id) = &claims.cid {
+        let client_id = extract_client_id(&req).ok().flatten();
+        if client_id.as_ref() != Some(bound_id) {
+            service.blacklist_key(claims.key_id, None).await?;
+            warn!(
+                "[Authentication] - Refresh token of key {} used by another client from {:?}. Key flagged!",
+                claims.key_id, ip
+            );
+            audit(
+                AuthEventType::Refresh,
+                Some(claims.key_id),
+                Some(claims.owner),
+                false,
+                ip,
+            )
+            .await;
+            return Err(KohakuError::Unauthorized(
+                "Refresh token is bound to another client".to_string(),
+            ));
+        }
+    }
+
+    // Refresh limit reached => Force a new login
+    if let Err(e) = service.record_refresh(claims.key_id).await {
+        warn!(
+            "[Authentication] - Key {} reached its refresh limit, login required",
+            claims.key_id
+        );
+        audit(
+            AuthEventType::Refresh,
+            Some(claims.key_id),
+            Some(claims.owner),
+            false,
+            ip,
+        )
+        .await;
+        return Err(e);
+    }
+
+    // Valid, not blacklisted refresh token => Create new access token
+    let token = service.create_bound_token(
+        claims.owner.clone(),
         claims.key_id,
-        claims.scopes,
+        claims.scopes.iter().map(Scope::to_string).collect(),
         TokenType::Access,
+        claims.cid,
     )?;
+    audit(
+        AuthEventType::Refresh,
+        Some(claims.key_id),
+        Some(claims.owner),
+        true,
+        ip,
+    )
+    .await;
     let response = TokenResponse {
         access_token: token,
         refresh_token: None,
@@ -100,9 +361,11 @@ async fn refresh(req: HttpRequest) -> Result<HttpResponse, KohakuError> {
 /// API Key creation endpoint.
 ///
 /// Will create a new API Key if the user uses an access token linked to the bootstrap key.
+/// Calls are rate limited per API key of the caller (`SERVER_KEYS_MANAGE_MAX_REQUESTS` / `SERVER_KEYS_MANAGE_WINDOW_SEC`).
 ///
 /// # Parameters
-/// - `req` : [`HttpRequest`] header to hold the `Authorization` via JWT access token.
+/// - `req` : [`HttpRequest`] of the caller, used for auditing
+/// - `claims` : [`AuthedClaims`] of the bootstrap JWT given via `Authorization` header. Rejections are recorded in the audit log
 /// - `body` : [`CreateKeyRequest`] in a JSON Format to hold the necessary data for creation
 ///
 /// # Returns
@@ -114,24 +377,73 @@ async fn refresh(req: HttpRequest) -> Result<HttpResponse, KohakuError> {
 /// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
 async fn create(
     req: HttpRequest,
+    claims: Result<AuthedClaims<KeysManage>, KohakuError>,
     body: web::Json<CreateKeyRequest>,
 ) -> Result<HttpResponse, KohakuError> {
-    let _ = check_authorization_token(&req, Some(vec!["keys:manage"])).await?;
-    if body.scopes.contains(&"keys:manage".to_string()) {
-        return Err(KohakuError::ValidationError(
-            "Invalid key scope: keys:manage is bootstrap key exclusive!".to_string(),
-        ));
-    }
+    let ip = peer_ip(&req);
+    let claims = audit_rejection(claims, AuthEventType::Create, ip.clone()).await?;
+    CREATE_LIMITER.check(claims.key_id).inspect_err(|_| {
+        warn!(
+            "[Authentication] - Key creation of key {} ({}) was rate limited",
+            claims.key_id, claims.owner
+        )
+    })?;
+    let scopes = parse_scopes(&body.scopes).and_then(|scopes| {
+        if scopes.contains(&RESERVED_SCOPE) {
+            return Err(KohakuError::ValidationError(format!(
+                "Invalid key scope: {} is bootstrap key exclusive!",
+                RESERVED_SCOPE
+            )));
+        }
+        Ok(scopes)
+    });
+    let scopes = match scopes {
+        Ok(scopes) => scopes,
+        Err(e) => {
+            audit(
+                AuthEventType::Create,
+                None,
+                Some(body.owner.clone()),
+                false,
+                ip,
+            )
+            .await;
+            return Err(e);
+        }
+    };
 
     let (key, prefix) = generate_key();
     let hashed_key = hash_key(&key)?;
-    let _ = create_apikey(
+    let created = create_apikey(
         hashed_key,
         prefix.clone(),
         body.owner.clone(),
-        body.scopes.clone(),
+        scopes.clone(),
+        body.allowed_ips.clone(),
+    )
+    .await;
+    let created = match created {
+        Ok(created) => created,
+        Err(e) => {
+            audit(
+                AuthEventType::Create,
+                None,
+                Some(body.owner.clone()),
+                false,
+                ip,
+            )
+            .await;
+            return Err(e);
+        }
+    };
+    audit(
+        AuthEventType::Create,
+        Some(created.id),
+        Some(created.owner),
+        true,
+        ip,
     )
-    .await?;
+    .await;
     info!(
         "[Authentication] - New API Key with prefix {} created!",
         prefix
@@ -139,7 +451,7 @@ async fn create(
 
     let response = CreateKeyResponse {
         api_key: key,
-        scopes: body.scopes.clone(),
+        scopes,
     };
     Ok(HttpResponse::Ok().json(response))
 }
@@ -147,9 +459,11 @@ async fn create(
 /// API Key revokation endpoint.
 ///
 /// Will revoke an API Key if the user uses an access token linked to the bootstrap key.
+/// Calls are rate limited per API key of the caller, see [`create`].
 ///
 /// # Parameters
-/// - `req` : [`HttpRequest`] header to hold the `Authorization` via JWT access token.
+/// - `req` : [`HttpRequest`] of the caller, used for auditing
+/// - `claims` : [`AuthedClaims`] of the bootstrap JWT given via `Authorization` header. Rejections are recorded in the audit log
 /// - `body` : [`RevokeKeyRequest`] in a JSON Format to hold the necessary data for revokation
 ///
 /// # Returns
@@ -161,9 +475,17 @@ async fn create(
 /// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
 async fn revoke(
     req: HttpRequest,
+    claims: Result<AuthedClaims<KeysManage>, KohakuError>,
     body: web::Json<RevokeKeyRequest>,
 ) -> Result<HttpResponse, KohakuError> {
-    let _ = check_authorization_token(&req, Some(vec!["keys:manage"])).await?;
+    let ip = peer_ip(&req);
+    let claims = audit_rejection(claims, AuthEventType::Revoke, ip.clone()).await?;
+    REVOKE_LIMITER.check(claims.key_id).inspect_err(|_| {
+        warn!(
+            "[Authentication] - Key revocation of key {} ({}) was rate limited",
+            claims.key_id, claims.owner
+        )
+    })?;
     let service = get_jwtservice()?;
 
     // Check if such a key actually exists
@@ -171,17 +493,278 @@ async fn revoke(
 
     let prefix = extract_prefix(&key)?;
     let candidates = get_apikey(None, Some(prefix.clone())).await?;
-    for candidate in candidates {
-        if let Ok(true) = verify_key(&key, &candidate.hashed_key) {
-            // Found key: Remove it from database and blacklist it
-            let key_id = candidate.id;
-            delete_apikey(Some(key_id), None).await?;
-            service.blacklist_key(key_id, None).await?;
-            info!("[Authentication] - API Key with prefix {} revoked!", prefix);
-            return Ok(HttpResponse::Ok().finish());
+    let Some(candidate) = find_matching_key(&key, &candidates) else {
+        audit(AuthEventType::Revoke, None, None, false, ip).await;
+        return Err(KohakuError::NotFound(
+            "API key could not be found!".to_string(),
+        ));
+    };
+
+    // Found key: Mark it as revoked and blacklist it
+    let key_id = candidate.id;
+    revoke_apikey(key_id).await?;
+    service.blacklist_key(key_id, None).await?;
+    info!("[Authentication] - API Key with prefix {} revoked!", prefix);
+    audit(
+        AuthEventType::Revoke,
+        Some(key_id),
+        Some(candidate.owner.clone()),
+        true,
+        ip,
+    )
+    .await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Owner revokation endpoint.
+///
+/// Will revoke all API keys of an owner if the user uses an access token linked to the bootstrap key.
+/// Live websocket connections of the revoked keys are closed.
+///
+/// # Parameters
+/// - `req` : [`HttpRequest`] of the caller, used for auditing
+/// - `_claims` : [`AuthedClaims`] of the bootstrap JWT given via `Authorization` header
+/// - `body` : [`RevokeOwnerRequest`] in a JSON Format holding the owner
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`RevokeOwnerResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn revoke_owner(
+    req: HttpRequest,
+    _claims: AuthedClaims<KeysManage>,
+    body: web::Json<RevokeOwnerRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let ip = peer_ip(&req);
+    let service = get_jwtservice()?;
+    let owner = body.into_inner().owner;
+
+    let key_ids = revoke_apikeys_by_owner(&owner).await?;
+    // The websocket manager is not running in every context (e.g. tests)
+    let manager = get_manager().ok();
+    for key_id in &key_ids {
+        service.blacklist_key(*key_id, None).await?;
+        if let Some(manager) = &manager {
+            manager.disconnect(key_id, None).await;
+        }
+        audit(
+            AuthEventType::Revoke,
+            Some(*key_id),
+            Some(owner.clone()),
+            true,
+            ip.clone(),
+        )
+        .await;
+    }
+    info!(
+        "[Authentication] - Revoked {} API key(s) of owner {}",
+        key_ids.len(),
+        owner
+    );
+    Ok(HttpResponse::Ok().json(RevokeOwnerResponse {
+        revoked: key_ids.len(),
+    }))
+}
+
+/// API Key rotation endpoint.
+///
+/// Will replace an API key by a new one with the same owner and scopes if the user uses an access token linked to the bootstrap key.
+/// The new key is created and the old one revoked in one transaction, so there is no point in time where neither works.
+/// Afterwards the old key is blacklisted and its websocket connection is closed.
+///
+/// # Parameters
+/// - `req` : [`HttpRequest`] of the caller, used for auditing
+/// - `_claims` : [`AuthedClaims`] of the bootstrap JWT given via `Authorization` header
+/// - `body` : [`RotateKeyRequest`] in a JSON Format holding the prefix of the old key
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`CreateKeyResponse`] of the new key
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn rotate(
+    req: HttpRequest,
+    _claims: AuthedClaims<KeysManage>,
+    body: web::Json<RotateKeyRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let ip = peer_ip(&req);
+    let service = get_jwtservice()?;
+    let old_prefix = body.into_inner().key_prefix;
+
+    // Prefixes aren't unique, so the key to rotate must be unambiguous
+    let mut candidates = get_apikey(None, Some(old_prefix.clone())).await?;
+    if candidates.len() != 1 {
+        audit(AuthEventType::Revoke, None, None, false, ip).await;
+        return Err(if candidates.is_empty() {
+            KohakuError::NotFound("API key could not be found!".to_string())
+        } else {
+            KohakuError::ValidationError(format!(
+                "Prefix {} matches multiple API keys, revoke them instead",
+                old_prefix
+            ))
+        });
+    }
+    let old = candidates.remove(0);
+
+    let (key, prefix) = generate_key();
+    let hashed_key = hash_key(&key)?;
+    let created = match rotate_apikey(old.id, hashed_key, prefix.clone()).await {
+        Ok(created) => created,
+        Err(e) => {
+            audit(
+                AuthEventType::Revoke,
+                Some(old.id),
+                Some(old.owner),
+                false,
+                ip,
+            )
+            .await;
+            return Err(e);
         }
+    };
+    service.blacklist_key(old.id, None).await?;
+    if let Ok(manager) = get_manager() {
+        manager.disconnect(&old.id, None).await;
     }
-    Err(KohakuError::NotFound(
-        "API key could not be found!".to_string(),
-    ))
+    audit(
+        AuthEventType::Revoke,
+        Some(old.id),
+        Some(old.owner),
+        true,
+        ip.clone(),
+    )
+    .await;
+    audit(
+        AuthEventType::Create,
+        Some(created.id),
+        Some(created.owner),
+        true,
+        ip,
+    )
+    .await;
+    info!(
+        "[Authentication] - API Key with prefix {} rotated to prefix {}!",
+        old_prefix, prefix
+    );
+
+    Ok(HttpResponse::Ok().json(CreateKeyResponse {
+        api_key: key,
+        scopes: parse_scopes(&created.scopes)?,
+    }))
+}
+
+/// Own API keys endpoint.
+///
+/// Lists the keys issued to the owner of the calling token. Keys of other owners are never returned.
+///
+/// # Parameters
+/// - `claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`ApiKeyPublic`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn my_keys(claims: AuthedClaims) -> Result<HttpResponse, KohakuError> {
+    let keys: Vec<ApiKeyPublic> = list_apikeys(&claims.owner)
+        .await?
+        .into_iter()
+        .map(ApiKeyPublic::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+/// Caller identity endpoint.
+///
+/// Lets clients confirm who they are authenticated as and what they may do, without decoding the JWT themselves.
+/// Unlike other endpoints, invalid tokens are answered with `401` instead of `400`.
+///
+/// # Parameters
+/// - `req` : [`HttpRequest`] holding the JWT in the `Authorization` header
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`WhoAmIResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn whoami(req: HttpRequest) -> Result<HttpResponse, KohakuError> {
+    let claims = check_authorization_token(&req, None)
+        .await
+        .map_err(|e| match e {
+            KohakuError::ValidationError(msg) => KohakuError::Unauthorized(msg),
+            e => e,
+        })?;
+    Ok(HttpResponse::Ok().json(WhoAmIResponse::from(claims)))
+}
+
+/// Audit log endpoint.
+///
+/// Returns the most recent authentication events (logins, refreshes, key creations and revocations), newest first.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the bootstrap JWT given via `Authorization` header
+/// - `query` : [`AuditQuery`] with an optional `limit` (Default: 100, Max: 1000)
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`crate::utils::comm::auth::models::AuthEvent`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn audit_log(
+    _claims: AuthedClaims<KeysManage>,
+    query: web::Query<AuditQuery>,
+) -> Result<HttpResponse, KohakuError> {
+    let limit = query
+        .limit
+        .unwrap_or(AUDIT_DEFAULT_LIMIT)
+        .clamp(1, AUDIT_MAX_LIMIT);
+    let events = get_auth_events(limit).await?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+/// JWT signing key rotation endpoint.
+///
+/// Replaces the secret JWTs are signed with without a restart, see [`crate::utils::comm::auth::jwt::JWTService::rotate_key`].
+/// Tokens signed with the previous secret stay valid for the grace window, so connected clients aren't logged out at once.
+/// Whoever knows the secret can forge tokens of any scope, so only the bootstrap key (`keys:manage`) may rotate it.
+///
+/// # Parameters
+/// - `claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`RotateSigningKeyRequest`] in a JSON Format holding the new secret and an optional grace window
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`RotateSigningKeyResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+pub async fn rotate_signing_key(
+    claims: AuthedClaims<KeysManage>,
+    body: web::Json<RotateSigningKeyRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let body = body.into_inner();
+    let grace_secs = body
+        .grace_secs
+        .unwrap_or(token_duration(&TokenType::Access) as i64);
+    let previous_valid_until = get_jwtservice()?.rotate_key(body.key.as_bytes(), grace_secs)?;
+    warn!(
+        "[Authentication] - JWT signing key rotated by {}! Tokens of the previous key are accepted for {}s",
+        claims.owner, grace_secs
+    );
+
+    Ok(HttpResponse::Ok().json(RotateSigningKeyResponse {
+        previous_valid_until,
+    }))
 }
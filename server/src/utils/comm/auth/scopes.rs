@@ -0,0 +1,142 @@
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::utils::error::KohakuError;
+
+/// Permission of an API key in a `category:verb` manner, e.g. `events:subscribe`.
+///
+/// Serialized as its `category:verb` string, so tokens and responses look the same as with plain strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Scope {
+    /// Area the scope grants access to, e.g. `events`
+    pub category: Cow<'static, str>,
+    /// Action the scope allows within its category, e.g. `subscribe`
+    pub verb: Cow<'static, str>,
+}
+
+/// Manage API keys. Exclusive to the bootstrap key
+pub const KEYS_MANAGE: Scope = Scope::from_static("keys", "manage");
+/// Administrative endpoints, e.g. purging stale codes
+pub const ADMIN_MANAGE: Scope = Scope::from_static("admin", "manage");
+/// Subscribe to notification codes and receive notifications
+pub const EVENTS_SUBSCRIBE: Scope = Scope::from_static("events", "subscribe");
+/// Register notification codes and send notifications
+pub const EVENTS_MANAGE: Scope = Scope::from_static("events", "manage");
+
+/// Scope of the bootstrap key. Never granted to general API keys
+pub const RESERVED_SCOPE: Scope = KEYS_MANAGE;
+
+/// All scopes that can be granted to general API keys
+pub const KNOWN_SCOPES: &[Scope] = &[ADMIN_MANAGE, EVENTS_SUBSCRIBE, EVENTS_MANAGE];
+
+impl Scope {
+    /// Creates a scope from static parts, e.g. for constants. Parts are not validated, see [`Scope::from_str`]
+    pub const fn from_static(category: &'static str, verb: &'static str) -> Self {
+        Self {
+            category: Cow::Borrowed(category),
+            verb: Cow::Borrowed(verb),
+        }
+    }
+}
+
+/// Helper: Whether a part of a scope is non-empty and only consists of lowercase alphanumerics, `_` and `-`
+fn is_valid_part(part: &str) -> bool {
+    !part.is_empty()
+        && part
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+impl FromStr for Scope {
+    type Err = KohakuError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((category, verb)) if is_valid_part(category) && is_valid_part(verb) => Ok(Self {
+                category: Cow::Owned(category.to_string()),
+                verb: Cow::Owned(verb.to_string()),
+            }),
+            _ => Err(KohakuError::ValidationError(format!(
+                "Invalid scope `{}`: Expected `category:verb` of lowercase alphanumerics, `_` and `-`",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.category, self.verb)
+    }
+}
+
+impl PartialEq<&str> for Scope {
+    fn eq(&self, other: &&str) -> bool {
+        other
+            .split_once(':')
+            .is_some_and(|(category, verb)| self.category == category && self.verb == verb)
+    }
+}
+
+impl Serialize for Scope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Scope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parses scopes as stored in [`crate::utils::comm::auth::models::ApiKey::scopes`]
+///
+/// # Parameters
+/// - `scopes` : Scopes in a `category:verb` manner
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The parsed [`Scope`]s in the given order
+/// - [`Err`] : A [`KohakuError::ValidationError`] naming the first malformed scope
+pub fn parse_scopes(scopes: &[String]) -> Result<Vec<Scope>, KohakuError> {
+    scopes.iter().map(|scope| scope.parse()).collect()
+}
+
+/// Validates scopes requested for a general API key
+///
+/// # Parameters
+/// - `scopes` : Requested scopes
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : All scopes are known and grantable
+/// - [`Err`] : A [`KohakuError::ValidationError`] if a scope of the `keys` category is requested or listing all unknown scopes
+pub fn validate_scopes(scopes: &[Scope]) -> Result<(), KohakuError> {
+    if scopes
+        .iter()
+        .any(|scope| scope.category == RESERVED_SCOPE.category)
+    {
+        return Err(KohakuError::ValidationError(format!(
+            "Illegal Argument: Any scope of the category `keys` is not allowed for general API keys! ({} is bootstrap key exclusive)",
+            RESERVED_SCOPE
+        )));
+    }
+
+    let unknown: Vec<String> = scopes
+        .iter()
+        .filter(|scope| !KNOWN_SCOPES.contains(scope))
+        .map(Scope::to_string)
+        .collect();
+    if !unknown.is_empty() {
+        let known: Vec<String> = KNOWN_SCOPES.iter().map(Scope::to_string).collect();
+        return Err(KohakuError::ValidationError(format!(
+            "Unknown scope(s): {}. Known scopes are: {}",
+            unknown.join(", "),
+            known.join(", ")
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use chrono::{Duration, Utc};
+use tracing::info;
+
+use crate::{
+    impl_task_wrapper,
+    utils::{
+        comm::auth::models::{purge_auth_events, purge_revoked},
+        config::get_config,
+        scheduler::tasks::Task,
+    },
+};
+
+/// Purges revoked API keys past their retention period every day at 03:00
+pub struct RevokedKeysPurge(Task);
+
+impl RevokedKeysPurge {
+    pub fn new() -> Self {
+        Self(Task::new("RevokedKeysPurge", "0 0 3 * * *", false))
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let retention = Duration::days(get_config().revoked_key_retention_days as i64);
+        let removed = purge_revoked(Utc::now().naive_utc() - retention)
+            .await
+            .map_err(|e| e.to_string())?;
+        if removed > 0 {
+            info!("[Authentication] - Purged {} revoked API key(s)", removed);
+        }
+        Ok(())
+    }
+}
+
+impl_task_wrapper!(RevokedKeysPurge);
+
+/// Purges authentication events older than `SERVER_HISTORY_RETENTION_DAYS` every day at 03:30
+pub struct AuthAuditPurge(Task);
+
+impl AuthAuditPurge {
+    pub fn new() -> Self {
+        Self(Task::new("AuthAuditPurge", "0 30 3 * * *", false))
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let retention = Duration::days(get_config().history_retention_days as i64);
+        let removed = purge_auth_events(Utc::now().naive_utc() - retention)
+            .await
+            .map_err(|e| e.to_string())?;
+        if removed > 0 {
+            info!("[Authentication] - Purged {} audit event(s)", removed);
+        }
+        Ok(())
+    }
+}
+
+impl_task_wrapper!(AuthAuditPurge);
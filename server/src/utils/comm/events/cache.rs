@@ -0,0 +1,114 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::Utc;
+
+/// Small in-memory cache whose entries expire after a fixed time to live.
+///
+/// Used to spare the database lookups of hot notification codes. Writers invalidate the affected keys,
+/// the TTL only bounds how long changes made elsewhere (e.g. directly in the database) stay unnoticed.
+pub struct TtlCache<V: Clone> {
+    ttl_secs: i64,
+    /// Entries by key with the unix timestamp (seconds) they expire at
+    entries: Mutex<HashMap<String, (i64, V)>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    /// # Parameters
+    /// - `ttl_secs` : Seconds an entry is served after it was stored. `0` disables the cache
+    pub fn new(ttl_secs: i64) -> Self {
+        Self {
+            ttl_secs,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gets a cached value, if it is not expired yet
+    pub fn get(&self, key: &str) -> Option<V> {
+        self.get_at(key, Utc::now().timestamp())
+    }
+
+    /// Same as [`TtlCache::get`] at a given time.
+    ///
+    /// # Parameters
+    /// - `key` : Key of the entry
+    /// - `now` : Current time as unix timestamp (seconds)
+    pub fn get_at(&self, key: &str, now: i64) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((expires_at, value)) if *expires_at > now => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Stores a value, replacing a previous one
+    pub fn insert(&self, key: &str, value: V) {
+        self.insert_at(key, value, Utc::now().timestamp());
+    }
+
+    /// Same as [`TtlCache::insert`] at a given time.
+    ///
+    /// # Parameters
+    /// - `key` : Key of the entry
+    /// - `value` : Value to cache
+    /// - `now` : Current time as unix timestamp (seconds)
+    pub fn insert_at(&self, key: &str, value: V, now: i64) {
+        if self.ttl_secs <= 0 {
+            return;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (now + self.ttl_secs, value));
+    }
+
+    /// Stores a value only if the key holds no unexpired entry, checked and stored under a single lock.
+    /// A disabled cache (TTL `0`) reserves nothing, but always succeeds
+    ///
+    /// # Returns
+    /// `true` if the value was stored, `false` if the key is already taken
+    pub fn reserve(&self, key: &str, value: V) -> bool {
+        self.reserve_at(key, value, Utc::now().timestamp())
+    }
+
+    /// Same as [`TtlCache::reserve`] at a given time.
+    ///
+    /// # Parameters
+    /// - `key` : Key of the entry
+    /// - `value` : Value to cache
+    /// - `now` : Current time as unix timestamp (seconds)
+    pub fn reserve_at(&self, key: &str, value: V, now: i64) -> bool {
+        if self.ttl_secs <= 0 {
+            return true;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries
+            .get(key)
+            .is_some_and(|(expires_at, _)| *expires_at > now)
+        {
+            return false;
+        }
+        entries.insert(key.to_string(), (now + self.ttl_secs, value));
+        true
+    }
+
+    /// Modifies a cached value in place, keeping its expiry. Does nothing if the key isn't cached
+    pub fn update(&self, key: &str, f: impl FnOnce(&mut V)) {
+        if let Some((_, value)) = self.entries.lock().unwrap().get_mut(key) {
+            f(value);
+        }
+    }
+
+    /// Removes the entry of a key
+    pub fn invalidate(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    /// Removes all entries
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -1,5 +1,50 @@
-/*
-TODO:
-Code to manage notifications
-  - notify ---> Use websocket implementation to notify subscribed clients
-*/
+use std::cmp::Reverse;
+
+use tracing::warn;
+
+use crate::utils::{
+    comm::{
+        events::models::NotificationData,
+        websocket::manager::{get_manager, DeliveryReport},
+    },
+    error::KohakuError,
+};
+
+/// Sends notifications to the connected clients via the [`crate::utils::comm::websocket::manager::WsConnectionManager`].
+///
+/// The notifications are ordered by [`NotificationData::priority`] (highest first) and sent with the highest
+/// priority among them, so they overtake queued messages of a lower priority.
+///
+/// Without an initialized manager, the notifications are dropped with a warning instead of failing,
+/// so the triggering task isn't aborted just because no client listens right now.
+///
+/// # Parameters
+/// - `notifications` : [`NotificationData`]s to send. Nothing is sent if empty
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [`DeliveryReport`] of the clients the notifications were queued for. Empty if nothing was sent
+/// - [`Err`] : A [`KohakuError`] indicating that ANY operation failed
+pub async fn dispatch(notifications: &[NotificationData]) -> Result<DeliveryReport, KohakuError> {
+    if notifications.is_empty() {
+        return Ok(DeliveryReport::default());
+    }
+    let mut ordered = notifications.to_vec();
+    ordered.sort_by_key(|n| Reverse(n.priority));
+    let priority = ordered[0].priority;
+
+    let manager = match get_manager() {
+        Ok(manager) => manager,
+        Err(e) => {
+            warn!(
+                "[Events] - Dropped {} notification(s): {}",
+                notifications.len(),
+                e
+            );
+            return Ok(DeliveryReport::default());
+        }
+    };
+    manager
+        .broadcast_with_priority(ordered, None, priority)
+        .await
+}
@@ -0,0 +1,234 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use url::Url;
+
+use crate::utils::error::KohakuError;
+
+/// Maximum length (characters) of the title of an embed
+pub const EMBED_TITLE_MAX: usize = 256;
+/// Maximum length (characters) of the description of an embed
+pub const EMBED_DESCRIPTION_MAX: usize = 4096;
+/// Maximum amount of fields of an embed
+pub const EMBED_FIELDS_MAX: usize = 25;
+/// Maximum length (characters) of the name of a field
+pub const EMBED_FIELD_NAME_MAX: usize = 256;
+/// Maximum length (characters) of the value of a field
+pub const EMBED_FIELD_VALUE_MAX: usize = 1024;
+/// Maximum length (characters) of the footer text of an embed
+pub const EMBED_FOOTER_MAX: usize = 2048;
+/// Maximum length (characters) of all texts of an embed combined
+pub const EMBED_TOTAL_MAX: usize = 6000;
+/// Maximum color of an embed (`0xFFFFFF`)
+pub const EMBED_COLOR_MAX: u32 = 0xFF_FF_FF;
+
+/// A single field of an embed
+#[derive(Debug, Clone)]
+struct EmbedField {
+    name: String,
+    value: String,
+    inline: bool,
+}
+
+/// Builds a Discord embed object for [`NotificationData::embed`](crate::utils::comm::events::models::NotificationData).
+///
+/// Every setter checks the limits of Discord, so a built embed is always accepted by the client:
+/// ```ignore
+/// let embed = EmbedBuilder::new()
+///     .title("Backup finished")?
+///     .field("Duration", "42s", true)?
+///     .color(0x1B6C8E)?
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EmbedBuilder {
+    title: Option<String>,
+    description: Option<String>,
+    url: Option<String>,
+    color: Option<u32>,
+    timestamp: Option<DateTime<Utc>>,
+    footer: Option<String>,
+    fields: Vec<EmbedField>,
+}
+
+/// Helper: Checks the length of a text against its limit
+fn check_length(name: &str, text: &str, max: usize) -> Result<(), KohakuError> {
+    let length = text.chars().count();
+    if length > max {
+        return Err(KohakuError::ValidationError(format!(
+            "Embed {} must be at most {} characters long but was {}",
+            name, max, length
+        )));
+    }
+    Ok(())
+}
+
+impl EmbedBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Parameters
+    /// - `title` : Title of the embed, at most [`EMBED_TITLE_MAX`] characters
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The builder with the title set
+    /// - [`Err`] : A [`KohakuError::ValidationError`] if a limit is exceeded
+    pub fn title(mut self, title: impl Into<String>) -> Result<Self, KohakuError> {
+        let title = title.into();
+        check_length("title", &title, EMBED_TITLE_MAX)?;
+        self.title = Some(title);
+        self.check_total()
+    }
+
+    /// # Parameters
+    /// - `description` : Description of the embed, at most [`EMBED_DESCRIPTION_MAX`] characters
+    ///
+    /// # Returns
+    /// See [`EmbedBuilder::title`]
+    pub fn description(mut self, description: impl Into<String>) -> Result<Self, KohakuError> {
+        let description = description.into();
+        check_length("description", &description, EMBED_DESCRIPTION_MAX)?;
+        self.description = Some(description);
+        self.check_total()
+    }
+
+    /// # Parameters
+    /// - `url` : Absolute `http(s)` URL the title links to
+    ///
+    /// # Returns
+    /// See [`EmbedBuilder::title`]
+    pub fn url(mut self, url: impl Into<String>) -> Result<Self, KohakuError> {
+        let url = url.into();
+        let valid = Url::parse(&url).is_ok_and(|parsed| {
+            matches!(parsed.scheme(), "http" | "https") && parsed.host_str().is_some()
+        });
+        if !valid {
+            return Err(KohakuError::ValidationError(format!(
+                "Invalid embed URL `{}`: Only absolute http(s) URLs are allowed",
+                url
+            )));
+        }
+        self.url = Some(url);
+        Ok(self)
+    }
+
+    /// # Parameters
+    /// - `color` : Color of the sidebar as RGB value, at most [`EMBED_COLOR_MAX`]
+    ///
+    /// # Returns
+    /// See [`EmbedBuilder::title`]
+    pub fn color(mut self, color: u32) -> Result<Self, KohakuError> {
+        if color > EMBED_COLOR_MAX {
+            return Err(KohakuError::ValidationError(format!(
+                "Embed color must be at most {:#08X} but was {:#X}",
+                EMBED_COLOR_MAX, color
+            )));
+        }
+        self.color = Some(color);
+        Ok(self)
+    }
+
+    /// # Parameters
+    /// - `timestamp` : Time shown in the footer of the embed
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// # Parameters
+    /// - `footer` : Footer text of the embed, at most [`EMBED_FOOTER_MAX`] characters
+    ///
+    /// # Returns
+    /// See [`EmbedBuilder::title`]
+    pub fn footer(mut self, footer: impl Into<String>) -> Result<Self, KohakuError> {
+        let footer = footer.into();
+        check_length("footer", &footer, EMBED_FOOTER_MAX)?;
+        self.footer = Some(footer);
+        self.check_total()
+    }
+
+    /// Appends a field. At most [`EMBED_FIELDS_MAX`] fields are allowed.
+    ///
+    /// # Parameters
+    /// - `name` : Name of the field, at most [`EMBED_FIELD_NAME_MAX`] characters
+    /// - `value` : Value of the field, at most [`EMBED_FIELD_VALUE_MAX`] characters
+    /// - `inline` : Whether the field is shown next to other inline fields
+    ///
+    /// # Returns
+    /// See [`EmbedBuilder::title`]
+    pub fn field(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+        inline: bool,
+    ) -> Result<Self, KohakuError> {
+        if self.fields.len() >= EMBED_FIELDS_MAX {
+            return Err(KohakuError::ValidationError(format!(
+                "Embeds can have at most {} fields",
+                EMBED_FIELDS_MAX
+            )));
+        }
+        let (name, value) = (name.into(), value.into());
+        check_length("field name", &name, EMBED_FIELD_NAME_MAX)?;
+        check_length("field value", &value, EMBED_FIELD_VALUE_MAX)?;
+        self.fields.push(EmbedField {
+            name,
+            value,
+            inline,
+        });
+        self.check_total()
+    }
+
+    /// Helper: Checks the combined length of all texts against [`EMBED_TOTAL_MAX`]
+    fn check_total(self) -> Result<Self, KohakuError> {
+        let length: usize = [&self.title, &self.description, &self.footer]
+            .into_iter()
+            .flatten()
+            .chain(self.fields.iter().flat_map(|f| [&f.name, &f.value]))
+            .map(|text| text.chars().count())
+            .sum();
+        if length > EMBED_TOTAL_MAX {
+            return Err(KohakuError::ValidationError(format!(
+                "Embed texts must be at most {} characters long combined but were {}",
+                EMBED_TOTAL_MAX, length
+            )));
+        }
+        Ok(self)
+    }
+
+    /// Builds the Discord embed object. Unset parts are omitted.
+    pub fn build(self) -> Value {
+        let mut embed = Map::new();
+        if let Some(title) = self.title {
+            embed.insert("title".to_string(), json!(title));
+        }
+        if let Some(description) = self.description {
+            embed.insert("description".to_string(), json!(description));
+        }
+        if let Some(url) = self.url {
+            embed.insert("url".to_string(), json!(url));
+        }
+        if let Some(color) = self.color {
+            embed.insert("color".to_string(), json!(color));
+        }
+        if let Some(timestamp) = self.timestamp {
+            embed.insert(
+                "timestamp".to_string(),
+                json!(timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            );
+        }
+        if let Some(footer) = self.footer {
+            embed.insert("footer".to_string(), json!({ "text": footer }));
+        }
+        if !self.fields.is_empty() {
+            let fields = self
+                .fields
+                .into_iter()
+                .map(|f| json!({ "name": f.name, "value": f.value, "inline": f.inline }))
+                .collect::<Vec<Value>>();
+            embed.insert("fields".to_string(), Value::Array(fields));
+        }
+        Value::Object(embed)
+    }
+}
@@ -1,7 +1,451 @@
-/*
-TODO:
-- Database Structs + Functions
-  - CRUD
-  - Source
-  - Target
-*/
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{comm::websocket::connection::PRIORITY_NORMAL, error::KohakuError};
+
+// =========================================== API ============================================= //
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterCodeRequest {
+    pub code: String,
+    pub description: Option<String>,
+}
+
+/// Body of `PATCH /events/codes/{code}`
+#[derive(Debug, Deserialize)]
+pub struct UpdateCodeRequest {
+    /// New description of the code. `null` or omitted removes the description
+    pub description: Option<String>,
+}
+
+/// Response of `DELETE /events/codes/{code}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnregisterCodeResponse {
+    pub code: String,
+    /// Amount of subscriptions that were removed together with the code
+    pub removed_subscriptions: usize,
+}
+
+/// Body of `POST /admin/codes/purge-stale`
+#[derive(Debug, Deserialize)]
+pub struct PurgeStaleRequest {
+    /// Codes unused for at least this many days and without subscriptions are removed
+    pub older_than_days: u32,
+}
+
+/// Response of `POST /admin/codes/purge-stale`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurgeStaleResponse {
+    /// Amount of removed codes
+    pub purged: usize,
+}
+
+/// Query of `GET /events/codes`
+#[derive(Debug, Deserialize)]
+pub struct CodeListQuery {
+    /// Only codes registered by this owner
+    pub owner: Option<String>,
+}
+
+/// Body of `POST /events/codes/bulk`
+#[derive(Debug, Deserialize)]
+pub struct BulkRegisterRequest {
+    pub codes: Vec<RegisterCodeRequest>,
+    /// Skip already registered codes instead of failing the whole request
+    #[serde(default)]
+    pub skip_duplicates: bool,
+}
+
+/// Query of `GET /events/subscriptions/by-code`
+#[derive(Debug, Deserialize)]
+pub struct CodePrefixQuery {
+    /// Prefix of the codes. Empty = all codes
+    #[serde(default)]
+    pub prefix: String,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Query of the subscription endpoints `POST /events/subscriptions` (listing) and `POST /events/subscriptions/manage`.
+///
+/// All parameters are optional when deserializing. Which ones are required is checked per endpoint,
+/// see [`SubscriptionQuery::list_filter`] and [`SubscriptionQuery::action`].
+#[derive(Debug, Default, Deserialize)]
+pub struct SubscriptionQuery {
+    /// Code to subscribe to
+    pub subscribe: Option<String>,
+    /// Code to unsubscribe from
+    pub unsubscribe: Option<String>,
+    pub channel_id: Option<i64>,
+    pub guild_id: Option<i64>,
+    /// Thread within the channel. [`None`] targets the channel itself
+    pub thread_id: Option<i64>,
+    /// Message format, see [`NotificationTarget::format`]
+    pub format: Option<String>,
+    /// Comma-separated Discord role ids to mention, see [`NotificationTarget::mention_roles`]
+    pub mention_roles: Option<String>,
+    /// Lifetime of the subscription in seconds. [`None`] never expires
+    pub expires_in: Option<i64>,
+}
+
+/// Body of `POST /events/subscriptions/manage/batch`
+#[derive(Debug, Deserialize)]
+pub struct BatchSubscribeRequest {
+    pub codes: Vec<String>,
+    pub channel_id: i64,
+    pub guild_id: i64,
+    pub thread_id: Option<i64>,
+    pub format: Option<String>,
+    #[serde(default)]
+    pub mention_roles: Vec<i64>,
+    /// Lifetime of the subscriptions in seconds. [`None`] never expires
+    pub expires_in: Option<i64>,
+}
+
+/// Query of `GET /events/subscriptions/export`
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub guild_id: i64,
+}
+
+/// Response of `POST /events/subscriptions/import`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportSubscriptionsResponse {
+    /// Amount of created or updated subscriptions
+    pub imported: usize,
+}
+
+/// Body of `POST /events/notify`
+#[derive(Debug, Deserialize)]
+pub struct NotifyRequest {
+    pub code: String,
+    /// Short description of what triggered the notification, e.g. `manual-announcement`
+    pub triggering_event: String,
+    /// Discord embed object
+    pub embed: Option<serde_json::Value>,
+    /// Plain message, formatted per target
+    pub message: Option<String>,
+}
+
+/// Body of `POST /events/notify/schedule`
+#[derive(Debug, Deserialize)]
+pub struct ScheduleNotificationRequest {
+    pub code: String,
+    /// Short description of what triggered the notification, e.g. `release-announcement`
+    pub triggering_event: String,
+    /// Discord embed object
+    pub embed: Option<serde_json::Value>,
+    /// Plain message, formatted per target
+    pub message: Option<String>,
+    /// Time the notification is sent at. Must be in the future
+    pub send_at: DateTime<Utc>,
+}
+
+/// Body of `PATCH /events/subscriptions/{id}/active`
+#[derive(Debug, Deserialize)]
+pub struct SetActiveRequest {
+    pub active: bool,
+}
+
+/// Operation requested via [`SubscriptionQuery::action`], holding the code
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionAction {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+impl SubscriptionQuery {
+    /// Validates the query of the listing endpoint: Only the optional `channel_id` and `guild_id` filters are allowed
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The `(channel_id, guild_id)` filters
+    /// - [`Err`] : A [`KohakuError::ValidationError`] if a parameter of the manage endpoint is set
+    pub fn list_filter(&self) -> Result<(Option<i64>, Option<i64>), KohakuError> {
+        if self.subscribe.is_some() || self.unsubscribe.is_some() {
+            return Err(KohakuError::ValidationError(
+                "`subscribe` and `unsubscribe` are not allowed when listing subscriptions!"
+                    .to_string(),
+            ));
+        }
+        Ok((self.channel_id, self.guild_id))
+    }
+
+    /// Validates the query of the manage endpoint: Exactly one of `subscribe` or `unsubscribe` and both ids are required
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The requested [`SubscriptionAction`] with the `channel_id` and `guild_id` of the target
+    /// - [`Err`] : A [`KohakuError::ValidationError`] naming the invalid combination
+    pub fn action(&self) -> Result<(SubscriptionAction, i64, i64), KohakuError> {
+        let action = match (&self.subscribe, &self.unsubscribe) {
+            (Some(code), None) => SubscriptionAction::Subscribe(code.clone()),
+            (None, Some(code)) => SubscriptionAction::Unsubscribe(code.clone()),
+            _ => {
+                return Err(KohakuError::ValidationError(
+                    "Exactly one of `subscribe` or `unsubscribe` must be set!".to_string(),
+                ))
+            }
+        };
+        match (self.channel_id, self.guild_id) {
+            (Some(channel_id), Some(guild_id)) => Ok((action, channel_id, guild_id)),
+            _ => Err(KohakuError::ValidationError(
+                "Both `channel_id` and `guild_id` must be set!".to_string(),
+            )),
+        }
+    }
+
+    /// Parses the comma-separated `mention_roles`
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The role ids. Empty if none were given
+    /// - [`Err`] : A [`KohakuError::ValidationError`] if an entry is not a valid id
+    pub fn mention_roles(&self) -> Result<Vec<i64>, KohakuError> {
+        self.mention_roles
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|role| !role.is_empty())
+            .map(|role| {
+                role.parse().map_err(|_| {
+                    KohakuError::ValidationError(format!(
+                        "Invalid role id in mention_roles: {}",
+                        role
+                    ))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Converts a lifetime in seconds into an expiry timestamp
+///
+/// # Parameters
+/// - `expires_in` : Lifetime in seconds. [`None`] never expires
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The expiry timestamp (UTC), or [`None`] if `expires_in` is [`None`]
+/// - [`Err`] : A [`KohakuError::ValidationError`] if `expires_in` is not positive or the expiry is out of range
+pub fn expiry_from_secs(expires_in: Option<i64>) -> Result<Option<NaiveDateTime>, KohakuError> {
+    match expires_in {
+        None => Ok(None),
+        Some(secs) if secs > 0 => Duration::try_seconds(secs)
+            .and_then(|lifetime| Utc::now().naive_utc().checked_add_signed(lifetime))
+            .map(Some)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(format!(
+                    "expires_in of {} seconds is out of range",
+                    secs
+                ))
+            }),
+        Some(_) => Err(KohakuError::ValidationError(
+            "expires_in must be a positive number of seconds".to_string(),
+        )),
+    }
+}
+
+// ========================================== Codes ============================================ //
+
+/// Representation of database entry of a notification code (topic clients can subscribe to)
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = crate::db::schema::notification_codes)]
+pub struct NotificationCode {
+    /// Unique identifier of the topic, e.g. `game:release`
+    pub code: String,
+    /// Human readable description of the topic
+    pub description: Option<String>,
+    /// Timestamp of the last notification sent under this code
+    pub last_used: Option<NaiveDateTime>,
+    /// Timestamp of creation (Default: Current Time UTC)
+    pub created_at: NaiveDateTime,
+    /// Owner of the API key that registered the code, i.e. the service managing the topic
+    pub owner: Option<String>,
+    /// Timestamp of the last notification that reached a client (or the fallback webhook)
+    pub last_success: Option<NaiveDateTime>,
+    /// Why the last failed notification didn't reach anyone, e.g. no client was online
+    pub last_error: Option<String>,
+    /// Timestamp of the last failed notification
+    pub last_error_at: Option<NaiveDateTime>,
+}
+
+/// Form to create a new [struct@NotificationCode].
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::db::schema::notification_codes)]
+pub struct NewNotificationCode {
+    pub code: String,
+    pub description: Option<String>,
+    pub owner: Option<String>,
+}
+
+// ========================================= Targets =========================================== //
+
+/// Representation of database entry of a subscription of a Discord channel (or thread) to a [struct@NotificationCode]
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = crate::db::schema::notification_targets)]
+pub struct NotificationTarget {
+    /// Serial Primary Key given by the database
+    pub id: i32,
+    /// Subscribed [struct@NotificationCode]
+    pub code: String,
+    /// Discord channel to post into
+    pub channel_id: i64,
+    /// Discord guild of the channel
+    pub guild_id: i64,
+    /// Discord thread within the channel. If set, notifications are posted into the thread instead of the channel
+    pub thread_id: Option<i64>,
+    /// Message format. See [`crate::utils::comm::events::template::render`] for the available fields
+    pub format: Option<String>,
+    /// Timestamp of creation (Default: Current Time UTC)
+    pub created_at: NaiveDateTime,
+    /// Discord roles to mention (`<@&id>`) on every notification
+    pub mention_roles: Vec<i64>,
+    /// Paused subscriptions (`false`) receive no notifications (Default: `true`)
+    pub active: bool,
+    /// Timestamp after which the subscription is ignored and removed. [`None`] never expires
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// Instance independent form of a [struct@NotificationTarget], used to move subscriptions between Kohaku instances.
+///
+/// Returned by `GET /events/subscriptions/export` and accepted as list by `POST /events/subscriptions/import`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SubscriptionExport {
+    pub code: String,
+    pub channel_id: i64,
+    pub guild_id: i64,
+    pub thread_id: Option<i64>,
+    pub format: Option<String>,
+    #[serde(default)]
+    pub mention_roles: Vec<i64>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// Helper: Subscriptions without a state are imported as active
+fn default_active() -> bool {
+    true
+}
+
+impl From<NotificationTarget> for SubscriptionExport {
+    fn from(target: NotificationTarget) -> Self {
+        Self {
+            code: target.code,
+            channel_id: target.channel_id,
+            guild_id: target.guild_id,
+            thread_id: target.thread_id,
+            format: target.format,
+            mention_roles: target.mention_roles,
+            active: target.active,
+            expires_at: target.expires_at,
+        }
+    }
+}
+
+/// Form to create a new [struct@NotificationTarget].
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::db::schema::notification_targets)]
+pub struct NewNotificationTarget {
+    pub code: String,
+    pub channel_id: i64,
+    pub guild_id: i64,
+    pub thread_id: Option<i64>,
+    pub format: Option<String>,
+    pub mention_roles: Vec<i64>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+// ====================================== Notifications ======================================== //
+
+/// Representation of database entry of a notification deferred to a later time, see [`crate::utils::comm::events::notifications::schedule_notification`]
+#[derive(Debug, Queryable, Selectable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = crate::db::schema::scheduled_notifications)]
+pub struct ScheduledNotification {
+    pub id: i32,
+    /// [struct@NotificationCode] the notification is sent under
+    pub code: String,
+    pub triggering_event: String,
+    /// Discord embed object
+    pub embed: Option<serde_json::Value>,
+    /// Plain message, formatted per target when sent
+    pub message: Option<String>,
+    /// Time (UTC) the notification is sent at
+    pub send_at: NaiveDateTime,
+    /// Time (UTC) the notification was actually sent at. [`None`] while pending
+    pub sent_at: Option<NaiveDateTime>,
+    /// Timestamp of creation (Default: Current Time UTC)
+    pub created_at: NaiveDateTime,
+}
+
+/// Form to create a new [struct@ScheduledNotification].
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::db::schema::scheduled_notifications)]
+pub struct NewScheduledNotification {
+    pub code: String,
+    pub triggering_event: String,
+    pub embed: Option<serde_json::Value>,
+    pub message: Option<String>,
+    pub send_at: NaiveDateTime,
+}
+
+/// A single notification for one [struct@NotificationTarget], sent to the connected clients.
+///
+/// If neither `embed`, a non-blank `message` nor `attachments` are set, nothing will be sent, see [`NotificationData::is_empty`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NotificationData {
+    /// [struct@NotificationCode] the notification was sent under
+    pub code: String,
+    /// Short description of what triggered the notification (e.g. the name of a task)
+    pub triggering_event: String,
+    pub channel_id: i64,
+    pub guild_id: i64,
+    /// If set, the client posts into this thread instead of the channel
+    pub thread_id: Option<i64>,
+    /// Discord embed object
+    pub embed: Option<serde_json::Value>,
+    /// Plain message, already formatted with the format of the target
+    pub message: Option<String>,
+    /// Discord roles the client mentions (`<@&id>`) in front of the message
+    pub mention_roles: Vec<i64>,
+    /// URLs of files (e.g. images) the client fetches and uploads alongside the message
+    #[serde(default)]
+    pub attachments: Option<Vec<String>>,
+    /// Notifications with a higher priority (e.g. security alerts) are delivered ahead of routine ones
+    #[serde(default = "default_priority")]
+    pub priority: u8,
+}
+
+/// Result of [`crate::utils::comm::events::notifications::notify`], also the response of `POST /events/notify`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotifyReport {
+    /// Notifications sent to the clients, one per notified target
+    pub sent: Vec<NotificationData>,
+    /// Amount of sent notifications no live client received (e.g. the bot is offline).
+    /// They are only buffered for clients that may resume their session
+    pub offline: usize,
+    /// The notification was posted to the fallback webhook, as no live client received it
+    pub fallback: bool,
+    /// The notification was suppressed as duplicate of a recent one, nothing was sent
+    pub suppressed: bool,
+}
+
+/// Helper: Priority of notifications without a priority, see [`PRIORITY_NORMAL`]
+fn default_priority() -> u8 {
+    PRIORITY_NORMAL
+}
+
+impl NotificationData {
+    /// Whether the notification has nothing to post: No embed, no (or only a blank) message and no attachments
+    pub fn is_empty(&self) -> bool {
+        self.embed.is_none()
+            && self
+                .message
+                .as_deref()
+                .is_none_or(|message| message.trim().is_empty())
+            && self.attachments.as_ref().is_none_or(Vec::is_empty)
+    }
+}
@@ -0,0 +1,1465 @@
+#[cfg(test)]
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::{prelude::*, result::DatabaseErrorKind, PgExpressionMethods};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use url::Url;
+
+#[cfg(not(test))]
+use crate::utils::{config::get_config, scheduler::try_get_scheduler};
+use crate::{
+    db::{schema, with_connection},
+    utils::{
+        comm::{
+            events::{
+                cache::TtlCache,
+                dispatcher::dispatch,
+                models::{
+                    NewNotificationCode, NewNotificationTarget, NewScheduledNotification,
+                    NotificationCode, NotificationData, NotificationTarget, NotifyReport,
+                    ScheduledNotification, SubscriptionExport,
+                },
+                template::{render, TemplateContext},
+                webhook::{post_webhook, WebhookPayload},
+            },
+            websocket::{
+                connection::PRIORITY_NORMAL,
+                manager::{get_manager, DeliveryReport},
+            },
+        },
+        error::KohakuError,
+        scheduler::Scheduler,
+    },
+};
+
+/// Allowed format of notification codes, e.g. `game:release` or `news.patch-notes`
+static CODE_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z0-9_.:-]{1,64}$").unwrap());
+/// Attachments per notification. Discord accepts at most 10 files per message
+pub const ATTACHMENTS_MAX: usize = 10;
+/// Code of the notifications sent by [`notify_guild`]. Not a valid code, so it never collides with registered ones
+pub const GUILD_BROADCAST_CODE: &str = "*broadcast";
+/// Triggering event of the notifications sent by [`notify_guild`]
+const GUILD_BROADCAST_EVENT: &str = "guild-broadcast";
+
+/// Registered codes by code, see [`get_code`]
+static CODE_CACHE: Lazy<TtlCache<NotificationCode>> = Lazy::new(|| TtlCache::new(get_cache_ttl()));
+/// Result of [`get_all_codes`], stored under [`ALL_CODES_KEY`]
+static ALL_CODES_CACHE: Lazy<TtlCache<Vec<NotificationCode>>> =
+    Lazy::new(|| TtlCache::new(get_cache_ttl()));
+/// Active subscriptions by code, see [`get_active_subscriptions`]
+static SUBSCRIPTION_CACHE: Lazy<TtlCache<Vec<NotificationTarget>>> =
+    Lazy::new(|| TtlCache::new(get_cache_ttl()));
+/// Key of the only entry of [`ALL_CODES_CACHE`]. Not a valid code, see [`CODE_PATTERN`]
+const ALL_CODES_KEY: &str = "*";
+/// Recently sent notifications by `code#content-hash`, see [`content_hash`]. Entries expire after the dedup window
+static RECENT_NOTIFICATIONS: Lazy<TtlCache<()>> = Lazy::new(|| TtlCache::new(get_dedup_window()));
+
+/// Will select the configured TTL of the code caches in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_cache_ttl() -> i64 {
+    get_config().events_cache_ttl_sec
+}
+
+/// Will select a fixed TTL of the code caches in a test environment (cargo test)
+#[cfg(test)]
+fn get_cache_ttl() -> i64 {
+    60
+}
+
+/// Will select the configured window of the notification deduplication in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_dedup_window() -> i64 {
+    get_config().events_dedup_window_sec
+}
+
+/// Will select a fixed window of the notification deduplication in a test environment (cargo test)
+#[cfg(test)]
+fn get_dedup_window() -> i64 {
+    60
+}
+
+/// Will select the configured subscription limit per guild in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_guild_limit() -> u32 {
+    get_config().events_max_subscriptions_per_guild
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Subscription limit per guild of the current test, see [`get_guild_limit`]
+    pub(crate) static GUILD_LIMIT: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Will select the limit set by the current test via [`GUILD_LIMIT`] (Default: unlimited) in a test environment (cargo test)
+#[cfg(test)]
+fn get_guild_limit() -> u32 {
+    GUILD_LIMIT.with(Cell::get)
+}
+
+/// Will select the configured fallback webhook in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_webhook_url() -> Option<String> {
+    get_config().events_webhook_url.clone()
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Fallback webhook of the current test, see [`get_webhook_url`]
+    pub(crate) static WEBHOOK_URL: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Will select the webhook set by the current test via [`WEBHOOK_URL`] (Default: none) in a test environment (cargo test)
+#[cfg(test)]
+fn get_webhook_url() -> Option<String> {
+    WEBHOOK_URL.with(|url| url.borrow().clone())
+}
+
+/// Will select the global scheduler in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_notify_scheduler() -> Option<Arc<Scheduler>> {
+    try_get_scheduler()
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Scheduler of the current test, see [`get_notify_scheduler`]
+    pub(crate) static NOTIFY_SCHEDULER: RefCell<Option<Arc<Scheduler>>> = const { RefCell::new(None) };
+}
+
+/// Will select the scheduler set by the current test via [`NOTIFY_SCHEDULER`] (Default: none) in a test environment (cargo test)
+#[cfg(test)]
+fn get_notify_scheduler() -> Option<Arc<Scheduler>> {
+    NOTIFY_SCHEDULER.with(|scheduler| scheduler.borrow().clone())
+}
+
+/// Helper: Drops all cached data of a code, e.g. after it was unregistered
+fn invalidate_code(code: &str) {
+    CODE_CACHE.invalidate(code);
+    SUBSCRIPTION_CACHE.invalidate(code);
+    ALL_CODES_CACHE.clear();
+}
+
+// ========================================== Codes ============================================ //
+
+/// Helper: Checks a notification code against [`CODE_PATTERN`]
+fn validate_code(code: &str) -> Result<(), KohakuError> {
+    if !CODE_PATTERN.is_match(code) {
+        return Err(KohakuError::ValidationError(format!(
+            "Invalid notification code `{}`: Only lowercase alphanumerics and `_ . : -` (max. 64 chars) are allowed!",
+            code
+        )));
+    }
+    Ok(())
+}
+
+/// Registers a new notification code clients can subscribe to
+///
+/// # Parameters
+/// - `code` : Identifier of the topic. Lowercase alphanumerics and `_ . : -`, up to 64 chars
+/// - `description` : Optional human readable description of the topic
+/// - `owner` : Optional owner of the topic, e.g. the owner of the registering API key
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The stored [struct@NotificationCode]
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if the code is malformed or already registered, or another [enum@KohakuError] based on the failing operation
+pub async fn register(
+    code: &str,
+    description: Option<String>,
+    owner: Option<String>,
+) -> Result<NotificationCode, KohakuError> {
+    validate_code(code)?;
+    let new_code = NewNotificationCode {
+        code: code.to_string(),
+        description,
+        owner,
+    };
+    with_connection(move |conn| {
+        diesel::insert_into(schema::notification_codes::table)
+            .values(&new_code)
+            .get_result(conn)
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    KohakuError::ValidationError(format!(
+                        "Notification code `{}` is already registered!",
+                        new_code.code
+                    ))
+                }
+                e => KohakuError::DatabaseError(e),
+            })
+    })
+    .await
+    .inspect(|_| ALL_CODES_CACHE.clear())
+}
+
+/// Registers multiple notification codes at once.
+///
+/// All codes are stored in a single transaction: If any code is malformed or (with `skip_duplicates = false`)
+/// already registered, none are stored.
+///
+/// # Parameters
+/// - `entries` : Pairs of code and optional description, see [`register`]
+/// - `owner` : Optional owner of all topics, see [`register`]
+/// - `skip_duplicates` : Skip codes that are already registered (or given twice) instead of failing
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The newly stored [struct@NotificationCode]s in the order of `entries`. Skipped codes are not included
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if a code is malformed or a duplicate is not skipped, or another [enum@KohakuError] based on the failing operation
+pub async fn register_many(
+    entries: &[(String, Option<String>)],
+    owner: Option<String>,
+    skip_duplicates: bool,
+) -> Result<Vec<NotificationCode>, KohakuError> {
+    for (code, _) in entries {
+        validate_code(code)?;
+    }
+    let new_codes: Vec<NewNotificationCode> = entries
+        .iter()
+        .map(|(code, description)| NewNotificationCode {
+            code: code.clone(),
+            description: description.clone(),
+            owner: owner.clone(),
+        })
+        .collect();
+
+    with_connection(move |conn| {
+        conn.transaction::<_, KohakuError, _>(|conn| {
+            let mut registered = Vec::with_capacity(new_codes.len());
+            for new_code in &new_codes {
+                let stored = diesel::insert_into(schema::notification_codes::table)
+                    .values(new_code)
+                    .on_conflict_do_nothing()
+                    .get_result::<NotificationCode>(conn)
+                    .optional()?;
+                match stored {
+                    Some(stored) => registered.push(stored),
+                    None if skip_duplicates => {}
+                    None => {
+                        return Err(KohakuError::ValidationError(format!(
+                            "Notification code `{}` is already registered!",
+                            new_code.code
+                        )))
+                    }
+                }
+            }
+            Ok(registered)
+        })
+    })
+    .await
+    .inspect(|_| ALL_CODES_CACHE.clear())
+}
+
+/// Removes a notification code and all of its subscriptions
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The amount of deleted subscriptions ([struct@NotificationTarget]s) of the code
+/// - [`Err`] : A [enum@KohakuError::NotFound] if the code is not registered, or another [enum@KohakuError] based on the failing operation
+pub async fn unregister(code_: &str) -> Result<usize, KohakuError> {
+    let target = code_.to_string();
+
+    let result = with_connection(move |conn| {
+        conn.transaction::<_, KohakuError, _>(|conn| {
+            // Deleted explicitly instead of relying on the cascade to count them
+            let removed = diesel::delete(
+                schema::notification_targets::table
+                    .filter(schema::notification_targets::code.eq(&target)),
+            )
+            .execute(conn)?;
+            let deleted =
+                diesel::delete(schema::notification_codes::table.find(&target)).execute(conn)?;
+            if deleted == 0 {
+                return Err(KohakuError::NotFound(format!(
+                    "Notification code `{}` is not registered!",
+                    target
+                )));
+            }
+            Ok(removed)
+        })
+    })
+    .await;
+    // Only after the delete, otherwise concurrent reads re-cache the old rows.
+    // Also invalidated on failure, as the code might have been removed elsewhere
+    invalidate_code(code_);
+    result
+}
+
+/// Gets a registered notification code. Served from a short-lived cache if possible
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [struct@NotificationCode]
+/// - [`Err`] : A [enum@KohakuError::NotFound] if the code is not registered, or another [enum@KohakuError] based on the failing operation
+pub async fn get_code(code_: &str) -> Result<NotificationCode, KohakuError> {
+    use schema::notification_codes::dsl::*;
+    if let Some(cached) = CODE_CACHE.get(code_) {
+        return Ok(cached);
+    }
+    let target = code_.to_string();
+    let missing = format!("Code `{}` is not registered!", code_);
+
+    let stored: NotificationCode = with_connection(move |conn| {
+        notification_codes
+            .find(target)
+            .first(conn)
+            .map_err(KohakuError::not_found_or_database(missing))
+    })
+    .await?;
+    CODE_CACHE.insert(code_, stored.clone());
+    Ok(stored)
+}
+
+/// Sets the timestamp of the last notification sent under a code
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic
+/// - `ts` : Timestamp of the notification
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The updated [struct@NotificationCode]
+/// - [`Err`] : A [enum@KohakuError::NotFound] if the code is not registered, or another [enum@KohakuError] based on the failing operation
+pub async fn update_code_ts(
+    code_: &str,
+    ts: DateTime<Utc>,
+) -> Result<NotificationCode, KohakuError> {
+    use schema::notification_codes::dsl::*;
+    let target = code_.to_string();
+    let missing = format!("Code `{}` is not registered!", code_);
+
+    let updated: NotificationCode = with_connection(move |conn| {
+        diesel::update(notification_codes.find(target))
+            .set(last_used.eq(Some(ts.naive_utc())))
+            .get_result(conn)
+            .map_err(KohakuError::not_found_or_database(missing))
+    })
+    .await?;
+    CODE_CACHE.insert(code_, updated.clone());
+    ALL_CODES_CACHE.clear();
+    Ok(updated)
+}
+
+/// Changes the description of a registered notification code. Its subscriptions are kept
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic
+/// - `description_` : New human readable description of the topic. [`None`] removes the description
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The updated [struct@NotificationCode]
+/// - [`Err`] : A [enum@KohakuError::NotFound] if the code is not registered, or another [enum@KohakuError] based on the failing operation
+pub async fn update_description(
+    code_: &str,
+    description_: Option<String>,
+) -> Result<NotificationCode, KohakuError> {
+    use schema::notification_codes::dsl::*;
+    let target = code_.to_string();
+    let missing = format!("Code `{}` is not registered!", code_);
+
+    let updated: NotificationCode = with_connection(move |conn| {
+        diesel::update(notification_codes.find(target))
+            .set(description.eq(description_))
+            .get_result(conn)
+            .map_err(KohakuError::not_found_or_database(missing))
+    })
+    .await?;
+    CODE_CACHE.insert(code_, updated.clone());
+    ALL_CODES_CACHE.clear();
+    Ok(updated)
+}
+
+/// Removes notification codes that weren't used for a while and have no subscriptions (including paused and expired ones)
+///
+/// # Parameters
+/// - `older_than` : Cutoff (UTC). Codes last used before it are removed. Codes that were never used count from their registration
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The amount of removed [struct@NotificationCode]s
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn purge_stale(older_than: NaiveDateTime) -> Result<usize, KohakuError> {
+    use schema::{notification_codes::dsl::*, notification_targets};
+
+    let purged: Vec<String> = with_connection(move |conn| {
+        let subscribed = notification_targets::table
+            .filter(notification_targets::code.eq(code))
+            .select(notification_targets::id);
+        diesel::delete(
+            notification_codes
+                .filter(
+                    last_used
+                        .lt(older_than)
+                        .or(last_used.is_null().and(created_at.lt(older_than))),
+                )
+                .filter(diesel::dsl::not(diesel::dsl::exists(subscribed))),
+        )
+        .returning(code)
+        .get_results(conn)
+        .map_err(KohakuError::DatabaseError)
+    })
+    .await?;
+    for purged_code in &purged {
+        invalidate_code(purged_code);
+    }
+    Ok(purged.len())
+}
+
+/// Gets all registered notification codes. Served from a short-lived cache if possible
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : All [struct@NotificationCode]s ordered by code
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn get_all_codes() -> Result<Vec<NotificationCode>, KohakuError> {
+    use schema::notification_codes::dsl::*;
+    if let Some(cached) = ALL_CODES_CACHE.get(ALL_CODES_KEY) {
+        return Ok(cached);
+    }
+
+    let codes: Vec<NotificationCode> = with_connection(|conn| {
+        notification_codes
+            .order(code.asc())
+            .load(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await?;
+    ALL_CODES_CACHE.insert(ALL_CODES_KEY, codes.clone());
+    Ok(codes)
+}
+
+// ====================================== Subscriptions ======================================== //
+
+/// Helper: Inserts a subscription or updates the format, mentioned roles and expiry of an existing one for the same target.
+/// New subscriptions are rejected with a [`KohakuError::ValidationError`] once the guild has `guild_limit` (`0` = unlimited) subscriptions
+fn upsert_target(
+    conn: &mut PgConnection,
+    target: NewNotificationTarget,
+    guild_limit: u32,
+) -> Result<NotificationTarget, KohakuError> {
+    use schema::notification_targets::dsl::*;
+
+    let existing: Option<NotificationTarget> = notification_targets
+        .filter(code.eq(&target.code))
+        .filter(channel_id.eq(target.channel_id))
+        .filter(guild_id.eq(target.guild_id))
+        .filter(thread_id.is_not_distinct_from(target.thread_id))
+        .first(conn)
+        .optional()?;
+
+    if let Some(existing) = existing {
+        return Ok(diesel::update(notification_targets.find(existing.id))
+            .set((
+                format.eq(target.format),
+                mention_roles.eq(target.mention_roles),
+                expires_at.eq(target.expires_at),
+            ))
+            .get_result(conn)?);
+    }
+
+    if guild_limit > 0 {
+        let now = Utc::now().naive_utc();
+        let count: i64 = notification_targets
+            .filter(guild_id.eq(target.guild_id))
+            .filter(expires_at.is_null().or(expires_at.gt(now)))
+            .count()
+            .get_result(conn)?;
+        if count >= i64::from(guild_limit) {
+            return Err(KohakuError::ValidationError(format!(
+                "Guild {} reached the limit of {} subscriptions!",
+                target.guild_id, guild_limit
+            )));
+        }
+    }
+    Ok(diesel::insert_into(notification_targets)
+        .values(&target)
+        .get_result(conn)?)
+}
+
+/// Subscribes a Discord channel (or a thread within it) to a notification code.
+///
+/// Subscribing the same target twice updates the format, mentioned roles and expiry of the existing subscription.
+/// New subscriptions are rejected once the guild reached the configured limit, see [`crate::utils::config::Config::events_max_subscriptions_per_guild`].
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic. Must be registered
+/// - `channel_id_` : Discord channel to post into
+/// - `guild_id_` : Discord guild of the channel
+/// - `thread_id_` : Optional Discord thread within the channel to post into instead
+/// - `format_` : Optional message format, see [`NotificationTarget::format`]
+/// - `mention_roles_` : Discord roles to mention on every notification
+/// - `expires_at_` : Optional timestamp (UTC) after which the subscription expires
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The stored [struct@NotificationTarget]
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if the guild reached its limit, or another [enum@KohakuError] based on the failing operation
+pub async fn subscribe(
+    code_: &str,
+    channel_id_: i64,
+    guild_id_: i64,
+    thread_id_: Option<i64>,
+    format_: Option<String>,
+    mention_roles_: Vec<i64>,
+    expires_at_: Option<NaiveDateTime>,
+) -> Result<NotificationTarget, KohakuError> {
+    // Ensure the code is registered
+    get_code(code_).await?;
+    let target = NewNotificationTarget {
+        code: code_.to_string(),
+        channel_id: channel_id_,
+        guild_id: guild_id_,
+        thread_id: thread_id_,
+        format: format_,
+        mention_roles: mention_roles_,
+        expires_at: expires_at_,
+    };
+
+    let guild_limit = get_guild_limit();
+
+    with_connection(move |conn| conn.transaction(|conn| upsert_target(conn, target, guild_limit)))
+        .await
+        .inspect(|_| SUBSCRIPTION_CACHE.invalidate(code_))
+}
+
+/// Subscribes a Discord channel (or a thread within it) to multiple notification codes at once.
+///
+/// All subscriptions are stored in a single transaction: If any code is not registered or the guild reaches its limit, none are stored.
+///
+/// # Parameters
+/// - `codes` : Identifiers of the topics. All must be registered
+/// - `channel_id_` : Discord channel to post into
+/// - `guild_id_` : Discord guild of the channel
+/// - `thread_id_` : Optional Discord thread within the channel to post into instead
+/// - `format_` : Optional message format used for all subscriptions, see [`NotificationTarget::format`]
+/// - `mention_roles_` : Discord roles to mention on every notification
+/// - `expires_at_` : Optional timestamp (UTC) after which the subscriptions expire
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The stored [struct@NotificationTarget]s in the order of `codes`
+/// - [`Err`] : A [enum@KohakuError::NotFound] if a code is not registered, a [enum@KohakuError::ValidationError] if the guild reached its limit,
+///   or another [enum@KohakuError] based on the failing operation
+pub async fn subscribe_many(
+    codes: &[&str],
+    channel_id_: i64,
+    guild_id_: i64,
+    thread_id_: Option<i64>,
+    format_: Option<String>,
+    mention_roles_: Vec<i64>,
+    expires_at_: Option<NaiveDateTime>,
+) -> Result<Vec<NotificationTarget>, KohakuError> {
+    let codes: Vec<String> = codes.iter().map(|c| c.to_string()).collect();
+    let guild_limit = get_guild_limit();
+
+    with_connection(move |conn| {
+        conn.transaction::<_, KohakuError, _>(|conn| {
+            let mut targets = Vec::with_capacity(codes.len());
+            for code_ in &codes {
+                let registered = schema::notification_codes::table
+                    .find(code_)
+                    .first::<NotificationCode>(conn)
+                    .optional()?;
+                if registered.is_none() {
+                    return Err(KohakuError::NotFound(format!(
+                        "Notification code `{}` is not registered!",
+                        code_
+                    )));
+                }
+
+                targets.push(upsert_target(
+                    conn,
+                    NewNotificationTarget {
+                        code: code_.to_string(),
+                        channel_id: channel_id_,
+                        guild_id: guild_id_,
+                        thread_id: thread_id_,
+                        format: format_.clone(),
+                        mention_roles: mention_roles_.clone(),
+                        expires_at: expires_at_,
+                    },
+                    guild_limit,
+                )?);
+            }
+            Ok(targets)
+        })
+    })
+    .await
+    .inspect(|targets| {
+        for target in targets {
+            SUBSCRIPTION_CACHE.invalidate(&target.code);
+        }
+    })
+}
+
+/// Exports all subscriptions of a guild, e.g. to move them to another Kohaku instance. Includes paused but no expired subscriptions.
+///
+/// # Parameters
+/// - `guild_id_` : Discord guild of the subscriptions
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [`SubscriptionExport`]s of the guild, oldest first
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn export_subscriptions(guild_id_: i64) -> Result<Vec<SubscriptionExport>, KohakuError> {
+    let targets = get_subscriptions(None, None, Some(guild_id_)).await?;
+    Ok(targets.into_iter().map(SubscriptionExport::from).collect())
+}
+
+/// Recreates exported subscriptions, see [`export_subscriptions`].
+///
+/// All subscriptions are stored in a single transaction: If any code is not registered or a guild reaches its limit, none are stored.
+/// Importing a subscription whose target already exists updates it instead, so importing twice is harmless.
+///
+/// # Parameters
+/// - `entries` : The [`SubscriptionExport`]s to import
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The stored [struct@NotificationTarget]s in the order of `entries`
+/// - [`Err`] : A [enum@KohakuError::NotFound] if a code is not registered, a [enum@KohakuError::ValidationError] if a guild reached its limit,
+///   or another [enum@KohakuError] based on the failing operation
+pub async fn import_subscriptions(
+    entries: Vec<SubscriptionExport>,
+) -> Result<Vec<NotificationTarget>, KohakuError> {
+    let guild_limit = get_guild_limit();
+
+    with_connection(move |conn| {
+        conn.transaction::<_, KohakuError, _>(|conn| {
+            let mut targets = Vec::with_capacity(entries.len());
+            for entry in entries {
+                let registered = schema::notification_codes::table
+                    .find(&entry.code)
+                    .first::<NotificationCode>(conn)
+                    .optional()?;
+                if registered.is_none() {
+                    return Err(KohakuError::NotFound(format!(
+                        "Notification code `{}` is not registered!",
+                        entry.code
+                    )));
+                }
+
+                let active_ = entry.active;
+                let mut target = upsert_target(
+                    conn,
+                    NewNotificationTarget {
+                        code: entry.code,
+                        channel_id: entry.channel_id,
+                        guild_id: entry.guild_id,
+                        thread_id: entry.thread_id,
+                        format: entry.format,
+                        mention_roles: entry.mention_roles,
+                        expires_at: entry.expires_at,
+                    },
+                    guild_limit,
+                )?;
+                if target.active != active_ {
+                    use schema::notification_targets::dsl::*;
+                    target = diesel::update(notification_targets.find(target.id))
+                        .set(active.eq(active_))
+                        .get_result(conn)?;
+                }
+                targets.push(target);
+            }
+            Ok(targets)
+        })
+    })
+    .await
+    .inspect(|targets| {
+        for target in targets {
+            SUBSCRIPTION_CACHE.invalidate(&target.code);
+        }
+    })
+}
+
+/// Unsubscribes a Discord channel (or a thread within it) from a notification code
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic
+/// - `channel_id_` : Discord channel of the subscription
+/// - `guild_id_` : Discord guild of the channel
+/// - `thread_id_` : Discord thread of the subscription. [`None`] only matches the subscription of the channel itself
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The subscription was deleted
+/// - [`Err`] : A [enum@KohakuError::NotFound] if no such subscription exists, or another [enum@KohakuError] based on the failing operation
+pub async fn unsubscribe(
+    code_: &str,
+    channel_id_: i64,
+    guild_id_: i64,
+    thread_id_: Option<i64>,
+) -> Result<(), KohakuError> {
+    use schema::notification_targets::dsl::*;
+    let target = code_.to_string();
+
+    let deleted = with_connection(move |conn| {
+        diesel::delete(
+            notification_targets
+                .filter(code.eq(target))
+                .filter(channel_id.eq(channel_id_))
+                .filter(guild_id.eq(guild_id_))
+                .filter(thread_id.is_not_distinct_from(thread_id_)),
+        )
+        .execute(conn)
+        .map_err(KohakuError::DatabaseError)
+    })
+    .await;
+    // Only after the delete, otherwise concurrent reads re-cache the old subscription
+    SUBSCRIPTION_CACHE.invalidate(code_);
+    if deleted? == 0 {
+        return Err(KohakuError::NotFound(
+            "Subscription could not be found!".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Gets subscriptions, optionally filtered by code, channel and / or guild. Includes paused but no expired subscriptions.
+///
+/// # Parameters
+/// - `code_` : Only subscriptions to this code
+/// - `channel_id_` : Only subscriptions of this channel (including its threads)
+/// - `guild_id_` : Only subscriptions within this guild
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The matching [struct@NotificationTarget]s, oldest first
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn get_subscriptions(
+    code_: Option<&str>,
+    channel_id_: Option<i64>,
+    guild_id_: Option<i64>,
+) -> Result<Vec<NotificationTarget>, KohakuError> {
+    use schema::notification_targets::dsl::*;
+    let code_ = code_.map(str::to_string);
+
+    with_connection(move |conn| {
+        let now = Utc::now().naive_utc();
+        let mut query = notification_targets
+            .filter(expires_at.is_null().or(expires_at.gt(now)))
+            .into_boxed();
+
+        if let Some(c) = code_ {
+            query = query.filter(code.eq(c));
+        }
+        if let Some(c) = channel_id_ {
+            query = query.filter(channel_id.eq(c));
+        }
+        if let Some(g) = guild_id_ {
+            query = query.filter(guild_id.eq(g));
+        }
+
+        query
+            .order(id.asc())
+            .load(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await
+}
+
+/// Gets subscriptions of all codes starting with a prefix, across all channels and guilds. Includes paused but no expired subscriptions.
+///
+/// Meant for bulk administration, e.g. finding everything tied to a game's codes (`game:`) for cleanup.
+///
+/// # Parameters
+/// - `prefix` : Prefix of the codes. An empty prefix matches all codes
+/// - `limit` : Maximum amount of returned subscriptions. Must be positive
+/// - `offset` : Amount of subscriptions to skip (pagination). Must not be negative
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The matching [struct@NotificationTarget]s, ordered by code and then oldest first
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if `limit` or `offset` are out of range, or another [enum@KohakuError] based on the failing operation
+pub async fn get_subscriptions_by_code_prefix(
+    prefix: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<NotificationTarget>, KohakuError> {
+    use schema::notification_targets::dsl::*;
+    if limit <= 0 || offset < 0 {
+        return Err(KohakuError::ValidationError(
+            "`limit` must be positive and `offset` must not be negative!".to_string(),
+        ));
+    }
+    // `_` is a valid code character, but a wildcard in LIKE patterns
+    let pattern = format!(
+        "{}%",
+        prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    with_connection(move |conn| {
+        let now = Utc::now().naive_utc();
+        notification_targets
+            .filter(code.like(pattern).escape('\\'))
+            .filter(expires_at.is_null().or(expires_at.gt(now)))
+            .order((code.asc(), id.asc()))
+            .limit(limit)
+            .offset(offset)
+            .load(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await
+}
+
+/// Gets the subscriptions of a code that receive notifications (i.e. are neither paused nor expired).
+/// Served from a short-lived cache if possible, which is invalidated on subscription changes
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The active [struct@NotificationTarget]s of `code_`, oldest first
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn get_active_subscriptions(code_: &str) -> Result<Vec<NotificationTarget>, KohakuError> {
+    use schema::notification_targets::dsl::*;
+    if let Some(cached) = SUBSCRIPTION_CACHE.get(code_) {
+        // Subscriptions may have expired since they were cached
+        let now = Utc::now().naive_utc();
+        return Ok(cached
+            .into_iter()
+            .filter(|target| target.expires_at.is_none_or(|expiry| expiry > now))
+            .collect());
+    }
+    let target = code_.to_string();
+
+    let targets: Vec<NotificationTarget> = with_connection(move |conn| {
+        let now = Utc::now().naive_utc();
+        notification_targets
+            .filter(code.eq(target))
+            .filter(active.eq(true))
+            .filter(expires_at.is_null().or(expires_at.gt(now)))
+            .order(id.asc())
+            .load(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await?;
+    SUBSCRIPTION_CACHE.insert(code_, targets.clone());
+    Ok(targets)
+}
+
+/// Pauses or resumes a subscription. Paused subscriptions are kept (including their format) but receive no notifications.
+///
+/// # Parameters
+/// - `id_` : Serial primary key of the subscription
+/// - `active_` : `false` to pause, `true` to resume
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The updated [struct@NotificationTarget]
+/// - [`Err`] : A [enum@KohakuError::NotFound] if no such subscription exists, or another [enum@KohakuError] based on the failing operation
+pub async fn set_subscription_active(
+    id_: i32,
+    active_: bool,
+) -> Result<NotificationTarget, KohakuError> {
+    use schema::notification_targets::dsl::*;
+
+    with_connection(move |conn| {
+        diesel::update(notification_targets.find(id_))
+            .set(active.eq(active_))
+            .get_result(conn)
+            .map_err(KohakuError::not_found_or_database(format!(
+                "Subscription {} could not be found!",
+                id_
+            )))
+    })
+    .await
+    .inspect(|target: &NotificationTarget| SUBSCRIPTION_CACHE.invalidate(&target.code))
+}
+
+/// Removes all expired subscriptions
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The amount of removed subscriptions
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn delete_expired_subscriptions() -> Result<usize, KohakuError> {
+    use schema::notification_targets::dsl::*;
+
+    with_connection(|conn| {
+        diesel::delete(notification_targets.filter(expires_at.le(Utc::now().naive_utc())))
+            .execute(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await
+    .inspect(|_| SUBSCRIPTION_CACHE.clear())
+}
+
+// ====================================== Notifications ======================================== //
+
+/// Checks the attachments of a notification: At most [`ATTACHMENTS_MAX`] absolute `http(s)` URLs
+///
+/// # Parameters
+/// - `urls` : URLs of the attachments
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : If the client can fetch the attachments
+/// - [`Err`] : A [enum@KohakuError::ValidationError] naming the first malformed URL
+pub fn validate_attachments(urls: &[String]) -> Result<(), KohakuError> {
+    if urls.len() > ATTACHMENTS_MAX {
+        return Err(KohakuError::ValidationError(format!(
+            "At most {} attachments are allowed per notification",
+            ATTACHMENTS_MAX
+        )));
+    }
+    for url in urls {
+        let valid = Url::parse(url).is_ok_and(|parsed| {
+            matches!(parsed.scheme(), "http" | "https") && parsed.host_str().is_some()
+        });
+        if !valid {
+            return Err(KohakuError::ValidationError(format!(
+                "Invalid attachment URL `{}`: Only absolute http(s) URLs are allowed",
+                url
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Hashes the content of a notification to detect duplicates
+///
+/// # Parameters
+/// - `embed` : Optional Discord embed object
+/// - `message` : Optional plain message, before formatting
+/// - `attachments` : Optional URLs of attached files
+/// - `override_channels` : Optional additional `(channel_id, guild_id)` targets
+/// - `priority` : Delivery priority
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The hex-encoded SHA-256 of all serialized parameters
+/// - [`Err`] : A [enum@KohakuError::InternalServerError] if the embed couldn't be serialized
+pub fn content_hash(
+    embed: Option<&serde_json::Value>,
+    message: Option<&str>,
+    attachments: Option<&[String]>,
+    override_channels: Option<&[(i64, i64)]>,
+    priority: u8,
+) -> Result<String, KohakuError> {
+    let serialized =
+        serde_json::to_string(&(embed, message, attachments, override_channels, priority))
+            .map_err(|e| {
+                KohakuError::InternalServerError(format!("Couldn't hash notification: {}", e))
+            })?;
+    Ok(Sha256::digest(serialized.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Helper: Applies the format of a target to the message of a notification, see [`render`]
+fn format_message(format: Option<&str>, ctx: &TemplateContext) -> Option<String> {
+    match format {
+        Some(format) => Some(render(format, ctx)),
+        None if ctx.content.is_empty() => None,
+        None => Some(ctx.content.to_string()),
+    }
+}
+
+/// Notifies all subscribers of a code by sending a [`NotificationData`] per subscription to the connected clients.
+///
+/// Paused subscriptions and notifications that are empty after formatting are skipped.
+/// A notification with the same content (see [`content_hash`]) as one sent under the same code within the dedup window
+/// (`SERVER_EVENTS_DEDUP_WINDOW_SEC`) is suppressed, e.g. if a scraper detects the same release on consecutive runs.
+/// The content is reserved before dispatching, so concurrent duplicates are suppressed as well. If nothing is sent, the reservation is released.
+/// If no live client receives the notifications and a fallback webhook is configured (`SERVER_EVENTS_WEBHOOK_URL`),
+/// the unformatted message is additionally posted there once, see [`post_webhook`]. A failing webhook is only logged.
+/// Notifications that reach no one are recorded as [`NotificationCode::last_error`] of the code, others as [`NotificationCode::last_success`].
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic. Must be registered
+/// - `triggering_event` : Short description of what triggered the notification (e.g. the name of a task)
+/// - `embed` : Optional Discord embed object
+/// - `message` : Optional plain message, formatted per target via [`NotificationTarget::format`]
+/// - `override_channels` : Additional `(channel_id, guild_id)` targets notified regardless of subscriptions (e.g. an ops alert channel).
+///   They receive the unformatted message. Channels already notified via a subscription are not notified twice
+/// - `attachments` : Optional URLs of files the clients attach to the notification, see [`validate_attachments`]
+/// - `priority` : Delivery priority, e.g. [`crate::utils::comm::websocket::connection::PRIORITY_NORMAL`]. Higher priorities are sent ahead of queued routine notifications
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : A [`NotifyReport`] of the sent [`NotificationData`]s, how many of them no live client received, whether they were posted to the
+///   fallback webhook and whether the notification was suppressed as duplicate
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if an attachment is malformed, or another [enum@KohakuError] based on the failing operation
+pub async fn notify(
+    code_: &str,
+    triggering_event: &str,
+    embed: Option<serde_json::Value>,
+    message: Option<String>,
+    override_channels: Option<Vec<(i64, i64)>>,
+    attachments: Option<Vec<String>>,
+    priority: u8,
+) -> Result<NotifyReport, KohakuError> {
+    if let Some(urls) = &attachments {
+        validate_attachments(urls)?;
+    }
+    let hash = content_hash(
+        embed.as_ref(),
+        message.as_deref(),
+        attachments.as_deref(),
+        override_channels.as_deref(),
+        priority,
+    )?;
+    let dedup_key = format!("{}#{}", code_, hash);
+    if !RECENT_NOTIFICATIONS.reserve(&dedup_key, ()) {
+        info!(
+            "[Events] - Suppressed duplicate notification of `{}` (triggered by {}, hash {})",
+            code_, triggering_event, hash
+        );
+        return Ok(NotifyReport {
+            suppressed: true,
+            ..Default::default()
+        });
+    }
+    let release = |_: &KohakuError| RECENT_NOTIFICATIONS.invalidate(&dedup_key);
+    let now = Utc::now();
+    update_code_ts(code_, now).await.inspect_err(release)?;
+
+    let targets = get_active_subscriptions(code_).await.inspect_err(release)?;
+    let ctx = TemplateContext {
+        content: message.as_deref().unwrap_or(""),
+        code: code_,
+        triggering_event,
+        timestamp: now,
+        embed: embed.as_ref(),
+    };
+    let mut skipped = 0;
+    let mut notifications: Vec<NotificationData> = targets
+        .into_iter()
+        .filter(|target| {
+            target.format.is_some() || embed.is_some() || message.is_some() || attachments.is_some()
+        })
+        .map(|target| NotificationData {
+            code: code_.to_string(),
+            triggering_event: triggering_event.to_string(),
+            channel_id: target.channel_id,
+            guild_id: target.guild_id,
+            thread_id: target.thread_id,
+            embed: embed.clone(),
+            message: format_message(target.format.as_deref(), &ctx),
+            mention_roles: target.mention_roles,
+            attachments: attachments.clone(),
+            priority,
+        })
+        // Formats can resolve to nothing, e.g. `{content}` without content
+        .filter(|notification| {
+            if notification.is_empty() {
+                info!(
+                    "[Events] - Skipped empty notification of `{}` for channel {}",
+                    code_, notification.channel_id
+                );
+                skipped += 1;
+            }
+            !notification.is_empty()
+        })
+        .collect();
+
+    for (channel_id, guild_id) in override_channels.unwrap_or_default() {
+        let notified = notifications
+            .iter()
+            .any(|n| n.channel_id == channel_id && n.thread_id.is_none());
+        let notification = NotificationData {
+            code: code_.to_string(),
+            triggering_event: triggering_event.to_string(),
+            channel_id,
+            guild_id,
+            thread_id: None,
+            embed: embed.clone(),
+            message: format_message(None, &ctx),
+            mention_roles: vec![],
+            attachments: attachments.clone(),
+            priority,
+        };
+        if !notified && !notification.is_empty() {
+            notifications.push(notification);
+        }
+    }
+
+    let report = match dispatch(&notifications).await {
+        Ok(report) => report,
+        Err(e) => {
+            release(&e);
+            record_outcome(code_, Some(e.to_string())).await;
+            return Err(e);
+        }
+    };
+    let offline = count_offline(&report, notifications.len());
+    if offline > 0 {
+        warn!(
+            "[Events] - {} notification(s) of `{}` matched subscriptions, but no client is online to deliver them",
+            offline, code_
+        );
+    }
+    let mut fallback = false;
+    let mut webhook_error = None;
+    if let Some(url) = get_webhook_url().filter(|_| offline > 0) {
+        let payload = WebhookPayload::new(
+            format_message(None, &ctx),
+            embed.clone(),
+            attachments.as_deref().unwrap_or_default(),
+        );
+        match post_webhook(&url, &payload).await {
+            Ok(()) => {
+                info!(
+                    "[Events] - Posted notification of `{}` to the fallback webhook",
+                    code_
+                );
+                fallback = true;
+            }
+            Err(e) => {
+                warn!(
+                    "[Events] - Failed to post notification of `{}` to the fallback webhook: {}",
+                    code_, e
+                );
+                webhook_error = Some(e);
+            }
+        }
+    }
+    if notifications.is_empty() {
+        if skipped > 0 {
+            let error = format!(
+                "All {} notification(s) were empty after formatting",
+                skipped
+            );
+            record_outcome(code_, Some(error)).await;
+        }
+    } else if offline > 0 && !fallback {
+        let mut error = format!("No client online to deliver {} notification(s)", offline);
+        if let Some(e) = webhook_error {
+            error = format!("{}, fallback webhook failed: {}", error, e);
+        }
+        record_outcome(code_, Some(error)).await;
+    } else {
+        record_outcome(code_, None).await;
+    }
+    // Notifications that reached no one (e.g. all subscriptions paused) don't suppress later ones
+    if notifications.is_empty() {
+        RECENT_NOTIFICATIONS.invalidate(&dedup_key);
+    }
+    info!(
+        "[Events] - Notified {} target(s) of `{}` (triggered by {})",
+        notifications.len(),
+        code_,
+        triggering_event
+    );
+    Ok(NotifyReport {
+        sent: notifications,
+        offline,
+        fallback,
+        suppressed: false,
+    })
+}
+
+/// Helper: Stores the outcome of a notification in its code, see [`NotificationCode::last_error`].
+/// A failing update is only logged, so it doesn't fail the notification itself
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic
+/// - `error` : Why the notification didn't reach anyone, [`None`] if it succeeded
+async fn record_outcome(code_: &str, error: Option<String>) {
+    use schema::notification_codes::dsl::*;
+    let target = code_.to_string();
+    let now = Utc::now().naive_utc();
+
+    let updated = with_connection(move |conn| {
+        let query = diesel::update(notification_codes.find(target));
+        match error {
+            Some(message) => query
+                .set((last_error.eq(Some(message)), last_error_at.eq(Some(now))))
+                .get_result::<NotificationCode>(conn),
+            None => query.set(last_success.eq(Some(now))).get_result(conn),
+        }
+        .map_err(KohakuError::DatabaseError)
+    })
+    .await;
+    match updated {
+        Ok(updated) => {
+            CODE_CACHE.insert(code_, updated);
+            ALL_CODES_CACHE.clear();
+        }
+        Err(e) => warn!(
+            "[Events] - Couldn't record the notification outcome of `{}`: {}",
+            code_, e
+        ),
+    }
+}
+
+/// Helper: Amount of sent notifications no live client received.
+/// Resumable clients count as delivered, but only live ones actually post the notifications
+fn count_offline(report: &DeliveryReport, sent: usize) -> usize {
+    let online = get_manager().is_ok_and(|manager| {
+        report
+            .delivered
+            .iter()
+            .any(|key_id| manager.is_connected(key_id))
+    });
+    if online {
+        0
+    } else {
+        sent
+    }
+}
+
+/// Notifies every channel of a guild with an active subscription, regardless of the subscribed codes (e.g. maintenance announcements).
+///
+/// Each channel is notified once, even if it is subscribed to multiple codes or via threads.
+/// The notifications carry [`GUILD_BROADCAST_CODE`] as code and the unformatted message, as the formats belong to the subscriptions.
+/// Unlike [`notify`], broadcasts are neither deduplicated nor posted to the fallback webhook.
+///
+/// # Parameters
+/// - `guild_id_` : Guild to notify
+/// - `embed` : Optional Discord embed object
+/// - `message` : Optional plain message
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : A [`NotifyReport`] of the sent [`NotificationData`]s and how many of them no live client received
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if neither embed nor message is given, or another [enum@KohakuError] based on the failing operation
+pub async fn notify_guild(
+    guild_id_: i64,
+    embed: Option<serde_json::Value>,
+    message: Option<String>,
+) -> Result<NotifyReport, KohakuError> {
+    use schema::notification_targets::dsl::*;
+
+    let message = message.filter(|message| !message.trim().is_empty());
+    if embed.is_none() && message.is_none() {
+        return Err(KohakuError::ValidationError(
+            "Either an embed or a message must be given!".to_string(),
+        ));
+    }
+
+    let channels: Vec<i64> = with_connection(move |conn| {
+        let now = Utc::now().naive_utc();
+        notification_targets
+            .filter(guild_id.eq(guild_id_))
+            .filter(active.eq(true))
+            .filter(expires_at.is_null().or(expires_at.gt(now)))
+            .select(channel_id)
+            .distinct()
+            .order(channel_id.asc())
+            .load(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await?;
+    let notifications: Vec<NotificationData> = channels
+        .into_iter()
+        .map(|channel| NotificationData {
+            code: GUILD_BROADCAST_CODE.to_string(),
+            triggering_event: GUILD_BROADCAST_EVENT.to_string(),
+            channel_id: channel,
+            guild_id: guild_id_,
+            thread_id: None,
+            embed: embed.clone(),
+            message: message.clone(),
+            mention_roles: vec![],
+            attachments: None,
+            priority: PRIORITY_NORMAL,
+        })
+        .collect();
+
+    let report = dispatch(&notifications).await?;
+    let offline = count_offline(&report, notifications.len());
+    info!(
+        "[Events] - Broadcasted to {} channel(s) of guild {}",
+        notifications.len(),
+        guild_id_
+    );
+    Ok(NotifyReport {
+        sent: notifications,
+        offline,
+        fallback: false,
+        suppressed: false,
+    })
+}
+
+// ======================================== Scheduled ========================================== //
+
+/// Helper: Registers a one-shot job sending a scheduled notification at its `send_at`, see [`send_scheduled`]
+async fn arm_scheduled(scheduled: &ScheduledNotification) -> Result<(), KohakuError> {
+    let scheduler = get_notify_scheduler().ok_or_else(|| {
+        KohakuError::InternalServerError("Scheduler is not initialized".to_string())
+    })?;
+    let id_ = scheduled.id;
+    scheduler
+        .add_oneshot_at(
+            "ScheduledNotification",
+            scheduled.send_at.and_utc(),
+            move || {
+                Box::pin(async move {
+                    send_scheduled(id_)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
+            },
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Defers a notification to a later time, e.g. to queue up a release announcement ahead of time.
+///
+/// The notification is stored and sent via [`notify`] at `when` by a one-shot job of the [`crate::utils::scheduler::Scheduler`].
+/// Pending notifications survive restarts, see [`reschedule_pending`].
+///
+/// # Parameters
+/// - `code_` : Identifier of the topic. Must be registered
+/// - `triggering_event_` : Short description of what triggered the notification
+/// - `embed_` : Optional Discord embed object
+/// - `message_` : Optional plain message, formatted per target when sent
+/// - `when` : Time the notification is sent at. Must be in the future
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The stored [struct@ScheduledNotification]
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if `when` is not in the future or neither embed nor message is given,
+///   a [enum@KohakuError::NotFound] if the code is not registered, or another [enum@KohakuError] based on the failing operation
+pub async fn schedule_notification(
+    code_: &str,
+    triggering_event_: &str,
+    embed_: Option<serde_json::Value>,
+    message_: Option<String>,
+    when: DateTime<Utc>,
+) -> Result<ScheduledNotification, KohakuError> {
+    use schema::scheduled_notifications::dsl::*;
+
+    if when <= Utc::now() {
+        return Err(KohakuError::ValidationError(
+            "Scheduled notifications must be sent in the future!".to_string(),
+        ));
+    }
+    if embed_.is_none() && message_.is_none() {
+        return Err(KohakuError::ValidationError(
+            "Either an embed or a message must be given!".to_string(),
+        ));
+    }
+    get_code(code_).await?;
+
+    let new = NewScheduledNotification {
+        code: code_.to_string(),
+        triggering_event: triggering_event_.to_string(),
+        embed: embed_,
+        message: message_,
+        send_at: when.naive_utc(),
+    };
+    let scheduled: ScheduledNotification = with_connection(move |conn| {
+        diesel::insert_into(scheduled_notifications)
+            .values(&new)
+            .get_result(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await?;
+
+    if let Err(e) = arm_scheduled(&scheduled).await {
+        // Without a job it would never be sent, so don't keep it pending
+        let id_ = scheduled.id;
+        with_connection(move |conn| {
+            diesel::delete(scheduled_notifications.find(id_))
+                .execute(conn)
+                .map_err(KohakuError::DatabaseError)
+        })
+        .await?;
+        return Err(e);
+    }
+    Ok(scheduled)
+}
+
+/// Sends a pending scheduled notification via [`notify`], see [`schedule_notification`].
+///
+/// The notification is marked as sent before it is sent, so it is sent at most once even if its job fires twice.
+///
+/// # Parameters
+/// - `id_` : Id of the [struct@ScheduledNotification]
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [`NotifyReport`], or [`None`] if the notification was already sent or its code was unregistered meanwhile
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn send_scheduled(id_: i32) -> Result<Option<NotifyReport>, KohakuError> {
+    use schema::scheduled_notifications::dsl::*;
+
+    let claimed: Option<ScheduledNotification> = with_connection(move |conn| {
+        diesel::update(scheduled_notifications.find(id_).filter(sent_at.is_null()))
+            .set(sent_at.eq(Utc::now().naive_utc()))
+            .get_result(conn)
+            .optional()
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await?;
+    let Some(scheduled) = claimed else {
+        return Ok(None);
+    };
+
+    let report = notify(
+        &scheduled.code,
+        &scheduled.triggering_event,
+        scheduled.embed,
+        scheduled.message,
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await?;
+    Ok(Some(report))
+}
+
+/// Registers the jobs of all pending scheduled notifications, e.g. after a restart. Overdue ones are sent right away
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The amount of pending notifications
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn reschedule_pending() -> Result<usize, KohakuError> {
+    use schema::scheduled_notifications::dsl::*;
+
+    let pending: Vec<ScheduledNotification> = with_connection(|conn| {
+        scheduled_notifications
+            .filter(sent_at.is_null())
+            .order(send_at.asc())
+            .load(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await?;
+    for scheduled in &pending {
+        arm_scheduled(scheduled).await?;
+    }
+    Ok(pending.len())
+}
+
+/// Permanently removes scheduled notifications that were sent before a given point in time. Pending ones are kept
+///
+/// # Parameters
+/// - `before` : Notifications sent before this timestamp (UTC) get removed
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : Amount of removed [struct@ScheduledNotification]s
+/// - [`Err`] : A [enum@KohakuError] based on the failing operation
+pub async fn purge_sent_scheduled(before: NaiveDateTime) -> Result<usize, KohakuError> {
+    use schema::scheduled_notifications::dsl::*;
+
+    with_connection(move |conn| {
+        diesel::delete(scheduled_notifications.filter(sent_at.lt(before)))
+            .execute(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await
+}
@@ -0,0 +1,571 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use tracing::info;
+
+use crate::utils::{
+    comm::{
+        auth::extractor::{AdminManage, AuthedClaims, EventsManage, EventsSubscribe},
+        events::{
+            models::{
+                expiry_from_secs, BatchSubscribeRequest, BulkRegisterRequest, CodeListQuery,
+                CodePrefixQuery, ExportQuery, ImportSubscriptionsResponse, NotifyRequest,
+                PurgeStaleRequest, PurgeStaleResponse, RegisterCodeRequest,
+                ScheduleNotificationRequest, SetActiveRequest, SubscriptionAction,
+                SubscriptionExport, SubscriptionQuery, UnregisterCodeResponse, UpdateCodeRequest,
+            },
+            notifications::{
+                export_subscriptions, get_all_codes, get_code, get_subscriptions,
+                get_subscriptions_by_code_prefix, import_subscriptions, notify, purge_stale,
+                register, register_many, schedule_notification, set_subscription_active, subscribe,
+                subscribe_many, unregister, unsubscribe, update_description,
+            },
+        },
+        websocket::connection::PRIORITY_NORMAL,
+    },
+    error::KohakuError,
+};
+
+/// Default amount of subscriptions returned by [`list_subscriptions_by_code`]
+const SUBSCRIPTIONS_DEFAULT_LIMIT: i64 = 100;
+/// Maximum amount of subscriptions returned by [`list_subscriptions_by_code`]
+const SUBSCRIPTIONS_MAX_LIMIT: i64 = 1000;
+
+/// Configures server so that requests get routed to the correct functions
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/codes", web::get().to(list_codes))
+        .route("/codes", web::post().to(register_code))
+        .route("/codes/bulk", web::post().to(register_codes))
+        .route("/codes/{code}", web::get().to(show_code))
+        .route("/codes/{code}", web::patch().to(update_code))
+        .route("/codes/{code}", web::delete().to(unregister_code))
+        .route("/subscriptions", web::post().to(list_subscriptions))
+        .route(
+            "/subscriptions/by-code",
+            web::get().to(list_subscriptions_by_code),
+        )
+        .route("/subscriptions/manage", web::post().to(manage_subscription))
+        .route(
+            "/subscriptions/manage/batch",
+            web::post().to(batch_subscribe),
+        )
+        .route(
+            "/subscriptions/export",
+            web::get().to(export_guild_subscriptions),
+        )
+        .route(
+            "/subscriptions/import",
+            web::post().to(import_guild_subscriptions),
+        )
+        .route("/subscriptions/{id}/active", web::patch().to(set_active))
+        .route("/notify", web::post().to(notify_now))
+        .route("/notify/schedule", web::post().to(schedule));
+}
+
+/// Notification code listing endpoint.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `query` : [`CodeListQuery`] with an optional `owner` filter
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`crate::utils::comm::events::models::NotificationCode`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn list_codes(
+    _claims: AuthedClaims<EventsSubscribe>,
+    query: web::Query<CodeListQuery>,
+) -> Result<HttpResponse, KohakuError> {
+    let mut codes = get_all_codes().await?;
+    if let Some(owner) = &query.owner {
+        codes.retain(|code| code.owner.as_ref() == Some(owner));
+    }
+    Ok(HttpResponse::Ok().json(codes))
+}
+
+/// Notification code details endpoint.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `path` : The code to look up
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`crate::utils::comm::events::models::NotificationCode`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn show_code(
+    _claims: AuthedClaims<EventsSubscribe>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, KohakuError> {
+    let code = get_code(&path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(code))
+}
+
+/// Notification code registration endpoint.
+///
+/// The owner of the caller's API key is stored as owner of the code.
+///
+/// # Parameters
+/// - `claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`RegisterCodeRequest`] in a JSON Format holding the code and its description
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the registered [`crate::utils::comm::events::models::NotificationCode`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn register_code(
+    claims: AuthedClaims<EventsManage>,
+    body: web::Json<RegisterCodeRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let body = body.into_inner();
+    let code = register(&body.code, body.description, Some(claims.owner.clone())).await?;
+    info!(
+        "[Events] - Registered notification code `{}` for {}",
+        code.code, claims.owner
+    );
+    Ok(HttpResponse::Ok().json(code))
+}
+
+/// Bulk notification code registration endpoint.
+///
+/// Registers all given codes at once, e.g. the full set of topics of a new game. If any code fails, nothing is registered.
+/// The owner of the caller's API key is stored as owner of the codes.
+///
+/// # Parameters
+/// - `claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`BulkRegisterRequest`] in a JSON Format holding the codes and whether to skip duplicates
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of newly registered [`crate::utils::comm::events::models::NotificationCode`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn register_codes(
+    claims: AuthedClaims<EventsManage>,
+    body: web::Json<BulkRegisterRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let body = body.into_inner();
+    if body.codes.is_empty() {
+        return Err(KohakuError::ValidationError(
+            "At least one code must be given!".to_string(),
+        ));
+    }
+    let entries: Vec<(String, Option<String>)> = body
+        .codes
+        .into_iter()
+        .map(|entry| (entry.code, entry.description))
+        .collect();
+    let codes = register_many(&entries, Some(claims.owner.clone()), body.skip_duplicates).await?;
+    info!(
+        "[Events] - Registered {} notification code(s) in bulk",
+        codes.len()
+    );
+    Ok(HttpResponse::Ok().json(codes))
+}
+
+/// Notification code update endpoint.
+///
+/// Changes the description of a code without touching its subscriptions.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `path` : The code to update
+/// - `body` : [`UpdateCodeRequest`] in a JSON Format holding the new description
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the updated [`crate::utils::comm::events::models::NotificationCode`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn update_code(
+    _claims: AuthedClaims<EventsManage>,
+    path: web::Path<String>,
+    body: web::Json<UpdateCodeRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let code = update_description(&path.into_inner(), body.into_inner().description).await?;
+    info!(
+        "[Events] - Updated description of notification code `{}`",
+        code.code
+    );
+    Ok(HttpResponse::Ok().json(code))
+}
+
+/// Notification code removal endpoint.
+///
+/// Removes the code together with all of its subscriptions. The amount of removed subscriptions is reported back.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `path` : The code to remove
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`UnregisterCodeResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn unregister_code(
+    _claims: AuthedClaims<EventsManage>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, KohakuError> {
+    let code = path.into_inner();
+    let removed_subscriptions = unregister(&code).await?;
+    info!(
+        "[Events] - Unregistered notification code `{}` and {} subscription(s)",
+        code, removed_subscriptions
+    );
+    Ok(HttpResponse::Ok().json(UnregisterCodeResponse {
+        code,
+        removed_subscriptions,
+    }))
+}
+
+/// Subscription listing endpoint.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `query` : [`SubscriptionQuery`] with an optional `channel_id` and / or `guild_id` filter, see [`SubscriptionQuery::list_filter`]
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`crate::utils::comm::events::models::NotificationTarget`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn list_subscriptions(
+    _claims: AuthedClaims<EventsSubscribe>,
+    query: web::Query<SubscriptionQuery>,
+) -> Result<HttpResponse, KohakuError> {
+    let (channel_id, guild_id) = query.list_filter()?;
+    let targets = get_subscriptions(None, channel_id, guild_id).await?;
+    Ok(HttpResponse::Ok().json(targets))
+}
+
+/// Subscription administration endpoint.
+///
+/// Lists subscriptions of all codes starting with `prefix` across all channels and guilds, e.g. for cleaning up a game's topics.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `query` : [`CodePrefixQuery`] with the code prefix and an optional `limit` (Default: 100, Max: 1000) and `offset`
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`crate::utils::comm::events::models::NotificationTarget`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn list_subscriptions_by_code(
+    _claims: AuthedClaims<EventsManage>,
+    query: web::Query<CodePrefixQuery>,
+) -> Result<HttpResponse, KohakuError> {
+    let limit = query
+        .limit
+        .unwrap_or(SUBSCRIPTIONS_DEFAULT_LIMIT)
+        .clamp(1, SUBSCRIPTIONS_MAX_LIMIT);
+    let targets =
+        get_subscriptions_by_code_prefix(&query.prefix, limit, query.offset.unwrap_or(0)).await?;
+    Ok(HttpResponse::Ok().json(targets))
+}
+
+/// Subscription management endpoint.
+///
+/// Either subscribes (`subscribe=CODE`) or unsubscribes (`unsubscribe=CODE`) a channel.
+/// With `thread_id`, the subscription targets a thread within the channel instead.
+/// `mention_roles` (comma-separated role ids) are mentioned on every notification.
+/// With `expires_in` (seconds), the subscription expires and gets removed afterwards.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `query` : [`SubscriptionQuery`] describing the operation and the target, see [`SubscriptionQuery::action`]
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200`, holding the [`crate::utils::comm::events::models::NotificationTarget`] on subscription
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn manage_subscription(
+    _claims: AuthedClaims<EventsSubscribe>,
+    query: web::Query<SubscriptionQuery>,
+) -> Result<HttpResponse, KohakuError> {
+    let query = query.into_inner();
+    let (action, channel_id, guild_id) = query.action()?;
+    let mention_roles = query.mention_roles()?;
+    let expires_at = expiry_from_secs(query.expires_in)?;
+    match action {
+        SubscriptionAction::Subscribe(code) => {
+            let target = subscribe(
+                &code,
+                channel_id,
+                guild_id,
+                query.thread_id,
+                query.format,
+                mention_roles,
+                expires_at,
+            )
+            .await?;
+            Ok(HttpResponse::Ok().json(target))
+        }
+        SubscriptionAction::Unsubscribe(code) => {
+            unsubscribe(&code, channel_id, guild_id, query.thread_id).await?;
+            Ok(HttpResponse::Ok().finish())
+        }
+    }
+}
+
+/// Batch subscription endpoint.
+///
+/// Subscribes a channel (or thread) to all given codes at once. If any code is not registered, nothing is subscribed.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`BatchSubscribeRequest`] in a JSON Format holding the codes and the target
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`crate::utils::comm::events::models::NotificationTarget`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn batch_subscribe(
+    _claims: AuthedClaims<EventsSubscribe>,
+    body: web::Json<BatchSubscribeRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let body = body.into_inner();
+    if body.codes.is_empty() {
+        return Err(KohakuError::ValidationError(
+            "At least one code must be given!".to_string(),
+        ));
+    }
+    let expires_at = expiry_from_secs(body.expires_in)?;
+    let codes: Vec<&str> = body.codes.iter().map(String::as_str).collect();
+    let targets = subscribe_many(
+        &codes,
+        body.channel_id,
+        body.guild_id,
+        body.thread_id,
+        body.format,
+        body.mention_roles,
+        expires_at,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(targets))
+}
+
+/// Subscription export endpoint.
+///
+/// Returns all subscriptions of a guild, e.g. as backup or to move them to another Kohaku instance via [`import_guild_subscriptions`].
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `query` : [`ExportQuery`] holding the guild
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`crate::utils::comm::events::models::SubscriptionExport`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn export_guild_subscriptions(
+    _claims: AuthedClaims<EventsSubscribe>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, KohakuError> {
+    let subscriptions = export_subscriptions(query.guild_id).await?;
+    Ok(HttpResponse::Ok().json(subscriptions))
+}
+
+/// Subscription import endpoint.
+///
+/// Recreates subscriptions returned by [`export_guild_subscriptions`]. If any code is not registered, nothing is imported.
+/// Already existing subscriptions are updated, so an import can safely be repeated.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : List of [`crate::utils::comm::events::models::SubscriptionExport`]s in a JSON Format
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`ImportSubscriptionsResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn import_guild_subscriptions(
+    _claims: AuthedClaims<EventsSubscribe>,
+    body: web::Json<Vec<SubscriptionExport>>,
+) -> Result<HttpResponse, KohakuError> {
+    let targets = import_subscriptions(body.into_inner()).await?;
+    info!("[Events] - Imported {} subscription(s)", targets.len());
+    Ok(HttpResponse::Ok().json(ImportSubscriptionsResponse {
+        imported: targets.len(),
+    }))
+}
+
+/// Subscription pause / resume endpoint.
+///
+/// Paused subscriptions keep their configuration and still appear in listings, but receive no notifications.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `path` : Id of the subscription
+/// - `body` : [`SetActiveRequest`] in a JSON Format holding the new state
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the updated [`crate::utils::comm::events::models::NotificationTarget`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn set_active(
+    _claims: AuthedClaims<EventsSubscribe>,
+    path: web::Path<i32>,
+    body: web::Json<SetActiveRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let target = set_subscription_active(path.into_inner(), body.active).await?;
+    info!(
+        "[Events] - Subscription {} is now {}",
+        target.id,
+        if target.active { "active" } else { "paused" }
+    );
+    Ok(HttpResponse::Ok().json(target))
+}
+
+/// Manual notification endpoint.
+///
+/// Sends a notification to all subscribers of a code right away, e.g. for tests or announcements without a scraper.
+///
+/// # Parameters
+/// - `claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`NotifyRequest`] in a JSON Format holding the code and the embed and / or message
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`crate::utils::comm::events::models::NotifyReport`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn notify_now(
+    claims: AuthedClaims<EventsManage>,
+    body: web::Json<NotifyRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let body = body.into_inner();
+    if body.embed.is_none() && body.message.is_none() {
+        return Err(KohakuError::ValidationError(
+            "Either an embed or a message must be given!".to_string(),
+        ));
+    }
+    let report = notify(
+        &body.code,
+        &body.triggering_event,
+        body.embed,
+        body.message,
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await?;
+    info!(
+        "[Events] - {} manually notified {} target(s) of `{}`",
+        claims.owner,
+        report.sent.len(),
+        body.code
+    );
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Deferred notification endpoint.
+///
+/// Stores a notification that is sent to all subscribers of its code at `send_at`, e.g. a release announcement.
+///
+/// # Parameters
+/// - `claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`ScheduleNotificationRequest`] in a JSON Format holding the notification and its send time
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the stored [`crate::utils::comm::events::models::ScheduledNotification`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn schedule(
+    claims: AuthedClaims<EventsManage>,
+    body: web::Json<ScheduleNotificationRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let body = body.into_inner();
+    let scheduled = schedule_notification(
+        &body.code,
+        &body.triggering_event,
+        body.embed,
+        body.message,
+        body.send_at,
+    )
+    .await?;
+    info!(
+        "[Events] - {} scheduled notification {} of `{}` for {}",
+        claims.owner, scheduled.id, scheduled.code, body.send_at
+    );
+    Ok(HttpResponse::Ok().json(scheduled))
+}
+
+/// Stale notification code purge endpoint.
+///
+/// Removes codes that weren't used for the given amount of days and have no subscriptions.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`PurgeStaleRequest`] in a JSON Format holding the minimum age in days
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`PurgeStaleResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+pub async fn purge_stale_codes(
+    _claims: AuthedClaims<AdminManage>,
+    body: web::Json<PurgeStaleRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    if body.older_than_days == 0 {
+        return Err(KohakuError::ValidationError(
+            "`older_than_days` must be positive!".to_string(),
+        ));
+    }
+    let cutoff = Utc::now()
+        .naive_utc()
+        .checked_sub_signed(Duration::days(body.older_than_days as i64))
+        .ok_or_else(|| {
+            KohakuError::ValidationError(format!(
+                "`older_than_days` of {} is out of range!",
+                body.older_than_days
+            ))
+        })?;
+    let purged = purge_stale(cutoff).await?;
+    info!(
+        "[Events] - Purged {} notification code(s) unused for {} day(s)",
+        purged, body.older_than_days
+    );
+    Ok(HttpResponse::Ok().json(PurgeStaleResponse { purged }))
+}
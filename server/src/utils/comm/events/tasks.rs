@@ -0,0 +1,85 @@
+use chrono::{Duration, Utc};
+use tracing::info;
+
+use crate::{
+    impl_task_wrapper,
+    utils::{
+        comm::events::notifications::{
+            delete_expired_subscriptions, purge_sent_scheduled, purge_stale,
+        },
+        config::get_config,
+        scheduler::tasks::Task,
+    },
+};
+
+/// Removes expired subscriptions every minute
+pub struct ExpiredSubscriptionsCleanup(Task);
+
+impl ExpiredSubscriptionsCleanup {
+    pub fn new() -> Self {
+        Self(Task::new(
+            "ExpiredSubscriptionsCleanup",
+            "0 * * * * *",
+            false,
+        ))
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let removed = delete_expired_subscriptions()
+            .await
+            .map_err(|e| e.to_string())?;
+        if removed > 0 {
+            info!("[Events] - Removed {} expired subscription(s)", removed);
+        }
+        Ok(())
+    }
+}
+
+impl_task_wrapper!(ExpiredSubscriptionsCleanup);
+
+/// Purges notification codes that are unused for `SERVER_EVENTS_STALE_CODE_DAYS` and have no subscriptions every day at 04:00
+pub struct StaleCodesPurge(Task);
+
+impl StaleCodesPurge {
+    pub fn new() -> Self {
+        Self(Task::new("StaleCodesPurge", "0 0 4 * * *", false))
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let retention = Duration::days(get_config().events_stale_code_days as i64);
+        let removed = purge_stale(Utc::now().naive_utc() - retention)
+            .await
+            .map_err(|e| e.to_string())?;
+        if removed > 0 {
+            info!("[Events] - Purged {} stale notification code(s)", removed);
+        }
+        Ok(())
+    }
+}
+
+impl_task_wrapper!(StaleCodesPurge);
+
+/// Purges scheduled notifications sent more than `SERVER_HISTORY_RETENTION_DAYS` ago every day at 04:30
+pub struct SentNotificationsPurge(Task);
+
+impl SentNotificationsPurge {
+    pub fn new() -> Self {
+        Self(Task::new("SentNotificationsPurge", "0 30 4 * * *", false))
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let retention = Duration::days(get_config().history_retention_days as i64);
+        let removed = purge_sent_scheduled(Utc::now().naive_utc() - retention)
+            .await
+            .map_err(|e| e.to_string())?;
+        if removed > 0 {
+            info!(
+                "[Events] - Purged {} sent scheduled notification(s)",
+                removed
+            );
+        }
+        Ok(())
+    }
+}
+
+impl_task_wrapper!(SentNotificationsPurge);
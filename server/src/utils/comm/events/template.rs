@@ -0,0 +1,112 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Legacy placeholder that gets replaced with the message
+const CONTENT_PLACEHOLDER: &str = "{content}";
+
+/// Values available to a notification format
+pub struct TemplateContext<'a> {
+    /// Message of the notification
+    pub content: &'a str,
+    /// Code the notification is sent under
+    pub code: &'a str,
+    /// What triggered the notification
+    pub triggering_event: &'a str,
+    /// Time of the notification
+    pub timestamp: DateTime<Utc>,
+    /// Embed of the notification, whose fields can be referenced via `{{embed.<field>}}`
+    pub embed: Option<&'a Value>,
+}
+
+impl TemplateContext<'_> {
+    /// Resolves a single variable
+    ///
+    /// # Parameters
+    /// - `key` : Name of the variable without braces, e.g. `content` or `embed.title`
+    ///
+    /// # Returns
+    /// An [`Option`] which is either
+    /// - [`Some`] : The value of the variable
+    /// - [`None`] : If the variable is unknown or the referenced value doesn't exist
+    fn resolve(&self, key: &str) -> Option<String> {
+        match key {
+            "content" => Some(self.content.to_string()),
+            "code" => Some(self.code.to_string()),
+            "triggering_event" => Some(self.triggering_event.to_string()),
+            "timestamp" => Some(self.timestamp.to_rfc3339()),
+            // Rendered by Discord in the local time of the reader
+            "timestamp:discord" => Some(format!("<t:{}>", self.timestamp.timestamp())),
+            _ => {
+                if let Some(path) = key.strip_prefix("embed.") {
+                    return self.embed_field(path);
+                }
+                if let Some(id) = key.strip_prefix("role:") {
+                    return id.parse::<u64>().ok().map(|id| format!("<@&{}>", id));
+                }
+                if let Some(emote) = key.strip_prefix("emote:") {
+                    let (name, id) = emote.split_once(':')?;
+                    let valid_name = !name.is_empty()
+                        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    return (valid_name && id.parse::<u64>().is_ok())
+                        .then(|| format!("<:{}:{}>", name, id));
+                }
+                None
+            }
+        }
+    }
+
+    /// Looks up a (nested, dot-separated) field of the embed. Only strings, numbers and booleans are resolved.
+    fn embed_field(&self, path: &str) -> Option<String> {
+        let mut value = self.embed?;
+        for segment in path.split('.') {
+            value = match value {
+                Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+                _ => value.get(segment)?,
+            };
+        }
+        match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Renders a notification format.
+///
+/// Resolves `{{variable}}` fields:
+/// - `{{content}}` : Message of the notification
+/// - `{{code}}` / `{{triggering_event}}` : Code and trigger of the notification
+/// - `{{timestamp}}` (RFC 3339) / `{{timestamp:discord}}` (Discord timestamp markup)
+/// - `{{embed.<field>}}` : Field of the embed, nested via dots (e.g. `{{embed.fields.0.value}}`)
+/// - `{{role:<id>}}` / `{{emote:<name>:<id>}}` : Discord role mention / custom emote
+///
+/// Unknown variables are kept literally. The legacy `{content}` placeholder is still replaced with the message.
+///
+/// # Parameters
+/// - `format` : Format of the subscription
+/// - `ctx` : [`TemplateContext`] holding the values
+///
+/// # Returns
+/// The rendered message
+pub fn render(format: &str, ctx: &TemplateContext) -> String {
+    let mut output = String::with_capacity(format.len());
+    let mut rest = format;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let key = rest[start + 2..start + 2 + len].trim();
+
+        output.push_str(&rest[..start].replace(CONTENT_PLACEHOLDER, ctx.content));
+        match ctx.resolve(key) {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&rest[start..start + len + 4]),
+        }
+        rest = &rest[start + len + 4..];
+    }
+    output.push_str(&rest.replace(CONTENT_PLACEHOLDER, ctx.content));
+    output
+}
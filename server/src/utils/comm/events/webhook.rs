@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use url::Url;
+
+use crate::utils::{breaker::BREAKER, error::KohakuError};
+
+/// Name of the Discord webhook in the [`BREAKER`]
+pub const WEBHOOK_SERVICE: &str = "discord-webhook";
+/// Seconds a webhook request may take before it is aborted
+const WEBHOOK_TIMEOUT_SEC: u64 = 10;
+/// Hosts serving Discord webhooks
+const WEBHOOK_HOSTS: [&str; 4] = [
+    "discord.com",
+    "discordapp.com",
+    "canary.discord.com",
+    "ptb.discord.com",
+];
+
+/// HTTP client shared by all webhook requests
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SEC))
+        .build()
+        .expect("Failed to build the webhook client")
+});
+
+/// Message posted to a Discord webhook, see <https://discord.com/developers/docs/resources/webhook#execute-webhook>
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WebhookPayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<serde_json::Value>,
+    /// Always empty: The webhook posts outside of the subscribed guilds, so it must not ping anyone
+    pub allowed_mentions: serde_json::Value,
+}
+
+impl WebhookPayload {
+    /// # Parameters
+    /// - `message` : Optional plain message
+    /// - `embed` : Optional Discord embed object
+    /// - `attachments` : URLs of files. Webhooks can't fetch them, so they are appended to the message as links
+    pub fn new(
+        message: Option<String>,
+        embed: Option<serde_json::Value>,
+        attachments: &[String],
+    ) -> Self {
+        let content = message
+            .into_iter()
+            .chain(attachments.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self {
+            content: Some(content).filter(|content| !content.trim().is_empty()),
+            embeds: embed.into_iter().collect(),
+            allowed_mentions: serde_json::json!({ "parse": [] }),
+        }
+    }
+}
+
+/// Checks the URL of a Discord webhook: `https://discord.com/api/webhooks/{id}/{token}`
+///
+/// # Parameters
+/// - `url` : URL of the webhook
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : If the URL points to a Discord webhook
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if the URL is malformed or points elsewhere
+pub fn validate_webhook_url(url: &str) -> Result<(), KohakuError> {
+    let valid = Url::parse(url).is_ok_and(|parsed| {
+        let segments: Vec<&str> = parsed
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        parsed.scheme() == "https"
+            && parsed
+                .host_str()
+                .is_some_and(|host| WEBHOOK_HOSTS.contains(&host))
+            && matches!(segments.as_slice(), ["api", "webhooks", id, _token] if id.parse::<u64>().is_ok())
+    });
+    if !valid {
+        // The URL may contain a token, so it isn't echoed
+        return Err(KohakuError::ValidationError(
+            "Invalid webhook URL: Expected `https://discord.com/api/webhooks/{id}/{token}`"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Posts a message to a Discord webhook, e.g. to deliver notifications while no client is connected.
+///
+/// The request is guarded by the [`BREAKER`] (service [`WEBHOOK_SERVICE`]), so an unreachable webhook isn't called over and over.
+///
+/// # Parameters
+/// - `url` : URL of the webhook, see [`validate_webhook_url`]
+/// - `payload` : The message
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : If the webhook accepted the message
+/// - [`Err`] : A [enum@KohakuError::ExternalServiceError] if the request failed or was rejected
+pub async fn post_webhook(url: &str, payload: &WebhookPayload) -> Result<(), KohakuError> {
+    BREAKER
+        .call(WEBHOOK_SERVICE, || async {
+            let response = CLIENT
+                .post(url)
+                .json(payload)
+                .send()
+                .await
+                // The URL contains the token of the webhook, so keep it out of the logs
+                .map_err(|e| {
+                    KohakuError::ExternalServiceError(format!(
+                        "Webhook request failed: {}",
+                        e.without_url()
+                    ))
+                })?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(KohakuError::ExternalServiceError(format!(
+                    "Webhook rejected the message with status {}",
+                    status
+                )));
+            }
+            Ok(())
+        })
+        .await
+}
@@ -1,40 +1,536 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::{BinaryHeap, HashMap},
+    io::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use actix_ws::{Message, MessageStream, Session};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use chrono::Utc;
+use flate2::{write::ZlibEncoder, Compression};
 use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tracing::info;
+use tracing::{info, warn};
 use uuid::Uuid;
 
-use crate::utils::comm::websocket::manager::WsConnectionManager;
+use crate::utils::{
+    comm::{
+        auth::extractor::{EventsSubscribe, RequiredScopes},
+        events::notifications::{subscribe, unsubscribe},
+        websocket::{limiter::RateLimiter, manager::WsConnectionManager},
+    },
+    error::KohakuError,
+};
 
 const HEARTBEAT_INTERVAL_SEC: u64 = 30;
 const HEARTBEAT_MAX_MISSED: i32 = 3;
+/// Connections without any client activity for this long are reaped, even if their heartbeat task is stuck
+pub const STALE_CONNECTION_SEC: i64 =
+    HEARTBEAT_INTERVAL_SEC as i64 * (HEARTBEAT_MAX_MISSED as i64 + 2);
+/// Inbound messages a client may send within [`INBOUND_WINDOW_SEC`]
+const INBOUND_MAX_MESSAGES: usize = 30;
+const INBOUND_WINDOW_SEC: i64 = 10;
+/// Handshake header a client uses to opt into compression (value: `deflate`). Echoed if accepted.
+///
+/// This is not RFC 7692 `permessage-deflate` (`Sec-WebSocket-Extensions` offers are not accepted).
+/// Once accepted, text messages of at least [`COMPRESSION_MIN_BYTES`] are sent as binary messages holding
+/// the zlib stream of the UTF-8 text (see [`deflate`]), smaller ones stay text messages. Clients never compress
+pub const COMPRESSION_HEADER: &str = "x-ws-compression";
+/// Smaller messages are sent uncompressed, as the compression overhead outweighs the savings
+const COMPRESSION_MIN_BYTES: usize = 512;
+/// Close code (application range) sent to a client whose connection was replaced by a newer one of the same API key
+pub const CLOSE_CODE_REPLACED: u16 = 4001;
+/// Handshake header holding the resume token. Sent by the server on connect, sent back by the client on reconnect
+pub const RESUME_TOKEN_HEADER: &str = "x-ws-resume-token";
+/// Seconds a resume token is valid after connecting.
+///
+/// The token is signed with the JWT secret and bound to the API key and the client id of the connection.
+/// It is only accepted alongside a valid API key of the same key id and can resume its connection once,
+/// within [`RESUME_WINDOW_SEC`] after the connection dropped.
+pub const RESUME_TOKEN_LIFETIME_SEC: usize = 24 * 60 * 60;
+/// Seconds after a connection dropped in which it can be resumed. Messages for the client are buffered meanwhile
+pub const RESUME_WINDOW_SEC: i64 = 120;
+/// Messages buffered per dropped connection. If exceeded, the oldest messages are dropped
+pub const RESUME_BUFFER_MAX_MESSAGES: usize = 100;
+/// Priority of routine messages. Queued messages with a higher priority are sent first
+pub const PRIORITY_NORMAL: u8 = 100;
+
+/// Attempts (including the failed first one) to deliver a message before it is given up
+pub const RETRY_MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry. Doubles with every further attempt (10s, 20s, 40s, 80s)
+pub const RETRY_BASE_DELAY_SEC: i64 = 10;
+/// Messages waiting for a retry. If exceeded, the oldest message is given up
+pub const RETRY_QUEUE_MAX: usize = 1000;
+/// Outcomes of retried messages kept in the delivery log
+pub const DELIVERY_LOG_MAX: usize = 1000;
+/// Default maximum size (bytes) of an outbound message, see [`crate::utils::config::Config::ws_max_payload_bytes`]
+pub const PAYLOAD_MAX_BYTES: usize = 65536;
+
+/// Current version of the [`WsMessage`] protocol. Sent to the client on connect via [`PROTOCOL_VERSION_HEADER`]
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Oldest version of the [`WsMessage`] protocol still accepted from clients
+pub const PROTOCOL_MIN_VERSION: u8 = 1;
+/// Handshake header holding the [`PROTOCOL_VERSION`] of the server
+pub const PROTOCOL_VERSION_HEADER: &str = "x-ws-protocol-version";
+
+/// Tags a client may attach to its connection
+pub const TAGS_MAX_COUNT: usize = 16;
+/// Maximum length of a tag name or value
+pub const TAG_MAX_LEN: usize = 64;
+
+/// Protocol messages exchanged with a client, e.g. `{"type": "set_tags", "tags": {"shard": "0"}}`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageType {
+    /// Attaches metadata to the connection (e.g. shard id, region), replacing previously set tags.
+    /// Used to filter broadcasts, see [`WsConnectionManager::broadcast_to_tag`]
+    SetTags { tags: HashMap<String, String> },
+    /// Subscribes a Discord channel (or a thread within it) to a notification code, see [`subscribe`].
+    /// Requires the `events:subscribe` scope
+    Subscribe {
+        code: String,
+        channel_id: i64,
+        guild_id: i64,
+        #[serde(default)]
+        thread_id: Option<i64>,
+    },
+    /// Unsubscribes a Discord channel (or a thread within it) from a notification code, see [`unsubscribe`].
+    /// Requires the `events:subscribe` scope
+    Unsubscribe {
+        code: String,
+        channel_id: i64,
+        guild_id: i64,
+        #[serde(default)]
+        thread_id: Option<i64>,
+    },
+    /// Sent by the server: The frame `reply_to` was processed successfully
+    Ack { reply_to: Uuid },
+    /// Sent by the server: The frame `reply_to` was rejected. [`Uuid::nil`] if the frame couldn't be parsed.
+    /// `code` is stable (see [`KohakuError::code`]) while `error` is meant for humans, see [`error_frame`]
+    Error {
+        reply_to: Uuid,
+        code: String,
+        error: String,
+    },
+}
+
+/// A [`MessageType`] with the metadata of its frame, e.g. `{"type": "set_tags", "message_id": "...", "timestamp": 0, "tags": {}}`.
+///
+/// `version`, `message_id` and `timestamp` are optional when receiving, so clients may send a bare [`MessageType`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WsMessage {
+    /// Protocol version the frame was written for, [`PROTOCOL_VERSION`] if the client didn't set one
+    #[serde(default = "default_version")]
+    pub version: u8,
+    /// Identifier of the frame, [`Uuid::nil`] if the client didn't set one
+    #[serde(default)]
+    pub message_id: Uuid,
+    /// Unix timestamp (seconds) the frame was created at, `0` if the client didn't set one
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub message: MessageType,
+}
+
+/// Helper: Protocol version of frames without a version, see [`PROTOCOL_VERSION`]
+fn default_version() -> u8 {
+    PROTOCOL_VERSION
+}
+
+impl WsMessage {
+    /// Creates a frame with a random id, created now.
+    ///
+    /// # Parameters
+    /// - `message` : Content of the frame
+    pub fn new(message: MessageType) -> Self {
+        Self::with_id(Uuid::new_v4(), message)
+    }
+
+    /// Creates a frame with a given id, created now. Allows deterministic ids, e.g. in tests.
+    ///
+    /// # Parameters
+    /// - `id` : Identifier of the frame
+    /// - `message` : Content of the frame
+    pub fn with_id(id: Uuid, message: MessageType) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            message_id: id,
+            timestamp: Utc::now().timestamp(),
+            message,
+        }
+    }
+
+    /// Parses a frame of a client and checks if its protocol version is supported.
+    ///
+    /// # Parameters
+    /// - `text` : Text message of the client
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The parsed frame
+    /// - [`Err`] : A [`String`] describing why the frame was rejected: Malformed, or a version outside of [`PROTOCOL_MIN_VERSION`] and [`PROTOCOL_VERSION`]
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let message: Self = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if !(PROTOCOL_MIN_VERSION..=PROTOCOL_VERSION).contains(&message.version) {
+            return Err(format!(
+                "Unsupported protocol version {} (supported: {}-{})",
+                message.version, PROTOCOL_MIN_VERSION, PROTOCOL_VERSION
+            ));
+        }
+        Ok(message)
+    }
+}
+
+/// Validates tags of a [`MessageType::SetTags`] message
+///
+/// # Parameters
+/// - `tags` : Tags to validate
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : If the tags can be attached to a connection
+/// - [`Err`] : A [`String`] describing why the tags were rejected
+pub fn validate_tags(tags: &HashMap<String, String>) -> Result<(), String> {
+    if tags.len() > TAGS_MAX_COUNT {
+        return Err(format!("At most {} tags are allowed", TAGS_MAX_COUNT));
+    }
+    for (name, value) in tags {
+        if name.is_empty() || name.len() > TAG_MAX_LEN || value.len() > TAG_MAX_LEN {
+            return Err(format!(
+                "Tag names must be 1-{} and values at most {} characters long",
+                TAG_MAX_LEN, TAG_MAX_LEN
+            ));
+        }
+    }
+    Ok(())
+}
 
 #[derive(Debug, Clone)]
 pub struct WsClientInfo {
     pub client_id: Uuid,
     pub owner: String,
     pub key_id: i32,
+    /// Scopes of the API key at the time of connecting
+    pub scopes: Vec<String>,
+    /// Whether text messages to the client are sent as zlib-compressed binary messages, see [`deflate`]
+    pub compression: bool,
+    /// Metadata set by the client via [`MessageType::SetTags`]
+    pub tags: HashMap<String, String>,
+}
+
+/// A message queued for the send task of a connection
+#[derive(Debug)]
+pub struct Outbound {
+    /// If messages queue up, higher priorities are sent first. See [`PRIORITY_NORMAL`]
+    pub priority: u8,
+    pub message: Message,
+}
+
+impl From<Message> for Outbound {
+    fn from(message: Message) -> Self {
+        Self {
+            priority: PRIORITY_NORMAL,
+            message,
+        }
+    }
+}
+
+/// Entry of an [`OutboundQueue`]: Ordered by priority, then by insertion (FIFO)
+#[derive(Debug)]
+struct QueuedOutbound {
+    seq: u64,
+    outbound: Outbound,
+}
+
+impl PartialEq for QueuedOutbound {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedOutbound {}
+
+impl PartialOrd for QueuedOutbound {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedOutbound {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.outbound
+            .priority
+            .cmp(&other.outbound.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Messages waiting for the send task of a connection.
+///
+/// Pops the message with the highest priority first, messages of the same priority in the order they were pushed.
+#[derive(Debug, Default)]
+pub struct OutboundQueue {
+    heap: BinaryHeap<QueuedOutbound>,
+    next_seq: u64,
+}
+
+impl OutboundQueue {
+    pub fn push(&mut self, outbound: Outbound) {
+        self.heap.push(QueuedOutbound {
+            seq: self.next_seq,
+            outbound,
+        });
+        self.next_seq += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<Outbound> {
+        self.heap.pop().map(|queued| queued.outbound)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+/// Compresses a text message for clients that opted into compression.
+///
+/// actix-ws frames cannot carry the RSV1 bit of `permessage-deflate`, so messages are compressed on
+/// application level instead: A compressed message is a binary message holding the zlib stream of the text.
+///
+/// # Parameters
+/// - `text` : Message to compress
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The zlib-compressed bytes of `text`
+/// - [`Err`] : An [`std::io::Error`] if the compression failed
+pub fn deflate(text: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(text.as_bytes())?;
+    encoder.finish()
+}
+
+/// Activity timestamps (unix seconds) and message counts of a connection, shared between its tasks and the manager
+#[derive(Debug)]
+pub struct ConnectionStats {
+    pub connected_at: i64,
+    last_activity: AtomicI64,
+    last_pong: AtomicI64,
+    messages_received: AtomicU64,
+    messages_sent: AtomicU64,
+}
+
+impl ConnectionStats {
+    /// # Parameters
+    /// - `connected_at` : Unix timestamp (seconds) of the connection start
+    pub fn new(connected_at: i64) -> Self {
+        Self {
+            connected_at,
+            last_activity: AtomicI64::new(connected_at),
+            last_pong: AtomicI64::new(connected_at),
+            messages_received: AtomicU64::new(0),
+            messages_sent: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a message of the client
+    pub fn record_activity(&self) {
+        self.last_activity
+            .store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Records a pong (heartbeat) of the client
+    pub fn record_pong(&self) {
+        self.last_pong
+            .store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Records a text or binary message received from the client
+    pub fn record_received(&self) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a text or binary message sent to the client
+    pub fn record_sent(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Amount of text and binary messages received from the client
+    pub fn messages_received(&self) -> u64 {
+        self.messages_received.load(Ordering::Relaxed)
+    }
+
+    /// Amount of text and binary messages sent to the client
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent.load(Ordering::Relaxed)
+    }
+
+    /// Unix timestamp (seconds) of the last message of the client
+    pub fn last_activity(&self) -> i64 {
+        self.last_activity.load(Ordering::Relaxed)
+    }
+
+    /// Unix timestamp (seconds) of the last pong of the client
+    pub fn last_pong(&self) -> i64 {
+        self.last_pong.load(Ordering::Relaxed)
+    }
+
+    /// Seconds since the client was last heard of (message or pong)
+    pub fn idle_secs(&self, now: i64) -> i64 {
+        now - self.last_activity().max(self.last_pong())
+    }
+}
+
+/// Converts a rejection of a frame into a [`MessageType::Error`], mirroring the HTTP error responses:
+/// The message is the one an HTTP response would carry (see [`KohakuError::details`]), so causes of server errors stay hidden.
+///
+/// # Parameters
+/// - `reply_to` : Identifier of the rejected frame
+/// - `error` : Why the frame was rejected
+pub fn error_frame(reply_to: Uuid, error: &KohakuError) -> MessageType {
+    MessageType::Error {
+        reply_to,
+        code: error.code().to_string(),
+        error: error.details().0,
+    }
+}
+
+/// Processes a frame of a client. Subscription changes are answered with [`MessageType::Ack`] or [`MessageType::Error`],
+/// rejected tags with [`MessageType::Error`].
+///
+/// # Parameters
+/// - `manager` : The associated [`WsConnectionManager`], storing the tags of the connection
+/// - `(key_id, client_id)` : Identifiers of the connection inside the manager
+/// - `frame` : Parsed frame of the client, see [`WsMessage::parse`]
+pub async fn handle_message(
+    manager: &WsConnectionManager,
+    (key_id, client_id): (i32, Uuid),
+    frame: WsMessage,
+) {
+    let result = match frame.message {
+        MessageType::SetTags { tags } => {
+            match validate_tags(&tags) {
+                Ok(_) => {
+                    manager.set_tags(&key_id, client_id, tags);
+                }
+                Err(e) => {
+                    warn!(
+                        "[WS - Conn] Rejected tags of client {}: {} [Key: {}]",
+                        client_id, e, key_id
+                    );
+                    reply(
+                        manager,
+                        (key_id, client_id),
+                        error_frame(frame.message_id, &KohakuError::ValidationError(e)),
+                    )
+                    .await;
+                }
+            }
+            return;
+        }
+        MessageType::Subscribe {
+            code,
+            channel_id,
+            guild_id,
+            thread_id,
+        } => match authorize_subscription(manager, (key_id, client_id)) {
+            Ok(_) => subscribe(&code, channel_id, guild_id, thread_id, None, vec![], None)
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        },
+        MessageType::Unsubscribe {
+            code,
+            channel_id,
+            guild_id,
+            thread_id,
+        } => match authorize_subscription(manager, (key_id, client_id)) {
+            Ok(_) => unsubscribe(&code, channel_id, guild_id, thread_id).await,
+            Err(e) => Err(e),
+        },
+        MessageType::Ack { .. } | MessageType::Error { .. } => {
+            warn!(
+                "[WS - Conn] Ignoring server-only message of client {} [Key: {}]",
+                client_id, key_id
+            );
+            return;
+        }
+    };
+
+    let response = match result {
+        Ok(_) => MessageType::Ack {
+            reply_to: frame.message_id,
+        },
+        Err(e) => {
+            warn!(
+                "[WS - Conn] Rejected subscription change of client {}: {} [Key: {}]",
+                client_id, e, key_id
+            );
+            error_frame(frame.message_id, &e)
+        }
+    };
+    reply(manager, (key_id, client_id), response).await;
+}
+
+/// Helper: Queues a reply for a client. Failures are only logged
+async fn reply(
+    manager: &WsConnectionManager,
+    (key_id, client_id): (i32, Uuid),
+    message: MessageType,
+) {
+    if let Err(e) = manager.send_message(&key_id, message).await {
+        warn!(
+            "[WS - Conn] Couldn't reply to client {}: {} [Key: {}]",
+            client_id, e, key_id
+        );
+    }
+}
+
+/// Helper: Checks that the connection is still registered and its API key may manage subscriptions
+fn authorize_subscription(
+    manager: &WsConnectionManager,
+    (key_id, client_id): (i32, Uuid),
+) -> Result<(), KohakuError> {
+    let info = manager
+        .connection_info(&key_id)
+        .filter(|info| info.client_id == client_id)
+        .ok_or_else(|| KohakuError::Unauthorized("Connection is not registered".to_string()))?;
+    let missing = EventsSubscribe::SCOPES.iter().find(|scope| {
+        !info
+            .scopes
+            .iter()
+            .any(|granted| **scope == granted.as_str())
+    });
+    match missing {
+        Some(scope) => Err(KohakuError::Forbidden(format!("Missing scope `{}`", scope))),
+        None => Ok(()),
+    }
 }
 
 pub struct WsConnection {
     pub info: WsClientInfo,
+    pub stats: Arc<ConnectionStats>,
     session: Session,
     extern_rx: MessageStream,
-    pub server_tx: UnboundedSender<Message>,
-    server_rx: UnboundedReceiver<Message>,
+    pub server_tx: UnboundedSender<Outbound>,
+    server_rx: UnboundedReceiver<Outbound>,
     heartbeat_tx: UnboundedSender<()>,
     pub heartbeat_rx: UnboundedReceiver<()>,
 }
 
 impl WsConnection {
     pub fn new(info: WsClientInfo, session: Session, stream: MessageStream) -> Self {
-        let (server_tx, server_rx) = unbounded_channel::<Message>();
+        let (server_tx, server_rx) = unbounded_channel::<Outbound>();
         let (heartbeat_tx, heartbeat_rx) = unbounded_channel::<()>();
 
         WsConnection {
             info,
+            stats: Arc::new(ConnectionStats::new(Utc::now().timestamp())),
             session,
             extern_rx: stream,
             server_tx,
@@ -56,15 +552,18 @@ impl WsConnection {
     pub fn run(self, manager: Arc<WsConnectionManager>) {
         let client_id = self.info.client_id;
         let key_id = self.info.key_id;
+        let compression = self.info.compression;
         let session = self.session;
         let extern_rx = self.extern_rx;
         let server_rx = self.server_rx;
         let heartbeat_tx = self.heartbeat_tx;
         let heartbeat_rx = self.heartbeat_rx;
+        let stats = self.stats;
 
         let session_send = session.clone();
+        let stats_send = stats.clone();
         let send_handle = tokio::spawn(async move {
-            Self::send(session_send, server_rx).await;
+            Self::send(session_send, server_rx, compression, stats_send).await;
         });
 
         let session_htbt = session.clone();
@@ -75,55 +574,128 @@ impl WsConnection {
         let session_recv = session.clone();
 
         actix_web::rt::spawn(async move {
-            Self::receive(session_recv, extern_rx, heartbeat_tx).await;
+            Self::receive(
+                session_recv,
+                extern_rx,
+                heartbeat_tx,
+                stats,
+                &manager,
+                (key_id, client_id),
+            )
+            .await;
 
             // Wait for the other tasks to complete
             let _ = tokio::join!(send_handle, htbt_handle);
             info!("[WS - Conn] Client {} connection ended, closing session and removing from manager [Key: {}]", client_id, key_id);
 
             let _ = session.close(None).await;
-            manager.remove_connection(&key_id).await;
+            manager.release(&key_id, client_id).await;
         });
     }
 
     /// Sends queued data from the server to the connected client.
+    /// Messages that queued up meanwhile are sent by priority, see [`OutboundQueue`].
     /// Will stop if any message cannot reach the client.
     ///
     /// # Parameters
     /// - `session` : The connected associated [`Session`] to the client
     /// - `server_rx`: Receiver half of the internal channel. Incoming messages are messages from other services within the server
-    async fn send(session: Session, mut server_rx: UnboundedReceiver<Message>) {
-        while let Some(msg) = server_rx.recv().await {
-            let mut session = session.clone();
-            let result = match msg {
-                Message::Text(text) => session.text(text).await,
-                Message::Binary(bin) => session.binary(bin).await,
-                Message::Ping(bytes) => session.ping(&bytes).await,
-                Message::Pong(bytes) => session.pong(&bytes).await,
-                Message::Close(reason) => session.close(reason).await,
-                _ => Ok(()),
-            };
-
-            if result.is_err() {
-                break;
+    /// - `compression` : Whether larger text messages are sent compressed, see [`deflate`]
+    /// - `stats` : [`ConnectionStats`] of the connection, counting the sent messages
+    async fn send(
+        session: Session,
+        mut server_rx: UnboundedReceiver<Outbound>,
+        compression: bool,
+        stats: Arc<ConnectionStats>,
+    ) {
+        let mut queue = OutboundQueue::default();
+        while let Some(outbound) = server_rx.recv().await {
+            queue.push(outbound);
+            while let Ok(outbound) = server_rx.try_recv() {
+                queue.push(outbound);
             }
+            while let Some(outbound) = queue.pop() {
+                let counted = matches!(outbound.message, Message::Text(_) | Message::Binary(_));
+                if Self::send_message(&session, outbound.message, compression)
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                if counted {
+                    stats.record_sent();
+                }
+            }
+        }
+    }
+
+    /// Helper: Sends a single message to the client, see [`WsConnection::send`]
+    async fn send_message(
+        session: &Session,
+        msg: Message,
+        compression: bool,
+    ) -> Result<(), actix_ws::Closed> {
+        let mut session = session.clone();
+        match msg {
+            Message::Text(text) if compression && text.len() >= COMPRESSION_MIN_BYTES => {
+                match deflate(&text) {
+                    Ok(bytes) => session.binary(bytes).await,
+                    Err(e) => {
+                        warn!(
+                            "[WS - Conn] Couldn't compress message, sending uncompressed: {}",
+                            e
+                        );
+                        session.text(text).await
+                    }
+                }
+            }
+            Message::Text(text) => session.text(text).await,
+            Message::Binary(bin) => session.binary(bin).await,
+            Message::Ping(bytes) => session.ping(&bytes).await,
+            Message::Pong(bytes) => session.pong(&bytes).await,
+            Message::Close(reason) => session.close(reason).await,
+            _ => Ok(()),
         }
     }
 
     /// Receives externally messages from the client that reached the server
-    /// Will only react to `Ping`, `Pong` and `Close` messages and will stop if either a closing event was detected
-    /// or the resulting pong does not reach the client.
+    /// Will only react to `Ping`, `Pong`, `Close` and [`WsMessage`] text messages and will stop if either a closing event was detected,
+    /// the resulting pong does not reach the client or the client exceeds the inbound rate limit.
     ///
     /// # Parameters
     /// - `session` : The connected associated [`Session`] to the client
     /// - `server_rx`: Receiver half of the internal channel. Incoming messages are messages from other services within the server
     /// - `heartbeat_tx` : Sender half of the internal heartbeat channel. Incoming pongs will be propagated to this channel to reset the missed pings counter
+    /// - `stats` : [`ConnectionStats`] of the connection, updated on every incoming message
+    /// - `manager` : The associated [`WsConnectionManager`], storing the tags of the connection
+    /// - `(key_id, client_id)` : Identifiers of the connection inside the manager
     async fn receive(
         mut session: Session,
         mut extern_rx: MessageStream,
         heartbeat_tx: UnboundedSender<()>,
+        stats: Arc<ConnectionStats>,
+        manager: &WsConnectionManager,
+        (key_id, client_id): (i32, Uuid),
     ) {
+        let mut limiter = RateLimiter::new(INBOUND_MAX_MESSAGES, INBOUND_WINDOW_SEC);
         while let Some(Ok(msg)) = extern_rx.next().await {
+            stats.record_activity();
+            if !limiter.check_and_add() {
+                warn!("[WS - Conn] Client exceeded the inbound rate limit, disconnecting");
+                // Sent directly, as the close frame below would overtake queued messages
+                let rejection =
+                    KohakuError::RateLimitExceeded("Inbound rate limit exceeded".to_string());
+                if let Ok(text) = manager.encode_message(error_frame(Uuid::nil(), &rejection)) {
+                    let _ = session.text(text).await;
+                }
+                let _ = session
+                    .close(Some(CloseReason {
+                        code: CloseCode::Policy,
+                        description: Some("Rate limit exceeded".to_string()),
+                    }))
+                    .await;
+                return;
+            }
             match msg {
                 Message::Close(_) => {
                     info!("[WS - Conn] Client send closing event, disconnecting");
@@ -131,13 +703,34 @@ impl WsConnection {
                     return;
                 }
                 Message::Ping(bytes) => {
-                    if session.pong(&bytes).await.is_err() {
+                    let Ok(()) = session.pong(&bytes).await else {
                         return;
-                    }
+                    };
                 }
                 Message::Pong(_) => {
+                    stats.record_pong();
                     let _ = heartbeat_tx.send(());
                 }
+                Message::Binary(_) => stats.record_received(),
+                Message::Text(text) => {
+                    stats.record_received();
+                    match WsMessage::parse(&text) {
+                        Ok(frame) => handle_message(manager, (key_id, client_id), frame).await,
+                        Err(e) => {
+                            warn!(
+                                "[WS - Conn] Rejected unknown message of client {}: {} [Key: {}]",
+                                client_id, e, key_id
+                            );
+                            let rejection = KohakuError::ValidationError(e);
+                            reply(
+                                manager,
+                                (key_id, client_id),
+                                error_frame(Uuid::nil(), &rejection),
+                            )
+                            .await;
+                        }
+                    }
+                }
                 _ => {}
             }
         }
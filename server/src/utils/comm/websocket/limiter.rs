@@ -0,0 +1,57 @@
+use std::collections::VecDeque;
+
+use chrono::Utc;
+
+/// Sliding window rate limiter for messages of a single connection.
+///
+/// Timestamps are kept in a ring buffer, so expired ones are popped from the front
+/// instead of rescanning the whole window on every message (amortized O(1)).
+pub struct RateLimiter {
+    max_messages: usize,
+    window_ms: i64,
+    timestamps: VecDeque<i64>,
+}
+
+impl RateLimiter {
+    /// # Parameters
+    /// - `max_messages` : Messages allowed within the window
+    /// - `window_secs` : Length of the sliding window (seconds)
+    pub fn new(max_messages: usize, window_secs: i64) -> Self {
+        Self {
+            max_messages,
+            window_ms: window_secs * 1000,
+            timestamps: VecDeque::with_capacity(max_messages),
+        }
+    }
+
+    /// Checks if another message is allowed right now and counts it if so.
+    ///
+    /// # Returns
+    /// `true` if the message is within the limit, `false` if it should be rejected
+    pub fn check_and_add(&mut self) -> bool {
+        self.check_and_add_at(Utc::now().timestamp_millis())
+    }
+
+    /// Same as [`RateLimiter::check_and_add`] at a given time.
+    ///
+    /// # Parameters
+    /// - `now_ms` : Current time as unix timestamp in milliseconds
+    ///
+    /// # Returns
+    /// `true` if the message is within the limit, `false` if it should be rejected
+    pub fn check_and_add_at(&mut self, now_ms: i64) -> bool {
+        while self
+            .timestamps
+            .front()
+            .is_some_and(|ts| *ts <= now_ms - self.window_ms)
+        {
+            self.timestamps.pop_front();
+        }
+
+        if self.timestamps.len() >= self.max_messages {
+            return false;
+        }
+        self.timestamps.push_back(now_ms);
+        true
+    }
+}
@@ -1,57 +1,428 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
 };
 
-use actix_ws::{Message, MessageStream, Session};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use chrono::Utc;
 use serde::Serialize;
 use tokio::sync::{mpsc::UnboundedSender, OnceCell};
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use uuid::Uuid;
 
+#[cfg(not(test))]
+use crate::utils::config::get_config;
 use crate::utils::{
-    comm::websocket::connection::{WsClientInfo, WsConnection},
+    comm::websocket::{
+        connection::{
+            ConnectionStats, MessageType, Outbound, WsClientInfo, WsConnection, WsMessage,
+            CLOSE_CODE_REPLACED, DELIVERY_LOG_MAX, PAYLOAD_MAX_BYTES, PRIORITY_NORMAL,
+            RESUME_BUFFER_MAX_MESSAGES, RESUME_WINDOW_SEC, RETRY_BASE_DELAY_SEC,
+            RETRY_MAX_ATTEMPTS, RETRY_QUEUE_MAX,
+        },
+        limiter::RateLimiter,
+        signing::sign_message,
+    },
     error::KohakuError,
 };
 
 static WS_CONNECTION_MANAGER: OnceCell<Arc<WsConnectionManager>> = OnceCell::const_new();
 
+/// A registered connection: The connected client and the queue of its send task
+#[derive(Clone)]
+struct ConnectionEntry {
+    info: WsClientInfo,
+    sender: UnboundedSender<Outbound>,
+    stats: Arc<ConnectionStats>,
+}
+
+/// A dropped connection that can still be resumed: Messages for its API key are buffered until then
+struct ResumeState {
+    key_id: i32,
+    /// Owner of the API key, see [`WsClientInfo::owner`]
+    owner: String,
+    /// Unix timestamp (seconds) after which the connection can't be resumed anymore
+    expires_at: i64,
+    messages: VecDeque<Outbound>,
+}
+
+/// A message whose delivery failed, re-attempted by [`WsConnectionManager::retry_pending`]
+struct RetryEntry {
+    key_id: i32,
+    priority: u8,
+    content: String,
+    /// Failed attempts so far, including the first delivery
+    attempts: u32,
+    /// Unix timestamp (seconds) of the next attempt
+    next_attempt_at: i64,
+}
+
+/// Final outcome of a message that went through the retry queue
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DeliveryRecord {
+    pub key_id: i32,
+    /// Attempts including the first delivery
+    pub attempts: u32,
+    /// `false` if the message was given up
+    pub delivered: bool,
+    /// Unix timestamp (seconds) of the outcome
+    pub at: i64,
+}
+
+/// Outcome of a [`WsConnectionManager::retry_pending`] run per API key id
+#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+pub struct RetryReport {
+    /// Message was queued for the reconnected client
+    pub delivered: Vec<i32>,
+    /// Attempt failed, the message is retried later
+    pub rescheduled: Vec<i32>,
+    /// Attempt failed for the last time, see [`RETRY_MAX_ATTEMPTS`]
+    pub given_up: Vec<i32>,
+}
+
+/// A live connection as listed by [`WsConnectionManager::connections`]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ConnectionSummary {
+    pub client_id: Uuid,
+    /// See [`WsClientInfo::owner`]
+    pub owner: String,
+    pub key_id: i32,
+    /// Unix timestamp (seconds) of the connection start
+    pub connected_at: i64,
+    pub uptime_secs: i64,
+    /// Unix timestamp (seconds) of the last message or pong of the client
+    pub last_seen: i64,
+    pub messages_received: u64,
+    pub messages_sent: u64,
+}
+
+/// Outcome of a [`WsConnectionManager::broadcast`] per API key id
+#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+pub struct DeliveryReport {
+    /// Message was queued for the client
+    pub delivered: Vec<i32>,
+    /// Message was dropped, as the client exceeded the outbound limit
+    pub rate_limited: Vec<i32>,
+    /// Message couldn't be queued. The connection was removed and the message will be retried
+    pub failed: Vec<i32>,
+    /// Owners of the reported API key ids, see [`WsClientInfo::owner`]. Missing for unknown clients
+    pub owners: BTreeMap<i32, String>,
+}
+
+impl DeliveryReport {
+    /// Helper: Describes a client for log lines, e.g. `key 3 (notifier)`
+    fn describe(&self, key_id: &i32) -> String {
+        match self.owners.get(key_id) {
+            Some(owner) => format!("key {} ({})", key_id, owner),
+            None => format!("key {}", key_id),
+        }
+    }
+}
+
 pub struct WsConnectionManager {
-    connections: RwLock<HashMap<i32, UnboundedSender<Message>>>,
+    connections: RwLock<HashMap<i32, ConnectionEntry>>,
+    /// Outbound limiters per API key id, created on the first message
+    outbound_limiters: Mutex<HashMap<i32, RateLimiter>>,
+    outbound_max_messages: usize,
+    outbound_window_secs: i64,
+    /// Amount of connections closed by [`WsConnectionManager::reap_stale`] since startup
+    reaped_total: AtomicU64,
+    /// Dropped connections by their client id (session id of the resume token)
+    resumable: Mutex<HashMap<Uuid, ResumeState>>,
+    /// Messages whose delivery failed, oldest first
+    retries: Mutex<VecDeque<RetryEntry>>,
+    /// Outcomes of retried messages, oldest first
+    delivery_log: Mutex<VecDeque<DeliveryRecord>>,
+    /// Secret outbound messages are signed with, see [`sign_message`]. [`None`] = unsigned
+    signing_secret: Option<Vec<u8>>,
+    /// Maximum size (bytes) of an outbound message, including the signature envelope
+    max_payload_bytes: usize,
+    /// Maximum amount of connections of a single owner. `0` allows unlimited connections
+    max_connections_per_owner: usize,
+}
+
+/// Will select the configured outbound limit (messages, window) in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_outbound_limit() -> (usize, i64) {
+    let config = get_config();
+    (
+        config.ws_outbound_max_messages,
+        config.ws_outbound_window_sec,
+    )
+}
+
+/// Will select a fixed outbound limit (messages, window) in a test environment (cargo test)
+#[cfg(test)]
+fn get_outbound_limit() -> (usize, i64) {
+    (1000, 10)
+}
+
+/// Will select the configured signing secret in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_signing_secret() -> Option<Vec<u8>> {
+    get_config().ws_signing_secret.clone()
+}
+
+/// Will leave outbound messages unsigned in a test environment (cargo test)
+#[cfg(test)]
+fn get_signing_secret() -> Option<Vec<u8>> {
+    None
+}
+
+/// Will select the configured maximum payload size in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_max_payload_bytes() -> usize {
+    get_config().ws_max_payload_bytes
+}
+
+/// Will select [`PAYLOAD_MAX_BYTES`] in a test environment (cargo test)
+#[cfg(test)]
+fn get_max_payload_bytes() -> usize {
+    PAYLOAD_MAX_BYTES
+}
+
+/// Will select the configured connection limit per owner in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_max_connections_per_owner() -> usize {
+    get_config().ws_max_connections_per_owner
+}
+
+/// Will allow unlimited connections per owner in a test environment (cargo test)
+#[cfg(test)]
+fn get_max_connections_per_owner() -> usize {
+    0
 }
 
 impl WsConnectionManager {
-    pub fn new() -> Self {
+    /// # Parameters
+    /// - `outbound_max_messages` : Messages that may be sent to a single API key within the window
+    /// - `outbound_window_secs` : Length of the sliding window of the outbound limit (seconds)
+    pub fn new(outbound_max_messages: usize, outbound_window_secs: i64) -> Self {
         Self {
             connections: RwLock::new(HashMap::new()),
+            outbound_limiters: Mutex::new(HashMap::new()),
+            outbound_max_messages,
+            outbound_window_secs,
+            reaped_total: AtomicU64::new(0),
+            resumable: Mutex::new(HashMap::new()),
+            retries: Mutex::new(VecDeque::new()),
+            delivery_log: Mutex::new(VecDeque::new()),
+            signing_secret: None,
+            max_payload_bytes: PAYLOAD_MAX_BYTES,
+            max_connections_per_owner: 0,
         }
     }
 
+    /// Signs all outbound messages with the given secret, see [`sign_message`].
+    ///
+    /// # Parameters
+    /// - `secret` : Secret shared with the clients
+    pub fn with_signing_secret(mut self, secret: Vec<u8>) -> Self {
+        self.signing_secret = Some(secret);
+        self
+    }
+
+    /// Rejects outbound messages larger than the given size. Defaults to [`PAYLOAD_MAX_BYTES`].
+    ///
+    /// # Parameters
+    /// - `max_payload_bytes` : Maximum size (bytes) of an outbound message, including the signature envelope
+    pub fn with_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = max_payload_bytes;
+        self
+    }
+
+    /// Limits the connections all API keys of a single owner may hold at once. Defaults to `0` (unlimited).
+    ///
+    /// # Parameters
+    /// - `max_connections_per_owner` : Maximum amount of connections per owner, see [`WsConnectionManager::register`]
+    pub fn with_max_connections_per_owner(mut self, max_connections_per_owner: usize) -> Self {
+        self.max_connections_per_owner = max_connections_per_owner;
+        self
+    }
+
     /// Prepares the necessary connection and registers it inside the manager.
-    /// If a connection via this API key is already present, no new connection will be established.
+    /// If a connection via this API key is already present, it is replaced (see [`WsConnectionManager::register`]).
     ///
     /// # Parameters
     /// - `info` : Necessary information about the connected client
     /// - `session` : Current active session to the client
     /// - `stream` : Current active stream from the client
+    /// - `resume_from` : Client id of a dropped connection (from a validated resume token) whose buffered messages are taken over
     ///
     /// # Returns
-    /// A [`Option<WsConnection>`] which is either:
-    /// - [`Some`] : A [`WsConnection`] that is registered inside the manager and can be executed via [`WsConnection::run`]
-    /// - [`None`] : If the API key is already in use with some connection
+    /// A [`Result`] which is either
+    /// - [`Ok`] : A [`WsConnection`] that is registered inside the manager and can be executed via [`WsConnection::run`]
+    /// - [`Err`] : A [`KohakuError::RateLimitExceeded`] if the owner of the API key reached its connection limit
     pub async fn add_connection(
         &self,
         info: WsClientInfo,
         session: Session,
         stream: MessageStream,
-    ) -> Option<WsConnection> {
+        resume_from: Option<Uuid>,
+    ) -> Result<WsConnection, KohakuError> {
+        let conn = WsConnection::new(info.clone(), session, stream);
+        self.register(
+            info,
+            conn.server_tx.clone(),
+            conn.stats.clone(),
+            resume_from,
+        )?;
+        Ok(conn)
+    }
+
+    /// Registers the queue of a connection inside the manager.
+    ///
+    /// An API key has at most one connection, so an existing connection of the key gets replaced: Its client
+    /// receives a close frame with [`CLOSE_CODE_REPLACED`], telling it that it was superseded rather than dropped.
+    /// A new connection beyond the connection limit of the owner (`SERVER_WS_MAX_CONNECTIONS_PER_OWNER`) is refused.
+    /// Replacing a connection never counts against the limit.
+    ///
+    /// # Parameters
+    /// - `info` : Necessary information about the connected client
+    /// - `sender` : Queue of the send task of the connection
+    /// - `stats` : [`ConnectionStats`] updated by the connection
+    /// - `resume_from` : Client id of a dropped connection of the same API key. Its buffered messages are queued first
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The [`WsClientInfo`] of the replaced connection, or [`None`] if the API key had no connection yet
+    /// - [`Err`] : A [`KohakuError::RateLimitExceeded`] if the owner reached its connection limit
+    pub(crate) fn register(
+        &self,
+        info: WsClientInfo,
+        sender: UnboundedSender<Outbound>,
+        stats: Arc<ConnectionStats>,
+        resume_from: Option<Uuid>,
+    ) -> Result<Option<WsClientInfo>, KohakuError> {
         let key_id = info.key_id;
-        if self.connections.read().unwrap().contains_key(&key_id) {
-            return None;
+        let mut connections = self.connections.write().unwrap();
+        if self.max_connections_per_owner > 0 && !connections.contains_key(&key_id) {
+            let owned = connections
+                .values()
+                .filter(|entry| entry.info.owner == info.owner)
+                .count();
+            if owned >= self.max_connections_per_owner {
+                warn!(
+                    "[WS - Conn] Refused connection {}: Owner {} already holds {} of {} connection(s) [Key: {}]",
+                    info.client_id, info.owner, owned, self.max_connections_per_owner, key_id
+                );
+                return Err(KohakuError::RateLimitExceeded(format!(
+                    "Connection limit of {} per owner reached",
+                    self.max_connections_per_owner
+                )));
+            }
+        }
+        {
+            // A new connection supersedes all dropped connections of the key
+            let mut resumable = self.resumable.lock().unwrap();
+            let now = Utc::now().timestamp();
+            let resumed = resume_from
+                .and_then(|session_id| resumable.remove(&session_id))
+                .filter(|state| state.key_id == key_id && state.expires_at >= now);
+            resumable.retain(|_, state| state.key_id != key_id);
+            if let Some(state) = resumed {
+                info!(
+                    "[WS - Conn] Connection {} resumed with {} buffered message(s) [Key: {}]",
+                    info.client_id,
+                    state.messages.len(),
+                    key_id
+                );
+                for msg in state.messages {
+                    let _ = sender.send(msg);
+                }
+            }
+        }
+        let Some(replaced) = connections.insert(
+            key_id,
+            ConnectionEntry {
+                info,
+                sender,
+                stats,
+            },
+        ) else {
+            return Ok(None);
+        };
+        drop(connections);
+
+        info!(
+            "[WS - Conn] Connection {} replaced by a newer connection [Key: {}]",
+            replaced.info.client_id, key_id
+        );
+        // If the send task already ended, the connection is closing anyway
+        let reason = CloseReason {
+            code: CloseCode::Other(CLOSE_CODE_REPLACED),
+            description: Some("Connection replaced by a newer connection".to_string()),
+        };
+        let _ = replaced.sender.send(Message::Close(Some(reason)).into());
+        Ok(Some(replaced.info))
+    }
+
+    /// Removes the connection of a specific client, if it is still the registered connection of its API key.
+    ///
+    /// Used by ending connections, so a connection that was replaced doesn't remove its successor.
+    /// The connection stays resumable for [`RESUME_WINDOW_SEC`], buffering messages for its API key meanwhile.
+    /// Connections closed by the server ([`WsConnectionManager::disconnect`]) are already removed and can't be resumed.
+    ///
+    /// # Parameters
+    /// - `key_id` - API key identifier for connections in the manager
+    /// - `client_id` - Identifier of the ending connection, see [`WsClientInfo::client_id`]
+    pub async fn release(&self, key_id: &i32, client_id: Uuid) {
+        let mut connections = self.connections.write().unwrap();
+        if connections
+            .get(key_id)
+            .is_some_and(|entry| entry.info.client_id == client_id)
+        {
+            let entry = connections.remove(key_id).unwrap();
+            self.outbound_limiters.lock().unwrap().remove(key_id);
+            self.resumable.lock().unwrap().insert(
+                client_id,
+                ResumeState {
+                    key_id: *key_id,
+                    owner: entry.info.owner,
+                    expires_at: Utc::now().timestamp() + RESUME_WINDOW_SEC,
+                    messages: VecDeque::new(),
+                },
+            );
         }
-        let conn = WsConnection::new(info, session, stream);
-        let sender = conn.server_tx.clone();
-        self.connections.write().unwrap().insert(key_id, sender);
-        Some(conn)
+    }
+
+    /// Replaces the tags of a connection, if it is still the registered connection of its API key
+    ///
+    /// # Parameters
+    /// - `key_id` - API key identifier for connections in the manager
+    /// - `client_id` - Identifier of the tagged connection
+    /// - `tags` - New tags of the connection, validated via [`crate::utils::comm::websocket::connection::validate_tags`]
+    ///
+    /// # Returns
+    /// `true` if the tags were stored, `false` if the connection is not registered (anymore)
+    pub fn set_tags(&self, key_id: &i32, client_id: Uuid, tags: HashMap<String, String>) -> bool {
+        let mut connections = self.connections.write().unwrap();
+        match connections.get_mut(key_id) {
+            Some(entry) if entry.info.client_id == client_id => {
+                info!(
+                    "[WS - Conn] Client {} set tags {:?} [Key: {}]",
+                    client_id, tags, key_id
+                );
+                entry.info.tags = tags;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes dropped connections that can't be resumed anymore
+    ///
+    /// # Returns
+    /// The amount of removed connections
+    pub fn prune_resumable(&self) -> usize {
+        let now = Utc::now().timestamp();
+        let mut resumable = self.resumable.lock().unwrap();
+        let before = resumable.len();
+        resumable.retain(|_, state| state.expires_at >= now);
+        before - resumable.len()
     }
 
     /// Removes a connection from the manager, making it unable to receive messages from the server
@@ -60,6 +431,118 @@ impl WsConnectionManager {
     /// - `key_id` - API key identifier for connections in the manager
     pub async fn remove_connection(&self, key_id: &i32) {
         self.connections.write().unwrap().remove(key_id);
+        self.outbound_limiters.lock().unwrap().remove(key_id);
+    }
+
+    /// Closes the connection of a client and removes it from the manager
+    ///
+    /// # Parameters
+    /// - `key_id` - API key identifier for connections in the manager
+    /// - `reason` - Optional [`CloseReason`] sent to the client
+    ///
+    /// # Returns
+    /// `true` if a client was connected via this API key, `false` otherwise
+    pub async fn disconnect(&self, key_id: &i32, reason: Option<CloseReason>) -> bool {
+        let entry = self.connections.write().unwrap().remove(key_id);
+        self.outbound_limiters.lock().unwrap().remove(key_id);
+        match entry {
+            Some(entry) => {
+                // The send task closes the session. If it already ended, the connection is closing anyway
+                let _ = entry.sender.send(Message::Close(reason).into());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Closes all connections whose client was not heard of (message or pong) for too long.
+    ///
+    /// Safety net for connections whose own heartbeat did not end them (e.g. leaked sockets).
+    ///
+    /// # Parameters
+    /// - `max_idle_secs` - Seconds a client may be idle before its connection is closed
+    ///
+    /// # Returns
+    /// The API key ids of the closed connections
+    pub async fn reap_stale(&self, max_idle_secs: i64) -> Vec<i32> {
+        let now = Utc::now().timestamp();
+        let stale: Vec<i32> = self
+            .connections
+            .read()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.stats.idle_secs(now) > max_idle_secs)
+            .map(|entry| entry.info.key_id)
+            .collect();
+
+        for key_id in &stale {
+            let reason = CloseReason {
+                code: CloseCode::Away,
+                description: Some("Connection idle for too long".to_string()),
+            };
+            if self.disconnect(key_id, Some(reason)).await {
+                self.reaped_total.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        stale
+    }
+
+    /// Amount of connections closed by [`WsConnectionManager::reap_stale`] since startup
+    pub fn reaped_total(&self) -> u64 {
+        self.reaped_total.load(Ordering::Relaxed)
+    }
+
+    /// Amount of currently registered connections
+    pub fn connection_count(&self) -> usize {
+        self.connections.read().unwrap().len()
+    }
+
+    /// Lists all registered connections, ordered by API key id.
+    /// Only clones the entries under the read lock, so senders aren't held up while the summaries are built.
+    pub fn connections(&self) -> Vec<ConnectionSummary> {
+        let entries: Vec<ConnectionEntry> =
+            self.connections.read().unwrap().values().cloned().collect();
+        let now = Utc::now().timestamp();
+        let mut summaries: Vec<ConnectionSummary> = entries
+            .into_iter()
+            .map(|entry| ConnectionSummary {
+                client_id: entry.info.client_id,
+                owner: entry.info.owner,
+                key_id: entry.info.key_id,
+                connected_at: entry.stats.connected_at,
+                uptime_secs: (now - entry.stats.connected_at).max(0),
+                last_seen: entry.stats.last_activity().max(entry.stats.last_pong()),
+                messages_received: entry.stats.messages_received(),
+                messages_sent: entry.stats.messages_sent(),
+            })
+            .collect();
+        summaries.sort_by_key(|summary| summary.key_id);
+        summaries
+    }
+
+    /// Checks whether a client is connected via an API key
+    ///
+    /// # Parameters
+    /// - `key_id` - API key identifier for connections in the manager
+    pub fn is_connected(&self, key_id: &i32) -> bool {
+        self.connections.read().unwrap().contains_key(key_id)
+    }
+
+    /// Gets information about the client connected via an API key
+    ///
+    /// # Parameters
+    /// - `key_id` - API key identifier for connections in the manager
+    ///
+    /// # Returns
+    /// An [`Option`] which is either
+    /// - [`Some`] : The [`WsClientInfo`] of the connected client
+    /// - [`None`] : If no client is connected via this API key
+    pub fn connection_info(&self, key_id: &i32) -> Option<WsClientInfo> {
+        self.connections
+            .read()
+            .unwrap()
+            .get(key_id)
+            .map(|entry| entry.info.clone())
     }
 
     /// Sends a [`Serialize`]-able payload to multiple clients.
@@ -73,47 +556,170 @@ impl WsConnectionManager {
     ///
     /// # Returns
     /// A [`Result`] which is either
-    /// - [`Ok`] - Indicating that the queueing of the message was successful
+    /// - [`Ok`] - A [`DeliveryReport`] of which clients the message was queued for, dropped or failed
     /// - [`Err`] - A [`KohakuError`] indicating that ANY operation failed
     pub async fn broadcast<T: Serialize>(
         &self,
         payload: T,
         key_ids: Option<Vec<i32>>,
-    ) -> Result<(), KohakuError> {
+    ) -> Result<DeliveryReport, KohakuError> {
+        self.broadcast_with_priority(payload, key_ids, PRIORITY_NORMAL)
+            .await
+    }
+
+    /// Sends a [`Serialize`]-able payload to multiple clients, ahead of queued messages with a lower priority.
+    ///
+    /// # Parameters
+    /// - `payload` - Generic serializable content
+    /// - `key_ids` - Vector of API key ids as targets. If [`None`] the payload will be send to all active connections
+    /// - `priority` - Priority of the message, see [`PRIORITY_NORMAL`]
+    ///
+    /// # Type Parameters
+    /// - `T` - Any struct that derives [`Serialize`]
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] - A [`DeliveryReport`], see [`WsConnectionManager::broadcast`]
+    /// - [`Err`] - A [`KohakuError`] indicating that ANY operation failed
+    pub async fn broadcast_with_priority<T: Serialize>(
+        &self,
+        payload: T,
+        key_ids: Option<Vec<i32>>,
+        priority: u8,
+    ) -> Result<DeliveryReport, KohakuError> {
         let collections = match key_ids {
             Some(given) => given,
             None => {
-                let stored = self.connections.read().unwrap().clone();
-                stored.keys().copied().collect::<Vec<i32>>()
+                // Dropped connections buffer the message until they are resumed
+                let stored = self.connections.read().unwrap();
+                let mut keys = stored.keys().copied().collect::<Vec<i32>>();
+                let resumable = self.resumable.lock().unwrap();
+                for state in resumable.values() {
+                    if !keys.contains(&state.key_id) {
+                        keys.push(state.key_id);
+                    }
+                }
+                keys
             }
         };
-        let mut successful = 0;
-        let mut failed_clients = Vec::new();
+        let content = self.encode(&payload)?;
+        let mut report = DeliveryReport::default();
 
         for key_id in collections {
-            match self.send_to_client(&payload, &key_id).await {
-                Ok(_) => successful += 1,
+            // Looked up first, as failed connections are removed
+            if let Some(owner) = self.owner_of(&key_id) {
+                report.owners.insert(key_id, owner);
+            }
+            match self.queue_text(&key_id, &content, priority) {
+                Ok(_) => report.delivered.push(key_id),
+                Err(KohakuError::RateLimitExceeded(e)) => {
+                    warn!("[WS - Broadcast] {} [{}]", e, report.describe(&key_id));
+                    report.rate_limited.push(key_id)
+                }
                 Err(e) => {
-                    error!("[WS - Broadcast] {}", e);
-                    failed_clients.push(key_id)
+                    error!("[WS - Broadcast] {} [{}]", e, report.describe(&key_id));
+                    report.failed.push(key_id)
                 }
             }
         }
 
         // Clean up
-        for key_id in &failed_clients {
+        let now = Utc::now().timestamp();
+        for key_id in &report.failed {
             self.remove_connection(key_id).await;
+            self.schedule_retry(RetryEntry {
+                key_id: *key_id,
+                priority,
+                content: content.clone(),
+                attempts: 1,
+                next_attempt_at: now + RETRY_BASE_DELAY_SEC,
+            });
         }
         info!(
-            "[WS - Broadcast] Broadcasted 1 message successfully {} time(s) and failed {} time(s)",
-            successful,
-            &failed_clients.len()
+            "[WS - Broadcast] Broadcasted 1 message successfully {} time(s), dropped {} time(s) and failed {} time(s)",
+            report.delivered.len(),
+            report.rate_limited.len(),
+            report.failed.len()
         );
-        Ok(())
+        if !report.failed.is_empty() {
+            let failed = report
+                .failed
+                .iter()
+                .map(|key_id| report.describe(key_id))
+                .collect::<Vec<String>>();
+            warn!("[WS - Broadcast] Failed clients: {}", failed.join(", "));
+        }
+        Ok(report)
+    }
+
+    /// Sends a [`Serialize`]-able payload to all connected clients whose API key holds a scope.
+    ///
+    /// The scopes are taken from the [`WsClientInfo`] of the connections, so no database lookup is needed.
+    ///
+    /// # Parameters
+    /// - `payload` - Generic serializable content
+    /// - `scope` - Scope the API key of a client must hold, e.g. `events:subscribe`
+    ///
+    /// # Type Parameters
+    /// - `T` - Any struct that derives [`Serialize`]
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] - A [`DeliveryReport`], see [`WsConnectionManager::broadcast`]
+    /// - [`Err`] - A [`KohakuError`] indicating that ANY operation failed
+    pub async fn broadcast_to_scope<T: Serialize>(
+        &self,
+        payload: T,
+        scope: &str,
+    ) -> Result<DeliveryReport, KohakuError> {
+        let key_ids = self
+            .connections
+            .read()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.info.scopes.iter().any(|s| s == scope))
+            .map(|entry| entry.info.key_id)
+            .collect();
+        self.broadcast(payload, Some(key_ids)).await
+    }
+
+    /// Sends a [`Serialize`]-able payload to all connected clients that tagged their connection,
+    /// see [`crate::utils::comm::websocket::connection::MessageType::SetTags`].
+    ///
+    /// # Parameters
+    /// - `payload` - Generic serializable content
+    /// - `name` - Name of the tag, e.g. `shard`
+    /// - `value` - Value the tag must have
+    ///
+    /// # Type Parameters
+    /// - `T` - Any struct that derives [`Serialize`]
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] - A [`DeliveryReport`], see [`WsConnectionManager::broadcast`]
+    /// - [`Err`] - A [`KohakuError`] indicating that ANY operation failed
+    pub async fn broadcast_to_tag<T: Serialize>(
+        &self,
+        payload: T,
+        name: &str,
+        value: &str,
+    ) -> Result<DeliveryReport, KohakuError> {
+        let key_ids = self
+            .connections
+            .read()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.info.tags.get(name).is_some_and(|v| v == value))
+            .map(|entry| entry.info.key_id)
+            .collect();
+        self.broadcast(payload, Some(key_ids)).await
     }
 
     /// Sends a [`Serialize`]-able payload to a connected client.
     ///
+    /// Messages exceeding the outbound limit of the API key are dropped instead of being queued.
+    /// If the client is not connected, but its connection can still be resumed, the message is buffered instead.
+    ///
     /// # Parameters
     /// - `payload` - Generic serializable content
     /// - `key_id` - Identifier for target client via API key id
@@ -124,17 +730,211 @@ impl WsConnectionManager {
     /// # Returns
     /// A [`Result`] which is either
     /// - [`Ok`] - Indicating that the queueing of the message was successful
-    /// - [`Err`] - A [`KohakuError`] indicating that ANY operation failed
+    /// - [`Err`] - A [`KohakuError::RateLimitExceeded`] if the message was dropped, or another [`KohakuError`] indicating that ANY operation failed
     pub async fn send_to_client<T: Serialize>(
         &self,
         payload: T,
         key_id: &i32,
     ) -> Result<(), KohakuError> {
-        let connections = self.connections.read().unwrap().clone();
-        let content = serde_json::to_string(&payload).unwrap();
+        self.send_to_client_with_priority(payload, key_id, PRIORITY_NORMAL)
+            .await
+    }
 
-        if let Some(sender) = connections.get(key_id) {
-            sender.send(Message::Text(content.into())).map_err(|e| {
+    /// Sends a protocol message to a connected client, framed as [`WsMessage`] with a fresh id and timestamp.
+    ///
+    /// # Parameters
+    /// - `key_id` - Identifier for target client via API key id
+    /// - `message` - Content of the frame
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] - The id of the sent frame
+    /// - [`Err`] - See [`WsConnectionManager::send_to_client`]
+    pub async fn send_message(
+        &self,
+        key_id: &i32,
+        message: MessageType,
+    ) -> Result<Uuid, KohakuError> {
+        let frame = WsMessage::new(message);
+        self.send_to_client(&frame, key_id).await?;
+        Ok(frame.message_id)
+    }
+
+    /// Sends a [`Serialize`]-able payload to a connected client, ahead of queued messages with a lower priority.
+    ///
+    /// # Parameters
+    /// - `payload` - Generic serializable content
+    /// - `key_id` - Identifier for target client via API key id
+    /// - `priority` - Priority of the message, see [`PRIORITY_NORMAL`]
+    ///
+    /// # Type Parameters
+    /// - `T` - Any struct that derives [`Serialize`]
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] - Indicating that the queueing of the message was successful
+    /// - [`Err`] - See [`WsConnectionManager::send_to_client`]
+    pub async fn send_to_client_with_priority<T: Serialize>(
+        &self,
+        payload: T,
+        key_id: &i32,
+        priority: u8,
+    ) -> Result<(), KohakuError> {
+        let content = self.encode(&payload)?;
+        self.queue_text(key_id, &content, priority)
+    }
+
+    /// Re-attempts the delivery of failed messages that are due, e.g. to clients that reconnected meanwhile.
+    ///
+    /// Every failed attempt doubles the delay to the next one, starting at [`RETRY_BASE_DELAY_SEC`].
+    /// After [`RETRY_MAX_ATTEMPTS`] the message is given up. Outcomes are recorded in the [`WsConnectionManager::delivery_log`].
+    ///
+    /// # Returns
+    /// A [`RetryReport`] of the attempted messages
+    pub async fn retry_pending(&self) -> RetryReport {
+        self.retry_pending_at(Utc::now().timestamp()).await
+    }
+
+    /// Same as [`WsConnectionManager::retry_pending`], but with a given timestamp
+    ///
+    /// # Parameters
+    /// - `now` : Unix timestamp (seconds) deciding which messages are due
+    pub async fn retry_pending_at(&self, now: i64) -> RetryReport {
+        let due: VecDeque<RetryEntry> = {
+            let mut retries = self.retries.lock().unwrap();
+            let (due, waiting) = retries
+                .drain(..)
+                .partition(|entry| entry.next_attempt_at <= now);
+            *retries = waiting;
+            due
+        };
+
+        let mut report = RetryReport::default();
+        for mut entry in due {
+            match self.queue_text(&entry.key_id, &entry.content, entry.priority) {
+                Ok(_) => {
+                    entry.attempts += 1;
+                    report.delivered.push(entry.key_id);
+                    self.record_delivery(&entry, true, now);
+                }
+                Err(e) => {
+                    if matches!(e, KohakuError::InternalServerError(_)) {
+                        self.remove_connection(&entry.key_id).await;
+                    }
+                    entry.attempts += 1;
+                    if entry.attempts >= RETRY_MAX_ATTEMPTS {
+                        warn!(
+                            "[WS - Retry] Gave up message to key {} after {} attempts: {}",
+                            entry.key_id, entry.attempts, e
+                        );
+                        report.given_up.push(entry.key_id);
+                        self.record_delivery(&entry, false, now);
+                    } else {
+                        entry.next_attempt_at =
+                            now + RETRY_BASE_DELAY_SEC * (1 << (entry.attempts - 1));
+                        report.rescheduled.push(entry.key_id);
+                        self.schedule_retry(entry);
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Amount of messages waiting for a retry
+    pub fn pending_retries(&self) -> usize {
+        self.retries.lock().unwrap().len()
+    }
+
+    /// Outcomes of retried messages (up to [`DELIVERY_LOG_MAX`]), oldest first
+    pub fn delivery_log(&self) -> Vec<DeliveryRecord> {
+        self.delivery_log.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Helper: Owner of a connected or resumable client, see [`WsClientInfo::owner`]
+    fn owner_of(&self, key_id: &i32) -> Option<String> {
+        if let Some(entry) = self.connections.read().unwrap().get(key_id) {
+            return Some(entry.info.owner.clone());
+        }
+        self.resumable
+            .lock()
+            .unwrap()
+            .values()
+            .find(|state| state.key_id == *key_id)
+            .map(|state| state.owner.clone())
+    }
+
+    /// Frames a protocol message as [`WsMessage`] and encodes it like queued messages, e.g. to send it right before closing a session.
+    ///
+    /// # Parameters
+    /// - `message` - Content of the frame
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] - The text to send, signed if a signing secret is set
+    /// - [`Err`] - See [`WsConnectionManager::send_to_client`]
+    pub fn encode_message(&self, message: MessageType) -> Result<String, KohakuError> {
+        self.encode(&WsMessage::new(message))
+    }
+
+    /// Helper: Serializes a payload and signs it if a signing secret is set
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The message to send
+    /// - [`Err`] : A [`KohakuError::InternalServerError`] if the payload can't be serialized,
+    ///   or a [`KohakuError::ValidationError`] if the message exceeds the maximum payload size
+    fn encode<T: Serialize>(&self, payload: &T) -> Result<String, KohakuError> {
+        let content = serde_json::to_string(payload).map_err(|e| {
+            KohakuError::InternalServerError(format!("Failed to serialize payload: {}", e))
+        })?;
+        let content = match &self.signing_secret {
+            Some(secret) => sign_message(secret, &content),
+            None => content,
+        };
+        if content.len() > self.max_payload_bytes {
+            return Err(KohakuError::ValidationError(format!(
+                "Payload of {} bytes exceeds the maximum of {} bytes",
+                content.len(),
+                self.max_payload_bytes
+            )));
+        }
+        Ok(content)
+    }
+
+    /// Helper: Queues a text message for a client, see [`WsConnectionManager::send_to_client`]
+    fn queue_text(&self, key_id: &i32, content: &str, priority: u8) -> Result<(), KohakuError> {
+        let sender = {
+            let connections = self.connections.read().unwrap();
+            let sender = connections.get(key_id).map(|entry| entry.sender.clone());
+            // Buffered while holding the lock, so a resuming connection can't miss the message
+            if sender.is_none() && self.buffer_for_resume(key_id, priority, content) {
+                return Ok(());
+            }
+            sender
+        };
+
+        if let Some(sender) = sender {
+            let allowed = self
+                .outbound_limiters
+                .lock()
+                .unwrap()
+                .entry(*key_id)
+                .or_insert_with(|| {
+                    RateLimiter::new(self.outbound_max_messages, self.outbound_window_secs)
+                })
+                .check_and_add();
+            if !allowed {
+                return Err(KohakuError::RateLimitExceeded(format!(
+                    "Dropped message to client with key_id {}: Outbound limit reached",
+                    key_id
+                )));
+            }
+            let outbound = Outbound {
+                priority,
+                message: Message::Text(content.to_string().into()),
+            };
+            sender.send(outbound).map_err(|e| {
                 KohakuError::InternalServerError(format!(
                     "Failed to send to client with key_id {} : {}",
                     key_id, e
@@ -147,6 +947,69 @@ impl WsConnectionManager {
             )))
         }
     }
+
+    /// Helper: Adds a message to the retry queue, giving up the oldest one if the queue is full
+    fn schedule_retry(&self, entry: RetryEntry) {
+        let dropped = {
+            let mut retries = self.retries.lock().unwrap();
+            let dropped = if retries.len() >= RETRY_QUEUE_MAX {
+                retries.pop_front()
+            } else {
+                None
+            };
+            retries.push_back(entry);
+            dropped
+        };
+        if let Some(dropped) = dropped {
+            warn!(
+                "[WS - Retry] Retry queue is full, gave up message to key {}",
+                dropped.key_id
+            );
+            self.record_delivery(&dropped, false, Utc::now().timestamp());
+        }
+    }
+
+    /// Helper: Records the final outcome of a retried message in the delivery log
+    fn record_delivery(&self, entry: &RetryEntry, delivered: bool, at: i64) {
+        let mut log = self.delivery_log.lock().unwrap();
+        if log.len() >= DELIVERY_LOG_MAX {
+            log.pop_front();
+        }
+        log.push_back(DeliveryRecord {
+            key_id: entry.key_id,
+            attempts: entry.attempts,
+            delivered,
+            at,
+        });
+    }
+
+    /// Helper: Buffers a message for the most recently dropped, still resumable connection of an API key
+    ///
+    /// # Returns
+    /// `true` if the message was buffered, `false` if the key has no resumable connection
+    fn buffer_for_resume(&self, key_id: &i32, priority: u8, content: &str) -> bool {
+        let now = Utc::now().timestamp();
+        let mut resumable = self.resumable.lock().unwrap();
+        let Some(state) = resumable
+            .values_mut()
+            .filter(|state| state.key_id == *key_id && state.expires_at >= now)
+            .max_by_key(|state| state.expires_at)
+        else {
+            return false;
+        };
+        if state.messages.len() >= RESUME_BUFFER_MAX_MESSAGES {
+            state.messages.pop_front();
+            warn!(
+                "[WS - Resume] Buffer of key {} is full, dropped the oldest message",
+                key_id
+            );
+        }
+        state.messages.push_back(Outbound {
+            priority,
+            message: Message::Text(content.to_string().into()),
+        });
+        true
+    }
 }
 
 /// Initializes a globally unqiue and accessible [`WsConnectionManager`] instance.
@@ -159,7 +1022,14 @@ impl WsConnectionManager {
 /// - [`Ok`] : [`WsConnectionManager`] is now accessible via [get_manager]
 /// - [`Err`] : A [KohakuError::InternalServerError] if the [`manager`] is already initialized
 pub fn init_manager() -> Result<(), KohakuError> {
-    let service = Arc::new(WsConnectionManager::new());
+    let (max_messages, window_secs) = get_outbound_limit();
+    let mut manager = WsConnectionManager::new(max_messages, window_secs)
+        .with_max_payload_bytes(get_max_payload_bytes())
+        .with_max_connections_per_owner(get_max_connections_per_owner());
+    if let Some(secret) = get_signing_secret() {
+        manager = manager.with_signing_secret(secret);
+    }
+    let service = Arc::new(manager);
     WS_CONNECTION_MANAGER.set(service).map_err(|_| {
         KohakuError::InternalServerError(
             "Websocket Connection Manager already initialized".to_string(),
@@ -1,3 +1,6 @@
 pub mod connection;
+pub mod limiter;
 pub mod manager;
 pub mod routes;
+pub mod signing;
+pub mod tasks;
@@ -1,15 +1,44 @@
-use actix_web::{web, HttpRequest, HttpResponse};
-use tracing::info;
+use std::collections::HashMap;
+
+use actix_web::{
+    http::header::{HeaderName, HeaderValue},
+    web, HttpRequest, HttpResponse,
+};
+use tracing::{info, warn};
 use uuid::Uuid;
 
+#[cfg(not(test))]
+use crate::utils::config::get_config;
 use crate::utils::{
     comm::{
-        auth::{check_authorization_key, extract_key},
-        websocket::{connection::WsClientInfo, manager::get_manager},
+        auth::{
+            check_authorization_key, extract_key,
+            extractor::{AdminManage, AuthedClaims},
+            jwt::get_jwtservice,
+        },
+        websocket::{
+            connection::{
+                WsClientInfo, COMPRESSION_HEADER, PROTOCOL_VERSION, PROTOCOL_VERSION_HEADER,
+                RESUME_TOKEN_HEADER, RESUME_TOKEN_LIFETIME_SEC,
+            },
+            manager::get_manager,
+        },
     },
     error::KohakuError,
 };
 
+/// Will select whether compression may be negotiated in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_compression_allowed() -> bool {
+    get_config().ws_compression
+}
+
+/// Will disable compression in a test environment (cargo test)
+#[cfg(test)]
+fn get_compression_allowed() -> bool {
+    false
+}
+
 pub async fn ws_handler(
     req: HttpRequest,
     stream: web::Payload,
@@ -22,29 +51,97 @@ pub async fn ws_handler(
     }
     let verified_key = check_authorization_key(api_key.unwrap()).await?;
 
+    // Compression is opt-in per client and only offered if enabled. See `COMPRESSION_HEADER` for the wire format
+    let compression = get_compression_allowed()
+        && req
+            .headers()
+            .get(COMPRESSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("deflate"));
+
+    // An invalid or foreign resume token only means a fresh session, the API key was already verified
+    let resume_from = req
+        .headers()
+        .get(RESUME_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|token| match get_jwtservice() {
+            Ok(service) => match service.validate_resume_token(token.trim()) {
+                Ok(claims) if claims.key_id == verified_key.id => Some(claims.sid),
+                Ok(_) => {
+                    warn!(
+                        "[WS - Conn] Resume token of another key was presented for key {}",
+                        verified_key.id
+                    );
+                    None
+                }
+                Err(e) => {
+                    warn!("[WS - Conn] Ignoring resume token: {}", e);
+                    None
+                }
+            },
+            Err(_) => None,
+        });
+
     let info = WsClientInfo {
         client_id: Uuid::new_v4(),
         owner: verified_key.owner,
         key_id: verified_key.id,
+        scopes: verified_key.scopes,
+        compression,
+        tags: HashMap::new(),
     };
 
-    let (response, session, msg_stream) = actix_ws::handle(&req, stream)
+    let (mut response, session, msg_stream) = actix_ws::handle(&req, stream)
         .map_err(|e| KohakuError::InternalServerError(e.to_string()))?;
+    if compression {
+        response.headers_mut().insert(
+            HeaderName::from_static(COMPRESSION_HEADER),
+            HeaderValue::from_static("deflate"),
+        );
+    }
+    response.headers_mut().insert(
+        HeaderName::from_static(PROTOCOL_VERSION_HEADER),
+        HeaderValue::from(PROTOCOL_VERSION as u16),
+    );
+    let resume_token = get_jwtservice()?.create_resume_token(
+        verified_key.id,
+        info.client_id,
+        RESUME_TOKEN_LIFETIME_SEC,
+    )?;
+    response.headers_mut().insert(
+        HeaderName::from_static(RESUME_TOKEN_HEADER),
+        HeaderValue::from_str(&resume_token)
+            .map_err(|e| KohakuError::InternalServerError(e.to_string()))?,
+    );
 
     let manager = get_manager()?;
     let conn = manager
-        .add_connection(info.clone(), session, msg_stream)
-        .await;
-    if let Some(conn_) = conn {
-        info!(
-            "[WS - Conn] Established new connection {} for key with id {}",
-            info.client_id, verified_key.id
-        );
-        conn_.run(manager);
-    } else {
-        return Err(KohakuError::InternalServerError(
-            "Couldn't create WebSocketConnection!".to_string(),
-        ));
-    }
+        .add_connection(info.clone(), session, msg_stream, resume_from)
+        .await?;
+    info!(
+        "[WS - Conn] Established new connection {} for key with id {}",
+        info.client_id, verified_key.id
+    );
+    conn.run(manager);
     Ok(response)
 }
+
+/// WebSocket connection listing endpoint.
+///
+/// Lists all live connections with their owner, uptime and message counts, e.g. for troubleshooting.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the list of [`crate::utils::comm::websocket::manager::ConnectionSummary`]s
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+pub async fn list_connections(
+    _claims: AuthedClaims<AdminManage>,
+) -> Result<HttpResponse, KohakuError> {
+    Ok(HttpResponse::Ok().json(get_manager()?.connections()))
+}
@@ -0,0 +1,81 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::utils::error::KohakuError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Envelope of a signed websocket message.
+///
+/// The payload is kept as the exact serialized text, so the signature can be verified without re-serializing it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedMessage {
+    /// Serialized JSON payload
+    pub payload: String,
+    /// Hex-encoded HMAC-SHA256 of `payload`
+    pub signature: String,
+}
+
+/// Helper: Creates a MAC instance for the given secret
+fn mac(secret: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length")
+}
+
+/// Wraps a serialized payload into a [`SignedMessage`] envelope.
+///
+/// # Parameters
+/// - `secret` : Secret shared with the clients
+/// - `payload` : Serialized JSON payload
+///
+/// # Returns
+/// The serialized [`SignedMessage`]
+pub fn sign_message(secret: &[u8], payload: &str) -> String {
+    let mut mac = mac(secret);
+    mac.update(payload.as_bytes());
+    let signature = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    serde_json::to_string(&SignedMessage {
+        payload: payload.to_string(),
+        signature,
+    })
+    .unwrap()
+}
+
+/// Unwraps a [`SignedMessage`] envelope and verifies its signature.
+///
+/// # Parameters
+/// - `secret` : Secret shared with the clients
+/// - `message` : Serialized [`SignedMessage`]
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The serialized payload of the envelope
+/// - [`Err`] : A [`KohakuError::ValidationError`] if the message is no envelope or the signature doesn't match
+pub fn verify_message(secret: &[u8], message: &str) -> Result<String, KohakuError> {
+    let envelope: SignedMessage = serde_json::from_str(message)
+        .map_err(|e| KohakuError::ValidationError(format!("Invalid signed message: {}", e)))?;
+
+    let signature = (0..envelope.signature.len())
+        .step_by(2)
+        .map(|i| {
+            envelope
+                .signature
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| KohakuError::ValidationError("Signature is not valid hex".to_string()))?;
+
+    let mut mac = mac(secret);
+    mac.update(envelope.payload.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| KohakuError::ValidationError("Signature does not match".to_string()))?;
+
+    Ok(envelope.payload)
+}
@@ -0,0 +1,67 @@
+use tracing::{info, warn};
+
+use crate::{
+    impl_task_wrapper,
+    utils::{
+        comm::websocket::{connection::STALE_CONNECTION_SEC, manager::get_manager},
+        scheduler::tasks::Task,
+    },
+};
+
+/// Closes stale websocket connections and forgets expired resumable connections every minute
+pub struct StaleConnectionReaper(Task);
+
+impl StaleConnectionReaper {
+    pub fn new() -> Self {
+        Self(Task::new("StaleConnectionReaper", "30 * * * * *", false))
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let manager = get_manager().map_err(|e| e.to_string())?;
+        let reaped = manager.reap_stale(STALE_CONNECTION_SEC).await;
+        if !reaped.is_empty() {
+            warn!(
+                "[WS - Reaper] Closed {} stale connection(s) [Keys: {:?}]",
+                reaped.len(),
+                reaped
+            );
+        }
+        let pruned = manager.prune_resumable();
+        info!(
+            "[WS - Reaper] connections={} reaped_total={} resume_pruned={}",
+            manager.connection_count(),
+            manager.reaped_total(),
+            pruned
+        );
+        Ok(())
+    }
+}
+
+impl_task_wrapper!(StaleConnectionReaper);
+
+/// Re-attempts failed message deliveries every 10 seconds, see [`crate::utils::comm::websocket::manager::WsConnectionManager::retry_pending`]
+pub struct DeliveryRetry(Task);
+
+impl DeliveryRetry {
+    pub fn new() -> Self {
+        Self(Task::new("DeliveryRetry", "*/10 * * * * *", false))
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let manager = get_manager().map_err(|e| e.to_string())?;
+        let report = manager.retry_pending().await;
+        if !report.delivered.is_empty() || !report.given_up.is_empty() {
+            info!(
+                "[WS - Retry] Re-delivered {} message(s), gave up {} [Keys: {:?} / {:?}], pending={}",
+                report.delivered.len(),
+                report.given_up.len(),
+                report.delivered,
+                report.given_up,
+                manager.pending_retries()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl_task_wrapper!(DeliveryRetry);
@@ -1,16 +1,75 @@
+use actix_web::http::{header::HeaderName, Method, Uri};
 use std::{env, str::FromStr, sync::Arc};
 use tokio::sync::OnceCell;
 
+use crate::utils::{comm::events::webhook::validate_webhook_url, error::KohakuError};
+
 static CONFIG: OnceCell<Arc<Config>> = OnceCell::const_new();
 
-fn read_env(name: &str, default: Option<&str>) -> String {
-    let value = env::var(name);
-    if let Some(def) = default {
-        value.unwrap_or_else(|_| def.to_string())
-    } else {
-        value
-            .unwrap_or_else(|_| panic!("{} not set!", name))
-            .to_string()
+/// Minimum length (in bytes) of `SERVER_ENCRYPTION_KEY`, as it is used as HS256 secret
+pub const MIN_ENCRYPTION_KEY_LEN: usize = 32;
+/// `User-Agent` of outbound HTTP requests if `SERVER_HTTP_USER_AGENT` is not set
+pub const DEFAULT_USER_AGENT: &str = concat!("kohaku/", env!("CARGO_PKG_VERSION"));
+
+fn read_env(name: &str, default: Option<&str>) -> Result<String, KohakuError> {
+    match (env::var(name), default) {
+        (Ok(value), _) => Ok(value),
+        (Err(_), Some(def)) => Ok(def.to_string()),
+        (Err(_), None) => Err(KohakuError::ValidationError(format!("{} not set!", name))),
+    }
+}
+
+/// Splits a comma-separated list, trimming entries and dropping empty ones
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|entry| entry.trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// Parses a bind address like `0.0.0.0:8080`, `localhost:8080` or `[::1]:8080` (IPv6 in brackets)
+fn parse_bind(entry: &str) -> Result<(String, u16), KohakuError> {
+    let invalid = || {
+        KohakuError::ValidationError(format!(
+            "Invalid SERVER_BIND entry `{}`: Expected `host:port`, e.g. `0.0.0.0:8080` or `[::]:8080`",
+            entry
+        ))
+    };
+    let (host, port) = entry.rsplit_once(':').ok_or_else(invalid)?;
+    let port = port.parse::<u16>().map_err(|_| invalid())?;
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6
+            .strip_suffix(']')
+            .filter(|v6| v6.parse::<std::net::Ipv6Addr>().is_ok())
+            .ok_or_else(invalid)?,
+        None if host.is_empty() || host.contains(':') => return Err(invalid()),
+        None => host,
+    };
+    Ok((host.to_string(), port))
+}
+
+/// Output format of the log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable, multi-line output for local development
+    Pretty,
+    /// One JSON object per line for log aggregators
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = KohakuError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(KohakuError::ValidationError(format!(
+                "SERVER_LOG_FORMAT must be either `pretty` or `json` but was `{}`",
+                s
+            ))),
+        }
     }
 }
 
@@ -20,39 +79,451 @@ pub struct Config {
     // Server
     pub server_addr: String,
     pub server_port: u16,
+    /// Addresses (host, port) the server listens on. Taken from `SERVER_BIND`, or [`Config::server_addr`] and [`Config::server_port`] if unset
+    pub server_binds: Vec<(String, u16)>,
+    /// Origins allowed to call the API from a browser. Empty = no cross-origin requests
+    pub cors_allowed_origins: Vec<String>,
+    /// HTTP methods allowed for cross-origin requests
+    pub cors_allowed_methods: Vec<String>,
+    /// Request headers allowed for cross-origin requests
+    pub cors_allowed_headers: Vec<String>,
+    /// Maximum size (bytes) of request bodies. Larger bodies are rejected with `400`
+    pub max_body_bytes: usize,
+    /// Compresses responses if the client accepts it (`Accept-Encoding`)
+    pub http_compression: bool,
 
     // Logging
     pub logging_level: tracing::Level,
+    pub log_format: LogFormat,
+    /// Path of a daily rolling log file, written in addition to stdout. [`None`] = stdout only
+    pub log_file: Option<String>,
 
     // Database
     pub database_url: String,
+    /// Attempts to acquire a pooled connection before giving up
+    pub database_retry_attempts: u32,
+    /// Base delay (milliseconds) between attempts, doubled after each failed attempt
+    pub database_retry_delay_ms: u64,
+    /// Time (milliseconds) a single attempt waits for a free pooled connection
+    pub database_checkout_timeout_ms: u64,
 
     // Communication
     pub bootstrap_key: String,
+    /// Messages the server may send to a single API key via websocket within [`Config::ws_outbound_window_sec`]
+    pub ws_outbound_max_messages: usize,
+    /// Length of the sliding window of the outbound limit (seconds)
+    pub ws_outbound_window_sec: i64,
+    /// Allows clients to opt into zlib-compressed websocket messages, see [`crate::utils::comm::websocket::connection::COMPRESSION_HEADER`]
+    pub ws_compression: bool,
+    /// Secret shared with the clients to sign outbound websocket messages. [`None`] = unsigned
+    pub ws_signing_secret: Option<Vec<u8>>,
+    /// Maximum size (bytes) of an outbound websocket message. Larger messages are rejected before sending
+    pub ws_max_payload_bytes: usize,
+    /// Websocket connections all API keys of a single owner may hold at once. `0` allows unlimited connections
+    pub ws_max_connections_per_owner: usize,
+    /// Requests a single API key may make to each key management endpoint (create / revoke) within [`Config::keys_manage_window_sec`]
+    pub keys_manage_max_requests: usize,
+    /// Length of the sliding window of the key management limit (seconds)
+    pub keys_manage_window_sec: i64,
+    /// Consecutive failures of an external service opening its circuit, see [`crate::utils::breaker::CircuitBreaker`]
+    pub breaker_failure_threshold: u32,
+    /// Seconds an opened circuit fails fast before calls to the service are tested again
+    pub breaker_cooldown_sec: i64,
+    /// `User-Agent` of outbound HTTP requests, see [`crate::utils::http`]
+    pub http_user_agent: String,
+    /// Seconds an outbound HTTP request may take to connect
+    pub http_connect_timeout_sec: u64,
+    /// Seconds an outbound HTTP request may wait for data before it is aborted
+    pub http_read_timeout_sec: u64,
+    /// Secret for signing JWTs. Must be at least [`MIN_ENCRYPTION_KEY_LEN`] bytes long
     pub encryption_key: Vec<u8>,
+    /// `iss` claim of issued JWTs. Tokens of other issuers are rejected
+    pub jwt_issuer: String,
+    /// `aud` claim of issued JWTs. Tokens for other audiences are rejected
+    pub jwt_audience: String,
+    /// Days revoked API keys are kept for audits before they are purged
+    pub revoked_key_retention_days: u32,
+    /// Days authentication events and sent scheduled notifications are kept before they are purged. `0` disables the purge
+    pub history_retention_days: u32,
+    /// Maximum amount of API keys on the JWT blacklist. If exceeded, the entries expiring soonest are evicted
+    pub jwt_blacklist_max: usize,
+    /// Access tokens an API key may mint via refresh tokens before a new login is required. `0` allows unlimited refreshes
+    pub jwt_refresh_max: u32,
+    /// Seconds notification codes and their subscriptions are cached. `0` disables the cache
+    pub events_cache_ttl_sec: i64,
+    /// Seconds identical notifications of a code are suppressed after being sent. `0` disables the deduplication
+    pub events_dedup_window_sec: i64,
+    /// Days after which unused notification codes without subscriptions are purged. `0` disables the purge
+    pub events_stale_code_days: u32,
+    /// Maximum amount of subscriptions of a single guild. `0` = unlimited
+    pub events_max_subscriptions_per_guild: u32,
+    /// Discord webhook notifications are posted to while no client is connected. [`None`] = no fallback
+    pub events_webhook_url: Option<String>,
 }
 
 impl Config {
-    pub fn new() -> Self {
-        Self {
-            server_addr: read_env("SERVER_ADDR", Some("127.0.0.1")),
-            server_port: read_env("SERVER_PORT", Some("8080"))
+    /// Reads the configuration from the environment.
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The loaded [`Config`]
+    /// - [`Err`] : A [`KohakuError::ValidationError`] if a required variable is missing or a value is invalid
+    pub fn new() -> Result<Self, KohakuError> {
+        let server_port = read_env("SERVER_PORT", Some("8080"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError("SERVER_PORT must be a valid port number".to_string())
+            })?;
+        let server_addr = read_env("SERVER_ADDR", Some("127.0.0.1"))?;
+        let server_binds = match parse_list(&read_env("SERVER_BIND", Some(""))?) {
+            entries if entries.is_empty() => vec![(server_addr.clone(), server_port)],
+            entries => entries
+                .iter()
+                .map(|entry| parse_bind(entry))
+                .collect::<Result<_, _>>()?,
+        };
+        let logging_level = tracing::Level::from_str(&read_env(
+            "SERVER_LOGGING_LEVEL",
+            Some("INFO"),
+        )?)
+        .map_err(|_| {
+            KohakuError::ValidationError("SERVER_LOGGING_LEVEL must be a valid level".to_string())
+        })?;
+
+        let database_retry_attempts = read_env("DATABASE_RETRY_ATTEMPTS", Some("3"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "DATABASE_RETRY_ATTEMPTS must be a positive number".to_string(),
+                )
+            })?;
+        let database_retry_delay_ms = read_env("DATABASE_RETRY_DELAY_MS", Some("100"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "DATABASE_RETRY_DELAY_MS must be a positive number".to_string(),
+                )
+            })?;
+        let database_checkout_timeout_ms = read_env("DATABASE_CHECKOUT_TIMEOUT_MS", Some("2000"))?
+            .parse::<u64>()
+            .ok()
+            .filter(|millis| *millis > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "DATABASE_CHECKOUT_TIMEOUT_MS must be a positive number".to_string(),
+                )
+            })?;
+
+        let ws_outbound_max_messages = read_env("SERVER_WS_OUTBOUND_MAX_MESSAGES", Some("60"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_WS_OUTBOUND_MAX_MESSAGES must be a positive number".to_string(),
+                )
+            })?;
+        let ws_outbound_window_sec = read_env("SERVER_WS_OUTBOUND_WINDOW_SEC", Some("10"))?
+            .parse::<i64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_WS_OUTBOUND_WINDOW_SEC must be a positive number".to_string(),
+                )
+            })?;
+
+        let keys_manage_max_requests = read_env("SERVER_KEYS_MANAGE_MAX_REQUESTS", Some("10"))?
+            .parse::<usize>()
+            .ok()
+            .filter(|max| *max > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_KEYS_MANAGE_MAX_REQUESTS must be a positive number".to_string(),
+                )
+            })?;
+        let keys_manage_window_sec = read_env("SERVER_KEYS_MANAGE_WINDOW_SEC", Some("60"))?
+            .parse::<i64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_KEYS_MANAGE_WINDOW_SEC must be a positive number".to_string(),
+                )
+            })?;
+
+        let breaker_failure_threshold = read_env("SERVER_BREAKER_FAILURE_THRESHOLD", Some("5"))?
+            .parse::<u32>()
+            .ok()
+            .filter(|threshold| *threshold > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_BREAKER_FAILURE_THRESHOLD must be a positive number".to_string(),
+                )
+            })?;
+        let breaker_cooldown_sec = read_env("SERVER_BREAKER_COOLDOWN_SEC", Some("60"))?
+            .parse::<i64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_BREAKER_COOLDOWN_SEC must be a positive number".to_string(),
+                )
+            })?;
+
+        let http_user_agent = match read_env("SERVER_HTTP_USER_AGENT", Some(""))?.trim() {
+            "" => DEFAULT_USER_AGENT.to_string(),
+            agent => agent.to_string(),
+        };
+        if reqwest::header::HeaderValue::from_str(&http_user_agent).is_err() {
+            return Err(KohakuError::ValidationError(
+                "SERVER_HTTP_USER_AGENT must be a valid header value".to_string(),
+            ));
+        }
+
+        let http_connect_timeout_sec = read_env("SERVER_HTTP_CONNECT_TIMEOUT_SEC", Some("5"))?
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_HTTP_CONNECT_TIMEOUT_SEC must be a positive number".to_string(),
+                )
+            })?;
+
+        let http_read_timeout_sec = read_env("SERVER_HTTP_READ_TIMEOUT_SEC", Some("30"))?
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_HTTP_READ_TIMEOUT_SEC must be a positive number".to_string(),
+                )
+            })?;
+
+        let ws_compression = read_env("SERVER_WS_COMPRESSION", Some("false"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_WS_COMPRESSION must be either `true` or `false`".to_string(),
+                )
+            })?;
+
+        let ws_max_connections_per_owner =
+            read_env("SERVER_WS_MAX_CONNECTIONS_PER_OWNER", Some("0"))?
                 .parse()
-                .expect("SERVER_PORT must be a valid port number"),
-            logging_level: tracing::Level::from_str(&read_env(
-                "SERVER_LOGGING_LEVEL",
-                Some("INFO"),
-            ))
-            .unwrap(),
-            database_url: read_env("DATABASE_URL", None),
-            bootstrap_key: read_env("BOOTSTRAP_KEY", None),
-            encryption_key: read_env("SERVER_ENCRYPTION_KEY", None).into_bytes(),
+                .map_err(|_| {
+                    KohakuError::ValidationError(
+                        "SERVER_WS_MAX_CONNECTIONS_PER_OWNER must be a positive number or 0"
+                            .to_string(),
+                    )
+                })?;
+
+        let ws_max_payload_bytes = read_env("SERVER_WS_MAX_PAYLOAD_BYTES", Some("65536"))?
+            .parse::<usize>()
+            .ok()
+            .filter(|bytes| *bytes > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_WS_MAX_PAYLOAD_BYTES must be a positive number".to_string(),
+                )
+            })?;
+
+        let max_body_bytes = read_env("SERVER_MAX_BODY_BYTES", Some("65536"))?
+            .parse::<usize>()
+            .ok()
+            .filter(|bytes| *bytes > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_MAX_BODY_BYTES must be a positive number".to_string(),
+                )
+            })?;
+
+        let http_compression = read_env("SERVER_HTTP_COMPRESSION", Some("true"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_HTTP_COMPRESSION must be either `true` or `false`".to_string(),
+                )
+            })?;
+
+        let cors_allowed_origins = parse_list(&read_env("SERVER_CORS_ORIGINS", Some(""))?);
+        if let Some(origin) = cors_allowed_origins
+            .iter()
+            .find(|origin| *origin == "*" || Uri::from_str(origin).is_err())
+        {
+            return Err(KohakuError::ValidationError(format!(
+                "SERVER_CORS_ORIGINS contains an invalid origin: {}",
+                origin
+            )));
+        }
+        let cors_allowed_methods = parse_list(&read_env(
+            "SERVER_CORS_METHODS",
+            Some("GET,POST,PATCH,DELETE"),
+        )?);
+        if let Some(method) = cors_allowed_methods
+            .iter()
+            .find(|method| Method::from_str(method).is_err())
+        {
+            return Err(KohakuError::ValidationError(format!(
+                "SERVER_CORS_METHODS contains an invalid method: {}",
+                method
+            )));
         }
+        let cors_allowed_headers = parse_list(&read_env(
+            "SERVER_CORS_HEADERS",
+            Some("Authorization,Content-Type,X-API-Key"),
+        )?);
+        if let Some(header) = cors_allowed_headers
+            .iter()
+            .find(|header| HeaderName::from_str(header).is_err())
+        {
+            return Err(KohakuError::ValidationError(format!(
+                "SERVER_CORS_HEADERS contains an invalid header: {}",
+                header
+            )));
+        }
+
+        let revoked_key_retention_days = read_env("SERVER_REVOKED_KEY_RETENTION_DAYS", Some("90"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_REVOKED_KEY_RETENTION_DAYS must be a positive number".to_string(),
+                )
+            })?;
+
+        let history_retention_days = read_env("SERVER_HISTORY_RETENTION_DAYS", Some("90"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_HISTORY_RETENTION_DAYS must be a positive number or 0".to_string(),
+                )
+            })?;
+
+        let jwt_blacklist_max = read_env("SERVER_JWT_BLACKLIST_MAX", Some("10000"))?
+            .parse::<usize>()
+            .ok()
+            .filter(|max| *max > 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_JWT_BLACKLIST_MAX must be a positive number".to_string(),
+                )
+            })?;
+
+        let jwt_refresh_max = read_env("SERVER_JWT_REFRESH_MAX", Some("0"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_JWT_REFRESH_MAX must be a positive number or 0".to_string(),
+                )
+            })?;
+
+        let events_cache_ttl_sec = read_env("SERVER_EVENTS_CACHE_TTL_SEC", Some("5"))?
+            .parse::<i64>()
+            .ok()
+            .filter(|secs| *secs >= 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_EVENTS_CACHE_TTL_SEC must not be negative".to_string(),
+                )
+            })?;
+
+        let events_dedup_window_sec = read_env("SERVER_EVENTS_DEDUP_WINDOW_SEC", Some("300"))?
+            .parse::<i64>()
+            .ok()
+            .filter(|secs| *secs >= 0)
+            .ok_or_else(|| {
+                KohakuError::ValidationError(
+                    "SERVER_EVENTS_DEDUP_WINDOW_SEC must not be negative".to_string(),
+                )
+            })?;
+
+        let events_stale_code_days = read_env("SERVER_EVENTS_STALE_CODE_DAYS", Some("0"))?
+            .parse()
+            .map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_EVENTS_STALE_CODE_DAYS must not be negative".to_string(),
+                )
+            })?;
+
+        let events_max_subscriptions_per_guild =
+            read_env("SERVER_EVENTS_MAX_SUBSCRIPTIONS_PER_GUILD", Some("0"))?
+                .parse()
+                .map_err(|_| {
+                    KohakuError::ValidationError(
+                        "SERVER_EVENTS_MAX_SUBSCRIPTIONS_PER_GUILD must not be negative"
+                            .to_string(),
+                    )
+                })?;
+
+        let events_webhook_url = Some(read_env("SERVER_EVENTS_WEBHOOK_URL", Some(""))?)
+            .filter(|url| !url.trim().is_empty());
+        if let Some(url) = &events_webhook_url {
+            validate_webhook_url(url).map_err(|_| {
+                KohakuError::ValidationError(
+                    "SERVER_EVENTS_WEBHOOK_URL must be a Discord webhook URL".to_string(),
+                )
+            })?;
+        }
+
+        let encryption_key = read_env("SERVER_ENCRYPTION_KEY", None)?.into_bytes();
+        if encryption_key.len() < MIN_ENCRYPTION_KEY_LEN {
+            return Err(KohakuError::ValidationError(format!(
+                "SERVER_ENCRYPTION_KEY must be at least {} bytes long but was {} bytes",
+                MIN_ENCRYPTION_KEY_LEN,
+                encryption_key.len()
+            )));
+        }
+
+        Ok(Self {
+            server_addr,
+            server_port,
+            server_binds,
+            cors_allowed_origins,
+            cors_allowed_methods,
+            cors_allowed_headers,
+            max_body_bytes,
+            http_compression,
+            logging_level,
+            log_format: LogFormat::from_str(&read_env("SERVER_LOG_FORMAT", Some("pretty"))?)?,
+            log_file: Some(read_env("SERVER_LOG_FILE", Some(""))?.trim().to_string())
+                .filter(|path| !path.is_empty()),
+            database_url: read_env("DATABASE_URL", None)?,
+            database_retry_attempts,
+            database_retry_delay_ms,
+            database_checkout_timeout_ms,
+            bootstrap_key: read_env("BOOTSTRAP_KEY", None)?,
+            ws_outbound_max_messages,
+            ws_outbound_window_sec,
+            ws_compression,
+            ws_signing_secret: Some(read_env("SERVER_WS_SIGNING_SECRET", Some(""))?)
+                .filter(|secret| !secret.is_empty())
+                .map(String::into_bytes),
+            ws_max_payload_bytes,
+            ws_max_connections_per_owner,
+            keys_manage_max_requests,
+            keys_manage_window_sec,
+            breaker_failure_threshold,
+            breaker_cooldown_sec,
+            http_user_agent,
+            http_connect_timeout_sec,
+            http_read_timeout_sec,
+            encryption_key,
+            jwt_issuer: read_env("SERVER_JWT_ISSUER", Some("kohaku"))?,
+            jwt_audience: read_env("SERVER_JWT_AUDIENCE", Some("kohaku-api"))?,
+            revoked_key_retention_days,
+            history_retention_days,
+            jwt_blacklist_max,
+            jwt_refresh_max,
+            events_cache_ttl_sec,
+            events_dedup_window_sec,
+            events_stale_code_days,
+            events_max_subscriptions_per_guild,
+            events_webhook_url,
+        })
     }
 }
 
 pub fn init_config() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Arc::new(Config::new());
+    let config = Arc::new(Config::new()?);
     CONFIG
         .set(config)
         .map_err(|_| "Config already initialized")?;
@@ -1,5 +1,8 @@
 use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
 use thiserror::Error;
+use tracing::error;
+
+use crate::utils::middleware::logging::current_request_id;
 
 #[derive(Debug, Error)]
 pub enum KohakuError {
@@ -18,9 +21,26 @@ pub enum KohakuError {
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Rate limit exceeded: {0}")]
+    RateLimitExceeded(String),
+
     #[error("External service error: {0}")]
     ExternalServiceError(String),
 
+    #[error("External API error from {service}: {message}")]
+    ExternalApiError {
+        service: String,
+        /// HTTP status of the response, [`None`] if no response was received
+        status: Option<u16>,
+        message: String,
+    },
+
+    #[error("Timeout during {operation}")]
+    Timeout { operation: String },
+
     #[error("Internal server error: {0}")]
     InternalServerError(String),
 
@@ -33,21 +53,66 @@ pub enum KohakuError {
 }
 
 impl KohakuError {
-    fn details(&self) -> (String, StatusCode) {
+    /// Maps the error of a query that expects a single row, e.g. `.first(conn)` or `.get_result(conn)`.
+    ///
+    /// Usage: `.map_err(KohakuError::not_found_or_database("API key could not be found!"))`
+    ///
+    /// # Parameters
+    /// - `message` : Message of the [`KohakuError::NotFound`] if no row was found
+    ///
+    /// # Returns
+    /// A closure turning [`diesel::result::Error::NotFound`] into a [`KohakuError::NotFound`]
+    /// and every other error into a [`KohakuError::DatabaseError`]
+    pub fn not_found_or_database(
+        message: impl Into<String>,
+    ) -> impl FnOnce(diesel::result::Error) -> Self {
+        move |e| match e {
+            diesel::result::Error::NotFound => KohakuError::NotFound(message.into()),
+            e => KohakuError::DatabaseError(e),
+        }
+    }
+
+    /// Stable identifier of the kind of error, e.g. for websocket error frames.
+    /// Unlike the messages, codes never change, so clients may match on them
+    pub fn code(&self) -> &'static str {
+        match self {
+            KohakuError::DatabaseError(_) => "database_error",
+            KohakuError::DatabaseConnectionError(_) => "database_unavailable",
+            KohakuError::NotFound(_) => "not_found",
+            KohakuError::ValidationError(_) => "validation_error",
+            KohakuError::Unauthorized(_) => "unauthorized",
+            KohakuError::Forbidden(_) => "forbidden",
+            KohakuError::RateLimitExceeded(_) => "rate_limited",
+            KohakuError::ExternalServiceError(_) => "external_service_error",
+            KohakuError::ExternalApiError { .. } => "external_api_error",
+            KohakuError::Timeout { .. } => "timeout",
+            KohakuError::InternalServerError(_) => "internal_error",
+            KohakuError::OperationError { .. } => "operation_error",
+        }
+    }
+
+    /// Message and status presented to clients. Causes of server errors are hidden
+    pub(crate) fn details(&self) -> (String, StatusCode) {
         let (message, status) = match self {
             KohakuError::DatabaseConnectionError(_) => (
                 "Service temporarily unavailable".to_string(),
                 StatusCode::INTERNAL_SERVER_ERROR,
             ),
-            KohakuError::ExternalServiceError(_) => (
+            KohakuError::ExternalServiceError(_) | KohakuError::ExternalApiError { .. } => (
                 "External service error".to_string(),
                 StatusCode::BAD_GATEWAY,
             ),
+            KohakuError::Timeout { operation } => (
+                format!("Timeout during {}", operation),
+                StatusCode::GATEWAY_TIMEOUT,
+            ),
 
             // Propagate message
             KohakuError::NotFound(msg) => (msg.clone(), StatusCode::NOT_FOUND),
             KohakuError::ValidationError(msg) => (msg.clone(), StatusCode::BAD_REQUEST),
             KohakuError::Unauthorized(msg) => (msg.clone(), StatusCode::UNAUTHORIZED),
+            KohakuError::Forbidden(msg) => (msg.clone(), StatusCode::FORBIDDEN),
+            KohakuError::RateLimitExceeded(msg) => (msg.clone(), StatusCode::TOO_MANY_REQUESTS),
 
             // Default
             _ => (
@@ -64,10 +129,21 @@ impl ResponseError for KohakuError {
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
         let (message, status) = self.details();
 
-        HttpResponse::build(status).json(serde_json::json!({
+        let mut body = serde_json::json!({
           "error": message,
           "status": status.as_u16()
-        }))
+        });
+        // Server errors hide their cause from the client, so keep it in the logs
+        if status.is_server_error() {
+            error!("{}", self);
+        }
+
+        // Ties client-reported errors to the server logs
+        if let Some(request_id) = current_request_id() {
+            body["request_id"] = serde_json::json!(request_id);
+        }
+
+        HttpResponse::build(status).json(body)
     }
 
     fn status_code(&self) -> StatusCode {
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ACCEPT},
+    Client, RequestBuilder, Response,
+};
+use serde::de::DeserializeOwned;
+
+use crate::utils::error::KohakuError;
+
+/// HTTP client shared by all outbound requests, e.g. of scraper tasks
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    let (user_agent, connect_timeout, read_timeout) = get_client_settings();
+    build_client(&user_agent, connect_timeout, read_timeout)
+        .expect("Failed to build the HTTP client")
+});
+
+/// Will select the configured user agent and timeouts in a non-test environment (cargo run)
+#[cfg(not(test))]
+fn get_client_settings() -> (String, Duration, Duration) {
+    let config = crate::utils::config::get_config();
+    (
+        config.http_user_agent.clone(),
+        Duration::from_secs(config.http_connect_timeout_sec),
+        Duration::from_secs(config.http_read_timeout_sec),
+    )
+}
+
+/// Will select short timeouts in a test environment (cargo test)
+#[cfg(test)]
+fn get_client_settings() -> (String, Duration, Duration) {
+    (
+        "kohaku-test".to_string(),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+    )
+}
+
+/// Builds a HTTP client with the given user agent and timeouts
+///
+/// # Parameters
+/// - `user_agent` : `User-Agent` header of all requests
+/// - `connect_timeout` : Time a request may take to connect
+/// - `read_timeout` : Time a request may wait for data before it is aborted
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [`Client`]
+/// - [`Err`] : A [enum@KohakuError::ValidationError] if the user agent is not a valid header value,
+///   or a [enum@KohakuError::InternalServerError] if the client couldn't be built
+pub fn build_client(
+    user_agent: &str,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+) -> Result<Client, KohakuError> {
+    let user_agent = HeaderValue::from_str(user_agent).map_err(|_| {
+        KohakuError::ValidationError("User agent must be a valid header value".to_string())
+    })?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/json, text/html;q=0.9, */*;q=0.8"),
+    );
+
+    Client::builder()
+        .user_agent(user_agent)
+        .default_headers(headers)
+        .connect_timeout(connect_timeout)
+        .read_timeout(read_timeout)
+        .build()
+        .map_err(|e| {
+            KohakuError::InternalServerError(format!("Couldn't build the HTTP client: {}", e))
+        })
+}
+
+/// Returns the shared HTTP client, e.g. for requests the helpers below don't cover.
+/// Failures of its requests should be mapped via [`map_error`].
+pub fn client() -> &'static Client {
+    &CLIENT
+}
+
+/// Maps a failed request to a [`KohakuError`]
+///
+/// # Parameters
+/// - `service` : Name of the requested service, e.g. `anilist`
+/// - `error` : The failure of the request
+///
+/// # Returns
+/// A [enum@KohakuError::Timeout] if the request timed out, otherwise a [enum@KohakuError::ExternalApiError]
+pub fn map_error(service: &str, error: reqwest::Error) -> KohakuError {
+    if error.is_timeout() {
+        return KohakuError::Timeout {
+            operation: format!("request to {}", service),
+        };
+    }
+    KohakuError::ExternalApiError {
+        service: service.to_string(),
+        status: error.status().map(|status| status.as_u16()),
+        // URLs may contain tokens, so keep them out of the logs
+        message: error.without_url().to_string(),
+    }
+}
+
+/// Sends a request, rejecting responses without a success status
+///
+/// # Parameters
+/// - `service` : Name of the requested service, e.g. `anilist`
+/// - `request` : The request, e.g. `client().get(url)`
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [`Response`] with a `2xx` status
+/// - [`Err`] : A [enum@KohakuError] via [`map_error`], or a [enum@KohakuError::ExternalApiError] holding the status
+pub async fn send(service: &str, request: RequestBuilder) -> Result<Response, KohakuError> {
+    let response = request.send().await.map_err(|e| map_error(service, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(KohakuError::ExternalApiError {
+            service: service.to_string(),
+            status: Some(status.as_u16()),
+            message: format!("Unexpected status {}", status),
+        });
+    }
+    Ok(response)
+}
+
+/// Fetches a URL as text, e.g. a HTML page
+///
+/// # Parameters
+/// - `service` : Name of the requested service, e.g. `anilist`
+/// - `url` : The URL to fetch
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The body of the response
+/// - [`Err`] : A [enum@KohakuError] based on the failure, see [`send`]
+pub async fn get_text(service: &str, url: &str) -> Result<String, KohakuError> {
+    send(service, client().get(url))
+        .await?
+        .text()
+        .await
+        .map_err(|e| map_error(service, e))
+}
+
+/// Fetches a URL as JSON
+///
+/// # Parameters
+/// - `service` : Name of the requested service, e.g. `anilist`
+/// - `url` : The URL to fetch
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The deserialized body of the response
+/// - [`Err`] : A [enum@KohakuError] based on the failure, see [`send`]. Bodies not matching `T` are a [enum@KohakuError::ExternalApiError]
+pub async fn get_json<T: DeserializeOwned>(service: &str, url: &str) -> Result<T, KohakuError> {
+    send(service, client().get(url))
+        .await?
+        .json()
+        .await
+        .map_err(|e| map_error(service, e))
+}
@@ -0,0 +1,16 @@
+use actix_web::middleware::{Compress, Condition};
+
+use crate::utils::config::Config;
+
+/// Builds the response compression of the [`actix_web::App`] from the [`Config`].
+///
+/// If enabled, responses (including error responses) are compressed with an encoding the client accepts (`Accept-Encoding`).
+///
+/// # Parameters
+/// - `config` : [`Config`] holding the toggle
+///
+/// # Returns
+/// A [`Compress`] middleware wrapped in a [`Condition`], to be wrapped around the [`actix_web::App`]
+pub fn build_compression(config: &Config) -> Condition<Compress> {
+    Condition::new(config.http_compression, Compress::default())
+}
@@ -0,0 +1,27 @@
+use actix_cors::Cors;
+
+use crate::utils::config::Config;
+
+/// Maximum time (seconds) browsers may cache a preflight response
+const CORS_MAX_AGE_SEC: usize = 3600;
+
+/// Builds the CORS policy of the [`actix_web::App`] from the [`Config`].
+///
+/// Without configured origins, no cross-origin requests are allowed.
+///
+/// # Parameters
+/// - `config` : [`Config`] holding the allowed origins, methods and headers
+///
+/// # Returns
+/// A [`Cors`] middleware to be wrapped around the [`actix_web::App`]
+pub fn build_cors(config: &Config) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.cors_allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.cors_allowed_headers.iter().map(String::as_str))
+        .max_age(CORS_MAX_AGE_SEC);
+
+    for origin in &config.cors_allowed_origins {
+        cors = cors.allowed_origin(origin);
+    }
+    cors
+}
@@ -0,0 +1,116 @@
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpMessage,
+};
+use tracing::{error, info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+/// Response header holding the request ID
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: Uuid;
+}
+
+/// ID of a request, stored in the request extensions by [`request_logger`]
+#[derive(Debug, Clone, Copy)]
+pub struct RequestId(pub Uuid);
+
+/// Returns the ID of the request currently being handled.
+///
+/// # Returns
+/// An [`Option`] which is either
+/// - [`Some`] : The [`Uuid`] of the request if called while [`request_logger`] handles it
+/// - [`None`] : If called outside of a request (e.g. in scheduled tasks)
+pub fn current_request_id() -> Option<Uuid> {
+    REQUEST_ID.try_with(|id| *id).ok()
+}
+
+/// Middleware that assigns each request a [`RequestId`] and logs method, path, status and latency on completion.
+///
+/// The ID gets stored in the request extensions, returned via the `X-Request-Id` header
+/// and is available through [`current_request_id`] while the request is handled.
+/// Log lines of the request are emitted within a `request` span holding the ID.
+/// Server errors get logged as `error`, client errors as `warn`.
+///
+/// # Parameters
+/// - `req` : The incoming [`ServiceRequest`]
+/// - `next` : The remaining middleware chain & handler
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The [`ServiceResponse`] of the inner services
+/// - [`Err`] : An [`Error`] from the inner services
+pub async fn request_logger(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = Uuid::new_v4();
+    req.extensions_mut().insert(RequestId(request_id));
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let start = Instant::now();
+
+    // Log lines emitted while handling the request carry its ID via the span
+    let span = info_span!("request", request_id = %request_id);
+    let result = REQUEST_ID
+        .scope(request_id, next.call(req))
+        .instrument(span)
+        .await;
+    let latency_ms = start.elapsed().as_millis();
+
+    match result {
+        Ok(mut res) => {
+            let status = res.status();
+            if status.is_server_error() {
+                error!(
+                    request_id = %request_id,
+                    method = %method,
+                    path = %path,
+                    status = status.as_u16(),
+                    latency_ms = latency_ms,
+                    "Request failed"
+                );
+            } else if status.is_client_error() {
+                warn!(
+                    request_id = %request_id,
+                    method = %method,
+                    path = %path,
+                    status = status.as_u16(),
+                    latency_ms = latency_ms,
+                    "Request rejected"
+                );
+            } else {
+                info!(
+                    request_id = %request_id,
+                    method = %method,
+                    path = %path,
+                    status = status.as_u16(),
+                    latency_ms = latency_ms,
+                    "Request handled"
+                );
+            }
+            if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(res)
+        }
+        Err(e) => {
+            error!(
+                request_id = %request_id,
+                method = %method,
+                path = %path,
+                latency_ms = latency_ms,
+                "Request failed: {}",
+                e
+            );
+            Err(e)
+        }
+    }
+}
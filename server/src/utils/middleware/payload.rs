@@ -0,0 +1,55 @@
+use actix_web::{error::JsonPayloadError, web};
+
+use crate::utils::{config::Config, error::KohakuError};
+
+/// Builds the JSON body extractor configuration of the [`actix_web::App`] from the [`Config`].
+///
+/// Oversized or malformed bodies are rejected with a [`KohakuError::ValidationError`] (`400`)
+/// before the handler is called.
+///
+/// # Parameters
+/// - `config` : [`Config`] holding the maximum body size
+///
+/// # Returns
+/// A [`web::JsonConfig`] to be registered via [`actix_web::App::app_data`]
+pub fn build_json_config(config: &Config) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(config.max_body_bytes)
+        .error_handler(|err, _req| {
+            let message = match err {
+                JsonPayloadError::OverflowKnownLength { length, limit } => format!(
+                    "Request body too large: {} bytes (limit: {} bytes)",
+                    length, limit
+                ),
+                JsonPayloadError::Overflow { limit } => {
+                    format!("Request body too large (limit: {} bytes)", limit)
+                }
+                err => format!("Invalid JSON body: {}", err),
+            };
+            KohakuError::ValidationError(message).into()
+        })
+}
+
+/// Builds the query extractor configuration of the [`actix_web::App`].
+///
+/// Malformed query parameters (e.g. a non-numeric id) are rejected with a [`KohakuError::ValidationError`] (`400`)
+/// before the handler is called.
+///
+/// # Returns
+/// A [`web::QueryConfig`] to be registered via [`actix_web::App::app_data`]
+pub fn build_query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|err, _req| {
+        KohakuError::ValidationError(format!("Invalid query parameters: {}", err)).into()
+    })
+}
+
+/// Builds the raw body extractor configuration (e.g. [`web::Bytes`]) of the [`actix_web::App`] from the [`Config`].
+///
+/// # Parameters
+/// - `config` : [`Config`] holding the maximum body size
+///
+/// # Returns
+/// A [`web::PayloadConfig`] to be registered via [`actix_web::App::app_data`]
+pub fn build_payload_config(config: &Config) -> web::PayloadConfig {
+    web::PayloadConfig::new(config.max_body_bytes)
+}
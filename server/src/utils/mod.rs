@@ -2,8 +2,12 @@
 // TODO: Remove it, when everything is actually used
 #![allow(dead_code)]
 
+pub mod breaker;
 pub mod comm;
 pub mod config;
 pub mod error;
+pub mod http;
+pub mod middleware;
+pub mod retry;
 pub mod scheduler;
 mod tests;
@@ -0,0 +1,60 @@
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+use tracing::warn;
+
+use crate::utils::error::KohakuError;
+
+/// Runs an async fallible operation, retrying it with exponential backoff on failure.
+///
+/// Meant for calls to external services (e.g. scrapers calling upstream APIs), so a short outage doesn't fail a whole task.
+/// The first attempt happens immediately. Every further attempt waits `base_delay * 2^(n-1)` beforehand,
+/// plus a random jitter of up to half of that delay, so concurrent callers don't retry in lockstep.
+///
+/// # Parameters
+/// - `attempts` : Maximum amount of attempts (at least one attempt is always made)
+/// - `base_delay` : Delay before the second attempt (without jitter)
+/// - `f` : Creates the operation. Called once per attempt
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The result of the first successful attempt
+/// - [`Err`] : The [`KohakuError`] of the last attempt
+pub async fn retry_with_backoff<F, Fut, T>(
+    attempts: u32,
+    base_delay: Duration,
+    mut f: F,
+) -> Result<T, KohakuError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, KohakuError>>,
+{
+    let mut attempt = 1;
+    let mut delay = base_delay;
+
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(e) => {
+                let wait = delay + jitter(delay);
+                warn!(
+                    "[Retry] Operation failed (attempt {}/{}): {} - retrying in {:?}",
+                    attempt, attempts, e, wait
+                );
+                tokio::time::sleep(wait).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Helper: Random duration between zero and half of the given delay
+fn jitter(delay: Duration) -> Duration {
+    let max = delay.as_millis() as u64 / 2;
+    if max == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::rng().random_range(0..=max))
+}
@@ -1,36 +1,82 @@
-use std::{error::Error, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+use chrono::{DateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use croner::{
+    parser::{CronParser, Seconds},
+    Cron,
+};
+use futures_util::future::BoxFuture;
 use tokio::sync::{Mutex, OnceCell};
 use tokio_cron_scheduler::{job::job_data::Uuid, Job, JobScheduler};
 
+pub mod routes;
 pub mod tasks;
-use crate::utils::{
-    error::KohakuError,
-    scheduler::tasks::{Runnable, Task},
+use crate::{
+    impl_task_wrapper,
+    utils::{
+        error::KohakuError,
+        scheduler::tasks::{Runnable, Task, TaskStats},
+    },
 };
 
 static SCHEDULER: OnceCell<Arc<Scheduler>> = OnceCell::const_new();
+
+/// Job of a closure scheduled via [`Scheduler::add_closure`]
+type ClosureJob = Box<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// Task running a [`ClosureJob`], so closures are logged the same way as tasks of [`impl_task_wrapper`]
+struct ClosureTask(Task, ClosureJob);
+
+impl ClosureTask {
+    async fn execute(&self) -> Result<(), String> {
+        (self.1)().await
+    }
+}
+
+impl_task_wrapper!(ClosureTask);
 pub struct Scheduler {
     scheduler: Arc<Mutex<JobScheduler>>,
+    /// Set once [`Scheduler::start`] succeeded
+    running: AtomicBool,
+    /// Scheduled jobs. Jobs that only run once are no longer counted after their run
+    job_count: Arc<AtomicUsize>,
 }
 
 impl Scheduler {
     pub async fn new() -> Result<Self, Box<dyn Error>> {
         Ok(Self {
             scheduler: Arc::new(Mutex::new(JobScheduler::new().await?)),
+            running: AtomicBool::new(false),
+            job_count: Arc::new(AtomicUsize::new(0)),
         })
     }
 
-    /// Schedule a given task for the scheduler
+    /// Schedule a given task for the scheduler, either by its cron expression or its interval (see [`Task::every`])
     pub async fn add_task<T>(&self, task: T) -> Result<Uuid, KohakuError>
     where
         T: Runnable + std::ops::Deref<Target = Task> + 'static + Send + Sync,
     {
+        if task.interval.is_none() {
+            validate_cron(&task.cron)?;
+        }
+
+        let timezone = task.timezone.unwrap_or(Tz::UTC);
         let task = Arc::new(task);
-        let job = Job::new_async(&task.cron, {
+        let run = {
             let task = Arc::clone(&task);
-            move |uuid, scheduler| {
+            let job_count = Arc::clone(&self.job_count);
+            move |uuid, scheduler: JobScheduler| {
                 let task = Arc::clone(&task);
+                let job_count = Arc::clone(&job_count);
                 Box::pin(async move {
                     // Run task
                     task.run().await;
@@ -38,15 +84,25 @@ impl Scheduler {
                     // Remove task if it should only run once
                     if task.run_once {
                         scheduler.remove(&uuid).await.unwrap();
+                        job_count.fetch_sub(1, Ordering::SeqCst);
                     }
-                })
+                }) as BoxFuture<'static, ()>
             }
-        })
+        };
+        let job = match task.interval {
+            Some(interval) => Job::new_repeated_async(interval, run),
+            None => Job::new_async_tz(&task.cron, timezone, run),
+        }
         .map_err(|e| KohakuError::OperationError {
             operation: "Scheduler-Job-Creation".to_string(),
             source: Box::new(e),
         })?;
 
+        self.add_job(job).await
+    }
+
+    /// Helper: Adds a created job to the underlying [`JobScheduler`] and counts it
+    async fn add_job(&self, job: Job) -> Result<Uuid, KohakuError> {
         let scheduler = self.scheduler.lock().await;
         let uuid = scheduler
             .add(job)
@@ -55,9 +111,108 @@ impl Scheduler {
                 operation: "Scheduler-Job-Add".to_string(),
                 source: Box::new(e),
             })?;
+        self.job_count.fetch_add(1, Ordering::SeqCst);
         Ok(uuid.into())
     }
 
+    /// Schedule a closure without defining a task via [`impl_task_wrapper`]
+    ///
+    /// # Parameters
+    /// - `name` : Name of the job for logging purposes
+    /// - `cron` : Schedule of the job, see [`Task::cron`]
+    /// - `run_once` : Whether the job is removed after its first run
+    /// - `f` : Closure creating the future to run on every fire time. Errors are logged like errors of tasks
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The identifier of the scheduled job
+    /// - [`Err`] : A [`KohakuError`] if the cron expression is invalid or the job couldn't be scheduled
+    pub async fn add_closure<F>(
+        &self,
+        name: &str,
+        cron: &str,
+        run_once: bool,
+        f: F,
+    ) -> Result<Uuid, KohakuError>
+    where
+        F: Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync + 'static,
+    {
+        self.add_task(ClosureTask(Task::new(name, cron, run_once), Box::new(f)))
+            .await
+    }
+
+    /// Schedule a closure running once at a fixed point in time, e.g. a deferred announcement
+    ///
+    /// # Parameters
+    /// - `name` : Name of the job for logging purposes
+    /// - `when` : Time the job runs at. Times in the past run the job right away
+    /// - `f` : Closure creating the future to run. Errors are logged like errors of tasks
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The identifier of the scheduled job
+    /// - [`Err`] : A [`KohakuError::OperationError`] if the job couldn't be scheduled
+    pub async fn add_oneshot_at<F>(
+        &self,
+        name: &str,
+        when: DateTime<Utc>,
+        f: F,
+    ) -> Result<Uuid, KohakuError>
+    where
+        F: Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync + 'static,
+    {
+        let delay = (when - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+        // One-shot jobs fire after a delay instead of a cron schedule
+        let task = Arc::new(ClosureTask(Task::new(name, "", true), Box::new(f)));
+        let job = Job::new_one_shot_async(delay, {
+            let job_count = Arc::clone(&self.job_count);
+            move |uuid, scheduler| {
+                let task = Arc::clone(&task);
+                let job_count = Arc::clone(&job_count);
+                Box::pin(async move {
+                    task.run().await;
+
+                    let _ = scheduler.remove(&uuid).await;
+                    job_count.fetch_sub(1, Ordering::SeqCst);
+                })
+            }
+        })
+        .map_err(|e| KohakuError::OperationError {
+            operation: "Scheduler-Job-Creation".to_string(),
+            source: Box::new(e),
+        })?;
+
+        self.add_job(job).await
+    }
+
+    /// Execution history of all tasks that ran at least once since startup
+    ///
+    /// # Returns
+    /// The [`TaskStats`] by task name
+    pub fn task_stats(&self) -> BTreeMap<String, TaskStats> {
+        tasks::task_stats()
+    }
+
+    /// Next time a scheduled task fires
+    ///
+    /// # Parameters
+    /// - `id` : Identifier of the task returned by [`Scheduler::add_task`]
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The next fire time in UTC, or [`None`] if the task won't fire again or is unknown
+    /// - [`Err`] : A [`KohakuError::OperationError`] if the scheduler couldn't be queried
+    pub async fn next_run(&self, id: &Uuid) -> Result<Option<DateTime<Utc>>, KohakuError> {
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler
+            .next_tick_for_job((*id).into())
+            .await
+            .map_err(|e| KohakuError::OperationError {
+                operation: "Scheduler-Next-Tick".to_string(),
+                source: Box::new(e),
+            })
+    }
+
     /// Start scheduler
     pub async fn start(&self) -> Result<(), KohakuError> {
         let scheduler = self.scheduler.lock().await;
@@ -68,8 +223,183 @@ impl Scheduler {
                 operation: "Scheduler-Start".to_string(),
                 source: Box::new(e),
             })?;
+        self.running.store(true, Ordering::SeqCst);
         Ok(())
     }
+
+    /// Whether [`Scheduler::start`] succeeded, i.e. scheduled jobs are fired
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Liveness check: Waits until the job scheduler is available, e.g. isn't blocked by a pending operation
+    ///
+    /// # Returns
+    /// Whether the scheduler is running, see [`Scheduler::is_running`]
+    pub async fn ping(&self) -> bool {
+        let _scheduler = self.scheduler.lock().await;
+        self.is_running()
+    }
+
+    /// Amount of scheduled jobs. Jobs that only run once are no longer counted after their run
+    pub fn job_count(&self) -> usize {
+        self.job_count.load(Ordering::SeqCst)
+    }
+}
+
+/// Parses a cron expression the same way the underlying [`JobScheduler`] does.
+///
+/// Expressions need six fields (`sec min hour day-of-month month day-of-week`).
+///
+/// # Parameters
+/// - `expr` : Cron expression to parse
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The parsed [`Cron`] schedule
+/// - [`Err`] : A [`KohakuError::ValidationError`] describing why the expression is invalid
+fn parse_cron(expr: &str) -> Result<Cron, KohakuError> {
+    CronParser::builder()
+        .seconds(Seconds::Required)
+        .dom_and_dow(true)
+        .build()
+        .parse(expr)
+        .map_err(|e| KohakuError::ValidationError(format!("Invalid cron expression `{expr}`: {e}")))
+}
+
+/// Checks if the given cron expression can be scheduled.
+///
+/// # Parameters
+/// - `expr` : Cron expression to check
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The expression is valid
+/// - [`Err`] : A [`KohakuError::ValidationError`] describing why the expression is invalid
+pub fn validate_cron(expr: &str) -> Result<(), KohakuError> {
+    parse_cron(expr).map(|_| ())
+}
+
+/// Parses a human-readable interval: A positive amount followed by a unit (`s`, `m`, `h` or `d`), e.g. `30s` or `1h`
+///
+/// # Parameters
+/// - `expr` : Interval to parse
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The parsed [`Duration`]
+/// - [`Err`] : A [`KohakuError::ValidationError`] describing why the interval is invalid
+pub fn parse_interval(expr: &str) -> Result<Duration, KohakuError> {
+    let invalid =
+        |reason: &str| KohakuError::ValidationError(format!("Invalid interval `{expr}`: {reason}"));
+    let trimmed = expr.trim();
+    let Some(unit) = trimmed.chars().last() else {
+        return Err(invalid("expected an amount followed by s, m, h or d"));
+    };
+    let factor = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(invalid("unit must be one of s, m, h or d")),
+    };
+    let amount: u64 = trimmed[..trimmed.len() - 1]
+        .parse()
+        .map_err(|_| invalid("amount must be a positive whole number"))?;
+    if amount == 0 {
+        return Err(invalid("amount must be a positive whole number"));
+    }
+    amount
+        .checked_mul(factor)
+        .map(Duration::from_secs)
+        .ok_or_else(|| invalid("interval is too large"))
+}
+
+/// Converts a fixed interval to the equivalent cron expression, for schedules aligned to the wall clock.
+///
+/// Cron fields can only repeat evenly within their parent field, so supported intervals are
+/// seconds dividing a minute, minutes dividing an hour, hours dividing a day and exactly one day.
+/// Runs are aligned to the start of the parent field (e.g. `15m` fires at :00, :15, :30 and :45).
+/// Other intervals can still be scheduled via [`Task::every`], counted from when the task is added.
+///
+/// # Parameters
+/// - `interval` : Time between two runs
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The cron expression firing every `interval`
+/// - [`Err`] : A [`KohakuError::ValidationError`] if the interval can't be expressed as cron
+pub fn interval_to_cron(interval: Duration) -> Result<String, KohakuError> {
+    let secs = interval.as_secs();
+    let divides =
+        |unit: u64, parent: u64| secs.is_multiple_of(unit) && parent.is_multiple_of(secs / unit);
+    if interval.subsec_nanos() != 0 || secs == 0 {
+        return Err(KohakuError::ValidationError(format!(
+            "Interval {interval:?} must be a positive amount of whole seconds"
+        )));
+    }
+    match secs {
+        s if s < 60 && divides(1, 60) => Ok(format!("*/{s} * * * * *")),
+        s if s < 60 * 60 && divides(60, 60) => Ok(format!("0 */{} * * * *", s / 60)),
+        s if s < 24 * 60 * 60 && divides(60 * 60, 24) => {
+            Ok(format!("0 0 */{} * * *", s / (60 * 60)))
+        }
+        s if s == 24 * 60 * 60 => Ok("0 0 0 * * *".to_string()),
+        _ => Err(KohakuError::ValidationError(format!(
+            "Interval {interval:?} can't be scheduled: It must evenly divide a minute, an hour or a day, or be exactly one day"
+        ))),
+    }
+}
+
+/// Parses an IANA timezone name, e.g. `Europe/Berlin`
+///
+/// # Parameters
+/// - `name` : Name of the timezone
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : The parsed [`Tz`]
+/// - [`Err`] : A [`KohakuError::ValidationError`] if the timezone is unknown
+pub fn parse_timezone(name: &str) -> Result<Tz, KohakuError> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| KohakuError::ValidationError(format!("Unknown timezone `{name}`")))
+}
+
+/// Calculates the next fire times of a cron expression, starting from now.
+///
+/// Like [`Scheduler::add_task`], the expression is evaluated with the UTC offset `timezone` has right now,
+/// so the preview matches the actual runs and does not follow upcoming DST changes either.
+///
+/// # Parameters
+/// - `expr` : Cron expression to evaluate
+/// - `count` : Maximum amount of fire times to return
+/// - `timezone` : Timezone the expression is evaluated in, see [`Task::timezone`]
+///
+/// # Returns
+/// A [`Result`] which is either
+/// - [`Ok`] : Up to `count` upcoming fire times in UTC. Can be shorter if the schedule ends (e.g. a fixed year)
+/// - [`Err`] : A [`KohakuError::ValidationError`] if the expression is invalid
+pub fn next_fire_times(
+    expr: &str,
+    count: usize,
+    timezone: Tz,
+) -> Result<Vec<DateTime<Utc>>, KohakuError> {
+    let cron = parse_cron(expr)?;
+    let now = Utc::now();
+    let offset = timezone.offset_from_utc_datetime(&now.naive_utc()).fix();
+    let mut times = Vec::with_capacity(count);
+    let mut current = now.with_timezone(&offset);
+    while times.len() < count {
+        match cron.find_next_occurrence(&current, false) {
+            Ok(next) => {
+                times.push(next.with_timezone(&Utc));
+                current = next;
+            }
+            Err(_) => break,
+        }
+    }
+    Ok(times)
 }
 
 pub async fn init_scheduler() -> Result<(), KohakuError> {
@@ -82,6 +412,14 @@ pub async fn init_scheduler() -> Result<(), KohakuError> {
     Ok(())
 }
 
+/// Get the current [`Scheduler`] instance without panicking, e.g. for health checks.
+///
+/// # Returns
+/// The [`Scheduler`], or [`None`] if it was not initialized via [`init_scheduler`]
+pub fn try_get_scheduler() -> Option<Arc<Scheduler>> {
+    SCHEDULER.get().cloned()
+}
+
 pub async fn get_scheduler() -> Arc<Scheduler> {
     SCHEDULER
         .get()
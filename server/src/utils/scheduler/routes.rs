@@ -0,0 +1,93 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    comm::auth::extractor::{AdminManage, AuthedClaims},
+    error::KohakuError,
+    scheduler::{get_scheduler, next_fire_times, parse_timezone},
+};
+
+/// Amount of upcoming fire times returned by the validation endpoint
+const PREVIEW_FIRE_TIMES: usize = 5;
+
+// =========================================== API ============================================= //
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateCronRequest {
+    pub cron: String,
+    /// IANA timezone the expression is evaluated in, e.g. `Europe/Berlin`. Defaults to UTC
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidateCronResponse {
+    pub cron: String,
+    pub timezone: String,
+    /// Upcoming fire times in UTC
+    pub next_runs: Vec<DateTime<Utc>>,
+}
+
+// ========================================= Routes ============================================ //
+
+/// Configures server so that requests get routed to the correct functions
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/validate", web::post().to(validate))
+        .route("/stats", web::get().to(stats));
+}
+
+/// Task execution history endpoint.
+///
+/// Returns the [`crate::utils::scheduler::tasks::TaskStats`] of all tasks that ran since startup by task name,
+/// so failing tasks are visible without searching the logs.
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the stats
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn stats(_claims: AuthedClaims<AdminManage>) -> Result<HttpResponse, KohakuError> {
+    let stats = get_scheduler().await.task_stats();
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+/// Cron expression validation endpoint.
+///
+/// Parses the given expression the same way [`crate::utils::scheduler::Scheduler::add_task`] does
+/// and returns the next few fire times, so operators can check a schedule before using it.
+/// The fire times use the current UTC offset of the timezone, as the scheduler does (see [`next_fire_times`]).
+///
+/// # Parameters
+/// - `_claims` : [`AuthedClaims`] of the JWT given via `Authorization` header
+/// - `body` : [`ValidateCronRequest`] in a JSON Format holding the candidate expression
+///
+/// # Returns
+/// A [`Result`] which either is
+/// - [`Ok`] : A [`HttpResponse`] with status `200` which holds the [`ValidateCronResponse`]
+/// - [`Err`] : A [`KohakuError`] based on failed operations. The [`KohakuError`] gets automatically converted to a [`HttpResponse`]
+///
+/// # Errors
+/// Please see [`KohakuError::details`] for the mapping of [`KohakuError`] to [`actix_web::http::StatusCode`]
+async fn validate(
+    _claims: AuthedClaims<AdminManage>,
+    body: web::Json<ValidateCronRequest>,
+) -> Result<HttpResponse, KohakuError> {
+    let timezone = match &body.timezone {
+        Some(name) => parse_timezone(name)?,
+        None => Tz::UTC,
+    };
+    let next_runs = next_fire_times(&body.cron, PREVIEW_FIRE_TIMES, timezone)?;
+    let response = ValidateCronResponse {
+        cron: body.cron.clone(),
+        timezone: timezone.name().to_string(),
+        next_runs,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
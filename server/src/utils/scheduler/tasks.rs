@@ -1,12 +1,24 @@
-use std::future::Future;
+use std::{collections::BTreeMap, future::Future, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::utils::error::KohakuError;
 
 pub struct Task {
     // Name of task for logging purposes
     pub name: String,
-    // Schedule (see tokio_cron_scheduler)
+    // Schedule (see tokio_cron_scheduler). Unused if `interval` is set
     pub cron: String,
+    // Fixed time between two runs, counted from when the task is added. Replaces `cron` if set
+    pub interval: Option<Duration>,
     // How often the task should be repeated. (-1 = Infinite)
     pub run_once: bool,
+    // Timezone the schedule is evaluated in. Defaults to UTC
+    // Note: The scheduler fixes the UTC offset when the task is added, so DST changes apply after a restart
+    pub timezone: Option<Tz>,
 }
 
 impl Task {
@@ -14,16 +26,88 @@ impl Task {
         Self {
             name: name.to_string(),
             cron: cron.to_string(),
+            interval: None,
             run_once,
+            timezone: None,
+        }
+    }
+
+    /// Creates a task repeating in a fixed interval instead of a cron expression.
+    ///
+    /// The first run happens one `interval` after the task was added, runs are not aligned to the wall clock.
+    /// For aligned runs (e.g. `15m` at :00, :15, :30 and :45), use [`crate::utils::scheduler::interval_to_cron`] instead.
+    ///
+    /// # Parameters
+    /// - `name` : Name of task for logging purposes
+    /// - `interval` : Time between two runs, e.g. parsed via [`crate::utils::scheduler::parse_interval`]
+    /// - `run_once` : Whether the task is removed after its first run
+    ///
+    /// # Returns
+    /// A [`Result`] which is either
+    /// - [`Ok`] : The task, scheduled as repeated job
+    /// - [`Err`] : A [`KohakuError::ValidationError`] if the interval is zero
+    pub fn every(name: &str, interval: Duration, run_once: bool) -> Result<Self, KohakuError> {
+        if interval.is_zero() {
+            return Err(KohakuError::ValidationError(
+                "Interval must be positive".to_string(),
+            ));
         }
+        let mut task = Self::new(name, "", run_once);
+        task.interval = Some(interval);
+        Ok(task)
+    }
+
+    /// Evaluates the schedule in the given timezone, e.g. `"0 0 9 * * *"` fires at 9am local time
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = Some(timezone);
+        self
+    }
+}
+
+/// Execution history of a task since startup
+#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+pub struct TaskStats {
+    /// Amount of executions, including failed ones
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run: Option<DateTime<Utc>>,
+    /// Error of the latest failed execution, kept after later successful executions
+    pub last_error: Option<String>,
+}
+
+/// [`TaskStats`] by task name, updated by every task of [`impl_task_wrapper`]
+static TASK_STATS: Lazy<Mutex<BTreeMap<String, TaskStats>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Records an execution of a task in its [`TaskStats`]
+///
+/// # Parameters
+/// - `name` : Name of the executed task
+/// - `result` : Outcome of the execution
+pub fn record_run(name: &str, result: &Result<(), String>) {
+    let mut stats = TASK_STATS.lock().unwrap();
+    let entry = stats.entry(name.to_string()).or_default();
+    entry.runs += 1;
+    entry.last_run = Some(Utc::now());
+    if let Err(e) = result {
+        entry.failures += 1;
+        entry.last_error = Some(e.clone());
     }
 }
 
+/// Snapshot of the [`TaskStats`] of all tasks that ran at least once, by task name
+pub fn task_stats() -> BTreeMap<String, TaskStats> {
+    TASK_STATS.lock().unwrap().clone()
+}
+
 pub trait Runnable: Send + Sync {
     fn run(&self) -> impl Future<Output = ()> + Send;
 }
 /// Use this macro to quickly implement the foundation of your task!
 ///
+/// Every run is wrapped in a `task` span holding the task `name` and a `run_id`,
+/// so log lines of concurrent runs can be told apart.
+///
 /// Example:
 /// ```
 ///   pub struct MyTask(Task);
@@ -54,11 +138,20 @@ macro_rules! impl_task_wrapper {
 
             impl $crate::utils::scheduler::tasks::Runnable for $t {
               async fn run(&self) -> () {
-                if let Err(e) = self.execute().await {
-                  tracing::error!("[ Task - {} ] - Failure detected: {}", self.0.name, e);
-                  return;
-                }
-                tracing::info!("[ Task - {} ] - Done!", self.0.name);
+                let span = tracing::info_span!(
+                  "task",
+                  name = %self.0.name,
+                  run_id = %uuid::Uuid::new_v4()
+                );
+                tracing::Instrument::instrument(async {
+                  let result = self.execute().await;
+                  $crate::utils::scheduler::tasks::record_run(&self.0.name, &result);
+                  if let Err(e) = result {
+                    tracing::error!("[ Task - {} ] - Failure detected: {}", self.0.name, e);
+                    return;
+                  }
+                  tracing::info!("[ Task - {} ] - Done!", self.0.name);
+                }, span).await
               }
             }
         )*
@@ -1,5 +1,139 @@
 #![cfg(test)]
 
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::{Arc, Once},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use diesel::{Connection, PgConnection};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use uuid::Uuid;
+
+use crate::{
+    db::run_migrations,
+    utils::{
+        comm::websocket::{
+            connection::{ConnectionStats, Outbound, WsClientInfo},
+            manager::WsConnectionManager,
+        },
+        error::KohakuError,
+    },
+};
+
+mod test_api;
+mod test_breaker;
 mod test_comm_auth;
+mod test_comm_events;
+mod test_comm_websocket;
 mod test_config;
+mod test_db;
+mod test_error;
+mod test_http;
+mod test_middleware;
+mod test_retry;
 mod test_scheduler;
+
+static MIGRATED: Once = Once::new();
+
+/// Runs pending migrations on the test database (`TEST_DATABASE_URL`) once per test run.
+///
+/// Tests using the database are marked with `#[ignore = "requires TEST_DATABASE_URL"]`
+/// and can be run via `cargo test -- --include-ignored`.
+pub fn setup_db() {
+    MIGRATED.call_once(|| {
+        let url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL must be set for a testing environment");
+        let mut conn = PgConnection::establish(&url).expect("Couldn't connect to test database");
+        run_migrations(&mut conn).expect("Couldn't migrate test database");
+    });
+}
+
+/// Helper: Registers a websocket client on a manager.
+///
+/// # Parameters
+/// - `manager` : Manager to register the client on
+/// - `key_id` : ID of the API key the client connected with
+/// - `owner` : Owner of the API key
+/// - `scopes` : Scopes of the API key
+/// - `connected_at` : Unix timestamp (seconds) the connection started at
+/// - `resume_from` : Client ID of a dropped connection to resume
+///
+/// # Returns
+/// The result of [`WsConnectionManager::register`] and the message queue of the new client
+pub fn register_ws_client(
+    manager: &WsConnectionManager,
+    key_id: i32,
+    owner: &str,
+    scopes: Vec<&str>,
+    connected_at: i64,
+    resume_from: Option<Uuid>,
+) -> (
+    Result<Option<WsClientInfo>, KohakuError>,
+    UnboundedReceiver<Outbound>,
+) {
+    let (tx, rx) = unbounded_channel();
+    let info = WsClientInfo {
+        client_id: Uuid::new_v4(),
+        owner: owner.to_string(),
+        key_id,
+        scopes: scopes.into_iter().map(str::to_string).collect(),
+        compression: false,
+        tags: HashMap::new(),
+    };
+    let registered = manager.register(
+        info,
+        tx,
+        Arc::new(ConnectionStats::new(connected_at)),
+        resume_from,
+    );
+    (registered, rx)
+}
+
+/// Helper: Serves a single HTTP request on a local port, answering with `status` and a JSON `body` after `delay`.
+///
+/// # Returns
+/// The base URL of the server and a handle yielding the head and the body of the received request
+pub fn mock_http_server(
+    status: u16,
+    body: &'static str,
+    delay: Duration,
+) -> (String, JoinHandle<(String, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            head.push_str(&line);
+        }
+        let mut request_body = vec![0; content_length];
+        reader.read_exact(&mut request_body).unwrap();
+        std::thread::sleep(delay);
+        // The client may have given up already
+        let _ = write!(
+            reader.get_mut(),
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        (head, String::from_utf8(request_body).unwrap())
+    });
+    (url, handle)
+}
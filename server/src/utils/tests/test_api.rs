@@ -0,0 +1,204 @@
+use actix_web::{http::StatusCode, test, App};
+use rstest::rstest;
+
+use crate::{
+    api::{
+        self,
+        health::{probe, SchedulerHealth},
+    },
+    utils::{
+        comm::auth::{
+            jwt::{get_jwtservice, init_jwtservice},
+            models::TokenType,
+        },
+        error::KohakuError,
+        scheduler::Scheduler,
+        tests::setup_db,
+    },
+};
+
+#[rstest]
+#[case("/api/v1/admin/tasks/validate", false)]
+#[case("/api/admin/tasks/validate", true)]
+#[actix_web::test]
+async fn test_api_versions_mounted(#[case] uri: &str, #[case] deprecated: bool) {
+    let app = test::init_service(App::new().configure(api::configure)).await;
+
+    // Route exists (no 404) but requires a token
+    let req = test::TestRequest::post()
+        .uri(uri)
+        .set_json(serde_json::json!({ "cron": "0 * * * * *" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers().contains_key("Deprecation"), deprecated);
+}
+
+#[actix_web::test]
+async fn test_api_unknown_version() {
+    let app = test::init_service(App::new().configure(api::configure)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v2/admin/tasks/validate")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[rstest]
+#[case::not_initialized(None, false, 0)]
+#[case::stopped(Some(false), false, 1)]
+#[case::running(Some(true), true, 1)]
+#[actix_web::test]
+async fn test_scheduler_health(
+    #[case] started: Option<bool>,
+    #[case] running: bool,
+    #[case] jobs: usize,
+) {
+    let scheduler = match started {
+        Some(started) => {
+            let scheduler = Scheduler::new().await.unwrap();
+            scheduler
+                .add_closure("Noop", "0 0 0 1 1 *", false, || Box::pin(async { Ok(()) }))
+                .await
+                .unwrap();
+            if started {
+                scheduler.start().await.unwrap();
+            }
+            Some(scheduler)
+        }
+        None => None,
+    };
+
+    let health = SchedulerHealth::from_scheduler(scheduler.as_ref());
+    assert_eq!(health, SchedulerHealth { running, jobs });
+
+    let metrics = health.to_metrics();
+    assert!(metrics.contains(&format!("kohaku_scheduler_running {}\n", u8::from(running))));
+    assert!(metrics.contains(&format!("kohaku_scheduler_jobs {}\n", jobs)));
+}
+
+#[rstest]
+#[case("/api/ready")]
+#[case("/metrics")]
+#[actix_web::test]
+async fn test_health_endpoints_mounted(#[case] uri: &str) {
+    let app = test::init_service(
+        App::new()
+            .configure(api::health::configure)
+            .configure(api::configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri(uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    // Readiness depends on the global scheduler, which other tests may initialize
+    assert!(matches!(
+        resp.status(),
+        StatusCode::OK | StatusCode::SERVICE_UNAVAILABLE
+    ));
+}
+
+#[actix_web::test]
+async fn test_probe() {
+    let ok = probe(|| async { Ok(3) }).await;
+    assert_eq!((ok.value, ok.error), (Some(3), None));
+    assert!(ok.latency_ms >= 0.0);
+
+    let failed = probe(|| async {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        Err::<usize, _>(KohakuError::InternalServerError("down".to_string()))
+    })
+    .await;
+    assert_eq!(failed.value, None);
+    assert!(failed.error.unwrap().contains("down"));
+    assert!(failed.latency_ms >= 20.0);
+}
+
+#[actix_web::test]
+async fn test_purge_stale_requires_token() {
+    let app = test::init_service(App::new().configure(api::configure)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/codes/purge-stale")
+        .set_json(serde_json::json!({ "older_than_days": 30 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[rstest]
+#[case::valid(36500, StatusCode::OK)]
+#[case::zero(0, StatusCode::BAD_REQUEST)]
+#[case::out_of_range(u32::MAX, StatusCode::BAD_REQUEST)]
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_purge_stale_endpoint(#[case] older_than_days: u32, #[case] status: StatusCode) {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["admin:manage".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let app = test::init_service(App::new().configure(api::configure)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/codes/purge-stale")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "older_than_days": older_than_days }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), status);
+    if status == StatusCode::OK {
+        // No code is a century old
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["purged"], serde_json::json!(0));
+    }
+}
+
+#[actix_web::test]
+async fn test_ping_requires_token() {
+    let app = test::init_service(App::new().configure(api::configure)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/admin/ping")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_ping() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["admin:manage".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let app = test::init_service(App::new().configure(api::configure)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/admin/ping")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Every subsystem is timed, even if it isn't initialized in the test environment
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["database"]["value"], serde_json::json!(true));
+    for subsystem in ["database", "scheduler", "websocket"] {
+        assert!(body[subsystem]["latency_ms"].is_f64());
+    }
+}
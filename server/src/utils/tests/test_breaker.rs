@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::utils::{
+    breaker::{CircuitBreaker, CircuitState},
+    error::KohakuError,
+};
+
+const NOW: i64 = 1_700_000_000;
+
+/// Helper: Breaker opening after 3 failures for 60 seconds, with 3 failures of `svc` recorded at [`NOW`]
+fn opened_breaker() -> CircuitBreaker {
+    let breaker = CircuitBreaker::new(3, 60);
+    for _ in 0..3 {
+        breaker.record_failure_at("svc", NOW);
+    }
+    breaker
+}
+
+// ================================= CircuitBreaker
+
+#[test]
+fn test_breaker_opens_after_threshold() {
+    let breaker = CircuitBreaker::new(3, 60);
+    for _ in 0..2 {
+        breaker.record_failure_at("svc", NOW);
+        assert_eq!(breaker.state_at("svc", NOW), CircuitState::Closed);
+        assert!(breaker.check_at("svc", NOW).is_ok());
+    }
+
+    breaker.record_failure_at("svc", NOW);
+    assert_eq!(breaker.state_at("svc", NOW), CircuitState::Open);
+    assert!(matches!(
+        breaker.check_at("svc", NOW + 59),
+        Err(KohakuError::ExternalServiceError(_))
+    ));
+    // Circuits are kept per service
+    assert_eq!(breaker.state_at("other", NOW), CircuitState::Closed);
+}
+
+#[test]
+fn test_breaker_success_resets_failures() {
+    let breaker = CircuitBreaker::new(3, 60);
+    breaker.record_failure_at("svc", NOW);
+    breaker.record_failure_at("svc", NOW);
+    breaker.record_success("svc");
+    breaker.record_failure_at("svc", NOW);
+    assert_eq!(breaker.state_at("svc", NOW), CircuitState::Closed);
+}
+
+#[test]
+fn test_breaker_recovery() {
+    let breaker = opened_breaker();
+
+    // #1 Cooldown passed: Half-open, calls pass through
+    assert_eq!(breaker.state_at("svc", NOW + 60), CircuitState::HalfOpen);
+    assert!(breaker.check_at("svc", NOW + 60).is_ok());
+
+    // #2 Failed test call: Opened for another cooldown
+    breaker.record_failure_at("svc", NOW + 60);
+    assert_eq!(breaker.state_at("svc", NOW + 61), CircuitState::Open);
+    assert!(breaker.check_at("svc", NOW + 119).is_err());
+
+    // #3 Successful test call: Closed again
+    assert!(breaker.check_at("svc", NOW + 120).is_ok());
+    breaker.record_success("svc");
+    assert_eq!(breaker.state_at("svc", NOW + 120), CircuitState::Closed);
+    breaker.record_failure_at("svc", NOW + 120);
+    assert_eq!(breaker.state_at("svc", NOW + 120), CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn test_breaker_call_fails_fast() {
+    let breaker = CircuitBreaker::new(2, 60);
+    let calls = AtomicU32::new(0);
+    let failing = || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(KohakuError::ExternalServiceError("down".to_string()))
+    };
+
+    for _ in 0..2 {
+        let val = breaker.call("svc", failing).await;
+        assert!(matches!(val, Err(KohakuError::ExternalServiceError(msg)) if msg == "down"));
+    }
+    assert_eq!(breaker.state("svc"), CircuitState::Open);
+
+    // The call isn't made while the circuit is open
+    let val = breaker.call("svc", failing).await;
+    assert!(matches!(val, Err(KohakuError::ExternalServiceError(msg)) if msg.contains("open")));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let val = breaker.call("other", || async { Ok(5) }).await;
+    assert_eq!(val.unwrap(), 5);
+}
+
+#[test]
+fn test_breaker_metrics() {
+    let breaker = opened_breaker();
+    breaker.record_failure_at("api", NOW);
+
+    let metrics = breaker.to_metrics();
+    assert!(metrics.contains("# TYPE kohaku_circuit_state gauge\n"));
+    assert!(metrics.contains("kohaku_circuit_state{service=\"api\"} 0\n"));
+    // Opened at a fixed time in the past, so the cooldown already passed
+    assert!(metrics.contains("kohaku_circuit_state{service=\"svc\"} 1\n"));
+}
@@ -1,17 +1,52 @@
 use std::{collections::HashSet, time::Duration};
 
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body_json, TestRequest},
+    App, FromRequest, ResponseError,
+};
 use chrono::Utc;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use regex::Regex;
 use rstest::rstest;
+use uuid::Uuid;
 
-use crate::utils::comm::auth::{
-    api_key::{extract_prefix, generate_key, hash_key, random_string, verify_key, CHARSET},
-    jwt::{get_jwtservice, init_jwtservice},
-    models::{Claims, TokenType},
-    token_duration,
+use crate::db::{
+    get_connection,
+    schema::{api_keys, auth_audit},
+};
+use crate::utils::{
+    comm::auth::{
+        api_key::{
+            extract_prefix, find_matching_key, generate_key, hash_key, random_string,
+            random_string_from, verify_key, CHARSET, PREFIX_CHARSET,
+        },
+        check_authorization_key, extract_client_id,
+        extractor::{AuthedClaims, KeysManage, NoScopes},
+        is_ip_allowed,
+        jwt::{get_jwtservice, init_jwtservice, JWTService},
+        limiter::{KeyRateLimiter, LoginLimiter},
+        models::{
+            create_apikey, get_auth_events, list_apikeys, purge_auth_events, purge_revoked,
+            record_auth_event, revoke_apikey, rotate_apikey, ApiKey, ApiKeyPublic, AuthEventType,
+            Claims, RevokeOwnerResponse, TokenResponse, TokenType, WhoAmIResponse,
+        },
+        parse_ip_rule, routes,
+        scopes::{
+            parse_scopes, validate_scopes, Scope, ADMIN_MANAGE, EVENTS_MANAGE, EVENTS_SUBSCRIBE,
+            KEYS_MANAGE,
+        },
+        token_duration,
+    },
+    error::KohakuError,
+    tests::setup_db,
 };
 
+/// Issuer and audience of the [`JWTService`] in a test environment
+const TEST_ISSUER: &str = "kohaku-test";
+const TEST_AUDIENCE: &str = "kohaku-test-api";
+
 // ========================================= API Keys ========================================== //
 // ================================= generate_key
 #[test]
@@ -36,6 +71,19 @@ fn test_generate_key_prefix_format() {
     assert_eq!(prefix.len(), 10);
 }
 
+#[test]
+fn test_generate_key_prefix_alphanumeric() {
+    for _ in 0..100 {
+        let (_, prefix) = generate_key();
+        let random = prefix.strip_prefix("khk_").unwrap();
+        assert!(
+            random.chars().all(|c| c.is_ascii_alphanumeric()),
+            "{}",
+            prefix
+        );
+    }
+}
+
 #[test]
 fn test_generate_key_uniqueness() {
     let keys: Vec<String> = (0..100)
@@ -86,6 +134,16 @@ fn test_random_string_empty() {
     assert_eq!(s, "");
 }
 
+#[test]
+fn test_random_string_from_charset() {
+    for i in 0..100 {
+        let s = random_string_from(PREFIX_CHARSET, i);
+        assert_eq!(s.len(), i);
+        assert!(s.bytes().all(|b| PREFIX_CHARSET.contains(&b)));
+    }
+    assert_eq!(random_string_from(b"a", 3), "aaa");
+}
+
 // ================================= hash_key
 
 #[test]
@@ -163,14 +221,53 @@ fn test_verify_key_empty() {
     let empty_hash = "";
     let hash = hash_key(&key).unwrap();
 
-    let val = verify_key(&empty_key, &hash);
+    let val = verify_key(empty_key, &hash);
     assert!(val.is_ok());
     assert!(!val.unwrap());
 
-    let val = verify_key(&key, &empty_hash);
+    let val = verify_key(&key, empty_hash);
     assert!(val.is_err());
 }
 
+// ================================= find_matching_key
+fn candidate(id: i32, hashed_key: String) -> ApiKey {
+    ApiKey {
+        id,
+        hashed_key,
+        key_prefix: "khk_aaaaaa".to_string(),
+        owner: "test-suite".to_string(),
+        scopes: vec![],
+        created_at: Utc::now().naive_utc(),
+        allowed_ips: vec![],
+        revoked_at: None,
+    }
+}
+
+#[test]
+fn test_find_matching_key_multiple_candidates() {
+    let (key, _) = generate_key();
+    let (other, _) = generate_key();
+    let candidates = vec![
+        candidate(1, hash_key(&other).unwrap()),
+        candidate(2, "corrupted-hash".to_string()),
+        candidate(3, hash_key(&key).unwrap()),
+        candidate(4, hash_key(&other).unwrap()),
+    ];
+
+    let found = find_matching_key(&key, &candidates);
+    assert_eq!(found.map(|k| k.id), Some(3));
+}
+
+#[test]
+fn test_find_matching_key_none() {
+    let (key, _) = generate_key();
+    let (other, _) = generate_key();
+    let candidates = vec![candidate(1, hash_key(&other).unwrap())];
+
+    assert!(find_matching_key(&key, &candidates).is_none());
+    assert!(find_matching_key(&key, &[]).is_none());
+}
+
 // ================================= extract_prefix
 
 #[test]
@@ -212,11 +309,11 @@ fn test_create_token_valid(
     let key = "encryption_key".to_string();
     let owner = "test-suite".to_string();
 
-    let _ = init_jwtservice(&key.as_bytes());
+    let _ = init_jwtservice(key.as_bytes());
     let service = get_jwtservice().unwrap();
     let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
 
-    let decoding_key = DecodingKey::from_secret(&key.as_bytes());
+    let decoding_key = DecodingKey::from_secret(key.as_bytes());
     let iat = Utc::now().timestamp() as usize;
     let duration = token_duration(&token_type);
     let exp = iat + duration;
@@ -225,7 +322,8 @@ fn test_create_token_valid(
     assert!(val.is_ok());
 
     // Should be decodeable
-    let validation = Validation::default();
+    let mut validation = Validation::default();
+    validation.set_audience(&[TEST_AUDIENCE]);
     let dec = decode::<Claims>(val.unwrap(), &decoding_key, &validation);
     assert!(dec.is_ok());
 
@@ -233,8 +331,10 @@ fn test_create_token_valid(
     let cl = dec.unwrap().claims;
     assert_eq!(cl.key_id, key_id);
     assert_eq!(cl.owner, owner);
-    assert_eq!(cl.scopes, scopes);
+    assert_eq!(parse_scopes(&scopes).unwrap(), cl.scopes);
     assert_eq!(cl.token_type, token_type);
+    assert_eq!(cl.iss, TEST_ISSUER);
+    assert_eq!(cl.aud, TEST_AUDIENCE);
 
     assert!(cl.iat - iat < 2);
     assert!(cl.exp - exp < 2);
@@ -264,7 +364,7 @@ fn test_create_token_invalid(
     let key = "encryption_key".to_string();
     let owner = "test-suite".to_string();
 
-    let _ = init_jwtservice(&key.as_bytes());
+    let _ = init_jwtservice(key.as_bytes());
     let service = get_jwtservice().unwrap();
     let scopes = scopes.iter().map(|s| s.to_string()).collect();
 
@@ -273,6 +373,42 @@ fn test_create_token_invalid(
     assert!(val.is_err());
 }
 
+// ================================= Claims lifetime
+fn claims_with(iat: usize, exp: usize) -> Claims {
+    Claims {
+        owner: "test-suite".to_string(),
+        key_id: 1,
+        scopes: vec![],
+        token_type: TokenType::Access,
+        exp,
+        iat,
+        iss: TEST_ISSUER.to_string(),
+        aud: TEST_AUDIENCE.to_string(),
+        nbf: None,
+        cid: None,
+    }
+}
+
+#[test]
+fn test_claims_lifetime_valid() {
+    let now = Utc::now().timestamp() as usize;
+    let claims = claims_with(now - 100, now + 200);
+
+    // Allow a second of drift between creation and check
+    assert!((199..=200).contains(&claims.remaining_secs()));
+    assert!((100..=101).contains(&claims.issued_ago()));
+    assert!(!claims.is_expired());
+}
+
+#[test]
+fn test_claims_lifetime_expired() {
+    let claims = claims_with(1_000, 1_900);
+
+    assert!(claims.remaining_secs() < 0);
+    assert!(claims.issued_ago() > 0);
+    assert!(claims.is_expired());
+}
+
 // ================================= JWTService::validate_token
 #[rstest]
 #[case(0, vec!["events:subscribe"], TokenType::Access)]
@@ -289,15 +425,19 @@ fn test_validate_token_valid(
     let claims = Claims {
         owner: "test-suite".to_string(),
         key_id,
-        scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        scopes: scopes.iter().map(|s| s.parse().unwrap()).collect(),
         token_type,
         exp,
         iat,
+        iss: TEST_ISSUER.to_string(),
+        aud: TEST_AUDIENCE.to_string(),
+        nbf: None,
+        cid: None,
     };
 
     let key = "encryption_key".to_string();
-    let encoding_key = EncodingKey::from_secret(&key.as_bytes());
-    let _ = init_jwtservice(&key.as_bytes());
+    let encoding_key = EncodingKey::from_secret(key.as_bytes());
+    let _ = init_jwtservice(key.as_bytes());
     let service = get_jwtservice().unwrap();
     let token = encode(&Header::default(), &claims, &encoding_key).unwrap();
 
@@ -324,16 +464,20 @@ fn test_validate_token_invalid(
     let claims = Claims {
         owner: "test-suite".to_string(),
         key_id,
-        scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        scopes: scopes.iter().map(|s| s.parse().unwrap()).collect(),
         token_type,
         exp,
         iat,
+        iss: TEST_ISSUER.to_string(),
+        aud: TEST_AUDIENCE.to_string(),
+        nbf: None,
+        cid: None,
     };
 
     let key1 = "encryption_key".to_string();
     let key2 = "another_encryption_key".to_string();
-    let encoding_key = EncodingKey::from_secret(&key2.as_bytes());
-    let _ = init_jwtservice(&key1.as_bytes());
+    let encoding_key = EncodingKey::from_secret(key2.as_bytes());
+    let _ = init_jwtservice(key1.as_bytes());
     let service = get_jwtservice().unwrap();
     let token = encode(&Header::default(), &claims, &encoding_key).unwrap();
 
@@ -341,13 +485,358 @@ fn test_validate_token_invalid(
     let val = service.validate_token(&token);
     assert!(val.is_err());
 }
+// ================================= JWTService::create_token_not_before
+#[test]
+fn test_token_not_before() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let now = Utc::now().timestamp() as usize;
+
+    // Before nbf (beyond the default leeway of 60 seconds)
+    let token = service
+        .create_token_not_before(
+            "test-suite".to_string(),
+            1,
+            vec![],
+            TokenType::Access,
+            Some(now + 3600),
+        )
+        .unwrap();
+    match service.validate_token(&token) {
+        Err(KohakuError::ValidationError(msg)) => assert!(msg.contains("nbf")),
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+
+    // After nbf
+    let token = service
+        .create_token_not_before(
+            "test-suite".to_string(),
+            1,
+            vec![],
+            TokenType::Access,
+            Some(now - 10),
+        )
+        .unwrap();
+    let claims = service.validate_token(&token).unwrap();
+    assert_eq!(claims.nbf, Some(now - 10));
+}
+
+#[test]
+fn test_token_not_before_shifts_expiry() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let nbf = Utc::now().timestamp() as usize + 3600;
+
+    let token = service
+        .create_token_not_before(
+            "test-suite".to_string(),
+            1,
+            vec![],
+            TokenType::Access,
+            Some(nbf),
+        )
+        .unwrap();
+    let mut validation = Validation::default();
+    validation.set_audience(&[TEST_AUDIENCE]);
+    let claims = decode::<Claims>(
+        &token,
+        &DecodingKey::from_secret(key.as_bytes()),
+        &validation,
+    )
+    .unwrap()
+    .claims;
+    // Lifetime starts at nbf, not at issuing
+    assert_eq!(claims.exp, nbf + token_duration(&TokenType::Access));
+}
+
+// ================================= Issuer & Audience
+#[rstest]
+#[case("other-instance", TEST_AUDIENCE, "iss")]
+#[case(TEST_ISSUER, "other-api", "aud")]
+fn test_validate_token_foreign_identity(
+    #[case] issuer: &str,
+    #[case] audience: &str,
+    #[case] claim: &str,
+) {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let foreign = JWTService::new(key.as_bytes(), issuer.to_string(), audience.to_string());
+    let token = foreign
+        .create_token("test-suite".to_string(), 1, vec![], TokenType::Access)
+        .unwrap();
+
+    // Same secret, but minted for another instance / audience
+    match get_jwtservice().unwrap().validate_token(&token) {
+        Err(KohakuError::ValidationError(msg)) => assert!(msg.contains(claim)),
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+    assert!(foreign.validate_token(&token).is_ok());
+}
+
+#[test]
+fn test_validate_token_missing_identity() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let now = Utc::now().timestamp() as usize;
+    // Tokens without `iss` / `aud` (e.g. of older versions) are rejected
+    let claims = serde_json::json!({
+        "owner": "test-suite",
+        "key_id": 1,
+        "scopes": [],
+        "token_type": "access",
+        "exp": now + 600,
+        "iat": now,
+    });
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(key.as_bytes()),
+    )
+    .unwrap();
+
+    let val = get_jwtservice().unwrap().validate_token(&token);
+    assert!(matches!(val, Err(KohakuError::ValidationError(_))));
+}
+
+// ================================= JWTService::create_resume_token
+
+#[test]
+fn test_resume_token_roundtrip() {
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let service = get_jwtservice().unwrap();
+    let session_id = Uuid::new_v4();
+
+    let token = service.create_resume_token(7, session_id, 600).unwrap();
+    let claims = service.validate_resume_token(&token).unwrap();
+    assert_eq!(claims.key_id, 7);
+    assert_eq!(claims.sid, session_id);
+    assert_eq!(claims.exp, claims.iat + 600);
+    assert_eq!(claims.aud, format!("{}:ws-resume", TEST_AUDIENCE));
+}
+
+#[test]
+fn test_resume_token_not_interchangeable() {
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let service = get_jwtservice().unwrap();
+
+    // A resume token is no access token ...
+    let resume = service.create_resume_token(7, Uuid::new_v4(), 600).unwrap();
+    assert!(matches!(
+        service.validate_token(&resume),
+        Err(KohakuError::ValidationError(_))
+    ));
+
+    // ... and an access token no resume token
+    let access = service
+        .create_token("test-suite".to_string(), 7, vec![], TokenType::Access)
+        .unwrap();
+    assert!(matches!(
+        service.validate_resume_token(&access),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[test]
+fn test_resume_token_expired() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let now = Utc::now().timestamp() as usize;
+    let claims = serde_json::json!({
+        "key_id": 7,
+        "sid": Uuid::new_v4(),
+        "exp": now - 600,
+        "iat": now - 1200,
+        "iss": TEST_ISSUER,
+        "aud": format!("{}:ws-resume", TEST_AUDIENCE),
+    });
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(key.as_bytes()),
+    )
+    .unwrap();
+
+    let val = get_jwtservice().unwrap().validate_resume_token(&token);
+    assert!(matches!(val, Err(KohakuError::ValidationError(_))));
+}
+
+// ================================= JWTService::rotate_key
+
+/// Helper: Service with fixed test identity and a token of it signed with the initial key
+fn rotation_service() -> (JWTService, String) {
+    let service = JWTService::new(
+        "initial_encryption_key_of_32_bytes".as_bytes(),
+        TEST_ISSUER.to_string(),
+        TEST_AUDIENCE.to_string(),
+    );
+    let token = service
+        .create_token("test-suite".to_string(), 7, vec![], TokenType::Access)
+        .unwrap();
+    (service, token)
+}
+
+#[test]
+fn test_rotate_key_grace_window() {
+    let (service, old_token) = rotation_service();
+    let old_resume = service.create_resume_token(7, Uuid::new_v4(), 600).unwrap();
+
+    let until = service
+        .rotate_key("rotated_encryption_key_of_32_bytes".as_bytes(), 60)
+        .unwrap();
+    assert!(until > Utc::now().timestamp());
+
+    // Tokens of both keys are valid during the grace window, new ones are signed with the new key
+    let new_token = service
+        .create_token("test-suite".to_string(), 7, vec![], TokenType::Access)
+        .unwrap();
+    assert_ne!(new_token, old_token);
+    assert_eq!(service.validate_token(&old_token).unwrap().key_id, 7);
+    assert_eq!(service.validate_token(&new_token).unwrap().key_id, 7);
+    assert!(service.validate_resume_token(&old_resume).is_ok());
+    assert!(decode::<Claims>(
+        &new_token,
+        &DecodingKey::from_secret("rotated_encryption_key_of_32_bytes".as_bytes()),
+        &{
+            let mut validation = Validation::default();
+            validation.set_audience(&[TEST_AUDIENCE]);
+            validation
+        },
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_rotate_key_without_grace_window() {
+    let (service, old_token) = rotation_service();
+
+    service
+        .rotate_key("rotated_encryption_key_of_32_bytes".as_bytes(), 0)
+        .unwrap();
+    assert!(matches!(
+        service.validate_token(&old_token),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[test]
+fn test_rotate_key_keeps_only_last_key() {
+    let (service, old_token) = rotation_service();
+
+    service
+        .rotate_key("rotated_encryption_key_of_32_bytes".as_bytes(), 60)
+        .unwrap();
+    service
+        .rotate_key("another_encryption_key_of_32_bytes".as_bytes(), 60)
+        .unwrap();
+    assert!(service.validate_token(&old_token).is_err());
+}
+
+#[rstest]
+#[case::short_key("too_short", 60)]
+#[case::negative_grace("rotated_encryption_key_of_32_bytes", -1)]
+#[case::grace_beyond_refresh("rotated_encryption_key_of_32_bytes", 30 * 24 * 60 * 60 + 1)]
+#[case::overflowing_grace("rotated_encryption_key_of_32_bytes", i64::MAX)]
+fn test_rotate_key_invalid(#[case] key: &str, #[case] grace_secs: i64) {
+    let (service, old_token) = rotation_service();
+
+    assert!(matches!(
+        service.rotate_key(key.as_bytes(), grace_secs),
+        Err(KohakuError::ValidationError(_))
+    ));
+    // The key in use is untouched
+    assert!(service.validate_token(&old_token).is_ok());
+}
+
+#[actix_web::test]
+async fn test_rotate_signing_key_endpoint() {
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let service = get_jwtservice().unwrap();
+    let bootstrap = service.create_bootstrap_token().unwrap().access_token;
+    let admin = service
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["admin:manage".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let app = init_service(App::new().route(
+        "/rotate",
+        actix_web::web::post().to(routes::rotate_signing_key),
+    ))
+    .await;
+    let rotate = |token: Option<&str>| {
+        let req = TestRequest::post()
+            .uri("/rotate")
+            .set_json(serde_json::json!({ "key": "too_short" }));
+        match token {
+            Some(token) => req.insert_header(("Authorization", format!("Bearer {}", token))),
+            None => req,
+        }
+        .to_request()
+    };
+
+    // #1 Requires a token
+    assert_eq!(
+        call_service(&app, rotate(None)).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // #2 Ordinary keys can't rotate, even with `admin:manage`
+    assert_eq!(
+        call_service(&app, rotate(Some(&admin))).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    // #3 Invalid keys are rejected (valid rotations would affect the shared service of other tests)
+    assert_eq!(
+        call_service(&app, rotate(Some(&bootstrap))).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+// ================================= JWTService::create_bootstrap_token
+
+#[test]
+fn test_bootstrap_response_is_token_response() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+
+    let bootstrap = serde_json::to_value(service.create_bootstrap_token().unwrap()).unwrap();
+    let general = serde_json::to_value(
+        service
+            .create_tokens(1, "test-suite", vec!["events:subscribe".to_string()])
+            .unwrap(),
+    )
+    .unwrap();
+
+    // Same JSON structure as a general login
+    let keys = |v: &serde_json::Value| {
+        v.as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<HashSet<String>>()
+    };
+    assert_eq!(keys(&bootstrap), keys(&general));
+
+    let response: TokenResponse = serde_json::from_value(bootstrap).unwrap();
+    assert!(!response.access_token.is_empty());
+    assert_eq!(response.refresh_token, None);
+    assert_eq!(response.token_type, "Bearer");
+    assert_eq!(response.expires_in, 600);
+}
+
 // ================================= JWTService::blacklist_key
 #[tokio::test]
 async fn test_blacklist_key() {
     let key_id = 12;
 
     let key = "encryption_key".to_string();
-    let _ = init_jwtservice(&key.as_bytes());
+    let _ = init_jwtservice(key.as_bytes());
     let service = get_jwtservice().unwrap();
 
     assert!(service.read_blacklist().await.is_empty());
@@ -364,6 +853,91 @@ async fn test_blacklist_key() {
     assert_eq!(blklist.len(), 1);
 }
 
+#[tokio::test]
+async fn test_blacklist_bounded() {
+    let service = JWTService::new(
+        "encryption_key".as_bytes(),
+        TEST_ISSUER.to_string(),
+        TEST_AUDIENCE.to_string(),
+    )
+    .with_blacklist_max(3);
+
+    // Later keys are blacklisted longer
+    for key_id in 1..=5 {
+        service
+            .blacklist_key(key_id, Some(60 * key_id as i64))
+            .await
+            .unwrap();
+        assert!(service.read_blacklist().await.len() <= 3);
+    }
+
+    // The keys expiring soonest were evicted
+    let mut remaining = service
+        .read_blacklist()
+        .await
+        .into_keys()
+        .collect::<Vec<i32>>();
+    remaining.sort();
+    assert_eq!(remaining, vec![3, 4, 5]);
+}
+
+#[tokio::test]
+async fn test_blacklist_bounded_drops_expired_first() {
+    let service = JWTService::new(
+        "encryption_key".as_bytes(),
+        TEST_ISSUER.to_string(),
+        TEST_AUDIENCE.to_string(),
+    )
+    .with_blacklist_max(2);
+
+    service.blacklist_key(1, Some(-1)).await.unwrap();
+    service.blacklist_key(2, Some(60)).await.unwrap();
+    service.blacklist_key(3, Some(30)).await.unwrap();
+
+    let blklist = service.read_blacklist().await;
+    assert_eq!(blklist.len(), 2);
+    assert!(!blklist.contains_key(&1));
+}
+
+// ================================= JWTService::record_refresh
+
+#[tokio::test]
+async fn test_refresh_limit() {
+    let service = JWTService::new(
+        "encryption_key".as_bytes(),
+        TEST_ISSUER.to_string(),
+        TEST_AUDIENCE.to_string(),
+    )
+    .with_refresh_max(3);
+
+    // Refreshing up to the limit
+    for expected in 1..=3 {
+        assert_eq!(service.record_refresh(7).await.unwrap(), expected);
+    }
+    // Beyond the limit => Rejected, other keys are not affected
+    assert!(matches!(
+        service.record_refresh(7).await,
+        Err(KohakuError::Unauthorized(_))
+    ));
+    assert_eq!(service.record_refresh(8).await.unwrap(), 1);
+
+    // Login resets the counter
+    service.reset_refreshes(7).await;
+    assert_eq!(service.record_refresh(7).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_refresh_unlimited() {
+    let service = JWTService::new(
+        "encryption_key".as_bytes(),
+        TEST_ISSUER.to_string(),
+        TEST_AUDIENCE.to_string(),
+    );
+    for _ in 0..100 {
+        assert!(service.record_refresh(7).await.is_ok());
+    }
+}
+
 // ================================= JWTService::is_blacklisted
 
 #[tokio::test]
@@ -372,7 +946,7 @@ async fn test_is_blacklisted() {
     let key_id_no = 455;
 
     let key = "encryption_key".to_string();
-    let _ = init_jwtservice(&key.as_bytes());
+    let _ = init_jwtservice(key.as_bytes());
     let service = get_jwtservice().unwrap();
 
     // Not prior blacklisted
@@ -392,3 +966,1045 @@ async fn test_is_blacklisted() {
     assert!(!service.is_blacklisted(key_id).await);
     assert!(!service.is_blacklisted(key_id_no).await);
 }
+
+// ====================================== Login Limiter ======================================== //
+
+#[tokio::test]
+async fn test_login_limiter_lockout_after_failures() {
+    let limiter = LoginLimiter::new(3, 60, 60);
+    let id = "prefix:khk_abcdef";
+
+    for _ in 0..2 {
+        limiter.record_failure(id).await;
+        assert!(limiter.check(id).await.is_ok());
+    }
+
+    // Third failure reaches the limit
+    limiter.record_failure(id).await;
+    let val = limiter.check(id).await;
+    assert!(matches!(val, Err(KohakuError::RateLimitExceeded(_))));
+    assert_eq!(
+        val.err().unwrap().status_code(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn test_login_limiter_independent_ids() {
+    let limiter = LoginLimiter::new(2, 60, 60);
+
+    limiter.record_failure("ip:10.0.0.1").await;
+    limiter.record_failure("ip:10.0.0.1").await;
+
+    assert!(limiter.check("ip:10.0.0.1").await.is_err());
+    assert!(limiter.check("ip:10.0.0.2").await.is_ok());
+    assert!(limiter.check("prefix:khk_abcdef").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_login_limiter_reset_on_success() {
+    let limiter = LoginLimiter::new(3, 60, 60);
+    let id = "prefix:khk_abcdef";
+
+    limiter.record_failure(id).await;
+    limiter.record_failure(id).await;
+    limiter.reset(id).await;
+
+    // Counter starts from zero again
+    limiter.record_failure(id).await;
+    limiter.record_failure(id).await;
+    assert!(limiter.check(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_login_limiter_lockout_expires() {
+    let limiter = LoginLimiter::new(1, 60, 1);
+    let id = "ip:10.0.0.1";
+
+    limiter.record_failure(id).await;
+    assert!(limiter.check(id).await.is_err());
+
+    // Wait for lockout to expire
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(limiter.check(id).await.is_ok());
+}
+
+#[test]
+fn test_key_rate_limiter() {
+    let limiter = KeyRateLimiter::new(2, 60);
+
+    assert!(limiter.check(1).is_ok());
+    assert!(limiter.check(1).is_ok());
+    let val = limiter.check(1);
+    assert!(matches!(val, Err(KohakuError::RateLimitExceeded(_))));
+    assert_eq!(
+        val.err().unwrap().status_code(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // Other keys have their own budget
+    assert!(limiter.check(2).is_ok());
+    assert!(limiter.check(-1).is_ok());
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_create_rate_limited() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    // Only test calling `/manage/create`, so the budget of the bootstrap key isn't shared
+    let token = get_jwtservice()
+        .unwrap()
+        .create_bootstrap_token()
+        .unwrap()
+        .access_token;
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let create = || {
+        TestRequest::post()
+            .uri("/manage/create")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(serde_json::json!({
+                "owner": format!("owner-{}", random_string(8)),
+                "scopes": [],
+            }))
+            .to_request()
+    };
+
+    // The test limit allows 3 creations per minute
+    for _ in 0..3 {
+        let resp = call_service(&app, create()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let resp = call_service(&app, create()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+// ========================================== Audit ============================================ //
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_record_auth_event() {
+    setup_db();
+    let owner = format!("audit-{}", random_string(8));
+
+    let val = record_auth_event(
+        AuthEventType::Login,
+        Some(42),
+        Some(owner.clone()),
+        false,
+        Some("127.0.0.1".to_string()),
+    )
+    .await;
+    assert!(val.is_ok());
+    let stored = val.unwrap();
+    assert_eq!(stored.event_type, "login");
+    assert_eq!(stored.key_id, Some(42));
+    assert!(!stored.success);
+
+    let events = get_auth_events(100).await.unwrap();
+    let found = events.iter().find(|e| e.owner.as_ref() == Some(&owner));
+    assert!(found.is_some());
+    assert_eq!(found.unwrap().id, stored.id);
+    assert_eq!(found.unwrap().ip.as_deref(), Some("127.0.0.1"));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_purge_auth_events() {
+    setup_db();
+    let owner = format!("audit-{}", random_string(8));
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let event = record_auth_event(AuthEventType::Login, None, Some(owner.clone()), true, None)
+            .await
+            .unwrap();
+        ids.push(event.id);
+    }
+    // Backdate the first event
+    let mut conn = get_connection().await.unwrap();
+    diesel::update(auth_audit::table.find(ids[0]))
+        .set(auth_audit::created_at.eq(Utc::now().naive_utc() - chrono::Duration::days(400)))
+        .execute(&mut conn)
+        .unwrap();
+
+    let cutoff = Utc::now().naive_utc() - chrono::Duration::days(365);
+    assert!(purge_auth_events(cutoff).await.unwrap() >= 1);
+
+    // Only the old event is purged
+    let remaining: Vec<i32> = auth_audit::table
+        .filter(auth_audit::owner.eq(&owner))
+        .select(auth_audit::id)
+        .load(&mut conn)
+        .unwrap();
+    assert_eq!(remaining, vec![ids[1]]);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_get_auth_events_newest_first() {
+    setup_db();
+    let first = record_auth_event(AuthEventType::Create, None, None, true, None)
+        .await
+        .unwrap();
+    let second = record_auth_event(AuthEventType::Revoke, None, None, true, None)
+        .await
+        .unwrap();
+
+    let events = get_auth_events(1000).await.unwrap();
+    let pos_first = events.iter().position(|e| e.id == first.id).unwrap();
+    let pos_second = events.iter().position(|e| e.id == second.id).unwrap();
+    assert!(pos_second < pos_first);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_rejected_tokens_audited() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    // Unique source of this test's requests
+    let nanos = Utc::now().timestamp_subsec_nanos();
+    let peer: std::net::SocketAddr = format!("[fd00::{:x}:{:x}]:4000", nanos >> 16, nanos & 0xffff)
+        .parse()
+        .unwrap();
+    let access = get_jwtservice()
+        .unwrap()
+        .create_token("test-suite".to_string(), 1, vec![], TokenType::Access)
+        .unwrap();
+
+    let app = init_service(App::new().configure(routes::configure)).await;
+    // Forged refresh token
+    let req = TestRequest::post()
+        .uri("/manage/refresh")
+        .peer_addr(peer)
+        .insert_header(("Authorization", "Bearer not.a.token"))
+        .to_request();
+    assert!(call_service(&app, req).await.status().is_client_error());
+    // Missing `keys:manage`
+    let req = TestRequest::post()
+        .uri("/manage/revoke")
+        .peer_addr(peer)
+        .insert_header(("Authorization", format!("Bearer {}", access)))
+        .set_json(serde_json::json!({ "api_key": "khk_aaaaaa" }))
+        .to_request();
+    assert_eq!(
+        call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let ip = peer.ip().to_string();
+    let mut audited: Vec<(String, bool)> = get_auth_events(1000)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.ip.as_deref() == Some(ip.as_str()))
+        .map(|e| (e.event_type, e.success))
+        .collect();
+    audited.sort();
+    assert_eq!(
+        audited,
+        vec![
+            ("refresh".to_string(), false),
+            ("revoke".to_string(), false)
+        ]
+    );
+}
+
+// ======================================== Own Keys =========================================== //
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_list_apikeys_filters_owner() {
+    setup_db();
+    let owner = format!("owner-{}", random_string(8));
+    let other = format!("owner-{}", random_string(8));
+    let first = create_apikey(
+        random_string(32),
+        "khk_aaaaaa".to_string(),
+        owner.clone(),
+        vec![EVENTS_SUBSCRIBE],
+        vec![],
+    )
+    .await
+    .unwrap();
+    let second = create_apikey(
+        random_string(32),
+        "khk_bbbbbb".to_string(),
+        owner.clone(),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+    create_apikey(
+        random_string(32),
+        "khk_cccccc".to_string(),
+        other,
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let keys = list_apikeys(&owner).await.unwrap();
+    let ids: Vec<i32> = keys.iter().map(|k| k.id).collect();
+    assert_eq!(ids, vec![first.id, second.id]);
+    assert!(list_apikeys("nobody-owns-this").await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_my_keys_endpoint() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let owner = format!("owner-{}", random_string(8));
+    let own = create_apikey(
+        random_string(32),
+        "khk_dddddd".to_string(),
+        owner.clone(),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+    create_apikey(
+        random_string(32),
+        "khk_eeeeee".to_string(),
+        format!("owner-{}", random_string(8)),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(owner.clone(), own.id, vec![], TokenType::Access)
+        .unwrap();
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::get()
+        .uri("/keys/mine")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = read_body_json(resp).await;
+    let keys = body.as_array().unwrap();
+    assert_eq!(keys.len(), 1);
+    assert!(keys[0].get("hashed_key").is_none());
+    let info: ApiKeyPublic = serde_json::from_value(keys[0].clone()).unwrap();
+    assert_eq!(info.id, own.id);
+    assert_eq!(info.owner, owner);
+}
+
+#[test]
+fn test_api_key_public_hides_hash() {
+    let key = ApiKey {
+        id: 3,
+        hashed_key: "$argon2id$secret-hash".to_string(),
+        key_prefix: "khk_abcdef".to_string(),
+        owner: "test-suite".to_string(),
+        scopes: vec!["events:subscribe".to_string()],
+        created_at: Utc::now().naive_utc(),
+        allowed_ips: vec![],
+        revoked_at: None,
+    };
+
+    let json = serde_json::to_value(ApiKeyPublic::from(key)).unwrap();
+    assert!(json.get("hashed_key").is_none());
+    assert!(!json.to_string().contains("secret-hash"));
+    assert_eq!(json["key_prefix"], "khk_abcdef");
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_revoke_owner_endpoint() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let owner = format!("owner-{}", random_string(8));
+    let other = format!("owner-{}", random_string(8));
+    let mut revoked_ids = Vec::new();
+    for prefix in ["khk_ffffff", "khk_gggggg", "khk_hhhhhh"] {
+        let created = create_apikey(
+            random_string(32),
+            prefix.to_string(),
+            owner.clone(),
+            vec![],
+            vec![],
+        )
+        .await
+        .unwrap();
+        revoked_ids.push(created.id);
+    }
+    let kept = create_apikey(
+        random_string(32),
+        "khk_iiiiii".to_string(),
+        other.clone(),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let token = service.create_bootstrap_token().unwrap().access_token;
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::post()
+        .uri("/manage/revoke-owner")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "owner": owner }))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: RevokeOwnerResponse = read_body_json(resp).await;
+    assert_eq!(body.revoked, 3);
+    assert!(list_apikeys(&owner).await.unwrap().is_empty());
+    for key_id in revoked_ids {
+        assert!(service.is_blacklisted(key_id).await);
+    }
+    // Keys of other owners are untouched
+    let remaining: Vec<i32> = list_apikeys(&other)
+        .await
+        .unwrap()
+        .iter()
+        .map(|k| k.id)
+        .collect();
+    assert_eq!(remaining, vec![kept.id]);
+    assert!(!service.is_blacklisted(kept.id).await);
+}
+
+/// Helper: Loads an API key by id, including revoked ones
+async fn load_apikey(key_id: i32) -> Option<ApiKey> {
+    let mut conn = get_connection().await.unwrap();
+    api_keys::table
+        .find(key_id)
+        .first(&mut conn)
+        .optional()
+        .unwrap()
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_revoke_soft_deletes_key() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let (api_key, prefix) = generate_key();
+    let created = create_apikey(
+        hash_key(&api_key).unwrap(),
+        prefix,
+        format!("owner-{}", random_string(8)),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+    assert!(check_authorization_key(&api_key).await.is_ok());
+
+    let token = service.create_bootstrap_token().unwrap().access_token;
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::post()
+        .uri("/manage/revoke")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "api_key": api_key }))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Login lookup rejects the key, but the entry is kept for audits
+    let val = check_authorization_key(&api_key).await;
+    assert!(matches!(val, Err(KohakuError::Unauthorized(_))));
+    assert!(service.is_blacklisted(created.id).await);
+    let stored = load_apikey(created.id).await.unwrap();
+    assert!(stored.revoked_at.is_some());
+
+    // Revoking again doesn't find the key anymore
+    let req = TestRequest::post()
+        .uri("/manage/revoke")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "api_key": api_key }))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_purge_revoked() {
+    setup_db();
+    let owner = format!("owner-{}", random_string(8));
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let created = create_apikey(
+            random_string(32),
+            "khk_kkkkkk".to_string(),
+            owner.clone(),
+            vec![],
+            vec![],
+        )
+        .await
+        .unwrap();
+        ids.push(created.id);
+    }
+    assert!(revoke_apikey(ids[0]).await.unwrap());
+    assert!(!revoke_apikey(ids[0]).await.unwrap());
+
+    // Revoked after the cutoff => kept
+    let cutoff = Utc::now().naive_utc() - chrono::Duration::days(1);
+    purge_revoked(cutoff).await.unwrap();
+    assert!(load_apikey(ids[0]).await.is_some());
+
+    let cutoff = Utc::now().naive_utc() + chrono::Duration::seconds(1);
+    assert!(purge_revoked(cutoff).await.unwrap() >= 1);
+    assert!(load_apikey(ids[0]).await.is_none());
+    // Active keys are never purged
+    assert!(load_apikey(ids[1]).await.is_some());
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_rotate_endpoint() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let owner = format!("owner-{}", random_string(8));
+    let (old_key, old_prefix) = generate_key();
+    let old = create_apikey(
+        hash_key(&old_key).unwrap(),
+        old_prefix.clone(),
+        owner.clone(),
+        vec![EVENTS_SUBSCRIBE],
+        vec!["10.0.0.0/24".to_string()],
+    )
+    .await
+    .unwrap();
+
+    let token = service.create_bootstrap_token().unwrap().access_token;
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::post()
+        .uri("/manage/rotate")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "key_prefix": old_prefix }))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = read_body_json(resp).await;
+    let new_key = body["api_key"].as_str().unwrap();
+    assert_eq!(body["scopes"], serde_json::json!(["events:subscribe"]));
+
+    // Old key is rejected, new key works and inherits owner and allowlist
+    let val = check_authorization_key(&old_key).await;
+    assert!(matches!(val, Err(KohakuError::Unauthorized(_))));
+    assert!(service.is_blacklisted(old.id).await);
+    let new = check_authorization_key(new_key).await.unwrap();
+    assert_eq!(new.owner, owner);
+    assert_eq!(new.allowed_ips, vec!["10.0.0.0/24"]);
+    assert!(!service.is_blacklisted(new.id).await);
+
+    // Already rotated
+    let req = TestRequest::post()
+        .uri("/manage/rotate")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "key_prefix": old_prefix }))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_rotate_apikey_missing() {
+    setup_db();
+    let val = rotate_apikey(-1, random_string(32), random_string(10)).await;
+    assert!(matches!(val, Err(KohakuError::NotFound(_))));
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_rotate_apikey_rollback() {
+    setup_db();
+    let (old_key, old_prefix) = generate_key();
+    let old = create_apikey(
+        hash_key(&old_key).unwrap(),
+        old_prefix,
+        format!("owner-{}", random_string(8)),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    // Prefix exceeds the column length => Creating the new key fails
+    let val = rotate_apikey(old.id, random_string(32), random_string(32)).await;
+    assert!(matches!(val, Err(KohakuError::DatabaseError(_))));
+    assert!(load_apikey(old.id).await.unwrap().revoked_at.is_none());
+    assert!(check_authorization_key(&old_key).await.is_ok());
+}
+
+#[actix_web::test]
+async fn test_revoke_owner_requires_bootstrap() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["events:subscribe".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::post()
+        .uri("/manage/revoke-owner")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "owner": "anyone" }))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_my_keys_requires_token() {
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::get().uri("/keys/mine").to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_whoami() {
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_bound_token(
+            "test-suite".to_string(),
+            4,
+            vec!["events:subscribe".to_string()],
+            TokenType::Access,
+            Some("device-1".to_string()),
+        )
+        .unwrap();
+    let app = init_service(App::new().configure(routes::configure)).await;
+
+    let req = TestRequest::get()
+        .uri("/whoami")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = read_body_json(resp).await;
+    let whoami: WhoAmIResponse = serde_json::from_value(body.clone()).unwrap();
+    assert_eq!(whoami.owner, "test-suite");
+    assert_eq!(whoami.scopes, vec!["events:subscribe"]);
+    assert_eq!(whoami.token_type, TokenType::Access);
+    assert!(whoami.exp > Utc::now().timestamp() as usize);
+    // Only the identity is exposed
+    assert_eq!(body.as_object().unwrap().len(), 4);
+}
+
+#[rstest]
+#[case::missing(None)]
+#[case::invalid(Some("Bearer not-a-token"))]
+#[actix_web::test]
+async fn test_whoami_unauthorized(#[case] authorization: Option<&str>) {
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let app = init_service(App::new().configure(routes::configure)).await;
+
+    let mut req = TestRequest::get().uri("/whoami");
+    if let Some(authorization) = authorization {
+        req = req.insert_header(("Authorization", authorization));
+    }
+    let resp = call_service(&app, req.to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ====================================== Client binding ======================================= //
+
+#[rstest]
+#[case(None, None)]
+#[case(Some("   "), None)]
+#[case(Some(" device-1 "), Some("device-1"))]
+fn test_extract_client_id(#[case] header: Option<&str>, #[case] expected: Option<&str>) {
+    let mut req = TestRequest::default();
+    if let Some(header) = header {
+        req = req.insert_header(("X-Client-Id", header));
+    }
+    let val = extract_client_id(&req.to_http_request()).unwrap();
+    assert_eq!(val.as_deref(), expected);
+}
+
+#[test]
+fn test_extract_client_id_too_long() {
+    let req = TestRequest::default()
+        .insert_header(("X-Client-Id", "a".repeat(129)))
+        .to_http_request();
+    let val = extract_client_id(&req);
+    assert!(matches!(val, Err(KohakuError::ValidationError(_))));
+}
+
+/// Helper: Issues the tokens of a login with a fresh key, optionally bound to `client_id`, and returns the key id and tokens
+async fn login_bound(client_id: Option<&str>) -> (i32, TokenResponse) {
+    let created = create_apikey(
+        random_string(32),
+        "khk_jjjjjj".to_string(),
+        format!("owner-{}", random_string(8)),
+        vec![EVENTS_SUBSCRIBE],
+        vec![],
+    )
+    .await
+    .unwrap();
+    let tokens = get_jwtservice()
+        .unwrap()
+        .create_bound_tokens(
+            created.id,
+            &created.owner,
+            created.scopes,
+            client_id.map(str::to_string),
+        )
+        .unwrap();
+    (created.id, tokens)
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_refresh_bound_same_client() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let (key_id, tokens) = login_bound(Some("device-1")).await;
+    let refresh_token = tokens.refresh_token.unwrap();
+    let claims = service.validate_token(&refresh_token).unwrap();
+    assert_eq!(claims.cid.as_deref(), Some("device-1"));
+
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::post()
+        .uri("/manage/refresh")
+        .insert_header(("Authorization", format!("Bearer {}", refresh_token)))
+        .insert_header(("X-Client-Id", "device-1"))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // The new access token stays bound to the client
+    let body: TokenResponse = read_body_json(resp).await;
+    let claims = service.validate_token(&body.access_token).unwrap();
+    assert_eq!(claims.cid.as_deref(), Some("device-1"));
+    assert!(!service.is_blacklisted(key_id).await);
+}
+
+#[rstest]
+#[case(Some("device-2"))]
+#[case(None)]
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_refresh_bound_other_client(#[case] client_id: Option<&str>) {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let (key_id, tokens) = login_bound(Some("device-1")).await;
+
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let mut req = TestRequest::post().uri("/manage/refresh").insert_header((
+        "Authorization",
+        format!("Bearer {}", tokens.refresh_token.unwrap()),
+    ));
+    if let Some(client_id) = client_id {
+        req = req.insert_header(("X-Client-Id", client_id));
+    }
+    let resp = call_service(&app, req.to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    // The key gets flagged, so the stolen token can't be used anymore
+    assert!(service.is_blacklisted(key_id).await);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_refresh_unbound_any_client() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let (_, tokens) = login_bound(None).await;
+    let refresh_token = tokens.refresh_token.unwrap();
+    assert_eq!(
+        get_jwtservice()
+            .unwrap()
+            .validate_token(&refresh_token)
+            .unwrap()
+            .cid,
+        None
+    );
+
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::post()
+        .uri("/manage/refresh")
+        .insert_header(("Authorization", format!("Bearer {}", refresh_token)))
+        .insert_header(("X-Client-Id", "device-2"))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_refresh_revoked_key() {
+    setup_db();
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let (key_id, tokens) = login_bound(None).await;
+
+    // Revoked without a blacklist entry, as if the entry already expired
+    assert!(revoke_apikey(key_id).await.unwrap());
+    assert!(!get_jwtservice().unwrap().is_blacklisted(key_id).await);
+
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::post()
+        .uri("/manage/refresh")
+        .insert_header((
+            "Authorization",
+            format!("Bearer {}", tokens.refresh_token.unwrap()),
+        ))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ========================================== Scopes =========================================== //
+
+#[rstest]
+#[case("events:subscribe", EVENTS_SUBSCRIBE)]
+#[case("keys:manage", KEYS_MANAGE)]
+#[case(
+    "custom_area:read-only",
+    Scope::from_static("custom_area", "read-only")
+)]
+#[case("v2:list", Scope::from_static("v2", "list"))]
+fn test_scope_parse(#[case] raw: &str, #[case] expected: Scope) {
+    let scope: Scope = raw.parse().unwrap();
+    assert_eq!(scope, expected);
+    assert_eq!(scope.to_string(), raw);
+    assert!(scope == raw);
+}
+
+#[rstest]
+#[case("")]
+#[case(":")]
+#[case("events")]
+#[case(":subscribe")]
+#[case("events:")]
+#[case("Events:subscribe")]
+#[case("events:sub:scribe")]
+#[case("events: subscribe")]
+#[case("events manage")]
+#[case("events:süb")]
+fn test_scope_parse_invalid(#[case] raw: &str) {
+    assert!(matches!(
+        raw.parse::<Scope>(),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[test]
+fn test_scope_serde() {
+    let scopes = vec![ADMIN_MANAGE, EVENTS_MANAGE];
+    let json = serde_json::to_string(&scopes).unwrap();
+    assert_eq!(json, r#"["admin:manage","events:manage"]"#);
+    assert_eq!(serde_json::from_str::<Vec<Scope>>(&json).unwrap(), scopes);
+
+    // Malformed scopes are rejected while deserializing
+    assert!(serde_json::from_str::<Scope>(r#""admin""#).is_err());
+}
+
+#[test]
+fn test_parse_scopes() {
+    let raw = vec!["events:subscribe".to_string(), "admin:manage".to_string()];
+    assert_eq!(
+        parse_scopes(&raw).unwrap(),
+        vec![EVENTS_SUBSCRIBE, ADMIN_MANAGE]
+    );
+
+    let raw = vec!["events:subscribe".to_string(), "admin".to_string()];
+    assert!(matches!(
+        parse_scopes(&raw),
+        Err(KohakuError::ValidationError(msg)) if msg.contains("`admin`")
+    ));
+}
+
+#[rstest]
+#[case(vec![])]
+#[case(vec!["events:subscribe"])]
+#[case(vec!["admin:manage", "events:subscribe", "events:manage"])]
+fn test_validate_scopes_known(#[case] scopes: Vec<&str>) {
+    let scopes: Vec<Scope> = scopes.into_iter().map(|s| s.parse().unwrap()).collect();
+    assert!(validate_scopes(&scopes).is_ok());
+}
+
+#[test]
+fn test_validate_scopes_lists_unknown() {
+    let scopes = vec![
+        EVENTS_SUBSCRIBE,
+        "event:subscibe".parse().unwrap(),
+        "admin:read".parse().unwrap(),
+    ];
+    match validate_scopes(&scopes) {
+        Err(KohakuError::ValidationError(msg)) => {
+            // Only the unknown scopes are listed
+            assert!(msg.starts_with("Unknown scope(s): event:subscibe, admin:read."));
+        }
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+}
+
+#[rstest]
+#[case("keys:manage")]
+#[case("keys:read")]
+fn test_validate_scopes_reserved(#[case] scope: &str) {
+    let scopes = vec![EVENTS_SUBSCRIBE, scope.parse().unwrap()];
+    let err = validate_scopes(&scopes).unwrap_err();
+    assert!(matches!(err, KohakuError::ValidationError(msg) if msg.contains("keys")));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_create_apikey_rejects_unknown_scope() {
+    setup_db();
+    let owner = format!("owner-{}", random_string(8));
+    let result = create_apikey(
+        random_string(32),
+        "khk_jjjjjj".to_string(),
+        owner.clone(),
+        vec!["event:subscibe".parse().unwrap()],
+        vec![],
+    )
+    .await;
+
+    assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+    assert!(list_apikeys(&owner).await.unwrap().is_empty());
+}
+
+// ====================================== IP Allowlist ========================================= //
+
+#[rstest]
+#[case(vec![], Some("203.0.113.7"))]
+#[case(vec![], None)]
+#[case(vec!["203.0.113.7"], Some("203.0.113.7"))]
+#[case(vec!["10.0.0.1", "203.0.113.0/24"], Some("203.0.113.200"))]
+#[case(vec!["0.0.0.0/0"], Some("198.51.100.1"))]
+#[case(vec!["2001:db8::/32"], Some("2001:db8::1"))]
+fn test_ip_allowed(#[case] allowed: Vec<&str>, #[case] ip: Option<&str>) {
+    let allowed: Vec<String> = allowed.into_iter().map(String::from).collect();
+    assert!(is_ip_allowed(&allowed, ip));
+}
+
+#[rstest]
+#[case(vec!["203.0.113.7"], Some("203.0.113.8"))]
+#[case(vec!["203.0.113.0/24"], Some("203.0.114.1"))]
+#[case(vec!["203.0.113.7"], None)]
+#[case(vec!["203.0.113.0/24"], Some("2001:db8::1"))]
+#[case(vec!["not-an-ip"], Some("203.0.113.7"))]
+fn test_ip_disallowed(#[case] allowed: Vec<&str>, #[case] ip: Option<&str>) {
+    let allowed: Vec<String> = allowed.into_iter().map(String::from).collect();
+    assert!(!is_ip_allowed(&allowed, ip));
+}
+
+#[rstest]
+#[case("10.0.0.0/33")]
+#[case("2001:db8::/129")]
+#[case("10.0.0/24")]
+#[case("10.0.0.0/abc")]
+#[case("")]
+fn test_parse_ip_rule_invalid(#[case] rule: &str) {
+    assert!(matches!(
+        parse_ip_rule(rule),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_create_apikey_allowed_ips() {
+    setup_db();
+    let owner = format!("owner-{}", random_string(8));
+    let created = create_apikey(
+        random_string(32),
+        "khk_ffffff".to_string(),
+        owner.clone(),
+        vec![],
+        vec!["10.0.0.0/8".to_string()],
+    )
+    .await
+    .unwrap();
+    assert_eq!(created.allowed_ips, vec!["10.0.0.0/8"]);
+
+    let invalid = create_apikey(
+        random_string(32),
+        "khk_gggggg".to_string(),
+        owner,
+        vec![],
+        vec!["10.0.0.0/64".to_string()],
+    )
+    .await;
+    assert!(matches!(invalid, Err(KohakuError::ValidationError(_))));
+}
+
+// ======================================== Extractor ========================================== //
+
+#[actix_web::test]
+async fn test_authed_claims_missing_token() {
+    let req = TestRequest::default().to_http_request();
+    let val = AuthedClaims::<NoScopes>::extract(&req).await;
+    let err = val.err().unwrap();
+    assert!(matches!(err, KohakuError::Unauthorized(_)));
+    assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_authed_claims_valid_token() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let token = service.create_bootstrap_token().unwrap().access_token;
+
+    let req = TestRequest::default()
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_http_request();
+    let val = AuthedClaims::<KeysManage>::extract(&req).await;
+    assert!(val.is_ok());
+    assert_eq!(val.unwrap().token_type, TokenType::Bootstrap);
+}
+
+#[actix_web::test]
+async fn test_authed_claims_missing_scope() {
+    let key = "encryption_key".to_string();
+    let _ = init_jwtservice(key.as_bytes());
+    let service = get_jwtservice().unwrap();
+    let token = service
+        .create_token(
+            "test-suite".to_string(),
+            30,
+            vec!["events:subscribe".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+
+    let req = TestRequest::default()
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_http_request();
+    assert!(AuthedClaims::<NoScopes>::extract(&req).await.is_ok());
+
+    let err = AuthedClaims::<KeysManage>::extract(&req)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, KohakuError::Forbidden(_)));
+    assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+}
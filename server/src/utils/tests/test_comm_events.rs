@@ -0,0 +1,2547 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body_json, TestRequest},
+    web, App,
+};
+use actix_ws::Message;
+use chrono::{TimeZone, Utc};
+use rstest::rstest;
+use serde_json::json;
+use serial_test::serial;
+use tokio::sync::mpsc::unbounded_channel;
+use uuid::Uuid;
+
+use diesel::prelude::*;
+
+use crate::db::{schema, with_connection};
+use crate::utils::{
+    breaker::BREAKER,
+    comm::{
+        auth::{
+            jwt::{get_jwtservice, init_jwtservice},
+            models::TokenType,
+        },
+        events::{
+            cache::TtlCache,
+            dispatcher::dispatch,
+            embed::{
+                EmbedBuilder, EMBED_COLOR_MAX, EMBED_DESCRIPTION_MAX, EMBED_FIELDS_MAX,
+                EMBED_FIELD_NAME_MAX, EMBED_FIELD_VALUE_MAX, EMBED_FOOTER_MAX, EMBED_TITLE_MAX,
+            },
+            models::{
+                expiry_from_secs, ImportSubscriptionsResponse, NewScheduledNotification,
+                NotificationData, NotifyReport, SubscriptionAction, SubscriptionExport,
+                SubscriptionQuery, UnregisterCodeResponse,
+            },
+            notifications::{
+                content_hash, delete_expired_subscriptions, export_subscriptions,
+                get_active_subscriptions, get_all_codes, get_code, get_subscriptions,
+                get_subscriptions_by_code_prefix, import_subscriptions, notify, notify_guild,
+                purge_sent_scheduled, purge_stale, register, register_many, schedule_notification,
+                send_scheduled, set_subscription_active, subscribe, subscribe_many, unregister,
+                unsubscribe, update_code_ts, update_description, validate_attachments,
+                ATTACHMENTS_MAX, GUILD_BROADCAST_CODE, GUILD_LIMIT, NOTIFY_SCHEDULER, WEBHOOK_URL,
+            },
+            routes,
+            template::{render, TemplateContext},
+            webhook::{post_webhook, validate_webhook_url, WebhookPayload, WEBHOOK_SERVICE},
+        },
+        websocket::{
+            connection::{
+                handle_message, ConnectionStats, MessageType, Outbound, WsClientInfo, WsMessage,
+                PRIORITY_NORMAL,
+            },
+            manager::{get_manager, init_manager, WsConnectionManager},
+        },
+    },
+    error::KohakuError,
+    middleware::payload::build_query_config,
+    scheduler::Scheduler,
+    tests::{mock_http_server, register_ws_client, setup_db},
+};
+
+/// Helper: Registers a fresh code so tests don't interfere with each other
+async fn fresh_code() -> String {
+    let code = format!("test:{}", Uuid::new_v4().simple());
+    register(&code, Some("Test code".to_string()), None)
+        .await
+        .unwrap();
+    code
+}
+
+// ========================================== Codes ============================================ //
+
+#[rstest]
+#[case("")]
+#[case("Upper:Case")]
+#[case("with space")]
+#[case("emoji:🎉")]
+#[tokio::test]
+async fn test_register_invalid_code(#[case] code: &str) {
+    assert!(matches!(
+        register(code, None, None).await,
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_register_and_unregister() {
+    setup_db();
+    let code = fresh_code().await;
+    assert!(get_all_codes()
+        .await
+        .unwrap()
+        .iter()
+        .any(|c| c.code == code));
+
+    // Duplicate registration
+    assert!(matches!(
+        register(&code, None, None).await,
+        Err(KohakuError::ValidationError(_))
+    ));
+
+    subscribe(&code, 1, 2, None, None, vec![], None)
+        .await
+        .unwrap();
+    assert_eq!(unregister(&code).await.unwrap(), 1);
+    assert!(get_subscriptions(Some(&code), None, None)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        unregister(&code).await,
+        Err(KohakuError::NotFound(_))
+    ));
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_unregister_endpoint_reports_subscriptions() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["events:manage".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let code = fresh_code().await;
+    for channel in 1..=3 {
+        subscribe(&code, channel, 2, None, None, vec![], None)
+            .await
+            .unwrap();
+    }
+    subscribe(&code, 1, 2, Some(4), None, vec![], None)
+        .await
+        .unwrap();
+
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let req = TestRequest::delete()
+        .uri(&format!("/codes/{}", code))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: UnregisterCodeResponse = read_body_json(resp).await;
+    assert_eq!(body.code, code);
+    assert_eq!(body.removed_subscriptions, 4);
+    assert!(get_subscriptions(Some(&code), None, None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let req = TestRequest::delete()
+        .uri(&format!("/codes/{}", code))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_register_with_owner() {
+    setup_db();
+    let code = format!("test:{}", Uuid::new_v4().simple());
+    let registered = register(&code, None, Some("team-a".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(registered.owner.as_deref(), Some("team-a"));
+    assert_eq!(
+        get_code(&code).await.unwrap().owner.as_deref(),
+        Some("team-a")
+    );
+
+    // Codes registered without an owner stay unowned
+    let unowned = fresh_code().await;
+    assert!(get_code(&unowned).await.unwrap().owner.is_none());
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_list_codes_by_owner() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let owner = format!("team-{}", Uuid::new_v4().simple());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            owner.clone(),
+            1,
+            vec!["events:manage".to_string(), "events:subscribe".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let app = init_service(
+        App::new()
+            .app_data(build_query_config())
+            .configure(routes::configure),
+    )
+    .await;
+    let code = format!("test:{}", Uuid::new_v4().simple());
+    let other = fresh_code().await;
+
+    // #1 Registering stores the owner of the caller
+    let req = TestRequest::post()
+        .uri("/codes")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "code": code }))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = read_body_json(resp).await;
+    assert_eq!(body["owner"], json!(owner));
+
+    // #2 Filtering by owner
+    let req = TestRequest::get()
+        .uri(&format!("/codes?owner={}", owner))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Vec<serde_json::Value> = read_body_json(resp).await;
+    let codes: Vec<&serde_json::Value> = body.iter().map(|c| &c["code"]).collect();
+    assert_eq!(codes, vec![&json!(code)]);
+
+    // #3 Without filter, all codes are listed
+    let req = TestRequest::get()
+        .uri("/codes")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    let body: Vec<serde_json::Value> = read_body_json(resp).await;
+    assert!(body.iter().any(|c| c["code"] == json!(code)));
+    assert!(body.iter().any(|c| c["code"] == json!(other)));
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_show_code_endpoint() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["events:subscribe".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let code = fresh_code().await;
+    let app = init_service(App::new().configure(routes::configure)).await;
+
+    // #1 Registered code
+    let req = TestRequest::get()
+        .uri(&format!("/codes/{}", code))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = read_body_json(resp).await;
+    assert_eq!(body["code"], json!(code));
+    assert_eq!(body["description"], json!("Test code"));
+    assert!(body["last_used"].is_null());
+    assert!(body["last_success"].is_null());
+    assert!(body["last_error"].is_null());
+
+    // #2 Unregistered code
+    let req = TestRequest::get()
+        .uri("/codes/test:not-registered")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert!(matches!(
+        get_code("test:not-registered").await,
+        Err(KohakuError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_update_description() {
+    setup_db();
+    let code = fresh_code().await;
+    let cached = get_code(&code).await.unwrap();
+    assert_eq!(cached.description.as_deref(), Some("Test code"));
+
+    let updated = update_description(&code, Some("Releases".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(updated.description.as_deref(), Some("Releases"));
+    assert_eq!(
+        get_code(&code).await.unwrap().description.as_deref(),
+        Some("Releases")
+    );
+    let codes = get_all_codes().await.unwrap();
+    let listed = codes.iter().find(|c| c.code == code).unwrap();
+    assert_eq!(listed.description.as_deref(), Some("Releases"));
+
+    // Removing the description
+    update_description(&code, None).await.unwrap();
+    assert!(get_code(&code).await.unwrap().description.is_none());
+
+    assert!(matches!(
+        update_description("test:not-registered", None).await,
+        Err(KohakuError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_update_code_endpoint() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = |scope: &str| {
+        get_jwtservice()
+            .unwrap()
+            .create_token(
+                "test-suite".to_string(),
+                1,
+                vec![scope.to_string()],
+                TokenType::Access,
+            )
+            .unwrap()
+    };
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let app = init_service(App::new().configure(routes::configure)).await;
+
+    // #1 Registered code
+    let req = TestRequest::patch()
+        .uri(&format!("/codes/{}", code))
+        .insert_header((
+            "Authorization",
+            format!("Bearer {}", token("events:manage")),
+        ))
+        .set_json(json!({"description": "Patch notes"}))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = read_body_json(resp).await;
+    assert_eq!(body["description"], json!("Patch notes"));
+    assert_eq!(
+        get_code(&code).await.unwrap().description.as_deref(),
+        Some("Patch notes")
+    );
+    // Subscriptions are kept
+    assert_eq!(get_active_subscriptions(&code).await.unwrap().len(), 1);
+
+    // #2 Unregistered code
+    let req = TestRequest::patch()
+        .uri("/codes/test:not-registered")
+        .insert_header((
+            "Authorization",
+            format!("Bearer {}", token("events:manage")),
+        ))
+        .set_json(json!({"description": "Patch notes"}))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // #3 Missing scope
+    let req = TestRequest::patch()
+        .uri(&format!("/codes/{}", code))
+        .insert_header((
+            "Authorization",
+            format!("Bearer {}", token("events:subscribe")),
+        ))
+        .set_json(json!({"description": "Other"}))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_purge_stale() {
+    setup_db();
+    // Far in the past, so codes of concurrent tests are never stale
+    let long_ago = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+    let cutoff = Utc
+        .with_ymd_and_hms(2001, 6, 1, 0, 0, 0)
+        .unwrap()
+        .naive_utc();
+
+    let stale = fresh_code().await;
+    update_code_ts(&stale, long_ago).await.unwrap();
+    let stale_subscribed = fresh_code().await;
+    update_code_ts(&stale_subscribed, long_ago).await.unwrap();
+    subscribe(&stale_subscribed, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let stale_paused = fresh_code().await;
+    update_code_ts(&stale_paused, long_ago).await.unwrap();
+    let paused = subscribe(&stale_paused, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    set_subscription_active(paused.id, false).await.unwrap();
+    let active = fresh_code().await;
+    update_code_ts(&active, Utc::now()).await.unwrap();
+    let unused = fresh_code().await;
+
+    assert_eq!(purge_stale(cutoff).await.unwrap(), 1);
+    assert!(matches!(
+        get_code(&stale).await,
+        Err(KohakuError::NotFound(_))
+    ));
+    for kept in [&stale_subscribed, &stale_paused, &active, &unused] {
+        assert!(get_code(kept).await.is_ok(), "{} was purged", kept);
+    }
+    let codes = get_all_codes().await.unwrap();
+    assert!(!codes.iter().any(|c| c.code == stale));
+
+    // Nothing left to purge
+    assert_eq!(purge_stale(cutoff).await.unwrap(), 0);
+}
+
+// ====================================== Subscriptions ======================================== //
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_register_many() {
+    setup_db();
+    let game = format!("game-{}", Uuid::new_v4().simple());
+    let entries: Vec<(String, Option<String>)> = ["release", "patch", "event"]
+        .iter()
+        .map(|topic| (format!("{}:{}", game, topic), Some(topic.to_string())))
+        .collect();
+
+    let registered = register_many(&entries, Some(game.clone()), false)
+        .await
+        .unwrap();
+    let codes: Vec<&str> = registered.iter().map(|c| c.code.as_str()).collect();
+    let expected: Vec<&str> = entries.iter().map(|(c, _)| c.as_str()).collect();
+    assert_eq!(codes, expected);
+    assert_eq!(registered[1].description.as_deref(), Some("patch"));
+    assert!(registered
+        .iter()
+        .all(|c| c.owner.as_deref() == Some(game.as_str())));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_register_many_rolls_back() {
+    setup_db();
+    let existing = fresh_code().await;
+    let new_code = format!("test:{}", Uuid::new_v4().simple());
+    let entries = vec![(new_code.clone(), None), (existing.clone(), None)];
+
+    // Duplicate => Nothing is stored
+    assert!(matches!(
+        register_many(&entries, None, false).await,
+        Err(KohakuError::ValidationError(_))
+    ));
+    let all = get_all_codes().await.unwrap();
+    assert!(!all.iter().any(|c| c.code == new_code));
+
+    // Malformed code => Nothing is stored
+    let malformed = vec![(new_code.clone(), None), ("Not Valid".to_string(), None)];
+    assert!(matches!(
+        register_many(&malformed, None, true).await,
+        Err(KohakuError::ValidationError(_))
+    ));
+    let all = get_all_codes().await.unwrap();
+    assert!(!all.iter().any(|c| c.code == new_code));
+
+    // Skipping duplicates stores the remaining codes
+    let registered = register_many(&entries, None, true).await.unwrap();
+    assert_eq!(registered.len(), 1);
+    assert_eq!(registered[0].code, new_code);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscribe_unregistered_code() {
+    setup_db();
+    let result = subscribe("test:not-registered", 1, 2, None, None, vec![], None).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscribe_thread() {
+    setup_db();
+    let code = fresh_code().await;
+
+    let channel = subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let thread = subscribe(&code, 10, 20, Some(30), None, vec![], None)
+        .await
+        .unwrap();
+    assert_ne!(channel.id, thread.id);
+    assert_eq!(channel.thread_id, None);
+    assert_eq!(thread.thread_id, Some(30));
+
+    let targets = get_subscriptions(Some(&code), Some(10), None)
+        .await
+        .unwrap();
+    assert_eq!(targets.len(), 2);
+    assert!(targets.iter().any(|t| t.thread_id == Some(30)));
+
+    // Unsubscribing the thread keeps the channel subscription
+    unsubscribe(&code, 10, 20, Some(30)).await.unwrap();
+    let targets = get_subscriptions(Some(&code), None, None).await.unwrap();
+    assert_eq!(targets.len(), 1);
+    assert_eq!(targets[0].id, channel.id);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscribe_twice_updates_format() {
+    setup_db();
+    let code = fresh_code().await;
+
+    let first = subscribe(&code, 10, 20, Some(30), None, vec![], None)
+        .await
+        .unwrap();
+    let second = subscribe(
+        &code,
+        10,
+        20,
+        Some(30),
+        Some("New: {content}".to_string()),
+        vec![],
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(first.id, second.id);
+    assert_eq!(second.format.as_deref(), Some("New: {content}"));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscribe_many() {
+    setup_db();
+    let codes = [fresh_code().await, fresh_code().await, fresh_code().await];
+    let codes: Vec<&str> = codes.iter().map(String::as_str).collect();
+
+    let targets = subscribe_many(&codes, 10, 20, None, None, vec![5], None)
+        .await
+        .unwrap();
+    assert_eq!(targets.len(), 3);
+    for (target, code) in targets.iter().zip(&codes) {
+        assert_eq!(target.code, *code);
+        assert_eq!(target.mention_roles, vec![5]);
+        assert_eq!(
+            get_subscriptions(Some(code), Some(10), Some(20))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscribe_many_rolls_back() {
+    setup_db();
+    let first = fresh_code().await;
+    let second = fresh_code().await;
+
+    let result = subscribe_many(
+        &[&first, "test:not-registered", &second],
+        10,
+        20,
+        None,
+        None,
+        vec![],
+        None,
+    )
+    .await;
+    assert!(matches!(result, Err(KohakuError::NotFound(_))));
+    for code in [&first, &second] {
+        assert!(get_subscriptions(Some(code), None, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscribe_guild_limit() {
+    setup_db();
+    GUILD_LIMIT.with(|limit| limit.set(3));
+    let guild = rand::random_range(1_000_000..i64::MAX);
+    let codes = [
+        fresh_code().await,
+        fresh_code().await,
+        fresh_code().await,
+        fresh_code().await,
+    ];
+
+    // #1 Up to the limit
+    for code in &codes[..3] {
+        subscribe(code, 10, guild, None, None, vec![], None)
+            .await
+            .unwrap();
+    }
+    // #2 Beyond the limit
+    let result = subscribe(&codes[3], 10, guild, None, None, vec![], None).await;
+    assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+    let result = subscribe_many(&[&codes[3]], 11, guild, None, None, vec![], None).await;
+    assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+    assert_eq!(
+        get_subscriptions(None, None, Some(guild))
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    // #3 Updating an existing subscription and other guilds are not affected
+    subscribe(
+        &codes[0],
+        10,
+        guild,
+        None,
+        Some("{content}".to_string()),
+        vec![],
+        None,
+    )
+    .await
+    .unwrap();
+    subscribe(&codes[3], 10, guild - 1, None, None, vec![], None)
+        .await
+        .unwrap();
+    GUILD_LIMIT.with(|limit| limit.set(0));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscription_export_import() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["events:subscribe".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    // Guild of this test only, so the export isn't affected by other tests
+    let guild = rand::random_range(1_000_000..i64::MAX);
+    let (first, second) = (fresh_code().await, fresh_code().await);
+    subscribe(&first, 10, guild, None, None, vec![], None)
+        .await
+        .unwrap();
+    let thread = subscribe(
+        &second,
+        10,
+        guild,
+        Some(11),
+        Some("New: {content}".to_string()),
+        vec![5, 6],
+        None,
+    )
+    .await
+    .unwrap();
+    set_subscription_active(thread.id, false).await.unwrap();
+    subscribe(&first, 10, guild - 1, None, None, vec![], None)
+        .await
+        .unwrap();
+    let app = init_service(App::new().configure(routes::configure)).await;
+
+    // #1 Export
+    let req = TestRequest::get()
+        .uri(&format!("/subscriptions/export?guild_id={}", guild))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let exported: Vec<SubscriptionExport> = read_body_json(resp).await;
+    assert_eq!(exported.len(), 2);
+    assert_eq!(exported, export_subscriptions(guild).await.unwrap());
+    assert!(!exported[1].active);
+
+    // #2 Import into an empty state
+    let guild_ = guild;
+    with_connection(move |conn| {
+        diesel::delete(
+            schema::notification_targets::table
+                .filter(schema::notification_targets::guild_id.eq(guild_)),
+        )
+        .execute(conn)
+        .map_err(KohakuError::DatabaseError)
+    })
+    .await
+    .unwrap();
+    assert!(export_subscriptions(guild).await.unwrap().is_empty());
+
+    for _ in 0..2 {
+        // Importing twice updates the existing subscriptions instead of duplicating them
+        let req = TestRequest::post()
+            .uri("/subscriptions/import")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(&exported)
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: ImportSubscriptionsResponse = read_body_json(resp).await;
+        assert_eq!(body.imported, 2);
+        assert_eq!(export_subscriptions(guild).await.unwrap(), exported);
+    }
+    // Paused subscriptions stay paused
+    assert!(get_active_subscriptions(&second).await.unwrap().is_empty());
+
+    // #3 Unregistered code: Nothing is imported
+    let mut invalid = exported.clone();
+    invalid[0].guild_id = guild - 2;
+    invalid[1].guild_id = guild - 2;
+    invalid[1].code = "test:not-registered".to_string();
+    let result = import_subscriptions(invalid).await;
+    assert!(matches!(result, Err(KohakuError::NotFound(_))));
+    assert!(export_subscriptions(guild - 2).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscriptions_by_code_prefix() {
+    setup_db();
+    let game = format!("game_{}", Uuid::new_v4().simple());
+    let codes = [
+        format!("{}:release", game),
+        format!("{}:patch", game),
+        format!("{}x:other", game),
+    ];
+    for (i, code) in codes.iter().enumerate() {
+        register(code, None, None).await.unwrap();
+        subscribe(code, 10 + i as i64, 1, None, None, vec![], None)
+            .await
+            .unwrap();
+        subscribe(code, 20 + i as i64, 2, None, None, vec![], None)
+            .await
+            .unwrap();
+    }
+
+    // Matches across codes and guilds, ordered by code
+    let targets = get_subscriptions_by_code_prefix(&format!("{}:", game), 100, 0)
+        .await
+        .unwrap();
+    let found: Vec<(&str, i64)> = targets
+        .iter()
+        .map(|t| (t.code.as_str(), t.guild_id))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (codes[1].as_str(), 1),
+            (codes[1].as_str(), 2),
+            (codes[0].as_str(), 1),
+            (codes[0].as_str(), 2),
+        ]
+    );
+
+    // Pagination
+    let page = get_subscriptions_by_code_prefix(&game, 2, 2).await.unwrap();
+    assert_eq!(page.len(), 2);
+    assert!(page.iter().all(|t| t.code == codes[0]));
+    let all = get_subscriptions_by_code_prefix(&game, 100, 0)
+        .await
+        .unwrap();
+    assert_eq!(all.len(), 6);
+
+    // `_` is matched literally instead of as wildcard
+    assert!(
+        get_subscriptions_by_code_prefix(&format!("{}_", game), 100, 0)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[rstest]
+#[case(0, 0)]
+#[case(10, -1)]
+#[tokio::test]
+async fn test_subscriptions_by_code_prefix_invalid_page(#[case] limit: i64, #[case] offset: i64) {
+    assert!(matches!(
+        get_subscriptions_by_code_prefix("game", limit, offset).await,
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_unsubscribe_missing() {
+    setup_db();
+    let code = fresh_code().await;
+    assert!(matches!(
+        unsubscribe(&code, 10, 20, None).await,
+        Err(KohakuError::NotFound(_))
+    ));
+}
+
+/// Helper: Registers a client with the given scopes on a fresh manager
+fn ws_subscriber(
+    scopes: Vec<&str>,
+) -> (
+    WsConnectionManager,
+    Uuid,
+    tokio::sync::mpsc::UnboundedReceiver<Outbound>,
+) {
+    let manager = WsConnectionManager::new(100, 10);
+    let (registered, rx) = register_ws_client(&manager, 1, "test-ws-subscriber", scopes, 0, None);
+    registered.unwrap();
+    let client_id = manager.connection_info(&1).unwrap().client_id;
+    (manager, client_id, rx)
+}
+
+/// Helper: Sends a frame as the client and returns the reply of the server
+async fn ws_request(
+    manager: &WsConnectionManager,
+    client_id: Uuid,
+    rx: &mut tokio::sync::mpsc::UnboundedReceiver<Outbound>,
+    message: MessageType,
+) -> (Uuid, MessageType) {
+    let frame = WsMessage::new(message);
+    let id = frame.message_id;
+    handle_message(manager, (1, client_id), frame).await;
+    let Message::Text(text) = rx.try_recv().unwrap().message else {
+        panic!("Expected a text reply");
+    };
+    (
+        id,
+        serde_json::from_str::<WsMessage>(&text).unwrap().message,
+    )
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_ws_subscribe_flow() {
+    setup_db();
+    let code = fresh_code().await;
+    let (manager, client_id, mut rx) = ws_subscriber(vec!["events:subscribe"]);
+    let subscription = |subscribe: bool| {
+        let (code, channel_id, guild_id, thread_id) = (code.clone(), 10, 20, None);
+        if subscribe {
+            MessageType::Subscribe {
+                code,
+                channel_id,
+                guild_id,
+                thread_id,
+            }
+        } else {
+            MessageType::Unsubscribe {
+                code,
+                channel_id,
+                guild_id,
+                thread_id,
+            }
+        }
+    };
+
+    let (id, reply) = ws_request(&manager, client_id, &mut rx, subscription(true)).await;
+    assert_eq!(reply, MessageType::Ack { reply_to: id });
+    let targets = get_subscriptions(Some(&code), None, None).await.unwrap();
+    assert_eq!(targets.len(), 1);
+    assert_eq!((targets[0].channel_id, targets[0].guild_id), (10, 20));
+
+    let (id, reply) = ws_request(&manager, client_id, &mut rx, subscription(false)).await;
+    assert_eq!(reply, MessageType::Ack { reply_to: id });
+    assert!(get_subscriptions(Some(&code), None, None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Nothing left to unsubscribe
+    let (id, reply) = ws_request(&manager, client_id, &mut rx, subscription(false)).await;
+    assert!(matches!(reply, MessageType::Error { reply_to, .. } if reply_to == id));
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_ws_subscribe_unregistered_code() {
+    setup_db();
+    let (manager, client_id, mut rx) = ws_subscriber(vec!["events:subscribe"]);
+    let message = MessageType::Subscribe {
+        code: "test:not-registered".to_string(),
+        channel_id: 10,
+        guild_id: 20,
+        thread_id: None,
+    };
+    let (id, reply) = ws_request(&manager, client_id, &mut rx, message).await;
+    assert!(matches!(reply, MessageType::Error { reply_to, .. } if reply_to == id));
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_ws_subscribe_unauthorized() {
+    setup_db();
+    let code = fresh_code().await;
+    let subscribe_message = MessageType::Subscribe {
+        code: code.clone(),
+        channel_id: 10,
+        guild_id: 20,
+        thread_id: None,
+    };
+
+    // Missing scope
+    let (manager, client_id, mut rx) = ws_subscriber(vec!["events:manage"]);
+    let (_, reply) = ws_request(&manager, client_id, &mut rx, subscribe_message.clone()).await;
+    assert!(
+        matches!(reply, MessageType::Error { code, error, .. } if code == "forbidden" && error.contains("events:subscribe"))
+    );
+
+    // Connection was replaced meanwhile
+    let (manager, _, mut rx) = ws_subscriber(vec!["events:subscribe"]);
+    let (_, reply) = ws_request(&manager, Uuid::new_v4(), &mut rx, subscribe_message).await;
+    assert!(matches!(reply, MessageType::Error { .. }));
+
+    assert!(get_subscriptions(Some(&code), None, None)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[rstest]
+#[case(
+    r#"{"type": "subscribe", "code": "test:a", "channel_id": 1, "guild_id": 2}"#,
+    None
+)]
+#[case(
+    r#"{"type": "unsubscribe", "code": "test:a", "channel_id": 1, "guild_id": 2, "thread_id": 3}"#,
+    Some(3)
+)]
+fn test_ws_subscription_message(#[case] text: &str, #[case] expected_thread: Option<i64>) {
+    let frame = WsMessage::parse(text).unwrap();
+    match frame.message {
+        MessageType::Subscribe {
+            code, thread_id, ..
+        }
+        | MessageType::Unsubscribe {
+            code, thread_id, ..
+        } => {
+            assert_eq!(code, "test:a");
+            assert_eq!(thread_id, expected_thread);
+        }
+        other => panic!("Unexpected message {:?}", other),
+    }
+}
+
+// ====================================== Notifications ======================================== //
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_targets() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(
+        &code,
+        10,
+        20,
+        None,
+        Some("Update: {content}".to_string()),
+        vec![],
+        None,
+    )
+    .await
+    .unwrap();
+    subscribe(&code, 11, 20, Some(31), None, vec![], None)
+        .await
+        .unwrap();
+
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("v1.2".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].channel_id, 10);
+    assert_eq!(sent[0].thread_id, None);
+    assert_eq!(sent[0].message.as_deref(), Some("Update: v1.2"));
+    assert_eq!(sent[1].thread_id, Some(31));
+    assert_eq!(sent[1].message.as_deref(), Some("v1.2"));
+
+    let codes = get_all_codes().await.unwrap();
+    let stored = codes.iter().find(|c| c.code == code).unwrap();
+    assert!(stored.last_used.is_some());
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_suppresses_duplicates() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let embed = json!({"title": "New release"});
+    let send = |message: &str| {
+        notify(
+            &code,
+            "scraper",
+            Some(embed.clone()),
+            Some(message.to_string()),
+            None,
+            None,
+            PRIORITY_NORMAL,
+        )
+    };
+
+    let report = send("v1.2").await.unwrap();
+    assert_eq!(report.sent.len(), 1);
+    assert!(!report.suppressed);
+    // Same content within the window
+    let report = send("v1.2").await.unwrap();
+    assert!(report.sent.is_empty());
+    assert!(report.suppressed);
+    // Different content
+    assert_eq!(send("v1.3").await.unwrap().sent.len(), 1);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_suppresses_concurrent_duplicates() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let send = || {
+        notify(
+            &code,
+            "scraper",
+            None,
+            Some("v2.0".to_string()),
+            None,
+            None,
+            PRIORITY_NORMAL,
+        )
+    };
+
+    let (first, second) = tokio::join!(send(), send());
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_ne!(first.suppressed, second.suppressed);
+    assert_eq!(first.sent.len() + second.sent.len(), 1);
+
+    // Other attachments are different content
+    let report = notify(
+        &code,
+        "scraper",
+        None,
+        Some("v2.0".to_string()),
+        None,
+        Some(vec!["https://cdn.example.com/v2.png".to_string()]),
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap();
+    assert!(!report.suppressed);
+}
+
+#[rstest]
+#[case::same(Some(json!({"title": "A"})), Some("a"), None, None, PRIORITY_NORMAL, true)]
+#[case::other_message(Some(json!({"title": "A"})), Some("b"), None, None, PRIORITY_NORMAL, false)]
+#[case::other_embed(Some(json!({"title": "B"})), Some("a"), None, None, PRIORITY_NORMAL, false)]
+#[case::no_embed(None, Some("a"), None, None, PRIORITY_NORMAL, false)]
+#[case::no_message(Some(json!({"title": "A"})), None, None, None, PRIORITY_NORMAL, false)]
+#[case::attachments(
+    Some(json!({"title": "A"})),
+    Some("a"),
+    Some(vec!["https://cdn.example.com/a.png".to_string()]),
+    None,
+    PRIORITY_NORMAL,
+    false
+)]
+#[case::override_channels(
+    Some(json!({"title": "A"})),
+    Some("a"),
+    None,
+    Some(vec![(1, 2)]),
+    PRIORITY_NORMAL,
+    false
+)]
+#[case::priority(Some(json!({"title": "A"})), Some("a"), None, None, PRIORITY_NORMAL + 1, false)]
+fn test_content_hash(
+    #[case] embed: Option<serde_json::Value>,
+    #[case] message: Option<&str>,
+    #[case] attachments: Option<Vec<String>>,
+    #[case] override_channels: Option<Vec<(i64, i64)>>,
+    #[case] priority: u8,
+    #[case] equal: bool,
+) {
+    let reference = content_hash(
+        Some(&json!({"title": "A"})),
+        Some("a"),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .unwrap();
+    let hash = content_hash(
+        embed.as_ref(),
+        message,
+        attachments.as_deref(),
+        override_channels.as_deref(),
+        priority,
+    )
+    .unwrap();
+    assert_eq!(hash.len(), 64);
+    assert_eq!(hash == reference, equal);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_skips_empty_after_format() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(
+        &code,
+        10,
+        20,
+        None,
+        Some("{content}".to_string()),
+        vec![],
+        None,
+    )
+    .await
+    .unwrap();
+    subscribe(&code, 11, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+
+    // Both targets resolve to a blank message
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("   ".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert!(sent.is_empty());
+
+    // An embed is sent, even if the message is blank
+    let embed = serde_json::json!({"title": "Update"});
+    let sent = notify(
+        &code,
+        "test",
+        Some(embed),
+        Some("   ".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(sent.len(), 2);
+}
+
+#[rstest]
+#[case(None, None, true)]
+#[case(None, Some(""), true)]
+#[case(None, Some(" \n\t"), true)]
+#[case(None, Some("Hello"), false)]
+#[case(Some(serde_json::json!({"title": "Update"})), None, false)]
+#[case(Some(serde_json::json!({"title": "Update"})), Some(" "), false)]
+fn test_notification_data_is_empty(
+    #[case] embed: Option<serde_json::Value>,
+    #[case] message: Option<&str>,
+    #[case] empty: bool,
+) {
+    let notification = NotificationData {
+        code: "code".to_string(),
+        triggering_event: "test".to_string(),
+        channel_id: 10,
+        guild_id: 20,
+        thread_id: None,
+        embed,
+        message: message.map(str::to_string),
+        mention_roles: vec![],
+        attachments: None,
+        priority: PRIORITY_NORMAL,
+    };
+    assert_eq!(notification.is_empty(), empty);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_mention_roles() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    let target = subscribe(&code, 10, 20, None, None, vec![111, 222], None)
+        .await
+        .unwrap();
+    assert_eq!(target.mention_roles, vec![111, 222]);
+
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("Hello".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].mention_roles, vec![111, 222]);
+
+    // Resubscribing updates the roles
+    let updated = subscribe(&code, 10, 20, None, None, vec![333], None)
+        .await
+        .unwrap();
+    assert_eq!(updated.id, target.id);
+    assert_eq!(updated.mention_roles, vec![333]);
+}
+
+#[rstest]
+#[case(None, Some(vec![]))]
+#[case(Some("1, 2,,3"), Some(vec![1, 2, 3]))]
+#[case(Some("1,abc"), None)]
+fn test_manage_query_mention_roles(#[case] raw: Option<&str>, #[case] expected: Option<Vec<i64>>) {
+    let query = SubscriptionQuery {
+        subscribe: Some("a".to_string()),
+        channel_id: Some(1),
+        guild_id: Some(2),
+        mention_roles: raw.map(String::from),
+        ..Default::default()
+    };
+    assert_eq!(query.mention_roles().ok(), expected);
+}
+
+/// Helper: Parses a query string like the query extractor does
+fn subscription_query(raw: &str) -> SubscriptionQuery {
+    web::Query::<SubscriptionQuery>::from_query(raw)
+        .unwrap()
+        .into_inner()
+}
+
+#[rstest]
+#[case("subscribe=a&channel_id=1&guild_id=2", SubscriptionAction::Subscribe("a".to_string()))]
+#[case("unsubscribe=b&channel_id=1&guild_id=2&thread_id=3", SubscriptionAction::Unsubscribe("b".to_string()))]
+fn test_subscription_query_action(#[case] raw: &str, #[case] expected: SubscriptionAction) {
+    assert_eq!(subscription_query(raw).action().unwrap(), (expected, 1, 2));
+}
+
+#[rstest]
+#[case::no_action("channel_id=1&guild_id=2")]
+#[case::both_actions("subscribe=a&unsubscribe=a&channel_id=1&guild_id=2")]
+#[case::missing_channel("subscribe=a&guild_id=2")]
+#[case::missing_guild("unsubscribe=a&channel_id=1")]
+#[case::missing_ids("subscribe=a")]
+#[case::empty("")]
+fn test_subscription_query_action_invalid(#[case] raw: &str) {
+    assert!(matches!(
+        subscription_query(raw).action(),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[rstest]
+#[case("", Some((None, None)))]
+#[case("channel_id=1", Some((Some(1), None)))]
+#[case("channel_id=1&guild_id=2", Some((Some(1), Some(2))))]
+#[case("subscribe=a&channel_id=1&guild_id=2", None)]
+#[case("unsubscribe=a", None)]
+fn test_subscription_query_list_filter(
+    #[case] raw: &str,
+    #[case] expected: Option<(Option<i64>, Option<i64>)>,
+) {
+    assert_eq!(subscription_query(raw).list_filter().ok(), expected);
+}
+
+#[rstest]
+#[case::invalid_combination(TestRequest::post().uri("/subscriptions/manage?channel_id=1&guild_id=2"))]
+#[case::missing_id(TestRequest::post().uri("/subscriptions/manage?subscribe=a&channel_id=1"))]
+#[case::malformed_id(TestRequest::post().uri("/subscriptions/manage?subscribe=a&channel_id=abc&guild_id=2"))]
+#[case::list_with_action(TestRequest::post().uri("/subscriptions?subscribe=a"))]
+#[case::list_malformed_id(TestRequest::post().uri("/subscriptions?guild_id=abc"))]
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscription_endpoints_reject_invalid_query(#[case] req: TestRequest) {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["events:subscribe".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+
+    let app = init_service(
+        App::new()
+            .app_data(build_query_config())
+            .configure(routes::configure),
+    )
+    .await;
+    let req = req
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+#[serial(ws_manager)]
+async fn test_notify_endpoint() {
+    setup_db();
+    let _ = init_manager();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["events:manage".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let manager = get_manager().unwrap();
+    let (tx, mut rx) = unbounded_channel();
+    let info = WsClientInfo {
+        client_id: Uuid::new_v4(),
+        owner: "test-notify-endpoint".to_string(),
+        key_id: i32::MAX - 3,
+        scopes: vec![],
+        compression: false,
+        tags: HashMap::new(),
+    };
+    manager
+        .register(info, tx, Arc::new(ConnectionStats::new(0)), None)
+        .unwrap();
+
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let post = |body: serde_json::Value| {
+        TestRequest::post()
+            .uri("/notify")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+
+    // #1 Subscribers receive the notification
+    let resp = call_service(
+        &app,
+        post(json!({ "code": code, "triggering_event": "manual", "message": "Hello" })),
+    )
+    .await;
+    manager.remove_connection(&(i32::MAX - 3)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: NotifyReport = read_body_json(resp).await;
+    assert_eq!(report.sent.len(), 1);
+    assert_eq!(report.offline, 0);
+    let received = std::iter::from_fn(|| rx.try_recv().ok()).any(|outbound| {
+        matches!(&outbound.message, Message::Text(text) if text.contains(&code) && text.contains("Hello"))
+    });
+    assert!(received);
+
+    // #2 Neither embed nor message
+    let resp = call_service(
+        &app,
+        post(json!({ "code": code, "triggering_event": "manual" })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // #3 Unregistered code
+    let resp = call_service(
+        &app,
+        post(json!({ "code": "test:unregistered", "triggering_event": "manual", "message": "Hello" })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+#[serial(ws_manager)]
+async fn test_notify_offline() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    subscribe(&code, 11, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let send = |message: &str| {
+        notify(
+            &code,
+            "test",
+            None,
+            Some(message.to_string()),
+            None,
+            None,
+            PRIORITY_NORMAL,
+        )
+    };
+
+    // #1 No client is connected
+    let report = send("offline").await.unwrap();
+    assert_eq!(report.sent.len(), 2);
+    assert_eq!(report.offline, 2);
+    assert!(!report.suppressed);
+
+    // #2 A live client receives them
+    let manager = get_manager().unwrap();
+    let (tx, _rx) = unbounded_channel();
+    let info = WsClientInfo {
+        client_id: Uuid::new_v4(),
+        owner: "test-offline".to_string(),
+        key_id: i32::MAX - 1,
+        scopes: vec![],
+        compression: false,
+        tags: HashMap::new(),
+    };
+    manager
+        .register(info, tx, Arc::new(ConnectionStats::new(0)), None)
+        .unwrap();
+    let report = send("online").await.unwrap();
+    manager.remove_connection(&(i32::MAX - 1)).await;
+    assert_eq!(report.sent.len(), 2);
+    assert_eq!(report.offline, 0);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_paused_subscription() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    let target = subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    assert!(target.active);
+
+    // Paused: still listed, but not notified
+    let paused = set_subscription_active(target.id, false).await.unwrap();
+    assert!(!paused.active);
+    assert_eq!(
+        get_subscriptions(Some(&code), None, None)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("Hello".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert!(sent.is_empty());
+
+    // Reactivated
+    set_subscription_active(target.id, true).await.unwrap();
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("Hello".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(sent.len(), 1);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_set_subscription_active_missing() {
+    setup_db();
+    assert!(matches!(
+        set_subscription_active(-1, false).await,
+        Err(KohakuError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_expired_subscription() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    let expires_at = expiry_from_secs(Some(1)).unwrap();
+    subscribe(&code, 10, 20, None, None, vec![], expires_at)
+        .await
+        .unwrap();
+
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("Hello".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(sent.len(), 1);
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("Hello".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert!(sent.is_empty());
+    assert!(get_subscriptions(Some(&code), None, None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Cleanup removes the row
+    assert!(delete_expired_subscriptions().await.unwrap() >= 1);
+}
+
+#[rstest]
+#[case(Some(0))]
+#[case(Some(-5))]
+#[case(Some(i64::MAX))]
+#[case(Some(i64::MAX / 1000))]
+fn test_expiry_from_secs_invalid(#[case] expires_in: Option<i64>) {
+    assert!(matches!(
+        expiry_from_secs(expires_in),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[test]
+fn test_expiry_from_secs_none() {
+    assert_eq!(expiry_from_secs(None).unwrap(), None);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_skips_empty() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+
+    let sent = notify(&code, "test", None, None, None, None, PRIORITY_NORMAL)
+        .await
+        .unwrap()
+        .sent;
+    assert!(sent.is_empty());
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_override_channels() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(
+        &code,
+        10,
+        20,
+        None,
+        Some("Update: {content}".to_string()),
+        vec![111],
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Channel 10 is subscribed already, 99 (twice) and 98 are not
+    let overrides = vec![(99, 90), (10, 20), (99, 90), (98, 91)];
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        Some("v1.2".to_string()),
+        Some(overrides),
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+
+    let targets: Vec<(i64, i64)> = sent.iter().map(|n| (n.channel_id, n.guild_id)).collect();
+    assert_eq!(targets, vec![(10, 20), (99, 90), (98, 91)]);
+    assert_eq!(sent[0].message.as_deref(), Some("Update: v1.2"));
+    // Override channels receive the plain message without mentions
+    assert_eq!(sent[1].message.as_deref(), Some("v1.2"));
+    assert!(sent[1].mention_roles.is_empty());
+
+    // Without content only the format of the subscriber has something to send
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        None,
+        Some(vec![(99, 90)]),
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].channel_id, 10);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_attachments() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+
+    // Attachments alone are something to send
+    let banner = "https://example.com/banner.png".to_string();
+    let sent = notify(
+        &code,
+        "test",
+        None,
+        None,
+        Some(vec![(99, 90)]),
+        Some(vec![banner.clone()]),
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(sent.len(), 2);
+    for notification in &sent {
+        assert_eq!(notification.attachments, Some(vec![banner.clone()]));
+    }
+    let payload = serde_json::to_value(&sent[0]).unwrap();
+    assert_eq!(payload["attachments"], serde_json::json!([banner]));
+
+    let val = notify(
+        &code,
+        "test",
+        None,
+        Some("Hello".to_string()),
+        None,
+        Some(vec!["not a url".to_string()]),
+        PRIORITY_NORMAL,
+    )
+    .await;
+    assert!(matches!(val, Err(KohakuError::ValidationError(_))));
+}
+
+#[rstest]
+#[case(vec![], true)]
+#[case(vec!["https://example.com/banner.png"], true)]
+#[case(vec!["http://cdn.example.com/a.jpg?size=large", "https://example.com/b.gif"], true)]
+#[case(vec!["example.com/banner.png"], false)]
+#[case(vec!["ftp://example.com/banner.png"], false)]
+#[case(vec!["file:///etc/passwd"], false)]
+#[case(vec!["https://"], false)]
+#[case(vec!["https://example.com/a.png"; ATTACHMENTS_MAX + 1], false)]
+fn test_validate_attachments(#[case] urls: Vec<&str>, #[case] valid: bool) {
+    let urls: Vec<String> = urls.into_iter().map(str::to_string).collect();
+    assert_eq!(validate_attachments(&urls).is_ok(), valid);
+}
+
+#[test]
+fn test_notification_data_attachments_default() {
+    // Payloads of older producers without attachments still deserialize
+    let notification: NotificationData = serde_json::from_value(serde_json::json!({
+        "code": "code",
+        "triggering_event": "test",
+        "channel_id": 10,
+        "guild_id": 20,
+        "thread_id": null,
+        "embed": null,
+        "message": "Hello",
+        "mention_roles": [],
+    }))
+    .unwrap();
+    assert_eq!(notification.attachments, None);
+}
+
+#[actix_web::test]
+#[serial(ws_manager)]
+async fn test_dispatch_orders_by_priority() {
+    let _ = init_manager();
+    let manager = get_manager().unwrap();
+    let (tx, mut rx) = unbounded_channel();
+    let info = WsClientInfo {
+        client_id: Uuid::new_v4(),
+        owner: "test-dispatch".to_string(),
+        key_id: i32::MAX,
+        scopes: vec![],
+        compression: false,
+        tags: HashMap::new(),
+    };
+    manager
+        .register(info, tx, Arc::new(ConnectionStats::new(0)), None)
+        .unwrap();
+
+    let notification = |channel_id: i64, priority: u8| NotificationData {
+        code: "dispatch-order".to_string(),
+        triggering_event: "test".to_string(),
+        channel_id,
+        guild_id: 20,
+        thread_id: None,
+        embed: None,
+        message: Some("Hello".to_string()),
+        mention_roles: vec![],
+        attachments: None,
+        priority,
+    };
+    dispatch(&[
+        notification(1, 0),
+        notification(2, u8::MAX),
+        notification(3, PRIORITY_NORMAL),
+    ])
+    .await
+    .unwrap();
+    manager.remove_connection(&i32::MAX).await;
+
+    // Other tests may broadcast to the global manager meanwhile
+    let outbound = std::iter::from_fn(|| rx.try_recv().ok())
+        .find(|outbound| match &outbound.message {
+            Message::Text(text) => text.contains("dispatch-order"),
+            _ => false,
+        })
+        .unwrap();
+    assert_eq!(outbound.priority, u8::MAX);
+    let Message::Text(text) = outbound.message else {
+        unreachable!()
+    };
+    let sent: Vec<NotificationData> = serde_json::from_str(&text).unwrap();
+    let channels: Vec<i64> = sent.iter().map(|n| n.channel_id).collect();
+    assert_eq!(channels, vec![2, 3, 1]);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_guild() {
+    setup_db();
+    let guild = rand::random_range(1_000_000..i64::MAX);
+    let codes = [fresh_code().await, fresh_code().await];
+
+    // Channel 1 under both codes, channel 3 only via a thread
+    subscribe(&codes[0], 1, guild, None, None, vec![], None)
+        .await
+        .unwrap();
+    subscribe(&codes[1], 1, guild, None, None, vec![], None)
+        .await
+        .unwrap();
+    subscribe(&codes[1], 2, guild, None, None, vec![], None)
+        .await
+        .unwrap();
+    subscribe(&codes[0], 3, guild, Some(30), None, vec![], None)
+        .await
+        .unwrap();
+    // Paused subscriptions and other guilds are not notified
+    let paused = subscribe(&codes[0], 4, guild, None, None, vec![], None)
+        .await
+        .unwrap();
+    set_subscription_active(paused.id, false).await.unwrap();
+    subscribe(&codes[0], 5, guild - 1, None, None, vec![], None)
+        .await
+        .unwrap();
+
+    let report = notify_guild(guild, None, Some("Maintenance at 03:00".to_string()))
+        .await
+        .unwrap();
+    let channels: Vec<i64> = report.sent.iter().map(|n| n.channel_id).collect();
+    assert_eq!(channels, vec![1, 2, 3]);
+    assert!(report.sent.iter().all(|n| n.code == GUILD_BROADCAST_CODE
+        && n.guild_id == guild
+        && n.thread_id.is_none()
+        && n.message.as_deref() == Some("Maintenance at 03:00")));
+
+    for code in &codes {
+        unregister(code).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_notify_guild_requires_content() {
+    for message in [None, Some("  ".to_string())] {
+        assert!(matches!(
+            notify_guild(20, None, message).await,
+            Err(KohakuError::ValidationError(_))
+        ));
+    }
+}
+
+/// Helper: Serves a single webhook request on a local port, answering with `status`
+///
+/// # Returns
+/// The URL of the webhook and a handle yielding the received request
+fn mock_webhook(status: u16) -> (String, std::thread::JoinHandle<(String, String)>) {
+    let (url, handle) = mock_http_server(status, "", Duration::ZERO);
+    (format!("{}/api/webhooks/1/token", url), handle)
+}
+
+#[rstest]
+#[case("https://discord.com/api/webhooks/123/abc-DEF_456", true)]
+#[case("https://discordapp.com/api/webhooks/123/abc", true)]
+#[case("https://ptb.discord.com/api/webhooks/123/abc/", true)]
+#[case("http://discord.com/api/webhooks/123/abc", false)]
+#[case("https://example.com/api/webhooks/123/abc", false)]
+#[case("https://discord.com/api/webhooks/abc/def", false)]
+#[case("https://discord.com/api/webhooks/123", false)]
+#[case("https://discord.com/api/channels/123/messages", false)]
+#[case("discord.com/api/webhooks/123/abc", false)]
+fn test_validate_webhook_url(#[case] url: &str, #[case] valid: bool) {
+    assert_eq!(validate_webhook_url(url).is_ok(), valid);
+}
+
+#[test]
+fn test_webhook_payload() {
+    // #1 Attachments are linked below the message, nobody is mentioned
+    let payload = WebhookPayload::new(
+        Some("Hello".to_string()),
+        Some(json!({ "title": "Title" })),
+        &["https://example.com/a.png".to_string()],
+    );
+    assert_eq!(
+        serde_json::to_value(&payload).unwrap(),
+        json!({
+            "content": "Hello\nhttps://example.com/a.png",
+            "embeds": [{ "title": "Title" }],
+            "allowed_mentions": { "parse": [] },
+        })
+    );
+
+    // #2 Embed only
+    let payload = WebhookPayload::new(None, Some(json!({ "title": "Title" })), &[]);
+    assert_eq!(payload.content, None);
+}
+
+#[tokio::test]
+async fn test_post_webhook() {
+    let payload = WebhookPayload::new(Some("Hello".to_string()), None, &[]);
+
+    // #1 Accepted
+    let (url, handle) = mock_webhook(204);
+    post_webhook(&url, &payload).await.unwrap();
+    let body: serde_json::Value = serde_json::from_str(&handle.join().unwrap().1).unwrap();
+    assert_eq!(body["content"], "Hello");
+
+    // #2 Rejected
+    let (url, handle) = mock_webhook(500);
+    let result = post_webhook(&url, &payload).await;
+    handle.join().unwrap();
+    assert!(matches!(result, Err(KohakuError::ExternalServiceError(_))));
+    BREAKER.record_success(WEBHOOK_SERVICE);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+#[serial(ws_manager)]
+async fn test_notify_records_outcome() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+
+    // #1 No client online => Failure recorded
+    let report = notify(
+        &code,
+        "test",
+        None,
+        Some("first".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.offline, 1);
+    let stored = get_code(&code).await.unwrap();
+    assert_eq!(
+        stored.last_error.as_deref(),
+        Some("No client online to deliver 1 notification(s)")
+    );
+    assert!(stored.last_error_at.is_some());
+    assert!(stored.last_success.is_none());
+
+    // #2 Delivered => Success recorded, the last error is kept
+    let manager = get_manager().unwrap();
+    let (tx, _rx) = unbounded_channel();
+    let info = WsClientInfo {
+        client_id: Uuid::new_v4(),
+        owner: "test-outcome".to_string(),
+        key_id: i32::MAX - 4,
+        scopes: vec![],
+        compression: false,
+        tags: HashMap::new(),
+    };
+    manager
+        .register(info, tx, Arc::new(ConnectionStats::new(0)), None)
+        .unwrap();
+    let report = notify(
+        &code,
+        "test",
+        None,
+        Some("second".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap();
+    manager.remove_connection(&(i32::MAX - 4)).await;
+    assert_eq!(report.offline, 0);
+    let stored = get_code(&code).await.unwrap();
+    assert!(stored.last_success.is_some());
+    assert!(stored.last_error.is_some());
+
+    // #3 Empty after formatting => Failure recorded
+    let code = fresh_code().await;
+    subscribe(
+        &code,
+        10,
+        20,
+        None,
+        Some("{content}".to_string()),
+        vec![],
+        None,
+    )
+    .await
+    .unwrap();
+    let report = notify(&code, "test", None, None, None, None, PRIORITY_NORMAL)
+        .await
+        .unwrap();
+    assert!(report.sent.is_empty());
+    assert_eq!(
+        get_code(&code).await.unwrap().last_error.as_deref(),
+        Some("All 1 notification(s) were empty after formatting")
+    );
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+#[serial(ws_manager)]
+async fn test_notify_webhook_fallback() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(
+        &code,
+        10,
+        20,
+        None,
+        Some("[{code}] {content}".to_string()),
+        vec![30],
+        None,
+    )
+    .await
+    .unwrap();
+    subscribe(&code, 11, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+
+    // No client is connected: The unformatted message is posted once
+    let (url, handle) = mock_webhook(204);
+    WEBHOOK_URL.with(|webhook| *webhook.borrow_mut() = Some(url));
+    let report = notify(
+        &code,
+        "test",
+        None,
+        Some("fallback".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await;
+    WEBHOOK_URL.with(|webhook| *webhook.borrow_mut() = None);
+    let report = report.unwrap();
+    assert_eq!(report.offline, 2);
+    assert!(report.fallback);
+    let body: serde_json::Value = serde_json::from_str(&handle.join().unwrap().1).unwrap();
+    assert_eq!(body["content"], "fallback");
+}
+
+#[rstest]
+#[case(Utc::now() - chrono::Duration::seconds(1), Some("Hello"))]
+#[case(Utc::now() + chrono::Duration::hours(1), None)]
+#[tokio::test]
+async fn test_schedule_notification_invalid(
+    #[case] when: chrono::DateTime<Utc>,
+    #[case] message: Option<&str>,
+) {
+    let result =
+        schedule_notification("test:code", "test", None, message.map(str::to_string), when).await;
+    assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+#[serial(ws_manager)]
+async fn test_schedule_notification_fires_once() {
+    setup_db();
+    let _ = init_manager();
+    let scheduler = Arc::new(Scheduler::new().await.unwrap());
+    scheduler.start().await.unwrap();
+    NOTIFY_SCHEDULER.with(|notify_scheduler| *notify_scheduler.borrow_mut() = Some(scheduler));
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let manager = get_manager().unwrap();
+    let (tx, mut rx) = unbounded_channel();
+    let info = WsClientInfo {
+        client_id: Uuid::new_v4(),
+        owner: "test-schedule".to_string(),
+        key_id: i32::MAX - 2,
+        scopes: vec![],
+        compression: false,
+        tags: HashMap::new(),
+    };
+    manager
+        .register(info, tx, Arc::new(ConnectionStats::new(0)), None)
+        .unwrap();
+
+    // #1 Not sent before its time
+    let scheduled = schedule_notification(
+        &code,
+        "test",
+        None,
+        Some("scheduled".to_string()),
+        Utc::now() + chrono::Duration::seconds(1),
+    )
+    .await
+    .unwrap();
+    assert!(scheduled.sent_at.is_none());
+    let received = |rx: &mut tokio::sync::mpsc::UnboundedReceiver<Outbound>| {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter(|outbound| match &outbound.message {
+                Message::Text(text) => text.contains(&code),
+                _ => false,
+            })
+            .count()
+    };
+    assert_eq!(received(&mut rx), 0);
+
+    // #2 Sent once at its time
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(received(&mut rx), 1);
+
+    // #3 A repeated fire doesn't send it again
+    assert!(send_scheduled(scheduled.id).await.unwrap().is_none());
+    manager.remove_connection(&(i32::MAX - 2)).await;
+    NOTIFY_SCHEDULER.with(|notify_scheduler| *notify_scheduler.borrow_mut() = None);
+    assert_eq!(received(&mut rx), 0);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_purge_sent_scheduled() {
+    use schema::scheduled_notifications::dsl::*;
+    setup_db();
+    let code_ = fresh_code().await;
+    let now = Utc::now().naive_utc();
+
+    // Sent long ago, sent recently, still pending
+    let sent = [
+        Some(now - chrono::Duration::days(400)),
+        Some(now - chrono::Duration::days(1)),
+        None,
+    ];
+    let mut ids = Vec::new();
+    for sent_at_ in sent {
+        let new = NewScheduledNotification {
+            code: code_.clone(),
+            triggering_event: "test".to_string(),
+            embed: None,
+            message: Some("scheduled".to_string()),
+            send_at: now,
+        };
+        let id_: i32 = with_connection(move |conn| {
+            diesel::insert_into(scheduled_notifications)
+                .values((&new, sent_at.eq(sent_at_)))
+                .returning(id)
+                .get_result(conn)
+                .map_err(KohakuError::DatabaseError)
+        })
+        .await
+        .unwrap();
+        ids.push(id_);
+    }
+
+    let cutoff = now - chrono::Duration::days(365);
+    assert!(purge_sent_scheduled(cutoff).await.unwrap() >= 1);
+
+    // Only the old sent notification is purged
+    let code_filter = code_.clone();
+    let mut remaining: Vec<i32> = with_connection(move |conn| {
+        scheduled_notifications
+            .filter(code.eq(code_filter))
+            .select(id)
+            .load(conn)
+            .map_err(KohakuError::DatabaseError)
+    })
+    .await
+    .unwrap();
+    remaining.sort();
+    assert_eq!(remaining, ids[1..].to_vec());
+    unregister(&code_).await.unwrap();
+}
+
+// ========================================== Cache ============================================ //
+
+#[test]
+fn test_ttl_cache_expiry() {
+    let cache = TtlCache::new(10);
+    cache.insert_at("test:a", 1, 100);
+    assert_eq!(cache.get_at("test:a", 109), Some(1));
+    assert_eq!(cache.get_at("test:a", 110), None);
+    // Expired entries are dropped
+    assert_eq!(cache.get_at("test:a", 100), None);
+}
+
+#[test]
+fn test_ttl_cache_invalidate_and_update() {
+    let cache = TtlCache::new(10);
+    cache.insert_at("test:a", 1, 100);
+    cache.insert_at("test:b", 2, 100);
+    cache.update("test:a", |value| *value += 1);
+    cache.update("test:missing", |value| *value += 1);
+    assert_eq!(cache.get_at("test:a", 100), Some(2));
+    assert_eq!(cache.get_at("test:missing", 100), None);
+
+    cache.invalidate("test:a");
+    assert_eq!(cache.get_at("test:a", 100), None);
+    assert_eq!(cache.get_at("test:b", 100), Some(2));
+    cache.clear();
+    assert_eq!(cache.get_at("test:b", 100), None);
+}
+
+#[test]
+fn test_ttl_cache_disabled() {
+    let cache = TtlCache::new(0);
+    cache.insert_at("test:a", 1, 100);
+    assert_eq!(cache.get_at("test:a", 100), None);
+}
+
+#[test]
+fn test_ttl_cache_reserve() {
+    let cache = TtlCache::new(10);
+    assert!(cache.reserve_at("test:a", 1, 100));
+    assert!(!cache.reserve_at("test:a", 2, 105));
+    assert_eq!(cache.get_at("test:a", 105), Some(1));
+    // Expired entries can be reserved again
+    assert!(cache.reserve_at("test:a", 3, 110));
+
+    // Disabled cache never blocks
+    let disabled = TtlCache::new(0);
+    assert!(disabled.reserve_at("test:a", 1, 100));
+    assert!(disabled.reserve_at("test:a", 1, 100));
+}
+
+/// Helper: Deletes the subscriptions of a code behind the back of the cache
+async fn delete_subscriptions_uncached(code: &str) {
+    let code = code.to_string();
+    with_connection(move |conn| {
+        diesel::delete(
+            schema::notification_targets::table.filter(schema::notification_targets::code.eq(code)),
+        )
+        .execute(conn)
+        .map_err(KohakuError::DatabaseError)
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscription_cache_hit() {
+    setup_db();
+    let code = fresh_code().await;
+    subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    assert_eq!(get_active_subscriptions(&code).await.unwrap().len(), 1);
+
+    // Served from the cache, so the deletion goes unnoticed
+    delete_subscriptions_uncached(&code).await;
+    assert_eq!(get_active_subscriptions(&code).await.unwrap().len(), 1);
+
+    // Subscribing invalidates the cache
+    subscribe(&code, 11, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    let targets = get_active_subscriptions(&code).await.unwrap();
+    assert_eq!(targets.len(), 1);
+    assert_eq!(targets[0].channel_id, 11);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_subscription_cache_invalidation() {
+    setup_db();
+    let code = fresh_code().await;
+    let target = subscribe(&code, 10, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    subscribe(&code, 11, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    assert_eq!(get_active_subscriptions(&code).await.unwrap().len(), 2);
+
+    set_subscription_active(target.id, false).await.unwrap();
+    assert_eq!(get_active_subscriptions(&code).await.unwrap().len(), 1);
+
+    unsubscribe(&code, 11, 20, None).await.unwrap();
+    assert!(get_active_subscriptions(&code).await.unwrap().is_empty());
+
+    subscribe_many(&[code.as_str()], 12, 20, None, None, vec![], None)
+        .await
+        .unwrap();
+    assert_eq!(get_active_subscriptions(&code).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_code_cache_invalidation() {
+    setup_db();
+    let code = fresh_code().await;
+    assert_eq!(get_code(&code).await.unwrap().code, code);
+    assert!(get_all_codes()
+        .await
+        .unwrap()
+        .iter()
+        .any(|c| c.code == code));
+
+    // Registering refreshes the list of all codes
+    let other = fresh_code().await;
+    assert!(get_all_codes()
+        .await
+        .unwrap()
+        .iter()
+        .any(|c| c.code == other));
+
+    unregister(&code).await.unwrap();
+    assert!(get_code(&code).await.is_err());
+    assert!(!get_all_codes()
+        .await
+        .unwrap()
+        .iter()
+        .any(|c| c.code == code));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_update_code_ts() {
+    setup_db();
+    let code = fresh_code().await;
+    let now = Utc::now();
+
+    let updated = update_code_ts(&code, now).await.unwrap();
+    assert_eq!(
+        updated.last_used.map(|ts| ts.and_utc().timestamp()),
+        Some(now.timestamp())
+    );
+    assert!(matches!(
+        update_code_ts("test:not-registered", now).await,
+        Err(KohakuError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_code_cache_tracks_last_used() {
+    setup_db();
+    let code = fresh_code().await;
+    assert!(get_code(&code).await.unwrap().last_used.is_none());
+
+    notify(
+        &code,
+        "test",
+        None,
+        Some("Hello".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap();
+    assert!(get_code(&code).await.unwrap().last_used.is_some());
+    let listed = get_all_codes().await.unwrap();
+    assert!(listed
+        .iter()
+        .find(|c| c.code == code)
+        .is_some_and(|c| c.last_used.is_some()));
+}
+
+// ========================================== Embeds =========================================== //
+
+#[test]
+fn test_embed_builder() {
+    let embed = EmbedBuilder::new()
+        .title("Backup finished")
+        .unwrap()
+        .description("All tables were saved")
+        .unwrap()
+        .url("https://example.com/backups/1")
+        .unwrap()
+        .color(0x1B6C8E)
+        .unwrap()
+        .timestamp(Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap())
+        .footer("Kohaku")
+        .unwrap()
+        .field("Duration", "42s", true)
+        .unwrap()
+        .field("Size", "1 GB", false)
+        .unwrap()
+        .build();
+
+    assert_eq!(
+        embed,
+        json!({
+            "title": "Backup finished",
+            "description": "All tables were saved",
+            "url": "https://example.com/backups/1",
+            "color": 0x1B6C8E,
+            "timestamp": "2025-01-02T03:04:05Z",
+            "footer": {"text": "Kohaku"},
+            "fields": [
+                {"name": "Duration", "value": "42s", "inline": true},
+                {"name": "Size", "value": "1 GB", "inline": false},
+            ],
+        })
+    );
+}
+
+#[test]
+fn test_embed_builder_omits_unset() {
+    let embed = EmbedBuilder::new().title("Only a title").unwrap().build();
+    assert_eq!(embed, json!({"title": "Only a title"}));
+}
+
+#[rstest]
+#[case::title(EmbedBuilder::new().title("a".repeat(EMBED_TITLE_MAX + 1)))]
+#[case::description(EmbedBuilder::new().description("a".repeat(EMBED_DESCRIPTION_MAX + 1)))]
+#[case::footer(EmbedBuilder::new().footer("a".repeat(EMBED_FOOTER_MAX + 1)))]
+#[case::field_name(EmbedBuilder::new().field("a".repeat(EMBED_FIELD_NAME_MAX + 1), "value", false))]
+#[case::field_value(EmbedBuilder::new().field("name", "a".repeat(EMBED_FIELD_VALUE_MAX + 1), false))]
+#[case::color(EmbedBuilder::new().color(EMBED_COLOR_MAX + 1))]
+#[case::url(EmbedBuilder::new().url("ftp://example.com"))]
+#[case::relative_url(EmbedBuilder::new().url("/backups/1"))]
+fn test_embed_builder_limits(#[case] result: Result<EmbedBuilder, KohakuError>) {
+    assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+}
+
+#[test]
+fn test_embed_builder_field_count() {
+    let mut builder = EmbedBuilder::new();
+    for i in 0..EMBED_FIELDS_MAX {
+        builder = builder.field(format!("{}", i), "value", true).unwrap();
+    }
+    assert!(matches!(
+        builder.field("one too many", "value", true),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[test]
+fn test_embed_builder_total_length() {
+    // Each part is within its own limit, but combined they exceed the total limit
+    let builder = EmbedBuilder::new()
+        .description("a".repeat(EMBED_DESCRIPTION_MAX))
+        .unwrap()
+        .field("name", "a".repeat(EMBED_FIELD_VALUE_MAX), false)
+        .unwrap();
+    assert!(matches!(
+        builder.footer("a".repeat(EMBED_FOOTER_MAX)),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+// ======================================== Templates ========================================== //
+
+/// Helper: Context with fixed values
+fn template_ctx(embed: Option<&serde_json::Value>) -> TemplateContext<'_> {
+    TemplateContext {
+        content: "v1.2 released",
+        code: "game:release",
+        triggering_event: "ReleaseScraper",
+        timestamp: Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap(),
+        embed,
+    }
+}
+
+#[rstest]
+#[case("{content}", "v1.2 released")]
+#[case("New: {content}!", "New: v1.2 released!")]
+#[case("{{content}}", "v1.2 released")]
+#[case(
+    "[{{ code }}] {{content}} ({{triggering_event}})",
+    "[game:release] v1.2 released (ReleaseScraper)"
+)]
+#[case("{{timestamp}}", "2026-01-02T03:04:05+00:00")]
+#[case("{{timestamp:discord}}", "<t:1767323045>")]
+#[case("{{role:123}} {{emote:kohaku:456}}", "<@&123> <:kohaku:456>")]
+#[case("{{unknown}} {{content}}", "{{unknown}} v1.2 released")]
+#[case("{{role:abc}}", "{{role:abc}}")]
+#[case("{{emote:bad name:1}}", "{{emote:bad name:1}}")]
+#[case("{{content", "{{content")]
+#[case("plain text", "plain text")]
+fn test_render_template(#[case] format: &str, #[case] expected: &str) {
+    assert_eq!(render(format, &template_ctx(None)), expected);
+}
+
+#[rstest]
+#[case("{{embed.title}}", "Patch 1.2")]
+#[case("{{embed.fields.0.value}} / {{embed.color}}", "Bugfixes / 16711680")]
+#[case("{{embed.missing}}", "{{embed.missing}}")]
+#[case("{{embed.fields}}", "{{embed.fields}}")]
+fn test_render_template_embed(#[case] format: &str, #[case] expected: &str) {
+    let embed = serde_json::json!({
+        "title": "Patch 1.2",
+        "color": 16711680,
+        "fields": [{ "name": "Changes", "value": "Bugfixes" }]
+    });
+    assert_eq!(render(format, &template_ctx(Some(&embed))), expected);
+}
+
+#[test]
+fn test_render_template_without_embed() {
+    assert_eq!(
+        render("{{embed.title}}", &template_ctx(None)),
+        "{{embed.title}}"
+    );
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_notify_template() {
+    setup_db();
+    let _ = init_manager();
+    let code = fresh_code().await;
+    subscribe(
+        &code,
+        10,
+        20,
+        None,
+        Some("{{embed.title}}: {{content}} via {{triggering_event}}".to_string()),
+        vec![],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let embed = serde_json::json!({ "title": "Patch" });
+    let sent = notify(
+        &code,
+        "Scraper",
+        Some(embed),
+        Some("Out now".to_string()),
+        None,
+        None,
+        PRIORITY_NORMAL,
+    )
+    .await
+    .unwrap()
+    .sent;
+    assert_eq!(
+        sent[0].message.as_deref(),
+        Some("Patch: Out now via Scraper")
+    );
+}
+
+// ========================================= Routes ============================================ //
+
+#[rstest]
+#[case(TestRequest::get().uri("/codes"))]
+#[case(TestRequest::post().uri("/codes/bulk"))]
+#[case(TestRequest::get().uri("/codes/test:code"))]
+#[case(TestRequest::patch().uri("/codes/test:code"))]
+#[case(TestRequest::delete().uri("/codes/test:code"))]
+#[case(TestRequest::post().uri("/subscriptions"))]
+#[case(TestRequest::post().uri("/subscriptions/manage?subscribe=a&channel_id=1&guild_id=2"))]
+#[case(TestRequest::get().uri("/subscriptions/by-code?prefix=game"))]
+#[case(TestRequest::post().uri("/subscriptions/manage/batch"))]
+#[case(TestRequest::patch().uri("/subscriptions/1/active"))]
+#[case(TestRequest::get().uri("/subscriptions/export?guild_id=1"))]
+#[case(TestRequest::post().uri("/subscriptions/import"))]
+#[case(TestRequest::post().uri("/notify"))]
+#[case(TestRequest::post().uri("/notify/schedule"))]
+#[actix_web::test]
+async fn test_routes_require_token(#[case] req: TestRequest) {
+    let app = init_service(App::new().configure(routes::configure)).await;
+    let resp = call_service(&app, req.to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,1160 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+    sync::Arc,
+    time::Duration,
+};
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body_json, TestRequest},
+    web, App, HttpServer,
+};
+use actix_ws::{CloseCode, Message};
+use chrono::Utc;
+use flate2::read::ZlibDecoder;
+use futures_util::{SinkExt, StreamExt};
+use rstest::rstest;
+use serde_json::json;
+use serial_test::serial;
+use tokio::sync::mpsc::{error::TryRecvError, unbounded_channel, UnboundedReceiver};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message as TungsteniteMessage},
+};
+use uuid::Uuid;
+
+use crate::{
+    api,
+    utils::{
+        comm::{
+            auth::{
+                api_key::{generate_key, hash_key},
+                jwt::{get_jwtservice, init_jwtservice},
+                models::{create_apikey, TokenType},
+            },
+            events::{dispatcher::dispatch, models::NotificationData},
+            websocket::{
+                connection::{
+                    deflate, error_frame, handle_message, validate_tags, ConnectionStats,
+                    MessageType, Outbound, OutboundQueue, WsClientInfo, WsMessage,
+                    CLOSE_CODE_REPLACED, PRIORITY_NORMAL, PROTOCOL_MIN_VERSION, PROTOCOL_VERSION,
+                    RESUME_BUFFER_MAX_MESSAGES, RETRY_BASE_DELAY_SEC, RETRY_MAX_ATTEMPTS,
+                    TAGS_MAX_COUNT, TAG_MAX_LEN,
+                },
+                limiter::RateLimiter,
+                manager::{
+                    get_manager, init_manager, DeliveryReport, RetryReport, WsConnectionManager,
+                },
+                routes::ws_handler,
+                signing::{sign_message, verify_message},
+            },
+        },
+        error::KohakuError,
+        tests::{register_ws_client, setup_db},
+    },
+};
+
+// ======================================= Rate Limiter ======================================== //
+
+#[test]
+fn test_rate_limiter_allows_within_limit() {
+    let mut limiter = RateLimiter::new(3, 10);
+    assert!(limiter.check_and_add_at(0));
+    assert!(limiter.check_and_add_at(1_000));
+    assert!(limiter.check_and_add_at(2_000));
+}
+
+#[test]
+fn test_rate_limiter_blocks_over_limit() {
+    let mut limiter = RateLimiter::new(3, 10);
+    for i in 0..3 {
+        assert!(limiter.check_and_add_at(i * 100));
+    }
+    assert!(!limiter.check_and_add_at(500));
+    // Rejected messages are not counted
+    assert!(!limiter.check_and_add_at(9_000));
+}
+
+#[test]
+fn test_rate_limiter_window_slides() {
+    let mut limiter = RateLimiter::new(2, 10);
+    assert!(limiter.check_and_add_at(0));
+    assert!(limiter.check_and_add_at(5_000));
+    assert!(!limiter.check_and_add_at(9_999));
+
+    // First message left the window
+    assert!(limiter.check_and_add_at(10_000));
+    assert!(!limiter.check_and_add_at(14_999));
+    // Second message left the window
+    assert!(limiter.check_and_add_at(15_000));
+}
+
+#[test]
+fn test_rate_limiter_zero_messages() {
+    let mut limiter = RateLimiter::new(0, 10);
+    assert!(!limiter.check_and_add());
+}
+
+#[test]
+fn test_rate_limiter_many_messages() {
+    let mut limiter = RateLimiter::new(1_000, 1);
+    for i in 0..100_000 {
+        // 1 message per ms => exactly at the limit
+        assert!(limiter.check_and_add_at(i));
+    }
+}
+
+// ======================================== Compression ======================================== //
+
+#[test]
+fn test_deflate_roundtrip() {
+    let text = json!({"message": "hello ".repeat(200)}).to_string();
+    let compressed = deflate(&text).unwrap();
+    assert!(compressed.len() < text.len());
+
+    let mut decoded = String::new();
+    ZlibDecoder::new(compressed.as_slice())
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, text);
+}
+
+// ========================================== Signing ========================================== //
+
+const SIGNING_SECRET: &[u8] = b"shared-test-secret";
+
+#[test]
+fn test_sign_message_roundtrip() {
+    let payload = json!({"message": "hello"}).to_string();
+    let signed = sign_message(SIGNING_SECRET, &payload);
+    assert_eq!(verify_message(SIGNING_SECRET, &signed).unwrap(), payload);
+}
+
+#[rstest]
+#[case::wrong_secret(b"other-secret", false)]
+#[case::tampered_payload(SIGNING_SECRET, true)]
+fn test_verify_message_rejects(#[case] secret: &[u8], #[case] tamper: bool) {
+    let mut signed = sign_message(SIGNING_SECRET, r#"{"message":"hello"}"#);
+    if tamper {
+        signed = signed.replace("hello", "hellO");
+    }
+    assert!(matches!(
+        verify_message(secret, &signed),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[rstest]
+#[case::not_an_envelope(r#"{"message":"hello"}"#)]
+#[case::invalid_hex(r#"{"payload":"{}","signature":"zz"}"#)]
+#[case::odd_length(r#"{"payload":"{}","signature":"abc"}"#)]
+fn test_verify_message_invalid(#[case] message: &str) {
+    assert!(matches!(
+        verify_message(SIGNING_SECRET, message),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+// ======================================== Priorities ========================================= //
+
+fn text(priority: u8, content: &str) -> Outbound {
+    Outbound {
+        priority,
+        message: Message::Text(content.to_string().into()),
+    }
+}
+
+fn popped_texts(queue: &mut OutboundQueue) -> Vec<String> {
+    let mut texts = vec![];
+    while let Some(outbound) = queue.pop() {
+        if let Message::Text(text) = outbound.message {
+            texts.push(text.to_string());
+        }
+    }
+    texts
+}
+
+#[test]
+fn test_outbound_queue_order() {
+    let mut queue = OutboundQueue::default();
+    queue.push(text(PRIORITY_NORMAL, "routine-1"));
+    queue.push(text(0, "low"));
+    queue.push(text(PRIORITY_NORMAL, "routine-2"));
+    queue.push(text(u8::MAX, "alert-1"));
+    queue.push(text(PRIORITY_NORMAL, "routine-3"));
+    queue.push(text(u8::MAX, "alert-2"));
+
+    // Highest priority first, same priorities first in first out
+    assert_eq!(
+        popped_texts(&mut queue),
+        vec![
+            "alert-1",
+            "alert-2",
+            "routine-1",
+            "routine-2",
+            "routine-3",
+            "low"
+        ]
+    );
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_outbound_default_priority() {
+    let outbound = Outbound::from(Message::Close(None));
+    assert_eq!(outbound.priority, PRIORITY_NORMAL);
+}
+
+#[actix_web::test]
+async fn test_send_with_priority_delivery_order() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut rx = register_client(&manager, 1, vec![]);
+
+    // A burst queued up before the send task got to it
+    manager.send_to_client("routine-1", &1).await.unwrap();
+    manager.send_to_client("routine-2", &1).await.unwrap();
+    manager
+        .send_to_client_with_priority("alert", &1, u8::MAX)
+        .await
+        .unwrap();
+    manager
+        .broadcast_with_priority("digest", None, 10)
+        .await
+        .unwrap();
+
+    // Drained like the send task does
+    let mut queue = OutboundQueue::default();
+    while let Ok(outbound) = rx.try_recv() {
+        queue.push(outbound);
+    }
+    assert_eq!(
+        popped_texts(&mut queue),
+        vec![
+            r#""alert""#,
+            r#""routine-1""#,
+            r#""routine-2""#,
+            r#""digest""#
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn test_resume_keeps_priority() {
+    let manager = WsConnectionManager::new(100, 10);
+    let _rx = register_client(&manager, 1, vec![]);
+    let session_id = drop_client(&manager, 1).await;
+    manager.send_to_client("routine", &1).await.unwrap();
+    manager
+        .send_to_client_with_priority("alert", &1, u8::MAX)
+        .await
+        .unwrap();
+
+    let (registered, mut rx) =
+        register_ws_client(&manager, 1, "test-client-1", vec![], 0, Some(session_id));
+    assert!(registered.is_ok());
+    let priorities: Vec<u8> = std::iter::from_fn(|| rx.try_recv().ok())
+        .map(|outbound| outbound.priority)
+        .collect();
+    assert_eq!(priorities, vec![PRIORITY_NORMAL, u8::MAX]);
+}
+
+// ========================================== Manager ========================================== //
+
+fn register_client(
+    manager: &WsConnectionManager,
+    key_id: i32,
+    scopes: Vec<&str>,
+) -> UnboundedReceiver<Outbound> {
+    register_client_since(manager, key_id, scopes, Utc::now().timestamp())
+}
+
+fn register_client_since(
+    manager: &WsConnectionManager,
+    key_id: i32,
+    scopes: Vec<&str>,
+    connected_at: i64,
+) -> UnboundedReceiver<Outbound> {
+    let owner = format!("test-client-{}", key_id);
+    let (registered, rx) = register_ws_client(manager, key_id, &owner, scopes, connected_at, None);
+    assert!(registered.unwrap().is_none());
+    rx
+}
+
+/// Helper: Next queued message of a connection
+fn recv(rx: &mut UnboundedReceiver<Outbound>) -> Result<Message, TryRecvError> {
+    rx.try_recv().map(|outbound| outbound.message)
+}
+
+#[actix_web::test]
+async fn test_register_replaces_connection() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut displaced = register_client(&manager, 1, vec![]);
+    let displaced_id = manager.connection_info(&1).unwrap().client_id;
+
+    let (tx, mut rx) = unbounded_channel();
+    let info = WsClientInfo {
+        client_id: Uuid::new_v4(),
+        owner: "test-client-1".to_string(),
+        key_id: 1,
+        scopes: vec![],
+        compression: false,
+        tags: HashMap::new(),
+    };
+    let replaced = manager
+        .register(info.clone(), tx, Arc::new(ConnectionStats::new(0)), None)
+        .unwrap();
+    assert_eq!(replaced.map(|r| r.client_id), Some(displaced_id));
+
+    // The displaced client is told why it was closed
+    match recv(&mut displaced) {
+        Ok(Message::Close(Some(reason))) => {
+            assert_eq!(reason.code, CloseCode::Other(CLOSE_CODE_REPLACED));
+            assert!(reason.description.unwrap().contains("replaced"));
+        }
+        other => panic!("Expected a close message, got {:?}", other),
+    }
+    assert!(recv(&mut rx).is_err());
+    assert_eq!(
+        manager.connection_info(&1).unwrap().client_id,
+        info.client_id
+    );
+
+    // The ending displaced connection doesn't remove its successor
+    manager.release(&1, displaced_id).await;
+    assert!(manager.is_connected(&1));
+    manager.release(&1, info.client_id).await;
+    assert!(!manager.is_connected(&1));
+}
+
+#[actix_web::test]
+async fn test_register_connection_limit_per_owner() {
+    let manager = WsConnectionManager::new(100, 10).with_max_connections_per_owner(2);
+    let register = |key_id, owner| register_ws_client(&manager, key_id, owner, vec![], 0, None).0;
+
+    // Up to the limit
+    assert!(register(1, "tenant").is_ok());
+    assert!(register(2, "tenant").is_ok());
+
+    // Beyond the limit => Refused
+    assert!(matches!(
+        register(3, "tenant"),
+        Err(KohakuError::RateLimitExceeded(_))
+    ));
+    assert!(!manager.is_connected(&3));
+
+    // Replacing a connection and other owners are not affected
+    assert!(register(2, "tenant").unwrap().is_some());
+    assert!(register(4, "other").is_ok());
+
+    // A closed connection frees its slot
+    manager.remove_connection(&1).await;
+    assert!(register(3, "tenant").is_ok());
+    assert_eq!(manager.connection_count(), 3);
+}
+
+/// Helper: Drops the connection of a key like an ending connection task would
+async fn drop_client(manager: &WsConnectionManager, key_id: i32) -> Uuid {
+    let client_id = manager.connection_info(&key_id).unwrap().client_id;
+    manager.release(&key_id, client_id).await;
+    client_id
+}
+
+fn received_texts(rx: &mut UnboundedReceiver<Outbound>) -> Vec<String> {
+    let mut texts = vec![];
+    while let Ok(msg) = recv(rx) {
+        if let Message::Text(text) = msg {
+            texts.push(text.to_string());
+        }
+    }
+    texts
+}
+
+#[actix_web::test]
+async fn test_resume_replays_buffered_messages() {
+    let manager = WsConnectionManager::new(100, 10);
+    let _rx = register_client(&manager, 1, vec![]);
+    let session_id = drop_client(&manager, 1).await;
+    assert!(!manager.is_connected(&1));
+
+    // Messages to a dropped, resumable connection are buffered
+    manager.send_to_client("first", &1).await.unwrap();
+    let report = manager.broadcast("second", None).await.unwrap();
+    assert_eq!(report.delivered, vec![1]);
+
+    let (registered, mut rx) =
+        register_ws_client(&manager, 1, "test-client-1", vec![], 0, Some(session_id));
+    assert!(registered.is_ok());
+    assert!(manager.is_connected(&1));
+    assert_eq!(received_texts(&mut rx), vec![r#""first""#, r#""second""#]);
+
+    // The buffer was handed over, the session can't be resumed twice
+    assert_eq!(manager.prune_resumable(), 0);
+    let (registered, mut again) =
+        register_ws_client(&manager, 1, "test-client-1", vec![], 0, Some(session_id));
+    assert!(registered.is_ok());
+    assert!(received_texts(&mut again).is_empty());
+}
+
+#[actix_web::test]
+async fn test_resume_other_key_gets_nothing() {
+    let manager = WsConnectionManager::new(100, 10);
+    let _rx = register_client(&manager, 1, vec![]);
+    let session_id = drop_client(&manager, 1).await;
+    manager.send_to_client("secret", &1).await.unwrap();
+
+    // A session of key 1 can't be taken over by key 2
+    let (registered, mut rx) =
+        register_ws_client(&manager, 2, "test-client-2", vec![], 0, Some(session_id));
+    assert!(registered.is_ok());
+    assert!(received_texts(&mut rx).is_empty());
+    assert!(matches!(
+        manager.send_to_client("later", &1).await,
+        Err(KohakuError::ExternalServiceError(_))
+    ));
+}
+
+#[actix_web::test]
+async fn test_new_connection_discards_buffer() {
+    let manager = WsConnectionManager::new(100, 10);
+    let _rx = register_client(&manager, 1, vec![]);
+    let session_id = drop_client(&manager, 1).await;
+    manager.send_to_client("missed", &1).await.unwrap();
+
+    // Connecting without resuming starts a fresh session
+    let mut fresh = register_client(&manager, 1, vec![]);
+    assert!(received_texts(&mut fresh).is_empty());
+    drop_client(&manager, 1).await;
+    let (registered, mut resumed) =
+        register_ws_client(&manager, 1, "test-client-1", vec![], 0, Some(session_id));
+    assert!(registered.is_ok());
+    assert!(received_texts(&mut resumed).is_empty());
+}
+
+#[actix_web::test]
+async fn test_resume_buffer_is_capped() {
+    let manager = WsConnectionManager::new(1, 10);
+    let _rx = register_client(&manager, 1, vec![]);
+    let session_id = drop_client(&manager, 1).await;
+
+    // Buffering ignores the outbound limit, but keeps only the newest messages
+    for i in 0..RESUME_BUFFER_MAX_MESSAGES + 5 {
+        manager.send_to_client(i, &1).await.unwrap();
+    }
+    let (registered, mut rx) =
+        register_ws_client(&manager, 1, "test-client-1", vec![], 0, Some(session_id));
+    assert!(registered.is_ok());
+    let texts = received_texts(&mut rx);
+    assert_eq!(texts.len(), RESUME_BUFFER_MAX_MESSAGES);
+    assert_eq!(texts[0], "5");
+}
+
+#[actix_web::test]
+async fn test_disconnect_is_not_resumable() {
+    let manager = WsConnectionManager::new(100, 10);
+    let _rx = register_client(&manager, 1, vec![]);
+    let client_id = manager.connection_info(&1).unwrap().client_id;
+
+    // Closed by the server: The ending connection task finds no entry anymore
+    assert!(manager.disconnect(&1, None).await);
+    manager.release(&1, client_id).await;
+    assert!(manager.send_to_client("msg", &1).await.is_err());
+}
+
+#[actix_web::test]
+async fn test_connection_state() {
+    let manager = WsConnectionManager::new(100, 10);
+    assert!(!manager.is_connected(&1));
+    assert!(manager.connection_info(&1).is_none());
+
+    let _rx = register_client(&manager, 1, vec!["events:subscribe"]);
+    assert!(manager.is_connected(&1));
+    let info = manager.connection_info(&1).unwrap();
+    assert_eq!(info.key_id, 1);
+    assert_eq!(info.owner, "test-client-1");
+    assert_eq!(info.scopes, vec!["events:subscribe"]);
+
+    manager.remove_connection(&1).await;
+    assert!(!manager.is_connected(&1));
+    assert!(manager.connection_info(&1).is_none());
+}
+
+#[actix_web::test]
+async fn test_disconnect_closes_connection() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut rx = register_client(&manager, 1, vec![]);
+
+    assert!(manager.disconnect(&1, None).await);
+    assert!(matches!(recv(&mut rx), Ok(Message::Close(None))));
+    assert!(!manager.is_connected(&1));
+    assert!(!manager.disconnect(&1, None).await);
+}
+
+#[test]
+fn test_connection_stats_idle() {
+    let now = Utc::now().timestamp();
+    let stats = ConnectionStats::new(now - 100);
+    assert_eq!(stats.idle_secs(now), 100);
+
+    stats.record_pong();
+    assert!(stats.idle_secs(now) <= 0);
+    assert!(stats.last_pong() >= now);
+    assert_eq!(stats.last_activity(), now - 100);
+}
+
+#[test]
+fn test_connection_stats_counts() {
+    let stats = ConnectionStats::new(0);
+    assert_eq!((stats.messages_received(), stats.messages_sent()), (0, 0));
+
+    stats.record_received();
+    stats.record_sent();
+    stats.record_sent();
+    assert_eq!((stats.messages_received(), stats.messages_sent()), (1, 2));
+}
+
+#[actix_web::test]
+async fn test_connections_listing() {
+    let manager = WsConnectionManager::new(100, 10);
+    assert!(manager.connections().is_empty());
+
+    let now = Utc::now().timestamp();
+    let _second = register_client_since(&manager, 2, vec![], now);
+    let _first = register_client_since(&manager, 1, vec![], now - 60);
+
+    let connections = manager.connections();
+    assert_eq!(
+        connections
+            .iter()
+            .map(|summary| (summary.key_id, summary.owner.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(1, "test-client-1"), (2, "test-client-2")]
+    );
+    assert_eq!(
+        connections[0].client_id,
+        manager.connection_info(&1).unwrap().client_id
+    );
+    assert_eq!(connections[0].connected_at, now - 60);
+    assert!(connections[0].uptime_secs >= 60);
+    assert_eq!(connections[0].messages_received, 0);
+
+    manager.remove_connection(&1).await;
+    assert_eq!(manager.connections().len(), 1);
+}
+
+#[actix_web::test]
+async fn test_reap_stale_connections() {
+    let manager = WsConnectionManager::new(100, 10);
+    let now = Utc::now().timestamp();
+    let mut stale = register_client_since(&manager, 1, vec![], now - 600);
+    let mut fresh = register_client_since(&manager, 2, vec![], now);
+
+    let reaped = manager.reap_stale(300).await;
+    assert_eq!(reaped, vec![1]);
+    assert_eq!(manager.reaped_total(), 1);
+    assert_eq!(manager.connection_count(), 1);
+    assert!(!manager.is_connected(&1));
+
+    match recv(&mut stale) {
+        Ok(Message::Close(Some(reason))) => assert_eq!(reason.code, CloseCode::Away),
+        other => panic!("Expected a close message, got {:?}", other),
+    }
+    assert!(recv(&mut fresh).is_err());
+}
+
+#[actix_web::test]
+async fn test_broadcast_to_scope() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut subscriber = register_client(&manager, 1, vec!["events:subscribe"]);
+    let mut other = register_client(&manager, 2, vec!["admin:manage"]);
+
+    let report = manager
+        .broadcast_to_scope(json!({"announcement": "hello"}), "events:subscribe")
+        .await
+        .unwrap();
+    assert_eq!(report.delivered, vec![1]);
+
+    match recv(&mut subscriber) {
+        Ok(Message::Text(text)) => assert_eq!(text.to_string(), r#"{"announcement":"hello"}"#),
+        other => panic!("Expected a text message, got {:?}", other),
+    }
+    assert!(recv(&mut subscriber).is_err());
+    assert!(recv(&mut other).is_err());
+}
+
+#[actix_web::test]
+async fn test_broadcast_to_tag() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut shard_0 = register_client(&manager, 1, vec![]);
+    let mut shard_1 = register_client(&manager, 2, vec![]);
+    let mut untagged = register_client(&manager, 3, vec![]);
+
+    for (key_id, shard) in [(1, "0"), (2, "1")] {
+        let client_id = manager.connection_info(&key_id).unwrap().client_id;
+        let message = format!(
+            r#"{{"type": "set_tags", "tags": {{"shard": "{}"}}}}"#,
+            shard
+        );
+        let MessageType::SetTags { tags } = serde_json::from_str(&message).unwrap() else {
+            panic!("Expected a set_tags message");
+        };
+        assert!(manager.set_tags(&key_id, client_id, tags));
+    }
+    assert_eq!(
+        manager.connection_info(&1).unwrap().tags.get("shard"),
+        Some(&"0".to_string())
+    );
+
+    let report = manager
+        .broadcast_to_tag(json!({"announcement": "hello"}), "shard", "1")
+        .await
+        .unwrap();
+    assert_eq!(report.delivered, vec![2]);
+    assert!(matches!(recv(&mut shard_1), Ok(Message::Text(_))));
+    assert!(recv(&mut shard_0).is_err());
+    assert!(recv(&mut untagged).is_err());
+}
+
+#[actix_web::test]
+async fn test_set_tags_of_replaced_connection() {
+    let manager = WsConnectionManager::new(100, 10);
+    let _rx = register_client(&manager, 1, vec![]);
+    let tags = HashMap::from([("region".to_string(), "eu".to_string())]);
+
+    // Only the registered connection of the key may tag itself
+    assert!(!manager.set_tags(&1, Uuid::new_v4(), tags.clone()));
+    assert!(!manager.set_tags(&2, Uuid::new_v4(), tags));
+    assert!(manager.connection_info(&1).unwrap().tags.is_empty());
+}
+
+#[rstest]
+#[case(HashMap::new(), true)]
+#[case(HashMap::from([("shard".to_string(), "0".to_string())]), true)]
+#[case(HashMap::from([("".to_string(), "0".to_string())]), false)]
+#[case(HashMap::from([("shard".to_string(), "0".repeat(TAG_MAX_LEN + 1))]), false)]
+#[case((0..=TAGS_MAX_COUNT).map(|i| (i.to_string(), String::new())).collect(), false)]
+fn test_validate_tags(#[case] tags: HashMap<String, String>, #[case] valid: bool) {
+    assert_eq!(validate_tags(&tags).is_ok(), valid);
+}
+
+#[rstest]
+#[case(r#"{"type": "unknown"}"#)]
+#[case(r#"{"type": "set_tags"}"#)]
+#[case(r#"{"type": "set_tags", "tags": {"shard": 0}}"#)]
+#[case("not json")]
+fn test_message_type_invalid(#[case] message: &str) {
+    assert!(serde_json::from_str::<MessageType>(message).is_err());
+    assert!(serde_json::from_str::<WsMessage>(message).is_err());
+}
+
+#[test]
+fn test_ws_message_new() {
+    let before = Utc::now().timestamp();
+    let first = WsMessage::new(MessageType::SetTags {
+        tags: HashMap::new(),
+    });
+    let second = WsMessage::new(first.message.clone());
+    assert_ne!(first.message_id, second.message_id);
+    assert!(first.timestamp >= before && first.timestamp <= Utc::now().timestamp());
+
+    let id = Uuid::new_v4();
+    assert_eq!(WsMessage::with_id(id, first.message.clone()).message_id, id);
+}
+
+#[test]
+fn test_ws_message_roundtrip() {
+    let message = WsMessage::with_id(
+        Uuid::new_v4(),
+        MessageType::SetTags {
+            tags: HashMap::from([("shard".to_string(), "0".to_string())]),
+        },
+    );
+    let text = serde_json::to_string(&message).unwrap();
+    assert!(text.contains(r#""type":"set_tags""#));
+    assert_eq!(serde_json::from_str::<WsMessage>(&text).unwrap(), message);
+}
+
+#[test]
+fn test_ws_message_bare() {
+    // Clients may omit the frame metadata
+    let message: WsMessage =
+        serde_json::from_str(r#"{"type": "set_tags", "tags": {"shard": "0"}}"#).unwrap();
+    assert_eq!(message.version, PROTOCOL_VERSION);
+    assert_eq!(message.message_id, Uuid::nil());
+    assert_eq!(message.timestamp, 0);
+    assert!(matches!(message.message, MessageType::SetTags { .. }));
+}
+
+#[rstest]
+#[case::current(PROTOCOL_VERSION, true)]
+#[case::oldest(PROTOCOL_MIN_VERSION, true)]
+#[case::too_old(PROTOCOL_MIN_VERSION - 1, false)]
+#[case::too_new(PROTOCOL_VERSION + 1, false)]
+fn test_ws_message_version(#[case] version: u8, #[case] supported: bool) {
+    let text = format!(
+        r#"{{"type": "set_tags", "version": {}, "tags": {{}}}}"#,
+        version
+    );
+    let result = WsMessage::parse(&text);
+    assert_eq!(result.is_ok(), supported);
+    if supported {
+        assert_eq!(result.unwrap().version, version);
+    }
+}
+
+// ======================================= Error Frames ======================================== //
+
+#[rstest]
+#[case::validation(KohakuError::ValidationError("Bad tags".to_string()), "validation_error", "Bad tags")]
+#[case::forbidden(KohakuError::Forbidden("Missing scope".to_string()), "forbidden", "Missing scope")]
+#[case::rate_limited(KohakuError::RateLimitExceeded("Slow down".to_string()), "rate_limited", "Slow down")]
+#[case::not_found(KohakuError::NotFound("No such code".to_string()), "not_found", "No such code")]
+#[case::timeout(KohakuError::Timeout { operation: "subscribe".to_string() }, "timeout", "Timeout during subscribe")]
+#[case::database(
+    KohakuError::DatabaseError(diesel::result::Error::RollbackTransaction),
+    "database_error",
+    "Internal server error"
+)]
+fn test_error_frame(#[case] error: KohakuError, #[case] code: &str, #[case] message: &str) {
+    let id = Uuid::new_v4();
+    assert_eq!(
+        error_frame(id, &error),
+        MessageType::Error {
+            reply_to: id,
+            code: code.to_string(),
+            error: message.to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_error_frame_json() {
+    let id = Uuid::new_v4();
+    let frame = WsMessage::new(error_frame(
+        id,
+        &KohakuError::Unauthorized("Connection is not registered".to_string()),
+    ));
+    let json: serde_json::Value = serde_json::to_value(&frame).unwrap();
+    assert_eq!(json["type"], "error");
+    assert_eq!(json["reply_to"], id.to_string());
+    assert_eq!(json["code"], "unauthorized");
+    assert_eq!(json["error"], "Connection is not registered");
+}
+
+#[actix_web::test]
+async fn test_rejected_tags_reply_error() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut rx = register_client(&manager, 1, vec![]);
+    let client_id = manager.connection_info(&1).unwrap().client_id;
+
+    let frame = WsMessage::new(MessageType::SetTags {
+        tags: HashMap::from([("".to_string(), "0".to_string())]),
+    });
+    let id = frame.message_id;
+    handle_message(&manager, (1, client_id), frame).await;
+
+    let Ok(Message::Text(text)) = recv(&mut rx) else {
+        panic!("Expected an error frame");
+    };
+    let reply = serde_json::from_str::<WsMessage>(&text).unwrap().message;
+    assert!(matches!(
+        reply,
+        MessageType::Error { reply_to, code, .. } if reply_to == id && code == "validation_error"
+    ));
+    assert!(manager.connection_info(&1).unwrap().tags.is_empty());
+}
+
+#[test]
+fn test_encode_message_signed() {
+    let manager = WsConnectionManager::new(100, 10).with_signing_secret(SIGNING_SECRET.to_vec());
+    let rejection = KohakuError::RateLimitExceeded("Inbound rate limit exceeded".to_string());
+
+    let text = manager
+        .encode_message(error_frame(Uuid::nil(), &rejection))
+        .unwrap();
+    let payload = verify_message(SIGNING_SECRET, &text).unwrap();
+    let frame: WsMessage = serde_json::from_str(&payload).unwrap();
+    assert!(matches!(frame.message, MessageType::Error { code, .. } if code == "rate_limited"));
+}
+
+#[actix_web::test]
+async fn test_send_message() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut rx = register_client(&manager, 1, vec![]);
+    let message = MessageType::SetTags {
+        tags: HashMap::new(),
+    };
+    let id = manager.send_message(&1, message.clone()).await.unwrap();
+
+    let texts = received_texts(&mut rx);
+    let frame: WsMessage = serde_json::from_str(&texts[0]).unwrap();
+    assert_eq!(frame.message_id, id);
+    assert_eq!(frame.version, PROTOCOL_VERSION);
+    assert_eq!(frame.message, message);
+}
+
+/// Helper: Broadcasts a message to a client whose send task already ended
+async fn fail_delivery(manager: &WsConnectionManager, key_id: i32, payload: &str) -> i64 {
+    let rx = register_client(manager, key_id, vec![]);
+    drop(rx);
+    let now = Utc::now().timestamp();
+    let report = manager
+        .broadcast(payload, Some(vec![key_id]))
+        .await
+        .unwrap();
+    assert_eq!(report.failed, vec![key_id]);
+    assert!(!manager.is_connected(&key_id));
+    now
+}
+
+#[actix_web::test]
+async fn test_broadcast_reports_owners() {
+    let manager = WsConnectionManager::new(100, 10);
+    let _connected = register_client(&manager, 1, vec![]);
+    drop(register_client(&manager, 2, vec![]));
+    let _resumable = register_client(&manager, 3, vec![]);
+    drop_client(&manager, 3).await;
+
+    let report = manager
+        .broadcast("msg", Some(vec![1, 2, 3, 4]))
+        .await
+        .unwrap();
+    assert_eq!(report.delivered, vec![1, 3]);
+    assert_eq!(report.failed, vec![2, 4]);
+    // Owners are known for connected, failed and resumable clients, but not for unknown keys
+    assert_eq!(
+        report.owners,
+        BTreeMap::from([
+            (1, "test-client-1".to_string()),
+            (2, "test-client-2".to_string()),
+            (3, "test-client-3".to_string()),
+        ])
+    );
+}
+
+#[actix_web::test]
+async fn test_send_unserializable_payload() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut rx = register_client(&manager, 1, vec![]);
+    // JSON objects only allow string keys
+    let payload = HashMap::from([((1, 2), "value")]);
+
+    let err = manager.send_to_client(&payload, &1).await.unwrap_err();
+    assert!(matches!(err, KohakuError::InternalServerError(_)));
+    let err = manager.broadcast(&payload, None).await.unwrap_err();
+    assert!(matches!(err, KohakuError::InternalServerError(_)));
+    assert!(recv(&mut rx).is_err());
+    assert!(manager.is_connected(&1));
+}
+
+#[rstest]
+#[case::fits(64, true)]
+#[case::exact(32, true)]
+#[case::oversized(31, false)]
+#[actix_web::test]
+async fn test_send_max_payload_size(#[case] max_payload_bytes: usize, #[case] allowed: bool) {
+    let manager = WsConnectionManager::new(100, 10).with_max_payload_bytes(max_payload_bytes);
+    let mut rx = register_client(&manager, 1, vec![]);
+    // Serialized with quotes: 32 bytes
+    let payload = "x".repeat(30);
+
+    let result = manager.send_to_client(&payload, &1).await;
+    assert_eq!(result.is_ok(), allowed);
+    if !allowed {
+        assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+    }
+    assert_eq!(recv(&mut rx).is_ok(), allowed);
+}
+
+#[actix_web::test]
+async fn test_broadcast_signed() {
+    let manager = WsConnectionManager::new(100, 10).with_signing_secret(SIGNING_SECRET.to_vec());
+    let mut rx = register_client(&manager, 1, vec![]);
+    manager
+        .broadcast(json!({"message": "hello"}), None)
+        .await
+        .unwrap();
+
+    let texts = received_texts(&mut rx);
+    assert_eq!(texts.len(), 1);
+    let payload = verify_message(SIGNING_SECRET, &texts[0]).unwrap();
+    assert_eq!(payload, r#"{"message":"hello"}"#);
+}
+
+#[actix_web::test]
+async fn test_retry_redelivers_after_reconnect() {
+    let manager = WsConnectionManager::new(100, 10);
+    let failed_at = fail_delivery(&manager, 1, "hello").await;
+    assert_eq!(manager.pending_retries(), 1);
+
+    // Not due yet
+    let report = manager.retry_pending_at(failed_at).await;
+    assert_eq!(report, RetryReport::default());
+
+    let mut rx = register_client(&manager, 1, vec![]);
+    let report = manager
+        .retry_pending_at(failed_at + RETRY_BASE_DELAY_SEC + 1)
+        .await;
+    assert_eq!(report.delivered, vec![1]);
+    assert_eq!(received_texts(&mut rx), vec![r#""hello""#]);
+    assert_eq!(manager.pending_retries(), 0);
+
+    let log = manager.delivery_log();
+    assert_eq!(log.len(), 1);
+    assert_eq!(
+        (log[0].key_id, log[0].attempts, log[0].delivered),
+        (1, 2, true)
+    );
+}
+
+#[actix_web::test]
+async fn test_retry_backoff_and_give_up() {
+    let manager = WsConnectionManager::new(100, 10);
+    let mut now = fail_delivery(&manager, 1, "hello").await;
+
+    // The delay doubles with every failed attempt
+    for attempt in 1..RETRY_MAX_ATTEMPTS - 1 {
+        now += RETRY_BASE_DELAY_SEC * (1 << (attempt - 1)) + 1;
+        let report = manager.retry_pending_at(now).await;
+        assert_eq!(report.rescheduled, vec![1]);
+
+        let next_delay = RETRY_BASE_DELAY_SEC * (1 << attempt);
+        let report = manager.retry_pending_at(now + next_delay - 2).await;
+        assert_eq!(report, RetryReport::default());
+    }
+
+    let report = manager.retry_pending_at(now + 10_000).await;
+    assert_eq!(report.given_up, vec![1]);
+    assert_eq!(manager.pending_retries(), 0);
+
+    let log = manager.delivery_log();
+    assert_eq!(log.len(), 1);
+    assert_eq!(
+        (log[0].key_id, log[0].attempts, log[0].delivered),
+        (1, RETRY_MAX_ATTEMPTS, false)
+    );
+}
+
+#[actix_web::test]
+async fn test_outbound_limit_per_key() {
+    let manager = WsConnectionManager::new(2, 60);
+    let mut limited = register_client(&manager, 1, vec![]);
+
+    for _ in 0..2 {
+        manager.send_to_client("msg", &1).await.unwrap();
+    }
+    let err = manager.send_to_client("msg", &1).await.unwrap_err();
+    assert!(matches!(err, KohakuError::RateLimitExceeded(_)));
+
+    // Only the allowed messages were queued
+    assert!(recv(&mut limited).is_ok());
+    assert!(recv(&mut limited).is_ok());
+    assert!(recv(&mut limited).is_err());
+}
+
+#[actix_web::test]
+async fn test_broadcast_reports_rate_limited() {
+    let manager = WsConnectionManager::new(1, 60);
+    let _busy = register_client(&manager, 1, vec![]);
+    let _idle = register_client(&manager, 2, vec![]);
+
+    manager.send_to_client("first", &1).await.unwrap();
+    let mut report = manager.broadcast("second", None).await.unwrap();
+    report.delivered.sort();
+
+    assert_eq!(
+        report,
+        DeliveryReport {
+            delivered: vec![2],
+            rate_limited: vec![1],
+            failed: vec![],
+            owners: BTreeMap::from([
+                (1, "test-client-1".to_string()),
+                (2, "test-client-2".to_string()),
+            ]),
+        }
+    );
+    // Rate limited clients stay connected
+    assert!(matches!(
+        manager.send_to_client("third", &1).await,
+        Err(KohakuError::RateLimitExceeded(_))
+    ));
+}
+
+// ======================================== End-to-end ========================================= //
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+#[serial(ws_manager)]
+async fn test_ws_end_to_end() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let _ = init_manager();
+    let (api_key, prefix) = generate_key();
+    let created = create_apikey(
+        hash_key(&api_key).unwrap(),
+        prefix,
+        format!("e2e-{}", Uuid::new_v4().simple()),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let server = HttpServer::new(|| App::new().route("/api/ws", web::get().to(ws_handler)))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let server_handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    // #1 Connect with the API key
+    let mut request = format!("ws://{}/api/ws", addr)
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("X-API-Key", api_key.parse().unwrap());
+    let (mut client, response) = connect_async(request).await.unwrap();
+    assert_eq!(response.status(), 101);
+    assert!(get_manager().unwrap().is_connected(&created.id));
+
+    // #2 Notifications reach the client
+    let code = format!("test:{}", Uuid::new_v4().simple());
+    let notification = NotificationData {
+        code: code.clone(),
+        triggering_event: "test".to_string(),
+        channel_id: 10,
+        guild_id: 20,
+        thread_id: None,
+        embed: None,
+        message: Some("Hello".to_string()),
+        mention_roles: vec![],
+        attachments: None,
+        priority: PRIORITY_NORMAL,
+    };
+    dispatch(&[notification]).await.unwrap();
+
+    // Other tests may broadcast to the client as well
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(frame) = client.next().await {
+            if let TungsteniteMessage::Text(text) = frame.unwrap() {
+                if text.contains(&code) {
+                    return text.to_string();
+                }
+            }
+        }
+        panic!("Connection closed before the notification arrived");
+    })
+    .await
+    .unwrap();
+    let received: Vec<NotificationData> = serde_json::from_str(&received).unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message.as_deref(), Some("Hello"));
+
+    let _ = client.close(None).await;
+    get_manager().unwrap().remove_connection(&created.id).await;
+    server_handle.stop(false).await;
+}
+
+#[actix_web::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+#[serial(ws_manager)]
+async fn test_ws_connections_endpoint() {
+    setup_db();
+    let _ = init_jwtservice("encryption_key".as_bytes());
+    let _ = init_manager();
+    let (api_key, prefix) = generate_key();
+    let owner = format!("listed-{}", Uuid::new_v4().simple());
+    let created = create_apikey(
+        hash_key(&api_key).unwrap(),
+        prefix,
+        owner.clone(),
+        vec![],
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let server = HttpServer::new(|| App::new().route("/api/ws", web::get().to(ws_handler)))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let server_handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    let mut request = format!("ws://{}/api/ws", addr)
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("X-API-Key", api_key.parse().unwrap());
+    let (mut client, _) = connect_async(request).await.unwrap();
+    let frame = WsMessage::new(MessageType::SetTags {
+        tags: HashMap::from([("shard".to_string(), "0".to_string())]),
+    });
+    client
+        .send(TungsteniteMessage::text(
+            serde_json::to_string(&frame).unwrap(),
+        ))
+        .await
+        .unwrap();
+
+    // The tags are set once the frame was received
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while get_manager()
+            .unwrap()
+            .connection_info(&created.id)
+            .is_none_or(|info| info.tags.is_empty())
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let token = get_jwtservice()
+        .unwrap()
+        .create_token(
+            "test-suite".to_string(),
+            1,
+            vec!["admin:manage".to_string()],
+            TokenType::Access,
+        )
+        .unwrap();
+    let app = init_service(App::new().configure(api::configure)).await;
+    let req = TestRequest::get()
+        .uri("/api/v1/admin/ws/connections")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: Vec<serde_json::Value> = read_body_json(resp).await;
+    let listed = body
+        .iter()
+        .find(|connection| connection["key_id"] == json!(created.id))
+        .expect("Connected client is listed");
+    assert_eq!(listed["owner"], json!(owner));
+    assert_eq!(
+        listed["client_id"],
+        json!(
+            get_manager()
+                .unwrap()
+                .connection_info(&created.id)
+                .unwrap()
+                .client_id
+        )
+    );
+    assert_eq!(listed["messages_received"], json!(1));
+    assert!(listed["connected_at"].is_i64());
+    assert!(listed["uptime_secs"].is_i64());
+
+    let _ = client.close(None).await;
+    get_manager().unwrap().remove_connection(&created.id).await;
+    server_handle.stop(false).await;
+}
@@ -1,32 +1,74 @@
 use std::{env, sync::Arc};
 
-use crate::utils::config::{get_config, init_config, Config};
+use crate::utils::{
+    config::{
+        get_config, init_config, Config, LogFormat, DEFAULT_USER_AGENT, MIN_ENCRYPTION_KEY_LEN,
+    },
+    error::KohakuError,
+};
 
 use rstest::rstest;
 use serial_test::serial;
 
-fn setup_env_vars(only_required: bool) {
+const ENCRYPTION_KEY: &str = "secret2-that-is-long-enough-for-hs256";
+
+pub(super) fn setup_env_vars(only_required: bool) {
     cleanup_env_vars(); // Ensure clean-slate
 
     env::set_var("DATABASE_URL", "some_url/db");
     env::set_var("BOOTSTRAP_KEY", "secret1");
-    env::set_var("SERVER_ENCRYPTION_KEY", "secret2");
+    env::set_var("SERVER_ENCRYPTION_KEY", ENCRYPTION_KEY);
     if !only_required {
-        // Skip these that are not required to not panic Config::new()
+        // Skip these that are not required to not fail Config::new()
         env::set_var("SERVER_ADDR", "localhost");
         env::set_var("SERVER_PORT", "9000");
         env::set_var("SERVER_LOGGING_LEVEL", "WARN");
     }
 }
 
-fn cleanup_env_vars() {
+pub(super) fn cleanup_env_vars() {
     let vars = vec![
         "SERVER_ADDR",
         "SERVER_PORT",
+        "SERVER_BIND",
         "SERVER_LOGGING_LEVEL",
+        "SERVER_LOG_FORMAT",
+        "SERVER_LOG_FILE",
         "DATABASE_URL",
         "BOOTSTRAP_KEY",
         "SERVER_ENCRYPTION_KEY",
+        "DATABASE_RETRY_ATTEMPTS",
+        "DATABASE_RETRY_DELAY_MS",
+        "DATABASE_CHECKOUT_TIMEOUT_MS",
+        "SERVER_CORS_ORIGINS",
+        "SERVER_CORS_METHODS",
+        "SERVER_CORS_HEADERS",
+        "SERVER_WS_OUTBOUND_MAX_MESSAGES",
+        "SERVER_WS_OUTBOUND_WINDOW_SEC",
+        "SERVER_WS_COMPRESSION",
+        "SERVER_WS_SIGNING_SECRET",
+        "SERVER_WS_MAX_PAYLOAD_BYTES",
+        "SERVER_WS_MAX_CONNECTIONS_PER_OWNER",
+        "SERVER_KEYS_MANAGE_MAX_REQUESTS",
+        "SERVER_KEYS_MANAGE_WINDOW_SEC",
+        "SERVER_BREAKER_FAILURE_THRESHOLD",
+        "SERVER_BREAKER_COOLDOWN_SEC",
+        "SERVER_HTTP_USER_AGENT",
+        "SERVER_HTTP_CONNECT_TIMEOUT_SEC",
+        "SERVER_HTTP_READ_TIMEOUT_SEC",
+        "SERVER_MAX_BODY_BYTES",
+        "SERVER_HTTP_COMPRESSION",
+        "SERVER_JWT_ISSUER",
+        "SERVER_JWT_AUDIENCE",
+        "SERVER_REVOKED_KEY_RETENTION_DAYS",
+        "SERVER_HISTORY_RETENTION_DAYS",
+        "SERVER_JWT_BLACKLIST_MAX",
+        "SERVER_JWT_REFRESH_MAX",
+        "SERVER_EVENTS_CACHE_TTL_SEC",
+        "SERVER_EVENTS_DEDUP_WINDOW_SEC",
+        "SERVER_EVENTS_STALE_CODE_DAYS",
+        "SERVER_EVENTS_MAX_SUBSCRIPTIONS_PER_GUILD",
+        "SERVER_EVENTS_WEBHOOK_URL",
     ];
     for v in vars {
         env::remove_var(v);
@@ -57,13 +99,16 @@ fn test_config_singleton() {
 fn test_config_with_env_vars() {
     setup_env_vars(false);
 
-    let config = Config::new();
+    let config = Config::new().unwrap();
     assert_eq!(config.server_addr, "localhost");
     assert_eq!(config.server_port, 9000);
     assert_eq!(config.logging_level, tracing::Level::WARN);
     assert_eq!(config.database_url, "some_url/db");
     assert_eq!(config.bootstrap_key, "secret1".to_string());
-    assert_eq!(config.encryption_key, "secret2".to_string().into_bytes());
+    assert_eq!(
+        config.encryption_key,
+        ENCRYPTION_KEY.to_string().into_bytes()
+    );
 
     cleanup_env_vars();
 }
@@ -73,32 +118,105 @@ fn test_config_with_env_vars() {
 fn test_config_defaults() {
     setup_env_vars(true);
 
-    let config = Config::new();
+    let config = Config::new().unwrap();
     assert_eq!(config.server_addr, "127.0.0.1");
     assert_eq!(config.server_port, 8080);
     assert_eq!(config.logging_level, tracing::Level::INFO);
+    assert_eq!(config.log_format, LogFormat::Pretty);
+    assert_eq!(config.log_file, None);
+    assert_eq!(config.database_retry_attempts, 3);
+    assert_eq!(config.database_retry_delay_ms, 100);
+    assert_eq!(config.database_checkout_timeout_ms, 2000);
+    assert_eq!(config.ws_outbound_max_messages, 60);
+    assert_eq!(config.ws_outbound_window_sec, 10);
+    assert!(!config.ws_compression);
+    assert!(config.ws_signing_secret.is_none());
+    assert_eq!(config.ws_max_payload_bytes, 65536);
+    assert_eq!(config.ws_max_connections_per_owner, 0);
+    assert_eq!(config.keys_manage_max_requests, 10);
+    assert_eq!(config.keys_manage_window_sec, 60);
+    assert_eq!(config.breaker_failure_threshold, 5);
+    assert_eq!(config.breaker_cooldown_sec, 60);
+    assert_eq!(config.events_cache_ttl_sec, 5);
+    assert_eq!(config.events_dedup_window_sec, 300);
+    assert_eq!(config.events_stale_code_days, 0);
+    assert_eq!(config.events_max_subscriptions_per_guild, 0);
+    assert_eq!(config.events_webhook_url, None);
+    assert_eq!(config.max_body_bytes, 65536);
+    assert!(config.http_compression);
+    assert_eq!(config.jwt_issuer, "kohaku");
+    assert_eq!(config.jwt_audience, "kohaku-api");
+    assert_eq!(config.revoked_key_retention_days, 90);
+    assert_eq!(config.history_retention_days, 90);
+    assert_eq!(config.http_user_agent, DEFAULT_USER_AGENT);
+    assert_eq!(config.http_connect_timeout_sec, 5);
+    assert_eq!(config.http_read_timeout_sec, 30);
+    assert_eq!(config.jwt_blacklist_max, 10000);
+    assert_eq!(config.jwt_refresh_max, 0);
+    assert!(config.cors_allowed_origins.is_empty());
+    assert_eq!(
+        config.cors_allowed_methods,
+        vec!["GET", "POST", "PATCH", "DELETE"]
+    );
 
     cleanup_env_vars();
 }
 
 #[test]
 #[serial]
-#[should_panic]
 fn test_missing_required_env_var() {
     cleanup_env_vars();
-    Config::new(); // Should panic as DATABASE_URL and SECRET are required and not set
+    // Should fail as DATABASE_URL and SECRET are required and not set
+    assert!(matches!(
+        Config::new(),
+        Err(KohakuError::ValidationError(_))
+    ));
 }
 
 #[rstest]
 #[case("SERVER_PORT", "abc")]
 #[case("SERVER_PORT", "1.5")]
 #[case("SERVER_PORT", "-1")]
+#[case("DATABASE_RETRY_ATTEMPTS", "-1")]
+#[case("DATABASE_RETRY_DELAY_MS", "fast")]
+#[case("DATABASE_CHECKOUT_TIMEOUT_MS", "0")]
+#[case("SERVER_CORS_ORIGINS", "*")]
+#[case("SERVER_CORS_ORIGINS", "https://ok.example, not an origin")]
+#[case("SERVER_CORS_METHODS", "GET,PO ST")]
+#[case("SERVER_CORS_HEADERS", "Authorization,X API Key")]
+#[case("SERVER_LOG_FORMAT", "xml")]
+#[case("SERVER_WS_OUTBOUND_MAX_MESSAGES", "-5")]
+#[case("SERVER_WS_OUTBOUND_WINDOW_SEC", "0")]
+#[case("SERVER_WS_COMPRESSION", "yes")]
+#[case("SERVER_WS_MAX_PAYLOAD_BYTES", "0")]
+#[case("SERVER_WS_MAX_CONNECTIONS_PER_OWNER", "-1")]
+#[case("SERVER_KEYS_MANAGE_MAX_REQUESTS", "0")]
+#[case("SERVER_KEYS_MANAGE_WINDOW_SEC", "-60")]
+#[case("SERVER_BREAKER_FAILURE_THRESHOLD", "0")]
+#[case("SERVER_BREAKER_COOLDOWN_SEC", "1m")]
+#[case("SERVER_EVENTS_CACHE_TTL_SEC", "-1")]
+#[case("SERVER_EVENTS_DEDUP_WINDOW_SEC", "-1")]
+#[case("SERVER_EVENTS_DEDUP_WINDOW_SEC", "5m")]
+#[case("SERVER_EVENTS_STALE_CODE_DAYS", "-30")]
+#[case("SERVER_EVENTS_MAX_SUBSCRIPTIONS_PER_GUILD", "-1")]
+#[case("SERVER_EVENTS_WEBHOOK_URL", "https://example.com/hook")]
+#[case("SERVER_EVENTS_WEBHOOK_URL", "http://discord.com/api/webhooks/123/abc")]
+#[case("SERVER_MAX_BODY_BYTES", "0")]
+#[case("SERVER_MAX_BODY_BYTES", "1MB")]
+#[case("SERVER_HTTP_COMPRESSION", "gzip")]
+#[case("SERVER_REVOKED_KEY_RETENTION_DAYS", "-1")]
+#[case("SERVER_HISTORY_RETENTION_DAYS", "90d")]
+#[case("SERVER_HTTP_USER_AGENT", "kohaku\u{1}bot")]
+#[case("SERVER_HTTP_CONNECT_TIMEOUT_SEC", "0")]
+#[case("SERVER_HTTP_READ_TIMEOUT_SEC", "-5")]
+#[case("SERVER_JWT_BLACKLIST_MAX", "0")]
+#[case("SERVER_JWT_REFRESH_MAX", "-1")]
 #[serial]
 fn test_parsing_fails(#[case] env_name: &str, #[case] invalid_value: &str) {
     setup_env_vars(true);
     env::set_var(env_name, invalid_value);
 
-    let result = std::panic::catch_unwind(|| Config::new());
+    let result = Config::new();
 
     assert!(result.is_err());
     cleanup_env_vars();
@@ -112,13 +230,150 @@ fn test_parsing_fails(#[case] env_name: &str, #[case] invalid_value: &str) {
 #[case("SERVER_LOGGING_LEVEL", "WARN")]
 #[case("SERVER_LOGGING_LEVEL", "DEBUG")]
 #[case("SERVER_LOGGING_LEVEL", "TRACE")]
+#[case("DATABASE_RETRY_ATTEMPTS", "5")]
+#[case("DATABASE_RETRY_DELAY_MS", "250")]
+#[case("DATABASE_CHECKOUT_TIMEOUT_MS", "500")]
+#[case("SERVER_CORS_ORIGINS", "https://admin.example.com")]
+#[case("SERVER_CORS_METHODS", "GET")]
+#[case("SERVER_CORS_HEADERS", "Authorization,X-Client-Id")]
+#[case("SERVER_LOG_FORMAT", "pretty")]
+#[case("SERVER_LOG_FORMAT", "JSON")]
+#[case("SERVER_WS_OUTBOUND_MAX_MESSAGES", "120")]
+#[case("SERVER_WS_OUTBOUND_WINDOW_SEC", "30")]
+#[case("SERVER_WS_COMPRESSION", "true")]
+#[case("SERVER_WS_MAX_PAYLOAD_BYTES", "1048576")]
+#[case("SERVER_WS_MAX_CONNECTIONS_PER_OWNER", "8")]
+#[case("SERVER_KEYS_MANAGE_MAX_REQUESTS", "3")]
+#[case("SERVER_KEYS_MANAGE_WINDOW_SEC", "3600")]
+#[case("SERVER_BREAKER_FAILURE_THRESHOLD", "1")]
+#[case("SERVER_BREAKER_COOLDOWN_SEC", "300")]
+#[case("SERVER_EVENTS_CACHE_TTL_SEC", "0")]
+#[case("SERVER_EVENTS_DEDUP_WINDOW_SEC", "0")]
+#[case("SERVER_EVENTS_STALE_CODE_DAYS", "180")]
+#[case("SERVER_EVENTS_MAX_SUBSCRIPTIONS_PER_GUILD", "50")]
+#[case("SERVER_EVENTS_WEBHOOK_URL", "https://discord.com/api/webhooks/1/a")]
+#[case("SERVER_MAX_BODY_BYTES", "1048576")]
+#[case("SERVER_HTTP_COMPRESSION", "false")]
+#[case("SERVER_REVOKED_KEY_RETENTION_DAYS", "365")]
+#[case("SERVER_HISTORY_RETENTION_DAYS", "0")]
+#[case("SERVER_HTTP_USER_AGENT", "kohaku-bot/1.0 (+https://example.com)")]
+#[case("SERVER_HTTP_CONNECT_TIMEOUT_SEC", "10")]
+#[case("SERVER_HTTP_READ_TIMEOUT_SEC", "120")]
+#[case("SERVER_JWT_BLACKLIST_MAX", "500")]
+#[case("SERVER_JWT_REFRESH_MAX", "96")]
 #[serial]
 fn test_parsing_succeeds(#[case] env_name: &str, #[case] invalid_value: &str) {
     setup_env_vars(true);
     env::set_var(env_name, invalid_value);
 
-    let result = std::panic::catch_unwind(|| Config::new());
+    let result = Config::new();
 
     assert!(result.is_ok());
     cleanup_env_vars();
 }
+
+#[rstest]
+#[case("")]
+#[case("short")]
+#[case("31-bytes-long-encryption-key-xx")]
+#[serial]
+fn test_encryption_key_too_short(#[case] key: &str) {
+    setup_env_vars(true);
+    env::set_var("SERVER_ENCRYPTION_KEY", key);
+
+    let result = Config::new();
+
+    assert!(key.len() < MIN_ENCRYPTION_KEY_LEN);
+    assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+    cleanup_env_vars();
+}
+
+#[test]
+#[serial]
+fn test_server_binds() {
+    setup_env_vars(false);
+
+    // #1 Falls back to SERVER_ADDR and SERVER_PORT
+    let config = Config::new().unwrap();
+    assert_eq!(config.server_binds, vec![("localhost".to_string(), 9000)]);
+
+    // #2 Multiple addresses
+    env::set_var(
+        "SERVER_BIND",
+        "10.0.0.5:8080, 0.0.0.0:80,[::]:8080 ,localhost:9001",
+    );
+    let config = Config::new().unwrap();
+    assert_eq!(
+        config.server_binds,
+        vec![
+            ("10.0.0.5".to_string(), 8080),
+            ("0.0.0.0".to_string(), 80),
+            ("::".to_string(), 8080),
+            ("localhost".to_string(), 9001),
+        ]
+    );
+    cleanup_env_vars();
+}
+
+#[rstest]
+#[case("0.0.0.0")]
+#[case(":8080")]
+#[case("0.0.0.0:http")]
+#[case("0.0.0.0:70000")]
+#[case("::1:8080")]
+#[case("[::1:8080")]
+#[case("[not-ipv6]:8080")]
+#[case("0.0.0.0:8080,broken")]
+#[serial]
+fn test_server_binds_invalid(#[case] bind: &str) {
+    setup_env_vars(true);
+    env::set_var("SERVER_BIND", bind);
+
+    assert!(matches!(
+        Config::new(),
+        Err(KohakuError::ValidationError(_))
+    ));
+    cleanup_env_vars();
+}
+
+#[test]
+#[serial]
+fn test_cors_origins_list() {
+    setup_env_vars(true);
+    env::set_var(
+        "SERVER_CORS_ORIGINS",
+        " https://a.example.com,https://b.example.com ,,",
+    );
+
+    let config = Config::new().unwrap();
+    assert_eq!(
+        config.cors_allowed_origins,
+        vec!["https://a.example.com", "https://b.example.com"]
+    );
+    cleanup_env_vars();
+}
+
+#[test]
+#[serial]
+fn test_log_format_json() {
+    setup_env_vars(true);
+    env::set_var("SERVER_LOG_FORMAT", "json");
+
+    let config = Config::new().unwrap();
+    assert_eq!(config.log_format, LogFormat::Json);
+    cleanup_env_vars();
+}
+
+#[rstest]
+#[case("/var/log/kohaku/server.log", Some("/var/log/kohaku/server.log"))]
+#[case("  ", None)]
+#[case("", None)]
+#[serial]
+fn test_log_file(#[case] value: &str, #[case] expected: Option<&str>) {
+    setup_env_vars(true);
+    env::set_var("SERVER_LOG_FILE", value);
+
+    let config = Config::new().unwrap();
+    assert_eq!(config.log_file.as_deref(), expected);
+    cleanup_env_vars();
+}
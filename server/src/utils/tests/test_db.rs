@@ -0,0 +1,79 @@
+use std::{panic, sync::Mutex, thread, time::Duration};
+
+use diesel::{
+    pg::PgConnection,
+    r2d2::{ConnectionManager, Pool},
+};
+
+use crate::{
+    db::{get_connection_from, get_connection_with_retry},
+    utils::error::KohakuError,
+};
+
+/// Pool with a single connection that times out quickly, making it easy to exhaust
+fn single_connection_pool() -> Pool<ConnectionManager<PgConnection>> {
+    let url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL must be set for a testing environment");
+    Pool::builder()
+        .max_size(1)
+        .connection_timeout(Duration::from_millis(100))
+        .build(ConnectionManager::<PgConnection>::new(url))
+        .unwrap()
+}
+
+// ================================= get_connection_with_retry
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_get_connection_immediate() {
+    let pool = single_connection_pool();
+    let val = get_connection_with_retry(&pool, 1, Duration::from_millis(10)).await;
+    assert!(val.is_ok());
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_get_connection_succeeds_after_release() {
+    let pool = single_connection_pool();
+    let held = pool.get().unwrap();
+
+    // Free the only connection while the retry loop is waiting
+    let releaser = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(250));
+        drop(held);
+    });
+
+    let val = get_connection_with_retry(&pool, 5, Duration::from_millis(100)).await;
+    releaser.join().unwrap();
+    assert!(val.is_ok());
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_get_connection_exhausted() {
+    let pool = single_connection_pool();
+    let _held = pool.get().unwrap();
+
+    let val = get_connection_with_retry(&pool, 2, Duration::from_millis(10)).await;
+    assert!(matches!(val, Err(KohakuError::DatabaseConnectionError(_))));
+}
+
+// ================================= get_connection_from
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn test_get_connection_poisoned_lock() {
+    let pool = Mutex::new(single_connection_pool());
+
+    // Poison the lock by panicking while holding it
+    let result = panic::catch_unwind(|| {
+        let _guard = pool.lock().unwrap();
+        panic!("panicked while holding the pool lock");
+    });
+    assert!(result.is_err());
+    assert!(pool.is_poisoned());
+
+    assert!(get_connection_from(&pool).await.is_ok());
+    // Still usable afterwards
+    assert!(get_connection_from(&pool).await.is_ok());
+}
@@ -0,0 +1,57 @@
+use actix_web::{body::to_bytes, http::StatusCode, ResponseError};
+use rstest::rstest;
+
+use crate::utils::error::KohakuError;
+
+// ========================================= Database ========================================== //
+
+#[rstest]
+#[case::not_found(diesel::result::Error::NotFound, StatusCode::NOT_FOUND)]
+#[case::other(
+    diesel::result::Error::RollbackTransaction,
+    StatusCode::INTERNAL_SERVER_ERROR
+)]
+fn test_not_found_or_database(#[case] error: diesel::result::Error, #[case] status: StatusCode) {
+    let err = KohakuError::not_found_or_database("Thing could not be found!")(error);
+    assert_eq!(err.status_code(), status);
+    match err {
+        KohakuError::NotFound(msg) => assert_eq!(msg, "Thing could not be found!"),
+        err => assert!(matches!(err, KohakuError::DatabaseError(_))),
+    }
+}
+
+// ========================================== Timeout ========================================== //
+
+#[actix_web::test]
+async fn test_timeout_response() {
+    let err = KohakuError::Timeout {
+        operation: "database query".to_string(),
+    };
+    assert_eq!(err.status_code(), StatusCode::GATEWAY_TIMEOUT);
+
+    let resp = err.error_response();
+    assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+    let body: serde_json::Value =
+        serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+    assert_eq!(body["status"], 504);
+    assert_eq!(body["error"], "Timeout during database query");
+}
+
+// ======================================= External API ======================================== //
+
+#[actix_web::test]
+async fn test_external_api_response() {
+    let err = KohakuError::ExternalApiError {
+        service: "anilist".to_string(),
+        status: Some(500),
+        message: "Unexpected status 500".to_string(),
+    };
+    assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+
+    // Details of the external service are not exposed
+    let resp = err.error_response();
+    let body: serde_json::Value =
+        serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+    assert_eq!(body["status"], 502);
+    assert_eq!(body["error"], "External service error");
+}
@@ -0,0 +1,103 @@
+use std::{net::TcpListener, time::Duration};
+
+use actix_web::{http::StatusCode, ResponseError};
+use serde::Deserialize;
+
+use crate::utils::{
+    error::KohakuError,
+    http::{build_client, get_json, get_text},
+    tests::mock_http_server,
+};
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Entry {
+    id: u32,
+    title: String,
+}
+
+#[tokio::test]
+async fn test_get_json() {
+    let (url, handle) = mock_http_server(200, r#"{"id":1,"title":"Kohaku"}"#, Duration::ZERO);
+
+    let entry: Entry = get_json("mock", &format!("{}/entries/1", url))
+        .await
+        .unwrap();
+    assert_eq!(
+        entry,
+        Entry {
+            id: 1,
+            title: "Kohaku".to_string()
+        }
+    );
+
+    // Default headers are sent
+    let head = handle.join().unwrap().0.to_lowercase();
+    assert!(head.starts_with("get /entries/1 "));
+    assert!(head.contains("user-agent: kohaku-test"));
+    assert!(head.contains("accept: application/json"));
+}
+
+#[tokio::test]
+async fn test_get_json_malformed_body() {
+    let (url, handle) = mock_http_server(200, r#"{"id":"one"}"#, Duration::ZERO);
+
+    let result = get_json::<Entry>("mock", &url).await;
+    assert!(matches!(
+        result,
+        Err(KohakuError::ExternalApiError { service, .. }) if service == "mock"
+    ));
+    handle.join().unwrap();
+}
+
+#[tokio::test]
+async fn test_get_text_error_status() {
+    let (url, handle) = mock_http_server(503, "", Duration::ZERO);
+
+    let err = get_text("mock", &url).await.unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+    match err {
+        KohakuError::ExternalApiError {
+            service, status, ..
+        } => {
+            assert_eq!(service, "mock");
+            assert_eq!(status, Some(503));
+        }
+        other => panic!("Expected an external API error, got {:?}", other),
+    }
+    handle.join().unwrap();
+}
+
+#[tokio::test]
+async fn test_get_text_timeout() {
+    // Answers after the read timeout of the test client (1s)
+    let (url, handle) = mock_http_server(200, "late", Duration::from_secs(3));
+
+    let err = get_text("mock", &url).await.unwrap_err();
+    assert!(matches!(
+        &err,
+        KohakuError::Timeout { operation } if operation == "request to mock"
+    ));
+    assert_eq!(err.status_code(), StatusCode::GATEWAY_TIMEOUT);
+    handle.join().unwrap();
+}
+
+#[tokio::test]
+async fn test_get_text_unreachable() {
+    // Nothing listens on the port anymore
+    let url = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    };
+
+    let result = get_text("mock", &url).await;
+    assert!(matches!(
+        result,
+        Err(KohakuError::ExternalApiError { status: None, .. })
+    ));
+}
+
+#[test]
+fn test_build_client_invalid_user_agent() {
+    let result = build_client("kohaku\n", Duration::from_secs(1), Duration::from_secs(1));
+    assert!(matches!(result, Err(KohakuError::ValidationError(_))));
+}
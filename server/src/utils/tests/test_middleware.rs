@@ -0,0 +1,348 @@
+use std::{
+    env,
+    io::{Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{
+    http::{header, StatusCode},
+    middleware::from_fn,
+    test, web, App, HttpResponse,
+};
+use flate2::read::GzDecoder;
+use serial_test::serial;
+use tracing_subscriber::fmt::MakeWriter;
+use uuid::Uuid;
+
+use crate::utils::{
+    config::Config,
+    error::KohakuError,
+    middleware::{
+        compression::build_compression,
+        cors::build_cors,
+        logging::{current_request_id, request_logger, REQUEST_ID_HEADER},
+        payload::build_json_config,
+    },
+    tests::test_config::{cleanup_env_vars, setup_env_vars},
+};
+
+// ========================================== CORS ============================================= //
+
+#[actix_web::test]
+#[serial]
+async fn test_cors_allowed_origin() {
+    setup_env_vars(true);
+    env::set_var("SERVER_CORS_ORIGINS", "https://admin.example.com");
+    let config = Config::new().unwrap();
+    cleanup_env_vars();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(build_cors(&config))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((header::ORIGIN, "https://admin.example.com"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(
+        resp.headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .unwrap(),
+        "https://admin.example.com"
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_cors_restrictive_default() {
+    setup_env_vars(true);
+    let config = Config::new().unwrap();
+    cleanup_env_vars();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(build_cors(&config))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((header::ORIGIN, "https://evil.example.com"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+}
+
+// ======================================= Body Limits ========================================= //
+
+#[derive(serde::Deserialize)]
+struct Echo {
+    text: String,
+}
+
+async fn echo(body: web::Json<Echo>) -> HttpResponse {
+    HttpResponse::Ok().body(body.into_inner().text)
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_json_body_limit() {
+    setup_env_vars(true);
+    env::set_var("SERVER_MAX_BODY_BYTES", "64");
+    let config = Config::new().unwrap();
+    cleanup_env_vars();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(build_json_config(&config))
+            .route("/", web::post().to(echo)),
+    )
+    .await;
+
+    // Within the limit
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(serde_json::json!({ "text": "short" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Exceeding the limit
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_json(serde_json::json!({ "text": "x".repeat(100) }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Request body too large"));
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_json_body_malformed() {
+    setup_env_vars(true);
+    let config = Config::new().unwrap();
+    cleanup_env_vars();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(build_json_config(&config))
+            .route("/", web::post().to(echo)),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/")
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload("{not json")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid JSON body"));
+}
+
+// ======================================== Compression ======================================== //
+
+#[actix_web::test]
+#[serial]
+async fn test_compressed_error_response() {
+    async fn failing() -> Result<HttpResponse, KohakuError> {
+        Err(KohakuError::NotFound("x".repeat(2048)))
+    }
+    setup_env_vars(true);
+    let config = Config::new().unwrap();
+    cleanup_env_vars();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(build_compression(&config))
+            .wrap(from_fn(request_logger))
+            .route("/", web::get().to(failing)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((header::ACCEPT_ENCODING, "gzip"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+        "gzip"
+    );
+    assert!(resp.headers().contains_key(REQUEST_ID_HEADER));
+
+    let compressed = test::read_body(resp).await;
+    let mut decoded = String::new();
+    GzDecoder::new(compressed.as_ref())
+        .read_to_string(&mut decoded)
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&decoded).unwrap();
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["error"], "x".repeat(2048));
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_compression_disabled() {
+    setup_env_vars(true);
+    env::set_var("SERVER_HTTP_COMPRESSION", "false");
+    let config = Config::new().unwrap();
+    cleanup_env_vars();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(build_compression(&config))
+            .route("/", web::get().to(|| async { "x".repeat(2048) })),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((header::ACCEPT_ENCODING, "gzip"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+}
+
+// ======================================== Logging ============================================ //
+
+/// Collects formatted log lines so tests can inspect them
+#[derive(Clone, Default)]
+pub(super) struct LogCapture(Arc<Mutex<Vec<u8>>>);
+
+impl LogCapture {
+    pub(super) fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogCapture {
+    type Writer = LogCapture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[actix_web::test]
+async fn test_request_id_header() {
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(request_logger))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let request_id = resp.headers().get(REQUEST_ID_HEADER).unwrap();
+    assert!(Uuid::parse_str(request_id.to_str().unwrap()).is_ok());
+}
+
+#[actix_web::test]
+async fn test_request_id_in_error_response() {
+    async fn failing() -> Result<HttpResponse, KohakuError> {
+        Err(KohakuError::NotFound("Nothing here".to_string()))
+    }
+
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(request_logger))
+            .route("/", web::get().to(failing)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let header_id = resp
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["request_id"], header_id);
+}
+
+#[actix_web::test]
+async fn test_no_request_id_outside_request() {
+    assert!(current_request_id().is_none());
+}
+
+#[actix_web::test]
+async fn test_request_id_logged_for_failing_request() {
+    async fn failing() -> Result<HttpResponse, KohakuError> {
+        Err(KohakuError::InternalServerError(
+            "Something broke".to_string(),
+        ))
+    }
+
+    let capture = LogCapture::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(capture.clone())
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(request_logger))
+            .route("/", web::get().to(failing)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let request_id = body["request_id"].as_str().unwrap();
+
+    let logs = capture.contents();
+    let failed_line = logs
+        .lines()
+        .find(|line| line.contains("Request failed"))
+        .unwrap();
+    assert!(failed_line.contains(&format!("request_id={request_id}")));
+    // Hidden cause is logged within the request span
+    let cause_line = logs
+        .lines()
+        .find(|line| line.contains("Something broke"))
+        .unwrap();
+    assert!(cause_line.contains(request_id));
+}
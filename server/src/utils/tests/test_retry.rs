@@ -0,0 +1,69 @@
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+use rstest::rstest;
+
+use crate::utils::{error::KohakuError, retry::retry_with_backoff};
+
+/// Operation failing with a [`KohakuError::ExternalServiceError`] until `succeed_at` calls were made
+async fn flaky(calls: &AtomicU32, succeed_at: u32) -> Result<u32, KohakuError> {
+    let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+    if call >= succeed_at {
+        Ok(call)
+    } else {
+        Err(KohakuError::ExternalServiceError(format!(
+            "Upstream failed on call {}",
+            call
+        )))
+    }
+}
+
+// ================================= retry_with_backoff
+
+#[rstest]
+#[case::immediate(3, 1)]
+#[case::after_retry(3, 2)]
+#[case::last_attempt(3, 3)]
+#[tokio::test]
+async fn test_retry_success(#[case] attempts: u32, #[case] succeed_at: u32) {
+    let calls = AtomicU32::new(0);
+    let val = retry_with_backoff(attempts, Duration::from_millis(10), || {
+        flaky(&calls, succeed_at)
+    })
+    .await;
+    assert_eq!(val.unwrap(), succeed_at);
+    assert_eq!(calls.load(Ordering::SeqCst), succeed_at);
+}
+
+#[rstest]
+#[case::single(1)]
+#[case::multiple(3)]
+#[tokio::test]
+async fn test_retry_exhausted(#[case] attempts: u32) {
+    let calls = AtomicU32::new(0);
+    let val = retry_with_backoff(attempts, Duration::from_millis(10), || {
+        flaky(&calls, u32::MAX)
+    })
+    .await;
+    match val {
+        Err(KohakuError::ExternalServiceError(msg)) => {
+            assert_eq!(msg, format!("Upstream failed on call {}", attempts))
+        }
+        other => panic!("Expected last ExternalServiceError, got {:?}", other),
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), attempts);
+}
+
+#[tokio::test]
+async fn test_retry_backoff() {
+    // Waits 20ms + 40ms (+ up to 50% jitter) before the third attempt
+    let calls = AtomicU32::new(0);
+    let start = Instant::now();
+    let val = retry_with_backoff(3, Duration::from_millis(20), || flaky(&calls, 3)).await;
+    let elapsed = start.elapsed();
+    assert!(val.is_ok());
+    assert!(elapsed >= Duration::from_millis(60), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+}
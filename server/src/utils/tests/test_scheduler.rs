@@ -6,11 +6,23 @@ use std::{
     time::Duration,
 };
 
+use chrono::{Timelike, Utc};
+use chrono_tz::Tz;
+use rstest::rstest;
 use serial_test::serial;
 
+use super::test_middleware::LogCapture;
 use crate::{
     impl_task_wrapper,
-    utils::scheduler::{get_scheduler, init_scheduler, tasks::Task, Scheduler},
+    utils::{
+        error::KohakuError,
+        scheduler::{
+            get_scheduler, init_scheduler, interval_to_cron, next_fire_times, parse_interval,
+            parse_timezone,
+            tasks::{task_stats, Runnable, Task},
+            validate_cron, Scheduler,
+        },
+    },
 };
 
 #[tokio::test]
@@ -42,6 +54,14 @@ impl TestTask {
         Self(Task::new("TestTask", "*/1 * * * * *", run_once))
     }
 
+    pub fn with_cron(cron: &str) -> Self {
+        Self(Task::new("TestTask", cron, true))
+    }
+
+    pub fn with_timezone(self, timezone: Tz) -> Self {
+        Self(self.0.with_timezone(timezone))
+    }
+
     async fn execute(&self) -> Result<(), String> {
         let counter = COUNTER.lock().unwrap();
         let counter = counter.as_ref().expect("Counter not initialized");
@@ -109,3 +129,401 @@ async fn test_execute_task_multiple_times() {
         count
     );
 }
+
+#[rstest]
+#[case::pretty(false)]
+#[case::json(true)]
+#[tokio::test]
+#[serial]
+async fn test_task_span(#[case] json: bool) {
+    *COUNTER.lock().unwrap() = Some(Arc::new(AtomicUsize::new(0)));
+
+    let capture = LogCapture::default();
+    let builder = tracing_subscriber::fmt()
+        .with_writer(capture.clone())
+        .with_ansi(false);
+    let _guard = if json {
+        tracing::subscriber::set_default(builder.json().finish())
+    } else {
+        tracing::subscriber::set_default(builder.finish())
+    };
+
+    TestTask::new(true).run().await;
+    TestTask::new(true).run().await;
+
+    let logs = capture.contents();
+    let lines: Vec<&str> = logs.lines().filter(|l| l.contains("Done!")).collect();
+    assert_eq!(lines.len(), 2);
+    for line in &lines {
+        assert!(line.contains("task"), "{}", line);
+        assert!(line.contains("TestTask"), "{}", line);
+        assert!(line.contains("run_id"), "{}", line);
+    }
+
+    // Every run gets its own id
+    let run_id = |line: &str| {
+        let start = line.find("run_id").unwrap();
+        line[start..]
+            .split(|c: char| c != '-' && c != '_' && !c.is_ascii_alphanumeric())
+            .find(|part| part.len() == 36)
+            .unwrap()
+            .to_string()
+    };
+    assert_ne!(run_id(lines[0]), run_id(lines[1]));
+}
+
+/// Fails every second execution
+struct AlternatingTask(Task, AtomicUsize);
+
+impl AlternatingTask {
+    pub fn new() -> Self {
+        Self(
+            Task::new("AlternatingTask", "*/1 * * * * *", false),
+            AtomicUsize::new(0),
+        )
+    }
+
+    async fn execute(&self) -> Result<(), String> {
+        let run = self.1.fetch_add(1, Ordering::SeqCst);
+        match run % 2 {
+            0 => Ok(()),
+            _ => Err(format!("Failure of run {}", run)),
+        }
+    }
+}
+
+impl_task_wrapper!(AlternatingTask);
+
+#[tokio::test]
+async fn test_task_stats() {
+    let task = AlternatingTask::new();
+    for _ in 0..5 {
+        task.run().await;
+    }
+
+    let stats = task_stats().remove("AlternatingTask").unwrap();
+    assert_eq!(stats.runs, 5);
+    assert_eq!(stats.failures, 2);
+    assert!(stats.last_run.is_some_and(|t| t <= Utc::now()));
+    // The latest error is kept after a successful run
+    assert_eq!(stats.last_error, Some("Failure of run 3".to_string()));
+
+    let scheduler = Scheduler::new().await.unwrap();
+    assert_eq!(scheduler.task_stats()["AlternatingTask"], stats);
+}
+
+#[tokio::test]
+async fn test_add_closure() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let scheduler = Scheduler::new().await.unwrap();
+    let closure_counter = counter.clone();
+    scheduler
+        .add_closure("TestClosure", "*/1 * * * * *", false, move || {
+            let counter = closure_counter.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+    let _ = scheduler.start().await;
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let count = counter.load(Ordering::SeqCst);
+    assert!(
+        count > 1,
+        "Closure should run multiple times, but ran {} time(s)",
+        count
+    );
+}
+
+#[tokio::test]
+async fn test_add_closure_failing_once() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let scheduler = Scheduler::new().await.unwrap();
+    let closure_counter = counter.clone();
+    scheduler
+        .add_closure("TestClosure", "*/1 * * * * *", true, move || {
+            let counter = closure_counter.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err("expected failure".to_string())
+            })
+        })
+        .await
+        .unwrap();
+    let _ = scheduler.start().await;
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    // Failures are logged, the job is removed nonetheless
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_add_oneshot_at() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let scheduler = Scheduler::new().await.unwrap();
+    let add = |when| {
+        let closure_counter = counter.clone();
+        scheduler.add_oneshot_at("TestOneshot", when, move || {
+            let counter = closure_counter.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+        })
+    };
+    add(Utc::now() + chrono::Duration::seconds(1))
+        .await
+        .unwrap();
+    // Overdue jobs run right away
+    add(Utc::now() - chrono::Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(scheduler.job_count(), 2);
+    let _ = scheduler.start().await;
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    assert_eq!(scheduler.job_count(), 0);
+}
+
+#[tokio::test]
+async fn test_add_closure_invalid_cron() {
+    let scheduler = Scheduler::new().await.unwrap();
+    let val = scheduler
+        .add_closure("TestClosure", "*/70 * * * *", true, || {
+            Box::pin(async { Ok(()) })
+        })
+        .await;
+    assert!(matches!(val, Err(KohakuError::ValidationError(_))));
+}
+
+// ------------------------------------------------------------------------
+
+#[rstest]
+#[case("*/1 * * * * *")]
+#[case("0 0,30 * * * *")]
+#[case("0 15 6,8,10 * Mar,Jun Fri")]
+fn test_validate_cron_valid(#[case] expr: &str) {
+    assert!(validate_cron(expr).is_ok());
+}
+
+#[rstest]
+#[case("")]
+#[case("*/70 * * * *")]
+#[case("60 * * * * *")]
+#[case("0 0 25 * * *")]
+#[case("not a cron")]
+fn test_validate_cron_invalid(#[case] expr: &str) {
+    let val = validate_cron(expr);
+    assert!(matches!(val, Err(KohakuError::ValidationError(_))));
+}
+
+#[test]
+fn test_next_fire_times() {
+    let times = next_fire_times("*/1 * * * * *", 5, Tz::UTC).unwrap();
+    assert_eq!(times.len(), 5);
+    // Every second => strictly increasing by exactly one second
+    for pair in times.windows(2) {
+        assert_eq!((pair[1] - pair[0]).num_seconds(), 1);
+    }
+
+    assert!(next_fire_times("*/70 * * * *", 5, Tz::UTC).is_err());
+}
+
+#[test]
+fn test_next_fire_times_timezone() {
+    // 9am in Tokyo (UTC+9, no DST) is midnight UTC
+    let times = next_fire_times("0 0 9 * * *", 3, Tz::Asia__Tokyo).unwrap();
+    assert_eq!(times.len(), 3);
+    for time in times {
+        assert_eq!((time.hour(), time.minute()), (0, 0));
+    }
+
+    let times = next_fire_times("0 0 9 * * *", 1, Tz::UTC).unwrap();
+    assert_eq!(times[0].hour(), 9);
+}
+
+#[test]
+fn test_next_fire_times_fixed_offset() {
+    // The scheduler pins the current UTC offset, so a year of daily runs in a DST timezone
+    // must not shift by an hour when the DST changes
+    let times = next_fire_times("0 0 9 * * *", 366, Tz::Europe__Berlin).unwrap();
+    assert_eq!(times.len(), 366);
+    let hour = times[0].hour();
+    assert!(hour == 7 || hour == 8);
+    assert!(times.iter().all(|time| time.hour() == hour));
+}
+
+#[rstest]
+#[case("30s", 30)]
+#[case("5m", 5 * 60)]
+#[case(" 1h ", 60 * 60)]
+#[case("2d", 2 * 24 * 60 * 60)]
+fn test_parse_interval_valid(#[case] expr: &str, #[case] secs: u64) {
+    assert_eq!(parse_interval(expr).unwrap(), Duration::from_secs(secs));
+}
+
+#[rstest]
+#[case("")]
+#[case("30")]
+#[case("0s")]
+#[case("-5m")]
+#[case("1.5h")]
+#[case("5 minutes")]
+#[case("h")]
+#[case("99999999999999999999d")]
+fn test_parse_interval_invalid(#[case] expr: &str) {
+    assert!(matches!(
+        parse_interval(expr),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[rstest]
+#[case("1s", "*/1 * * * * *")]
+#[case("30s", "*/30 * * * * *")]
+#[case("15m", "0 */15 * * * *")]
+#[case("1h", "0 0 */1 * * *")]
+#[case("6h", "0 0 */6 * * *")]
+#[case("1d", "0 0 0 * * *")]
+fn test_interval_to_cron(#[case] interval: &str, #[case] cron: &str) {
+    let val = interval_to_cron(parse_interval(interval).unwrap()).unwrap();
+    assert_eq!(val, cron);
+    assert!(validate_cron(&val).is_ok());
+}
+
+#[rstest]
+#[case(Duration::ZERO)]
+#[case(Duration::from_millis(1500))]
+#[case(Duration::from_secs(45))]
+#[case(Duration::from_secs(90))]
+#[case(Duration::from_secs(7 * 60 * 60))]
+#[case(Duration::from_secs(2 * 24 * 60 * 60))]
+fn test_interval_to_cron_unsupported(#[case] interval: Duration) {
+    assert!(matches!(
+        interval_to_cron(interval),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[rstest]
+#[case("90s")]
+#[case("7m")]
+#[case("45m")]
+#[case("36h")]
+#[case("2d")]
+#[tokio::test]
+async fn test_task_every(#[case] interval: &str) {
+    let interval = parse_interval(interval).unwrap();
+    let task = Task::every("TestTask", interval, false).unwrap();
+    assert_eq!(task.interval, Some(interval));
+
+    // Counted from when the task is added, not aligned to the wall clock
+    let scheduler = Scheduler::new().await.unwrap();
+    let added = Utc::now();
+    let id = scheduler.add_task(TestTask(task)).await.unwrap();
+    let next = scheduler.next_run(&id).await.unwrap().unwrap();
+    let expected = added + chrono::Duration::from_std(interval).unwrap();
+    assert!((next - expected).num_seconds().abs() <= 1);
+}
+
+#[test]
+fn test_task_every_zero() {
+    assert!(matches!(
+        Task::every("TestTask", Duration::ZERO, false),
+        Err(KohakuError::ValidationError(_))
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_execute_task_every() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    *COUNTER.lock().unwrap() = Some(counter.clone());
+
+    let task = Task::every("TestTask", Duration::from_secs(1), false).unwrap();
+    let scheduler = Scheduler::new().await.unwrap();
+    scheduler.add_task(TestTask(task)).await.unwrap();
+    scheduler.start().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+    assert!(counter.load(Ordering::SeqCst) >= 2);
+}
+
+#[rstest]
+#[case("Europe/Berlin", Some(Tz::Europe__Berlin))]
+#[case(" Asia/Tokyo ", Some(Tz::Asia__Tokyo))]
+#[case("UTC", Some(Tz::UTC))]
+#[case("Mars/Olympus", None)]
+#[case("", None)]
+fn test_parse_timezone(#[case] name: &str, #[case] expected: Option<Tz>) {
+    match expected {
+        Some(tz) => assert_eq!(parse_timezone(name).unwrap(), tz),
+        None => assert!(matches!(
+            parse_timezone(name),
+            Err(KohakuError::ValidationError(_))
+        )),
+    }
+}
+
+#[tokio::test]
+async fn test_add_task_with_timezone() {
+    let scheduler = Scheduler::new().await.unwrap();
+    let task = TestTask::with_cron("0 0 9 * * *").with_timezone(Tz::Asia__Tokyo);
+    let id = scheduler.add_task(task).await.unwrap();
+
+    let next = scheduler.next_run(&id).await.unwrap().unwrap();
+    assert!(next > Utc::now());
+    assert_eq!((next.hour(), next.minute(), next.second()), (0, 0, 0));
+
+    // Without a timezone the schedule is evaluated in UTC
+    let id = scheduler
+        .add_task(TestTask::with_cron("0 0 9 * * *"))
+        .await
+        .unwrap();
+    let next = scheduler.next_run(&id).await.unwrap().unwrap();
+    assert_eq!(next.hour(), 9);
+}
+
+#[tokio::test]
+async fn test_add_task_invalid_cron() {
+    let scheduler = Scheduler::new().await.unwrap();
+    let val = scheduler
+        .add_task(TestTask::with_cron("*/70 * * * *"))
+        .await;
+    assert!(matches!(val, Err(KohakuError::ValidationError(_))));
+}
+
+#[tokio::test]
+async fn test_scheduler_is_running() {
+    let scheduler = Scheduler::new().await.unwrap();
+    assert!(!scheduler.is_running());
+
+    scheduler.start().await.unwrap();
+    assert!(scheduler.is_running());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_scheduler_job_count() {
+    *COUNTER.lock().unwrap() = Some(Arc::new(AtomicUsize::new(0)));
+
+    let scheduler = Scheduler::new().await.unwrap();
+    assert_eq!(scheduler.job_count(), 0);
+
+    // #1 Added jobs are counted
+    scheduler.add_task(TestTask::new(true)).await.unwrap();
+    scheduler.add_task(TestTask::new(false)).await.unwrap();
+    assert_eq!(scheduler.job_count(), 2);
+
+    // #2 Jobs that only run once are no longer counted after their run
+    scheduler.start().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(scheduler.job_count(), 1);
+}